@@ -0,0 +1,372 @@
+use rusqlite::{params, Connection};
+
+use crate::error::{Result, VyuwerError};
+use crate::matching::{bitwise_majority, match_features, MatchParams};
+use crate::model::{feature_from_row, fetch_features, DescriptorMatrix, ImageFeature, FEATURE_SELECT};
+use crate::timestamp::{format_unix_secs, parse_utc_millis};
+
+// Function to read a camera's feature timestamps in ascending order, without touching the blobs
+pub(crate) fn feature_timestamps(conn: &Connection, camera_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT created_at_utc FROM image_features WHERE camera_id = ?1 ORDER BY created_at_utc, seq, id",
+    )?;
+    let timestamps = stmt
+        .query_map(params![camera_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(timestamps)
+}
+
+// Function to read a camera's feature timestamps as (Unix millis, stored string), in time
+// order. Stored strings may differ in format (e.g. with or without milliseconds), so they
+// are sorted by the parsed time; the sort is stable, keeping insertion order on ties.
+pub(crate) fn feature_times(conn: &Connection, camera_id: &str) -> Result<Vec<(i64, String)>> {
+    let mut times = feature_timestamps(conn, camera_id)?
+        .into_iter()
+        .map(|ts| Ok((parse_utc_millis(&ts)?, ts)))
+        .collect::<Result<Vec<(i64, String)>>>()?;
+    times.sort_by_key(|(millis, _)| *millis);
+    Ok(times)
+}
+
+// Function to find outages: (gap_start, gap_end) pairs of consecutive frames more than
+// `max_gap_secs` apart. Cameras with fewer than two features have no gaps.
+pub fn camera_gaps(camera_id: &str, max_gap_secs: f64, db_name: &str) -> Result<Vec<(String, String)>> {
+    if max_gap_secs.is_nan() || max_gap_secs < 0.0 {
+        return Err(VyuwerError::InvalidInput(format!(
+            "max_gap_secs must be non-negative, got {max_gap_secs}"
+        )));
+    }
+    let conn = Connection::open(db_name)?;
+    let times = feature_times(&conn, camera_id)?;
+
+    let max_gap_millis = max_gap_secs * 1000.0;
+    let gaps = times
+        .windows(2)
+        .filter(|pair| (pair[1].0 - pair[0].0) as f64 > max_gap_millis)
+        .map(|pair| (pair[0].1.clone(), pair[1].1.clone()))
+        .collect();
+    Ok(gaps)
+}
+
+// Function to count where in the frame a camera's keypoints fall, on a grid of
+// `grid.0` rows by `grid.1` columns laid over its stored frame size; cell [r][c] holds the
+// keypoints of every frame inside it. Features stored without a frame size cannot be
+// placed and are skipped; keypoints outside the frame land in the nearest edge cell.
+pub fn keypoint_heatmap(camera_id: &str, grid: (u32, u32), db_name: &str) -> Result<Vec<Vec<u32>>> {
+    let (rows, cols) = grid;
+    if rows == 0 || cols == 0 {
+        return Err(VyuwerError::InvalidInput(format!("a heatmap grid needs at least one cell, got {rows}x{cols}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let mut heatmap = vec![vec![0u32; cols as usize]; rows as usize];
+    for feature in fetch_features(&conn, camera_id, None)? {
+        let Some(size) = feature.frame_size.filter(|size| size.width > 0 && size.height > 0) else {
+            continue;
+        };
+        for keypoint in &feature.keypoints {
+            let cell = |position: f32, extent: u32, cells: u32| {
+                let fraction = f64::from(position) / f64::from(extent);
+                ((fraction * f64::from(cells)).floor().max(0.0) as usize).min(cells as usize - 1)
+            };
+            heatmap[cell(keypoint.y, size.height, rows)][cell(keypoint.x, size.width, cols)] += 1;
+        }
+    }
+    Ok(heatmap)
+}
+
+// Function to compute a camera's visual centroid. Descriptor counts vary per frame, so
+// only the first K rows of each feature are used, K being the smallest non-zero count;
+// row i of the centroid is the bitwise majority vote over row i of every feature, which
+// is the mean in Hamming space for binary descriptors (ties resolve to 0). Frames
+// without descriptors are ignored; None means the camera has no descriptors at all.
+pub fn camera_centroid(camera_id: &str, db_name: &str) -> Result<Option<DescriptorMatrix>> {
+    let conn = Connection::open(db_name)?;
+    let features = fetch_features(&conn, camera_id, None)?;
+    let matrices: Vec<&DescriptorMatrix> =
+        features.iter().map(|f| &f.descriptors).filter(|d| !d.is_empty()).collect();
+    let Some(first) = matrices.first() else {
+        return Ok(None);
+    };
+    if let Some(other) = matrices.iter().find(|d| d.cols != first.cols) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "camera {camera_id} mixes {}- and {}-byte descriptors",
+            first.cols, other.cols
+        )));
+    }
+
+    let rows = matrices.iter().map(|d| d.rows).min().unwrap_or(0);
+    let cols = first.cols;
+    let mut data = Vec::with_capacity(rows * cols);
+    for i in 0..rows {
+        let aligned: Vec<&[u8]> = matrices.iter().map(|d| d.row(i)).collect();
+        data.extend(bitwise_majority(&aligned, cols));
+    }
+    Ok(Some(DescriptorMatrix::new(rows, cols, data)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    // strftime pattern truncating a timestamp to the start of its bucket
+    fn pattern(self) -> &'static str {
+        match self {
+            Bucket::Hour => "%Y-%m-%dT%H:00:00Z",
+            Bucket::Day => "%Y-%m-%dT00:00:00Z",
+        }
+    }
+
+    fn secs(self) -> i64 {
+        match self {
+            Bucket::Hour => 3600,
+            Bucket::Day => 86_400,
+        }
+    }
+}
+
+// Function to count a camera's features per hour or day, as (bucket start, count) in
+// chronological order. With `fill_gaps`, empty buckets between the first and last are
+// included with a count of 0.
+pub fn activity_histogram(
+    camera_id: &str,
+    bucket: Bucket,
+    fill_gaps: bool,
+    db_name: &str,
+) -> Result<Vec<(String, u64)>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT strftime(?1, created_at_utc) AS bucket, COUNT(*) FROM image_features
+        WHERE camera_id = ?2 AND bucket IS NOT NULL
+        GROUP BY bucket ORDER BY bucket",
+    )?;
+    let counts = stmt
+        .query_map(params![bucket.pattern(), camera_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !fill_gaps || counts.len() < 2 {
+        return Ok(counts);
+    }
+
+    let step = bucket.secs();
+    let mut filled = Vec::new();
+    let mut next = parse_utc_millis(&counts[0].0)? / 1000;
+    for (start, count) in counts {
+        let secs = parse_utc_millis(&start)? / 1000;
+        while next < secs {
+            filled.push((format_unix_secs(next), 0));
+            next += step;
+        }
+        filled.push((start, count));
+        next = secs + step;
+    }
+    Ok(filled)
+}
+
+// Function to correlate two cameras' activity between `start_utc` and `end_utc` (inclusive):
+// both are counted per bucket over the same window and the Pearson coefficient of the two
+// series is returned. Values near 1 suggest overlapping coverage or a shared event. A camera
+// with the same count in every bucket (including no features at all) has no variance to
+// correlate, and gives 0.0.
+pub fn correlate_activity(
+    camera_a: &str,
+    camera_b: &str,
+    start_utc: &str,
+    end_utc: &str,
+    bucket: Bucket,
+    db_name: &str,
+) -> Result<f64> {
+    let start = parse_utc_millis(start_utc)?;
+    let end = parse_utc_millis(end_utc)?;
+    if start > end {
+        return Err(VyuwerError::InvalidInput(format!("range start {start_utc} is after its end {end_utc}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let step_millis = bucket.secs() * 1000;
+    let first = start.div_euclid(step_millis);
+    let buckets = (end.div_euclid(step_millis) - first + 1) as usize;
+
+    let count_series = |camera_id: &str| -> Result<Vec<f64>> {
+        let mut series = vec![0.0; buckets];
+        for ts in feature_timestamps(&conn, camera_id)? {
+            let millis = parse_utc_millis(&ts)?;
+            if (start..=end).contains(&millis) {
+                series[(millis.div_euclid(step_millis) - first) as usize] += 1.0;
+            }
+        }
+        Ok(series)
+    };
+    Ok(pearson(&count_series(camera_a)?, &count_series(camera_b)?))
+}
+
+// Function to count a camera's anomalies per bucket between `start_utc` and `end_utc`
+// (inclusive), as (bucket start, count) in chronological order. Every bucket the range
+// touches is listed, with 0 when it had no anomaly, so the series charts without gaps.
+// Anomalies are counted by description time; repeats folded into one row by the cooldown
+// count once.
+pub fn anomaly_timeseries(
+    camera_id: &str,
+    bucket: Bucket,
+    start_utc: &str,
+    end_utc: &str,
+    db_name: &str,
+) -> Result<Vec<(String, u64)>> {
+    let start = parse_utc_millis(start_utc)?;
+    let end = parse_utc_millis(end_utc)?;
+    if start > end {
+        return Err(VyuwerError::InvalidInput(format!("range start {start_utc} is after its end {end_utc}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let step_millis = bucket.secs() * 1000;
+    let first = start.div_euclid(step_millis);
+    let mut series: Vec<(String, u64)> = (first..=end.div_euclid(step_millis))
+        .map(|index| (format_unix_secs(index * bucket.secs()), 0))
+        .collect();
+
+    let mut stmt =
+        conn.prepare("SELECT datetime FROM image_description WHERE camera_id = ?1 AND anomaly IS NOT NULL")?;
+    let mut rows = stmt.query(params![camera_id])?;
+    while let Some(row) = rows.next()? {
+        let millis = parse_utc_millis(&row.get::<_, String>(0)?)?;
+        if (start..=end).contains(&millis) {
+            series[(millis.div_euclid(step_millis) - first) as usize].1 += 1;
+        }
+    }
+    Ok(series)
+}
+
+// Function to compute the Pearson correlation of two equal-length series, 0.0 when either is constant
+fn pearson(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+// Function to find a camera's busiest period: the `window_secs`-long window, starting at
+// one of its features, that contains the most features. Returns the window's first
+// timestamp and its count (the earliest window on ties), or None for a camera with no features.
+pub fn peak_activity_window(camera_id: &str, window_secs: u64, db_name: &str) -> Result<Option<(String, u64)>> {
+    if window_secs == 0 {
+        return Err(VyuwerError::InvalidInput("activity window must be at least 1 second".to_string()));
+    }
+    let conn = Connection::open(db_name)?;
+    let mut times = feature_times(&conn, camera_id)?;
+
+    let window_millis = i64::try_from(window_secs).unwrap_or(i64::MAX / 1000).saturating_mul(1000);
+    let mut best: Option<(usize, u64)> = None;
+    let mut end = 0;
+    for (start, (start_millis, _)) in times.iter().enumerate() {
+        while end < times.len() && times[end].0 - start_millis < window_millis {
+            end += 1;
+        }
+        let count = (end - start) as u64;
+        if best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((start, count));
+        }
+    }
+    Ok(best.map(|(start, count)| (times.swap_remove(start).1, count)))
+}
+
+// Match ratio a feature in another camera needs to count as a handoff candidate
+pub const HANDOFF_MIN_MATCH_RATIO: f64 = 0.3;
+
+// Function to suggest where something seen in `feature` reappeared: features of every
+// camera but `exclude_camera`, created within `window` (start and end inclusive), that
+// match `feature` with at least HANDOFF_MIN_MATCH_RATIO. Returns (camera_id, feature_id,
+// match_ratio), best match first.
+pub fn handoff_candidates(
+    feature: &ImageFeature,
+    exclude_camera: &str,
+    window: (&str, &str),
+    db_name: &str,
+) -> Result<Vec<(String, String, f64)>> {
+    let (start_utc, end_utc) = window;
+    let start = parse_utc_millis(start_utc)?;
+    let end = parse_utc_millis(end_utc)?;
+    if start > end {
+        return Err(VyuwerError::InvalidInput(format!("range start {start_utc} is after its end {end_utc}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!("{FEATURE_SELECT} WHERE camera_id != ?1"))?;
+    let mut rows = stmt.query(params![exclude_camera])?;
+
+    let match_params = MatchParams::default();
+    let mut candidates = Vec::new();
+    while let Some(row) = rows.next()? {
+        let other = feature_from_row(row)?;
+        if !(start..=end).contains(&parse_utc_millis(&other.created_at_utc)?) {
+            continue;
+        }
+        let match_ratio = match_features(feature, &other, &match_params).match_ratio();
+        if match_ratio >= HANDOFF_MIN_MATCH_RATIO {
+            candidates.push((other.camera_id, other.id, match_ratio));
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+    Ok(candidates)
+}
+
+// Function to estimate a camera's real frame rate from its `window` most recent features:
+// 1 / the median interval between consecutive timestamps. The median ignores the odd
+// outage, so a value well below the configured FPS means frames are being dropped or
+// throttled. Needs at least two features in the window, and distinct timestamps.
+pub fn estimated_fps(camera_id: &str, window: usize, db_name: &str) -> Result<f64> {
+    let conn = Connection::open(db_name)?;
+    let times = feature_times(&conn, camera_id)?;
+    let recent = &times[times.len().saturating_sub(window)..];
+    if recent.len() < 2 {
+        return Err(VyuwerError::InvalidInput(format!(
+            "estimating the frame rate of camera {camera_id} needs at least 2 features, found {}",
+            recent.len()
+        )));
+    }
+
+    let mut intervals: Vec<i64> = recent.windows(2).map(|pair| pair[1].0 - pair[0].0).collect();
+    intervals.sort_unstable();
+    let mid = intervals.len() / 2;
+    let median_millis = if intervals.len().is_multiple_of(2) {
+        (intervals[mid - 1] + intervals[mid]) as f64 / 2.0
+    } else {
+        intervals[mid] as f64
+    };
+    if median_millis <= 0.0 {
+        return Err(VyuwerError::InvalidInput(format!(
+            "camera {camera_id} has no measurable interval between its recent features"
+        )));
+    }
+    Ok(1000.0 / median_millis)
+}
+
+// Function to summarise how long a camera's frames took to extract, over the features
+// stored with `insert_image_feature_timed`: (min, p50, p95, max) in milliseconds, the
+// percentiles by nearest rank. High values point at cameras whose frames are expensive.
+pub fn extraction_latency_stats(camera_id: &str, db_name: &str) -> Result<(u64, u64, u64, u64)> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT extract_ms FROM image_features WHERE camera_id = ?1 AND extract_ms IS NOT NULL ORDER BY extract_ms",
+    )?;
+    let latencies = stmt
+        .query_map(params![camera_id], |row| row.get::<_, i64>(0))?
+        .map(|ms| Ok(ms?.max(0) as u64))
+        .collect::<Result<Vec<_>>>()?;
+    if latencies.is_empty() {
+        return Err(VyuwerError::InvalidInput(format!(
+            "camera {camera_id} has no features with a recorded extraction latency"
+        )));
+    }
+    let rank = |p: f64| latencies[((p * latencies.len() as f64).ceil() as usize).clamp(1, latencies.len()) - 1];
+    Ok((latencies[0], rank(0.5), rank(0.95), latencies[latencies.len() - 1]))
+}
@@ -0,0 +1,780 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "opencv")]
+use opencv::{core::Mat, prelude::*};
+
+use crate::audit;
+use crate::error::{Result, VyuwerError};
+use crate::matching::{match_features, MatchParams};
+use crate::model::{self, ImageDescription, ImageFeature};
+use crate::store::FeatureStore;
+use crate::timestamp::{now_utc_iso8601, parse_utc_millis, utc_iso8601_ago};
+#[cfg(feature = "opencv")]
+use crate::{
+    extract::{extract_orb_features, sharpness, OrbParams},
+    model::FrameSize,
+};
+
+// Match ratio against the baseline below which a frame counts as a scene change
+pub const DEFAULT_MIN_MATCH_RATIO: f64 = 0.3;
+
+// A frozen feed repeats its frame, so every pair in the window must match at least this well
+pub const FROZEN_MIN_MATCH_RATIO: f64 = 0.9;
+// Frame-difference mean at or below which a stored frame counts as static
+pub const FROZEN_MAX_MOTION_MEAN: f64 = 0.1;
+// Tampering is only reported after a calm frame; busier scenes change abruptly on their own
+pub const TAMPERING_MAX_PREVIOUS_MOTION: f64 = 5.0;
+// Absolute mean-luminance change (see `extract::brightness_delta`) from which a match-ratio
+// drop is put down to lighting rather than a changed scene
+pub const LIGHTING_CHANGE_MIN_DELTA: f64 = 40.0;
+// During a lighting change the match-ratio threshold is scaled by this factor, so only a
+// much deeper drop still counts as a scene change
+pub const LIGHTING_RATIO_DISCOUNT: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnomalyKind {
+    SceneChange,
+    // An `AnomalyScorer` score over its threshold, from several signals together
+    Combined,
+    // Raised by an `AnomalyRule` registered on a `RuleClassifier`
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyDetail {
+    pub kind: AnomalyKind,
+    pub match_ratio: f64,
+    // 0.0 right at the threshold, 1.0 when nothing matches
+    pub confidence: f64,
+}
+
+// Number of latest features the baseline is compared against when checking for drift
+pub const BASELINE_DRIFT_WINDOW: usize = 10;
+// Mean match ratio to the recent frames below which the baseline counts as stale
+pub const BASELINE_DRIFT_RATIO: f64 = 0.5;
+
+// Function to create the table of promoted baselines. A camera without a row here uses
+// its earliest stored feature.
+pub(crate) fn create_baseline_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS baselines (
+            camera_id TEXT PRIMARY KEY,
+            feature_id TEXT NOT NULL,
+            promoted_at_utc TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Function to load a camera's baseline: the promoted feature if it is still stored,
+// otherwise its earliest stored feature
+pub fn load_baseline(conn: &Connection, camera_id: &str) -> Result<Option<ImageFeature>> {
+    if let Some((feature_id, _)) = promoted_baseline(conn, camera_id)? {
+        if let Some(feature) = model::fetch_feature_by_id(conn, &feature_id)? {
+            return Ok(Some(feature));
+        }
+    }
+    Ok(model::fetch_features(conn, camera_id, Some(1))?.into_iter().next())
+}
+
+fn promoted_baseline(conn: &Connection, camera_id: &str) -> Result<Option<(String, String)>> {
+    Ok(conn
+        .query_row(
+            "SELECT feature_id, promoted_at_utc FROM baselines WHERE camera_id = ?1",
+            params![camera_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+// Function to make a stored feature the camera's baseline
+pub fn set_baseline(camera_id: &str, feature_id: &str, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    promote_baseline(&conn, camera_id, feature_id)
+}
+
+fn promote_baseline(conn: &Connection, camera_id: &str, feature_id: &str) -> Result<()> {
+    if model::fetch_feature_by_id(conn, feature_id)?.is_none() {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    conn.execute(
+        "INSERT INTO baselines (camera_id, feature_id, promoted_at_utc) VALUES (?1, ?2, ?3)
+        ON CONFLICT(camera_id) DO UPDATE SET
+            feature_id = excluded.feature_id, promoted_at_utc = excluded.promoted_at_utc",
+        params![camera_id, feature_id, now_utc_iso8601()],
+    )?;
+    audit::record(conn, "rotate_baseline", camera_id, Some(feature_id))
+}
+
+// Function to keep a camera's baseline current as lighting or seasons change. Meant to be
+// called periodically: once the baseline is at least `interval` old (since promotion, or
+// since it was stored), it is compared with the last BASELINE_DRIFT_WINDOW features. If its
+// mean match ratio has drifted below BASELINE_DRIFT_RATIO, the newest recent feature that
+// still looks normal (matching the other recent frames at DEFAULT_MIN_MATCH_RATIO on average)
+// becomes the baseline. Returns the id of the promoted feature, if any.
+pub fn auto_rotate_baseline(camera_id: &str, interval: Duration, db_name: &str) -> Result<Option<String>> {
+    let conn = Connection::open(db_name)?;
+    let Some(baseline) = load_baseline(&conn, camera_id)? else {
+        return Ok(None);
+    };
+    let since = match promoted_baseline(&conn, camera_id)? {
+        Some((feature_id, promoted_at_utc)) if feature_id == baseline.id => promoted_at_utc,
+        _ => baseline.created_at_utc.clone(),
+    };
+    if parse_utc_millis(&since)? > parse_utc_millis(&utc_iso8601_ago(interval))? {
+        return Ok(None);
+    }
+
+    let recent: Vec<ImageFeature> = model::fetch_latest_features(&conn, camera_id, BASELINE_DRIFT_WINDOW)?
+        .into_iter()
+        .filter(|f| f.id != baseline.id)
+        .collect();
+    if recent.len() < 2 {
+        return Ok(None);
+    }
+    let params = MatchParams::default();
+    let mean_ratio = |feature: &ImageFeature, others: &mut dyn Iterator<Item = &ImageFeature>| {
+        let ratios: Vec<f64> = others.map(|other| match_features(feature, other, &params).match_ratio()).collect();
+        ratios.iter().sum::<f64>() / ratios.len() as f64
+    };
+    if mean_ratio(&baseline, &mut recent.iter()) >= BASELINE_DRIFT_RATIO {
+        return Ok(None);
+    }
+
+    // Newest first, so the freshest normal frame wins
+    let candidate = recent.iter().enumerate().rev().find(|(i, feature)| {
+        let mut others = recent.iter().enumerate().filter(|(j, _)| j != i).map(|(_, other)| other);
+        mean_ratio(feature, &mut others) >= DEFAULT_MIN_MATCH_RATIO
+    });
+    let Some((_, candidate)) = candidate else {
+        return Ok(None);
+    };
+    promote_baseline(&conn, camera_id, &candidate.id)?;
+    Ok(Some(candidate.id.clone()))
+}
+
+// Normal features `suggest_baseline` compares with each other, spread evenly over the history
+pub const SUGGEST_BASELINE_SAMPLE: usize = 50;
+
+// Function to suggest a baseline: the camera's most central normal frame, i.e. the one with
+// the highest mean match ratio to the others. Features whose description records an anomaly
+// are left out, and histories longer than SUGGEST_BASELINE_SAMPLE are sampled evenly. The
+// earliest feature wins ties. Nothing is promoted; pass the id to `set_baseline` to accept
+// it. None when the camera has no normal feature.
+pub fn suggest_baseline(camera_id: &str, db_name: &str) -> Result<Option<String>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT f.id FROM image_features f
+        LEFT JOIN image_description d ON d.image_name = f.img_filename AND d.camera_id = f.camera_id
+        WHERE f.camera_id = ?1 AND d.anomaly IS NULL
+        ORDER BY f.created_at_utc, f.seq, f.id",
+    )?;
+    let ids = stmt
+        .query_map(params![camera_id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let step = ids.len().div_ceil(SUGGEST_BASELINE_SAMPLE).max(1);
+    let mut sample = Vec::new();
+    for id in ids.iter().step_by(step) {
+        sample.extend(model::fetch_feature_by_id(&conn, id)?);
+    }
+    if sample.len() < 2 {
+        return Ok(sample.pop().map(|feature| feature.id));
+    }
+
+    let params = MatchParams::default();
+    let mut best: Option<(f64, &ImageFeature)> = None;
+    for (i, feature) in sample.iter().enumerate() {
+        let total: f64 = sample
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| match_features(feature, other, &params).match_ratio())
+            .sum();
+        let mean = total / (sample.len() - 1) as f64;
+        if best.is_none_or(|(best_mean, _)| mean > best_mean) {
+            best = Some((mean, feature));
+        }
+    }
+    Ok(best.map(|(_, feature)| feature.id.clone()))
+}
+
+// Function to classify `current` against its camera's baseline (the promoted one, else
+// the earliest feature) held in any feature store
+pub fn classify_with_store<S: FeatureStore + ?Sized>(
+    store: &mut S,
+    current: &ImageFeature,
+    min_ratio: f64,
+) -> Result<Option<AnomalyDetail>> {
+    let baseline = store
+        .baseline(&current.camera_id)?
+        .ok_or_else(|| VyuwerError::MissingBaseline(current.camera_id.clone()))?;
+    Ok(classify_anomaly(&baseline, current, min_ratio))
+}
+
+// Function to work through a camera's backlog: every feature without a description is
+// classified against the baseline at `threshold` and described, with the anomaly detail
+// as JSON (or no anomaly). Features without an image file cannot be described and are
+// left alone, as are later features sharing an image already described in this run.
+// The backlog is read inside the write transaction, so a concurrent run waits rather
+// than describing the same images twice. Returns how many features were described;
+// all or none are written.
+pub fn classify_pending(camera_id: &str, threshold: f64, db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let baseline = load_baseline(&tx, camera_id)?
+        .ok_or_else(|| VyuwerError::MissingBaseline(camera_id.to_string()))?;
+    let pending = model::fetch_features_without_description(&tx, camera_id)?;
+    let mut described = HashSet::new();
+    let mut classified = 0;
+    for meta in pending {
+        let Some(image_name) = meta.img_filename else {
+            continue;
+        };
+        if !described.insert(image_name.clone()) {
+            continue;
+        }
+        let Some(current) = model::fetch_feature_by_id(&tx, &meta.id)? else {
+            continue;
+        };
+        let anomaly = classify_anomaly(&baseline, &current, threshold)
+            .map(|detail| serde_json::to_string(&detail))
+            .transpose()?;
+        model::insert_description(
+            &tx,
+            &ImageDescription {
+                image_name,
+                datetime: current.created_at_utc,
+                camera_id: current.camera_id,
+                anomaly,
+            },
+        )?;
+        classified += 1;
+    }
+    tx.commit()?;
+    Ok(classified)
+}
+
+// Function to flag `current` if it matches the baseline less than `min_ratio`
+pub fn classify_anomaly(baseline: &ImageFeature, current: &ImageFeature, min_ratio: f64) -> Option<AnomalyDetail> {
+    let match_ratio = match_features(current, baseline, &MatchParams::default()).match_ratio();
+    if match_ratio >= min_ratio {
+        return None;
+    }
+    Some(scene_change(match_ratio, min_ratio))
+}
+
+// Function to classify like `classify_anomaly`, discounting drops that coincide with an
+// exposure change: when `brightness_delta` reaches LIGHTING_CHANGE_MIN_DELTA in either
+// direction, `min_ratio` is scaled by LIGHTING_RATIO_DISCOUNT
+pub fn classify_anomaly_with_lighting(
+    baseline: &ImageFeature,
+    current: &ImageFeature,
+    min_ratio: f64,
+    brightness_delta: f64,
+) -> Option<AnomalyDetail> {
+    let min_ratio = if brightness_delta.abs() >= LIGHTING_CHANGE_MIN_DELTA {
+        min_ratio * LIGHTING_RATIO_DISCOUNT
+    } else {
+        min_ratio
+    };
+    classify_anomaly(baseline, current, min_ratio)
+}
+
+fn scene_change(match_ratio: f64, min_ratio: f64) -> AnomalyDetail {
+    #[cfg(feature = "metrics")]
+    crate::metrics::anomaly_detected(AnomalyKind::SceneChange);
+    let confidence = if min_ratio > 0.0 {
+        (1.0 - match_ratio / min_ratio).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    AnomalyDetail {
+        kind: AnomalyKind::SceneChange,
+        match_ratio,
+        confidence,
+    }
+}
+
+// |z| at which the motion signal counts fully in `AnomalyScorer::score`
+pub const MOTION_Z_SATURATION: f64 = 3.0;
+
+// Per-frame evidence for `AnomalyScorer`, as measured by the caller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalySignals {
+    // z-score of the frame's motion mean against the camera's history
+    pub motion_z: f64,
+    // Match ratio against the baseline, 1.0 for an unchanged scene
+    pub match_ratio: f64,
+    // Sharpness relative to the baseline's (current / baseline), 1.0 when unchanged
+    pub sharpness_ratio: f64,
+    // Mean-luminance change, see `extract::brightness_delta`
+    pub brightness_delta: f64,
+}
+
+// Relative weight of each signal; only the proportions matter
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyWeights {
+    pub motion: f64,
+    pub match_ratio: f64,
+    pub sharpness: f64,
+    pub brightness: f64,
+}
+
+impl Default for AnomalyWeights {
+    // Led by the match ratio, which `classify_anomaly` relies on alone
+    fn default() -> Self {
+        AnomalyWeights {
+            motion: 0.2,
+            match_ratio: 0.5,
+            sharpness: 0.15,
+            brightness: 0.15,
+        }
+    }
+}
+
+// Combines the signals of a frame into one score in [0, 1] and flags it over `threshold`.
+// Each signal is first mapped to [0, 1]: |motion_z| over MOTION_Z_SATURATION, the drop
+// in match ratio below 1, the loss of sharpness, and |brightness_delta| over
+// LIGHTING_CHANGE_MIN_DELTA, each capped at 1. The score is their weighted mean, so a
+// signal with weight 0 has no influence. Weights and threshold are meant to be tuned per camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyScorer {
+    weights: AnomalyWeights,
+    threshold: f64,
+}
+
+impl AnomalyScorer {
+    pub fn new(weights: AnomalyWeights, threshold: f64) -> Result<Self> {
+        let all = [weights.motion, weights.match_ratio, weights.sharpness, weights.brightness];
+        if !all.iter().all(|w| w.is_finite() && *w >= 0.0) || all.iter().sum::<f64>() == 0.0 {
+            return Err(VyuwerError::InvalidInput(format!(
+                "weights must be non-negative and not all zero, got {weights:?}"
+            )));
+        }
+        if !(threshold > 0.0 && threshold < 1.0) {
+            return Err(VyuwerError::InvalidInput(format!("threshold must be within (0, 1), got {threshold}")));
+        }
+        Ok(AnomalyScorer { weights, threshold })
+    }
+
+    pub fn weights(&self) -> AnomalyWeights {
+        self.weights
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn score(&self, signals: &AnomalySignals) -> f64 {
+        let w = self.weights;
+        let weighted = [
+            (w.motion, signals.motion_z.abs() / MOTION_Z_SATURATION),
+            (w.match_ratio, 1.0 - signals.match_ratio),
+            (w.sharpness, 1.0 - signals.sharpness_ratio),
+            (w.brightness, signals.brightness_delta.abs() / LIGHTING_CHANGE_MIN_DELTA),
+        ];
+        let total: f64 = weighted.iter().map(|(weight, _)| weight).sum();
+        weighted
+            .iter()
+            .map(|(weight, signal)| weight * signal.clamp(0.0, 1.0))
+            .sum::<f64>()
+            / total
+    }
+
+    // Function to flag the frame when its score reaches the threshold; confidence runs from
+    // 0.0 at the threshold to 1.0 at a score of 1
+    pub fn classify(&self, signals: &AnomalySignals) -> Option<AnomalyDetail> {
+        let score = self.score(signals);
+        if score < self.threshold {
+            return None;
+        }
+        #[cfg(feature = "metrics")]
+        crate::metrics::anomaly_detected(AnomalyKind::Combined);
+        Some(AnomalyDetail {
+            kind: AnomalyKind::Combined,
+            match_ratio: signals.match_ratio,
+            confidence: ((score - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0),
+        })
+    }
+}
+
+// Function to flag tampering (lens covered, camera knocked): between consecutive frames the
+// match ratio collapses below `drop_ratio` although the previous frame was calm. A frame
+// losing all its descriptors (e.g. a covered lens) counts as a collapse.
+pub fn detect_tampering(prev: &ImageFeature, current: &ImageFeature, drop_ratio: f64) -> bool {
+    if prev.descriptors.is_empty() || prev.motion_mean > TAMPERING_MAX_PREVIOUS_MOTION {
+        return false;
+    }
+    let match_ratio = if current.descriptors.is_empty() {
+        0.0
+    } else {
+        match_features(current, prev, &MatchParams::default()).match_ratio()
+    };
+    match_ratio < drop_ratio
+}
+
+// Function to pick a `min_ratio` from labelled pairs of the same scene (positives) and
+// of different scenes (negatives). Every observed match ratio is tried as a cut-off and
+// the one maximising Youden's J (true positive rate minus false positive rate) wins; the
+// threshold returned sits halfway between it and the next lower observed ratio, so it
+// falls in the gap between well-separated clusters.
+pub fn calibrate_threshold(
+    positive_pairs: &[(ImageFeature, ImageFeature)],
+    negative_pairs: &[(ImageFeature, ImageFeature)],
+) -> Result<f64> {
+    if positive_pairs.is_empty() || negative_pairs.is_empty() {
+        return Err(VyuwerError::InvalidInput(
+            "calibration needs at least one positive and one negative pair".to_string(),
+        ));
+    }
+    let params = MatchParams::default();
+    let ratios = |pairs: &[(ImageFeature, ImageFeature)]| -> Vec<f64> {
+        pairs.iter().map(|(a, b)| match_features(a, b, &params).match_ratio()).collect()
+    };
+    let (positives, negatives) = (ratios(positive_pairs), ratios(negative_pairs));
+
+    let mut cuts: Vec<f64> = positives.iter().chain(&negatives).copied().collect();
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup();
+    let rate = |scores: &[f64], cut: f64| scores.iter().filter(|&&r| r >= cut).count() as f64 / scores.len() as f64;
+    let mut best = (f64::NEG_INFINITY, 0);
+    for (i, &cut) in cuts.iter().enumerate() {
+        let j = rate(&positives, cut) - rate(&negatives, cut);
+        if j > best.0 {
+            best = (j, i);
+        }
+    }
+    let cut = cuts[best.1];
+    Ok(match best.1.checked_sub(1) {
+        Some(below) => (cut + cuts[below]) / 2.0,
+        None => cut,
+    })
+}
+
+// Outcome counts of `classify_anomaly` over a labelled set, an anomaly being the positive class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfusionCounts {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+impl ConfusionCounts {
+    // Function to get the share of flagged frames that were anomalies; 1.0 when nothing was flagged
+    pub fn precision(&self) -> f64 {
+        let flagged = self.true_positives + self.false_positives;
+        if flagged == 0 {
+            return 1.0;
+        }
+        self.true_positives as f64 / flagged as f64
+    }
+
+    // Function to get the share of anomalies that were flagged; 0.0 when the set has none
+    pub fn recall(&self) -> f64 {
+        let anomalies = self.true_positives + self.false_negatives;
+        if anomalies == 0 {
+            return 0.0;
+        }
+        self.true_positives as f64 / anomalies as f64
+    }
+}
+
+// How `classify_anomaly` did at one `min_ratio`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdMetrics {
+    pub threshold: f64,
+    pub counts: ConfusionCounts,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    // One entry per requested threshold, in the order given
+    pub thresholds: Vec<ThresholdMetrics>,
+    // Area under the ROC curve of the match ratio; None unless the set has both classes
+    pub auc: Option<f64>,
+}
+
+// Function to evaluate `classify_anomaly` against `baseline` on frames labelled true for
+// an anomaly. Each threshold gets its confusion counts with precision and recall. The AUC
+// does not depend on `thresholds`: it is the chance that a random anomaly matches the
+// baseline worse than a random normal frame (ties count half), i.e. the exact area under
+// the ROC curve swept over every possible threshold.
+pub fn evaluate_classifier(
+    labeled: &[(ImageFeature, bool)],
+    baseline: &ImageFeature,
+    thresholds: &[f64],
+) -> EvalReport {
+    let params = MatchParams::default();
+    let scored: Vec<(f64, bool)> = labeled
+        .iter()
+        .map(|(feature, anomalous)| (match_features(feature, baseline, &params).match_ratio(), *anomalous))
+        .collect();
+
+    let metrics = thresholds
+        .iter()
+        .map(|&threshold| {
+            let mut counts = ConfusionCounts::default();
+            for &(match_ratio, anomalous) in &scored {
+                // The same test as `classify_anomaly`
+                match (match_ratio < threshold, anomalous) {
+                    (true, true) => counts.true_positives += 1,
+                    (true, false) => counts.false_positives += 1,
+                    (false, false) => counts.true_negatives += 1,
+                    (false, true) => counts.false_negatives += 1,
+                }
+            }
+            ThresholdMetrics {
+                threshold,
+                counts,
+                precision: counts.precision(),
+                recall: counts.recall(),
+            }
+        })
+        .collect();
+
+    let (positives, negatives): (Vec<_>, Vec<_>) = scored.iter().partition(|(_, anomalous)| *anomalous);
+    let auc = (!positives.is_empty() && !negatives.is_empty()).then(|| {
+        let mut wins = 0.0;
+        for (positive, _) in &positives {
+            for (negative, _) in &negatives {
+                if positive < negative {
+                    wins += 1.0;
+                } else if positive == negative {
+                    wins += 0.5;
+                }
+            }
+        }
+        wins / (positives.len() * negatives.len()) as f64
+    });
+    EvalReport {
+        thresholds: metrics,
+        auc,
+    }
+}
+
+// Match-ratio thresholds with a dead band: a camera enters the alert state when its ratio
+// falls below `enter_threshold` and only leaves it once the ratio recovers to
+// `exit_threshold` or above, so a scene hovering around one threshold does not flap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hysteresis {
+    enter_threshold: f64,
+    exit_threshold: f64,
+}
+
+impl Hysteresis {
+    pub fn new(enter_threshold: f64, exit_threshold: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&enter_threshold) || !(enter_threshold..=1.0).contains(&exit_threshold) {
+            return Err(VyuwerError::InvalidInput(format!(
+                "need 0 <= enter_threshold <= exit_threshold <= 1, got {enter_threshold} and {exit_threshold}"
+            )));
+        }
+        Ok(Hysteresis {
+            enter_threshold,
+            exit_threshold,
+        })
+    }
+
+    pub fn enter_threshold(&self) -> f64 {
+        self.enter_threshold
+    }
+
+    pub fn exit_threshold(&self) -> f64 {
+        self.exit_threshold
+    }
+}
+
+impl Default for Hysteresis {
+    // Without a dead band this behaves like `classify_anomaly` at the default ratio
+    fn default() -> Self {
+        Hysteresis {
+            enter_threshold: DEFAULT_MIN_MATCH_RATIO,
+            exit_threshold: DEFAULT_MIN_MATCH_RATIO,
+        }
+    }
+}
+
+// Per-camera alert state for classifying a stream of frames with hysteresis.
+// State lives in memory only; a new tracker starts with every camera clear.
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyTracker {
+    hysteresis: Hysteresis,
+    alerting: HashSet<String>,
+}
+
+impl AnomalyTracker {
+    pub fn new(hysteresis: Hysteresis) -> Self {
+        AnomalyTracker {
+            hysteresis,
+            alerting: HashSet::new(),
+        }
+    }
+
+    pub fn is_alerting(&self, camera_id: &str) -> bool {
+        self.alerting.contains(camera_id)
+    }
+
+    // Function to classify `current` against `baseline`, updating the camera's alert state
+    pub fn classify(&mut self, baseline: &ImageFeature, current: &ImageFeature) -> Option<AnomalyDetail> {
+        let match_ratio = match_features(current, baseline, &MatchParams::default()).match_ratio();
+        self.observe(&current.camera_id, match_ratio)
+    }
+
+    // Function to feed one match ratio for a camera; returns the anomaly while the camera is alerting
+    pub fn observe(&mut self, camera_id: &str, match_ratio: f64) -> Option<AnomalyDetail> {
+        let threshold = if self.is_alerting(camera_id) {
+            self.hysteresis.exit_threshold
+        } else {
+            self.hysteresis.enter_threshold
+        };
+        if match_ratio >= threshold {
+            self.alerting.remove(camera_id);
+            return None;
+        }
+        self.alerting.insert(camera_id.to_string());
+        Some(scene_change(match_ratio, self.hysteresis.enter_threshold))
+    }
+}
+
+// Everything an `AnomalyRule` gets to look at for one frame
+#[derive(Clone, Copy)]
+pub struct FrameContext<'a> {
+    pub current: &'a ImageFeature,
+    pub baseline: &'a ImageFeature,
+    // Frame-difference statistics, see `extract::motion_stats`
+    pub motion_mean: f64,
+    pub motion_std: f64,
+    // The decoded frame, when the caller still has it
+    #[cfg(feature = "opencv")]
+    pub frame: Option<&'a Mat>,
+}
+
+impl<'a> FrameContext<'a> {
+    // Function to build a context with the motion statistics stored on `current` and no frame
+    pub fn new(current: &'a ImageFeature, baseline: &'a ImageFeature) -> Self {
+        FrameContext {
+            current,
+            baseline,
+            motion_mean: current.motion_mean,
+            motion_std: current.motion_std,
+            #[cfg(feature = "opencv")]
+            frame: None,
+        }
+    }
+}
+
+// A domain-specific check (e.g. "flag if a red object appears") run by a `RuleClassifier`
+// next to the built-in scene-change test. Returns the anomaly when the rule fires;
+// rules usually report `AnomalyKind::Custom`.
+pub trait AnomalyRule {
+    fn evaluate(&self, ctx: &FrameContext) -> Option<AnomalyDetail>;
+}
+
+// Classifies frames with `classify_anomaly` plus any registered rules
+pub struct RuleClassifier {
+    min_ratio: f64,
+    rules: Vec<Box<dyn AnomalyRule>>,
+}
+
+impl RuleClassifier {
+    pub fn new(min_ratio: f64) -> Self {
+        RuleClassifier {
+            min_ratio,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, rule: Box<dyn AnomalyRule>) {
+        self.rules.push(rule);
+    }
+
+    pub fn rules(&self) -> &[Box<dyn AnomalyRule>] {
+        &self.rules
+    }
+
+    // Function to run the scene-change test and every rule, in registration order, and
+    // return all anomalies raised; empty for a normal frame
+    pub fn classify(&self, ctx: &FrameContext) -> Vec<AnomalyDetail> {
+        let mut details: Vec<AnomalyDetail> = classify_anomaly(ctx.baseline, ctx.current, self.min_ratio)
+            .into_iter()
+            .collect();
+        for rule in &self.rules {
+            if let Some(detail) = rule.evaluate(ctx) {
+                #[cfg(feature = "metrics")]
+                crate::metrics::anomaly_detected(detail.kind);
+                details.push(detail);
+            }
+        }
+        details
+    }
+
+    // Function to reduce `classify` to its most confident anomaly, earliest on ties
+    pub fn classify_top(&self, ctx: &FrameContext) -> Option<AnomalyDetail> {
+        self.classify(ctx)
+            .into_iter()
+            .reduce(|best, detail| if detail.confidence > best.confidence { detail } else { best })
+    }
+}
+
+impl Default for RuleClassifier {
+    fn default() -> Self {
+        RuleClassifier::new(DEFAULT_MIN_MATCH_RATIO)
+    }
+}
+
+// Function to classify a live frame against the camera baseline without storing anything
+#[cfg(feature = "opencv")]
+pub fn evaluate_frame(image: &Mat, camera_id: &str, db_name: &str) -> Result<Option<AnomalyDetail>> {
+    let conn = Connection::open(db_name)?;
+    let baseline = load_baseline(&conn, camera_id)?
+        .ok_or_else(|| VyuwerError::MissingBaseline(camera_id.to_string()))?;
+
+    let params = OrbParams::default();
+    let (keypoints, descriptors) = extract_orb_features(image, &params)?;
+    let current = ImageFeature {
+        id: String::new(),
+        keypoints,
+        descriptors,
+        motion_mean: 0.0,
+        motion_std: 0.0,
+        created_at_utc: now_utc_iso8601(),
+        img_filename: None,
+        camera_id: camera_id.to_string(),
+        phash: None,
+        frame_size: Some(FrameSize::new(image.cols() as u32, image.rows() as u32)),
+        extraction_params: Some(params),
+        sharpness: Some(sharpness(image)?),
+        roi: None,
+    };
+    Ok(classify_anomaly(&baseline, &current, DEFAULT_MIN_MATCH_RATIO))
+}
+
+// Function to check whether a camera's last `window` features all show the same static
+// frame: near-zero motion and near-identical descriptors between every pair. Cameras with
+// fewer than `window` features are not reported as frozen.
+pub fn detect_frozen(camera_id: &str, window: usize, db_name: &str) -> Result<bool> {
+    if window < 2 {
+        return Err(VyuwerError::InvalidInput("a frozen-feed window needs at least 2 features".to_string()));
+    }
+    let conn = Connection::open(db_name)?;
+    let features = model::fetch_latest_features(&conn, camera_id, window)?;
+    if features.len() < window || features.iter().any(|f| f.motion_mean > FROZEN_MAX_MOTION_MEAN) {
+        return Ok(false);
+    }
+    let params = MatchParams::default();
+    let frozen = features.iter().enumerate().all(|(i, a)| {
+        features[i + 1..].iter().all(|b| {
+            // Two featureless frames (e.g. a black feed) are as identical as it gets
+            (a.descriptors.is_empty() && b.descriptors.is_empty())
+                || match_features(a, b, &params).match_ratio() >= FROZEN_MIN_MATCH_RATIO
+        })
+    });
+    Ok(frozen)
+}
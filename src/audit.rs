@@ -0,0 +1,71 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::timestamp::{now_utc_iso8601, parse_utc_millis};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    pub operation: String,
+    pub target_id: String,
+    pub details: Option<String>,
+}
+
+// Function to create the append-only audit log; triggers reject any UPDATE or DELETE
+pub(crate) fn create_audit_log(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            details TEXT
+        );
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;",
+    )?;
+    Ok(())
+}
+
+// Function to append one entry to the audit log
+pub(crate) fn record(conn: &Connection, operation: &str, target_id: &str, details: Option<&str>) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, operation, target_id, details) VALUES (?1, ?2, ?3, ?4)",
+        params![now_utc_iso8601(), operation, target_id, details],
+    )?;
+    Ok(())
+}
+
+// Function to read audit entries recorded at or after `ts`, oldest first. Timestamps
+// are compared after parsing, so `ts` may be given with or without milliseconds.
+pub fn audit_since(ts: &str, db_name: &str) -> Result<Vec<AuditEntry>> {
+    let since = parse_utc_millis(ts)?;
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare("SELECT id, timestamp, operation, target_id, details FROM audit_log ORDER BY id")?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                operation: row.get(2)?,
+                target_id: row.get(3)?,
+                details: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut recent = Vec::new();
+    for entry in entries {
+        if parse_utc_millis(&entry.timestamp)? >= since {
+            recent.push(entry);
+        }
+    }
+    Ok(recent)
+}
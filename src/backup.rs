@@ -0,0 +1,51 @@
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::Connection;
+
+use crate::error::{Result, VyuwerError};
+use crate::progress::ProgressUpdate;
+
+// How long to wait before retrying a step that found the source locked by a writer
+pub const BACKUP_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+// Function to copy a live database to `dst_path` with SQLite's online backup, `pages_per_step`
+// pages at a time. The source is only locked for the length of one step, so ingestion can
+// keep writing in between; writes made during the backup restart it from the beginning.
+// An existing file at `dst_path` is overwritten.
+pub fn backup_to(dst_path: &str, pages_per_step: usize, db_name: &str) -> Result<()> {
+    backup_to_with_progress(dst_path, pages_per_step, db_name, |_| {})
+}
+
+// Function to back up like `backup_to`, reporting pages copied out of the source's page
+// count after every step
+pub fn backup_to_with_progress(
+    dst_path: &str,
+    pages_per_step: usize,
+    db_name: &str,
+    mut progress: impl FnMut(ProgressUpdate),
+) -> Result<()> {
+    let pages_per_step = i32::try_from(pages_per_step)
+        .ok()
+        .filter(|&pages| pages > 0)
+        .ok_or_else(|| VyuwerError::InvalidInput(format!("cannot back up {pages_per_step} pages per step")))?;
+    let src = Connection::open(db_name)?;
+    let mut dst = Connection::open(dst_path)?;
+    let backup = Backup::new(&src, &mut dst)?;
+    loop {
+        let step = backup.step(pages_per_step)?;
+        let pages = backup.progress();
+        let total = pages.pagecount.max(0) as usize;
+        progress(ProgressUpdate {
+            done: total.saturating_sub(pages.remaining.max(0) as usize),
+            total: Some(total),
+            stage: "copying",
+        });
+        match step {
+            StepResult::Done => return Ok(()),
+            StepResult::More => {}
+            _ => thread::sleep(BACKUP_RETRY_DELAY),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use crate::model::ImageFeature;
+
+// Bounded LRU of recently fetched features, keyed by camera id
+pub struct FeatureCache {
+    entries: LruCache<String, ImageFeature>,
+    hits: u64,
+    misses: u64,
+}
+
+impl FeatureCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        FeatureCache {
+            entries: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.cap().get()
+    }
+
+    // Function to look up a camera, counting the hit or miss
+    pub fn get(&mut self, camera_id: &str) -> Option<ImageFeature> {
+        match self.entries.get(camera_id) {
+            Some(feature) => {
+                self.hits += 1;
+                Some(feature.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn put(&mut self, camera_id: &str, feature: ImageFeature) {
+        self.entries.put(camera_id.to_string(), feature);
+    }
+
+    // Function to drop the cached entry for a camera after it was written to
+    pub fn invalidate(&mut self, camera_id: &str) {
+        self.entries.pop(camera_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "opencv")]
+use std::time::Duration;
+
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+
+#[cfg(feature = "opencv")]
+use rusqlite::OptionalExtension;
+use rusqlite::{params, Connection};
+
+use crate::audit;
+use crate::error::{Result, VyuwerError};
+use crate::frames;
+use crate::model::{self, FEATURE_ID_TABLES};
+use crate::schema;
+#[cfg(feature = "opencv")]
+use crate::timestamp::{now_utc_iso8601, parse_utc_millis, utc_iso8601_ago};
+
+// What the insert path does with a feature below its camera's keypoint minimum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowKeypointAction {
+    // Refuse it with `VyuwerError::InsufficientKeypoints`
+    Reject,
+    // Store it with `low_quality` set, see `model::low_quality_features`
+    Flag,
+}
+
+impl LowKeypointAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            LowKeypointAction::Reject => "reject",
+            LowKeypointAction::Flag => "flag",
+        }
+    }
+}
+
+// A camera's minimum keypoint count for stored features, checked on every insert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeypointGate {
+    pub min_keypoints: usize,
+    pub action: LowKeypointAction,
+}
+
+// Function to create the table of per-camera metadata (location for map views, keypoint gate)
+pub(crate) fn create_camera_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cameras (
+            camera_id TEXT PRIMARY KEY,
+            latitude REAL,
+            longitude REAL,
+            min_keypoints INTEGER,
+            low_keypoint_action TEXT
+        )",
+        [],
+    )?;
+    schema::add_column_if_missing(conn, "cameras", "min_keypoints", "INTEGER")?;
+    schema::add_column_if_missing(conn, "cameras", "low_keypoint_action", "TEXT")?;
+    Ok(())
+}
+
+// Function to set or, with None, clear the keypoint minimum for a camera's new features.
+// Features already stored are not re-checked.
+pub fn set_keypoint_gate(camera_id: &str, gate: Option<KeypointGate>, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    conn.execute(
+        "INSERT INTO cameras (camera_id, min_keypoints, low_keypoint_action) VALUES (?1, ?2, ?3)
+        ON CONFLICT(camera_id) DO UPDATE SET
+            min_keypoints = excluded.min_keypoints, low_keypoint_action = excluded.low_keypoint_action",
+        params![
+            camera_id,
+            gate.map(|gate| gate.min_keypoints as i64),
+            gate.map(|gate| gate.action.as_str())
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn keypoint_gate(camera_id: &str, db_name: &str) -> Result<Option<KeypointGate>> {
+    let conn = Connection::open(db_name)?;
+    fetch_keypoint_gate(&conn, camera_id)
+}
+
+pub(crate) fn fetch_keypoint_gate(conn: &Connection, camera_id: &str) -> Result<Option<KeypointGate>> {
+    let mut stmt = conn.prepare(
+        "SELECT min_keypoints, low_keypoint_action FROM cameras
+        WHERE camera_id = ?1 AND min_keypoints IS NOT NULL",
+    )?;
+    let mut rows = stmt.query(params![camera_id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let action = match row.get::<_, Option<String>>(1)?.as_deref() {
+        Some("flag") => LowKeypointAction::Flag,
+        _ => LowKeypointAction::Reject,
+    };
+    Ok(Some(KeypointGate {
+        min_keypoints: row.get::<_, i64>(0)? as usize,
+        action,
+    }))
+}
+
+// Lengths OpenCV accepts for a distortion vector: (k1, k2, p1, p2[, k3[, k4, k5, k6[, s1..s4[, tx, ty]]]])
+const DISTORTION_LENGTHS: [usize; 5] = [4, 5, 8, 12, 14];
+
+// A camera's intrinsics as OpenCV's calib3d uses them
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraCalibration {
+    // 3x3 camera matrix [fx 0 cx; 0 fy cy; 0 0 1], row-major
+    pub intrinsics: [f64; 9],
+    // Distortion coefficients in OpenCV's order, one of `DISTORTION_LENGTHS` long
+    pub distortion: Vec<f64>,
+}
+
+// Function to create the table of per-camera calibrations, kept apart from `cameras`
+// since most cameras are never calibrated
+pub(crate) fn create_calibration_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS camera_calibration (
+            camera_id TEXT PRIMARY KEY,
+            intrinsics TEXT NOT NULL,
+            distortion TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Function to record a camera's calibration, replacing any earlier one
+pub fn set_calibration(camera_id: &str, calibration: &CameraCalibration, db_name: &str) -> Result<()> {
+    if !DISTORTION_LENGTHS.contains(&calibration.distortion.len()) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "{} distortion coefficients given, expected one of {DISTORTION_LENGTHS:?}",
+            calibration.distortion.len()
+        )));
+    }
+    if !calibration.intrinsics.iter().chain(&calibration.distortion).all(|v| v.is_finite()) {
+        return Err(VyuwerError::InvalidInput(format!("calibration of {camera_id} has non-finite values")));
+    }
+    let conn = Connection::open(db_name)?;
+    conn.execute(
+        "INSERT INTO camera_calibration (camera_id, intrinsics, distortion) VALUES (?1, ?2, ?3)
+        ON CONFLICT(camera_id) DO UPDATE SET intrinsics = excluded.intrinsics, distortion = excluded.distortion",
+        params![
+            camera_id,
+            serde_json::to_string(&calibration.intrinsics)?,
+            serde_json::to_string(&calibration.distortion)?
+        ],
+    )?;
+    Ok(())
+}
+
+// Function to get a camera's calibration, if one was recorded
+pub fn get_calibration(camera_id: &str, db_name: &str) -> Result<Option<CameraCalibration>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare("SELECT intrinsics, distortion FROM camera_calibration WHERE camera_id = ?1")?;
+    let mut rows = stmt.query(params![camera_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(CameraCalibration {
+            intrinsics: serde_json::from_str(&row.get::<_, String>(0)?)?,
+            distortion: serde_json::from_str(&row.get::<_, String>(1)?)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+// Longest side of a camera fingerprint in pixels
+#[cfg(feature = "opencv")]
+pub const FINGERPRINT_MAX_SIDE: i32 = 160;
+// Age after which a cached fingerprint is rebuilt from the camera's latest normal frame
+#[cfg(feature = "opencv")]
+pub const FINGERPRINT_REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+#[cfg(feature = "opencv")]
+const FINGERPRINT_JPEG_QUALITY: i32 = 80;
+
+// Function to create the table caching one small JPEG per camera, see `camera_fingerprint`
+pub(crate) fn create_fingerprint_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS camera_fingerprints (
+            camera_id TEXT PRIMARY KEY,
+            feature_id TEXT NOT NULL,
+            jpeg BLOB NOT NULL,
+            created_at_utc TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Function to get a small JPEG identifying a camera at a glance, e.g. in a camera list.
+// It is the camera's latest stored frame whose description reports no anomaly, scaled to
+// FINGERPRINT_MAX_SIDE. The result is cached and served from the cache until it is
+// FINGERPRINT_REFRESH old or its source feature is deleted.
+#[cfg(feature = "opencv")]
+pub fn camera_fingerprint(camera_id: &str, db_name: &str) -> Result<Vec<u8>> {
+    let conn = Connection::open(db_name)?;
+    let mut cached = conn.prepare(
+        "SELECT p.jpeg, p.created_at_utc FROM camera_fingerprints p
+        JOIN image_features f ON f.id = p.feature_id
+        WHERE p.camera_id = ?1",
+    )?;
+    let mut rows = cached.query(params![camera_id])?;
+    if let Some(row) = rows.next()? {
+        let created_at: String = row.get(1)?;
+        if parse_utc_millis(&created_at)? >= parse_utc_millis(&utc_iso8601_ago(FINGERPRINT_REFRESH))? {
+            return Ok(row.get(0)?);
+        }
+    }
+
+    let feature_id: String = conn
+        .query_row(
+            "SELECT f.id FROM image_features f
+            JOIN frames fr ON fr.feature_id = f.id
+            LEFT JOIN image_description d ON d.image_name = f.img_filename AND d.camera_id = f.camera_id
+            WHERE f.camera_id = ?1 AND d.anomaly IS NULL
+            ORDER BY f.created_at_utc DESC, f.seq DESC, f.id DESC LIMIT 1",
+            params![camera_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| VyuwerError::InvalidInput(format!("camera {camera_id} has no normal frame to fingerprint")))?;
+    let encoded =
+        frames::fetch_frame_bytes(&conn, &feature_id)?.ok_or_else(|| VyuwerError::MissingFrame(feature_id.clone()))?;
+    let frame = frames::decode_frame(&encoded)?;
+    let scale = f64::from(FINGERPRINT_MAX_SIDE) / f64::from(frame.cols().max(frame.rows()));
+    let small = if scale < 1.0 {
+        let size = Size::new(
+            ((f64::from(frame.cols()) * scale).round() as i32).max(1),
+            ((f64::from(frame.rows()) * scale).round() as i32).max(1),
+        );
+        let mut small = Mat::default();
+        imgproc::resize(&frame, &mut small, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+        small
+    } else {
+        frame
+    };
+    let mut jpeg = Vector::<u8>::new();
+    let quality = Vector::<i32>::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, FINGERPRINT_JPEG_QUALITY]);
+    imgcodecs::imencode(".jpg", &small, &mut jpeg, &quality)?;
+    let jpeg = jpeg.to_vec();
+    conn.execute(
+        "INSERT OR REPLACE INTO camera_fingerprints (camera_id, feature_id, jpeg, created_at_utc)
+        VALUES (?1, ?2, ?3, ?4)",
+        params![camera_id, feature_id, jpeg, now_utc_iso8601()],
+    )?;
+    Ok(jpeg)
+}
+
+// Function to record where a camera is mounted, in WGS84 degrees
+pub fn set_camera_location(camera_id: &str, latitude: f64, longitude: f64, db_name: &str) -> Result<()> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "({latitude}, {longitude}) is not a valid latitude/longitude"
+        )));
+    }
+    let conn = Connection::open(db_name)?;
+    conn.execute(
+        "INSERT INTO cameras (camera_id, latitude, longitude) VALUES (?1, ?2, ?3)
+        ON CONFLICT(camera_id) DO UPDATE SET latitude = excluded.latitude, longitude = excluded.longitude",
+        params![camera_id, latitude, longitude],
+    )?;
+    Ok(())
+}
+
+// Function to get a camera's (latitude, longitude), if one was recorded
+pub fn camera_location(camera_id: &str, db_name: &str) -> Result<Option<(f64, f64)>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT latitude, longitude FROM cameras
+        WHERE camera_id = ?1 AND latitude IS NOT NULL AND longitude IS NOT NULL",
+    )?;
+    let mut rows = stmt.query(params![camera_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+        None => Ok(None),
+    }
+}
+
+// Function to reduce a camera id to the form duplicates share: trimmed and lowercased,
+// so `Camera_1` and `camera_1 ` are one camera
+pub fn canonical_camera_id(camera_id: &str) -> String {
+    camera_id.trim().to_lowercase()
+}
+
+// Tables holding a camera id; the audit log keeps the ids it was written with
+const CAMERA_ID_TABLES: [&str; 6] =
+    ["cameras", "image_features", "image_description", "baselines", "camera_calibration", "camera_fingerprints"];
+
+// Function to group camera ids, from the registry and every table referencing cameras,
+// that normalize to the same canonical form. Only groups with more than one id are
+// returned, each sorted, ordered by their first id.
+pub fn find_duplicate_cameras(db_name: &str) -> Result<Vec<Vec<String>>> {
+    let conn = Connection::open(db_name)?;
+    let union = CAMERA_ID_TABLES
+        .iter()
+        .map(|table| format!("SELECT camera_id FROM {table}"))
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+    let mut stmt = conn.prepare(&format!("{union} ORDER BY camera_id"))?;
+    let camera_ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for camera_id in camera_ids {
+        groups.entry(canonical_camera_id(&camera_id)).or_default().push(camera_id);
+    }
+    let mut duplicates: Vec<Vec<String>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    duplicates.sort();
+    Ok(duplicates)
+}
+
+// Function to fold `duplicates` into `canonical_id` in one transaction: features and
+// descriptions are repointed (duplicate features sequenced after the canonical camera's),
+// and registry (location and keypoint gate), baseline, calibration and fingerprint entries
+// move over unless the canonical camera already has one, after which the duplicates'
+// entries are removed. Returns how many features moved.
+pub fn merge_cameras(canonical_id: &str, duplicates: &[&str], db_name: &str) -> Result<usize> {
+    if duplicates.contains(&canonical_id) {
+        return Err(VyuwerError::InvalidInput(format!("{canonical_id} cannot be merged into itself")));
+    }
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let mut moved = 0;
+    for duplicate in duplicates {
+        let offset: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(seq), 0) FROM image_features WHERE camera_id = ?1",
+            params![canonical_id],
+            |row| row.get(0),
+        )?;
+        let mut stmt = tx.prepare("SELECT id FROM image_features WHERE camera_id = ?1")?;
+        let feature_ids = stmt
+            .query_map(params![duplicate], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        moved += tx.execute(
+            "UPDATE image_features SET camera_id = ?1, seq = seq + ?3 WHERE camera_id = ?2",
+            params![canonical_id, duplicate, offset],
+        )?;
+        // The camera is part of the content hash
+        for feature_id in &feature_ids {
+            model::set_content_hash(&tx, feature_id)?;
+        }
+        tx.execute(
+            "UPDATE image_description SET camera_id = ?1 WHERE camera_id = ?2",
+            params![canonical_id, duplicate],
+        )?;
+        for (table, columns) in [
+            ("cameras", "latitude, longitude, min_keypoints, low_keypoint_action"),
+            ("baselines", "feature_id, promoted_at_utc"),
+            ("camera_calibration", "intrinsics, distortion"),
+            ("camera_fingerprints", "feature_id, jpeg, created_at_utc"),
+        ] {
+            tx.execute(
+                &format!(
+                    "INSERT OR IGNORE INTO {table} (camera_id, {columns})
+                    SELECT ?1, {columns} FROM {table} WHERE camera_id = ?2"
+                ),
+                params![canonical_id, duplicate],
+            )?;
+            tx.execute(&format!("DELETE FROM {table} WHERE camera_id = ?1"), params![duplicate])?;
+        }
+    }
+    audit::record(&tx, "merge_cameras", canonical_id, Some(&duplicates.join(", ")))?;
+    tx.commit()?;
+    Ok(moved)
+}
+
+// What `forget_camera` erased
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForgetReport {
+    // Rows deleted, by table; tables with nothing to delete are listed with 0
+    pub rows_removed: BTreeMap<String, usize>,
+    // Frame files deleted from the frame directory; files still shared with another
+    // camera's frames are kept
+    pub frame_files_removed: usize,
+}
+
+// Function to erase everything stored about a camera, e.g. for a data-protection request:
+// its features and every row hanging off them (descriptors, frames, tags, float and extra
+// descriptor sets, flow stats), its descriptions, registry entry, keypoint gate, baseline,
+// calibration and cached fingerprint, in one transaction; frame files are removed once it commits. The
+// append-only audit log keeps its entries, which hold ids and operations but no content,
+// and gains one for this erasure.
+pub fn forget_camera(camera_id: &str, db_name: &str) -> Result<ForgetReport> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let mut report = ForgetReport::default();
+    let camera_features = "SELECT id FROM image_features WHERE camera_id = ?1";
+
+    let files = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT frame_path, frame_sha256 FROM frames
+            WHERE feature_id IN ({camera_features}) AND frame_path IS NOT NULL"
+        ))?;
+        let files = stmt
+            .query_map(params![camera_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+        files
+    };
+    for table in FEATURE_ID_TABLES.iter().filter(|table| !CAMERA_ID_TABLES.contains(table)) {
+        let removed = tx.execute(
+            &format!("DELETE FROM {table} WHERE feature_id IN ({camera_features})"),
+            params![camera_id],
+        )?;
+        report.rows_removed.insert(table.to_string(), removed);
+    }
+    for table in CAMERA_ID_TABLES.iter().filter(|&&table| table != "image_features") {
+        // Baselines and fingerprints are keyed by camera but could point at this camera's
+        // feature from another
+        let by_feature = if FEATURE_ID_TABLES.contains(table) {
+            format!(" OR feature_id IN ({camera_features})")
+        } else {
+            String::new()
+        };
+        let removed =
+            tx.execute(&format!("DELETE FROM {table} WHERE camera_id = ?1{by_feature}"), params![camera_id])?;
+        report.rows_removed.insert(table.to_string(), removed);
+    }
+    let removed = tx.execute("DELETE FROM image_features WHERE camera_id = ?1", params![camera_id])?;
+    report.rows_removed.insert("image_features".to_string(), removed);
+    audit::record(&tx, "forget_camera", camera_id, None)?;
+    tx.commit()?;
+
+    report.frame_files_removed = frames::remove_unreferenced_files(&conn, &files)?;
+    Ok(report)
+}
@@ -0,0 +1,151 @@
+use rusqlite::Connection;
+
+use crate::audit;
+use crate::error::{Result, VyuwerError};
+use crate::matching::{match_features, MatchParams};
+use crate::model::{self, ImageFeature};
+use crate::progress::ProgressUpdate;
+
+// Function to group a camera's features into visually similar scenes.
+// Every pair of features is matched, so the cost is O(n²) descriptor matches;
+// use `max_features` to bound n on long recordings (the oldest features are taken).
+pub fn cluster_features(
+    camera_id: &str,
+    similarity_ratio: f64,
+    max_features: Option<usize>,
+    db_name: &str,
+) -> Result<Vec<Vec<String>>> {
+    cluster_features_with_progress(camera_id, similarity_ratio, max_features, db_name, |_| {})
+}
+
+// Function to cluster like `cluster_features`, reporting each feature once it has been
+// matched against all later ones
+pub fn cluster_features_with_progress(
+    camera_id: &str,
+    similarity_ratio: f64,
+    max_features: Option<usize>,
+    db_name: &str,
+    mut progress: impl FnMut(ProgressUpdate),
+) -> Result<Vec<Vec<String>>> {
+    check_similarity_ratio(similarity_ratio)?;
+    let conn = Connection::open(db_name)?;
+    let features = model::fetch_features(&conn, camera_id, max_features)?;
+
+    let match_params = MatchParams::default();
+    let mut sets = DisjointSet::new(features.len());
+    for i in 0..features.len() {
+        for j in (i + 1)..features.len() {
+            if match_features(&features[i], &features[j], &match_params).match_ratio() >= similarity_ratio {
+                sets.union(i, j);
+            }
+        }
+        progress(ProgressUpdate {
+            done: i + 1,
+            total: Some(features.len()),
+            stage: "matching",
+        });
+    }
+
+    // Emit clusters in order of their earliest feature
+    let mut clusters: Vec<Vec<String>> = Vec::new();
+    let mut cluster_of_root = vec![None; features.len()];
+    for (i, feature) in features.iter().enumerate() {
+        let root = sets.find(i);
+        let index = *cluster_of_root[root].get_or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[index].push(feature.id.clone());
+    }
+    Ok(clusters)
+}
+
+// Function to delete a camera's near-duplicate frames: walking in time order, a feature
+// is removed when it matches the last kept one with at least `similarity_ratio`.
+// Returns how many features were deleted. The deletion is audited as one "deduplicate"
+// entry, and open `Database` handles drop their cached features on their next read.
+pub fn deduplicate_camera(camera_id: &str, similarity_ratio: f64, db_name: &str) -> Result<usize> {
+    deduplicate_camera_with_progress(camera_id, similarity_ratio, db_name, |_| {})
+}
+
+// Function to deduplicate like `deduplicate_camera`, reporting after each feature is compared
+pub fn deduplicate_camera_with_progress(
+    camera_id: &str,
+    similarity_ratio: f64,
+    db_name: &str,
+    mut progress: impl FnMut(ProgressUpdate),
+) -> Result<usize> {
+    check_similarity_ratio(similarity_ratio)?;
+    let mut conn = Connection::open(db_name)?;
+    let features = model::fetch_features(&conn, camera_id, None)?;
+
+    let match_params = MatchParams::default();
+    let mut kept: Option<&ImageFeature> = None;
+    let mut duplicates = Vec::new();
+    for (i, feature) in features.iter().enumerate() {
+        match kept {
+            Some(kept) if match_features(kept, feature, &match_params).match_ratio() >= similarity_ratio => {
+                duplicates.push(feature.id.as_str());
+            }
+            _ => kept = Some(feature),
+        }
+        progress(ProgressUpdate {
+            done: i + 1,
+            total: Some(features.len()),
+            stage: "matching",
+        });
+    }
+
+    let tx = conn.transaction()?;
+    for id in &duplicates {
+        model::delete_feature_row(&tx, id)?;
+    }
+    audit::record(
+        &tx,
+        "deduplicate",
+        camera_id,
+        Some(&format!("{} duplicate feature(s)", duplicates.len())),
+    )?;
+    tx.commit()?;
+    Ok(duplicates.len())
+}
+
+fn check_similarity_ratio(similarity_ratio: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&similarity_ratio) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "similarity_ratio must be within [0, 1], got {similarity_ratio}"
+        )));
+    }
+    Ok(())
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        DisjointSet { parent: (0..n).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = i;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            self.parent[rb] = ra;
+        }
+    }
+}
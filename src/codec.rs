@@ -0,0 +1,175 @@
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{Result, VyuwerError};
+use crate::model::{DescriptorMatrix, KeyPointData};
+
+// Upper bound on any encoded blob. Length prefixes are checked against the remaining limit
+// before allocating, so a corrupt blob claiming a huge Vec fails instead of exhausting memory.
+pub const MAX_BLOB_BYTES: u64 = 64 * 1024 * 1024;
+
+// Little-endian, fixed-width integers and trailing bytes allowed: byte-compatible with
+// `bincode::serialize`, which wrote every blob stored before this module existed. The byte
+// order is pinned rather than native, so a database reads the same on any architecture;
+// tests/fixtures holds golden blobs guarding the layout.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_limit(MAX_BLOB_BYTES)
+        .with_little_endian()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(options().serialize(value)?)
+}
+
+pub fn decode<T: DeserializeOwned>(blob: &[u8]) -> Result<T> {
+    Ok(options().deserialize(blob)?)
+}
+
+// First byte of a v3 keypoint blob, naming how the rest is encoded
+pub const KEYPOINT_FORMAT_BINCODE: u8 = 0;
+pub const KEYPOINT_FORMAT_DELTA: u8 = 1;
+
+// Function to encode keypoints compactly, flagged KEYPOINT_FORMAT_DELTA: a varint count,
+// then the x, y, size and angle columns in turn, each value stored as the zigzag varint of
+// its bit pattern minus the previous keypoint's. Lossless. Keypoints keep their order, since
+// descriptor row i belongs to keypoint i; ORB emits them pyramid level by level, so
+// neighbours tend to lie close together and share a size, which is what shrinks the deltas.
+pub fn encode_keypoints_delta(keypoints: &[KeyPointData]) -> Vec<u8> {
+    let mut blob = vec![KEYPOINT_FORMAT_DELTA];
+    write_varint(&mut blob, keypoints.len() as u64);
+    let fields: [fn(&KeyPointData) -> f32; 4] = [|k| k.x, |k| k.y, |k| k.size, |k| k.angle];
+    for field in fields {
+        let mut previous = 0u32;
+        for keypoint in keypoints {
+            let bits = field(keypoint).to_bits();
+            let delta = bits.wrapping_sub(previous) as i32;
+            write_varint(&mut blob, u64::from(((delta << 1) ^ (delta >> 31)) as u32));
+            previous = bits;
+        }
+    }
+    blob
+}
+
+// Function to decode a keypoint blob that starts with a KEYPOINT_FORMAT_* byte
+pub fn decode_flagged_keypoints(blob: &[u8]) -> Result<Vec<KeyPointData>> {
+    match blob.split_first() {
+        Some((&KEYPOINT_FORMAT_BINCODE, rest)) => decode(rest),
+        Some((&KEYPOINT_FORMAT_DELTA, rest)) => decode_keypoints_delta(rest),
+        Some((&format, _)) => Err(corrupt(format!("unknown keypoint format {format}"))),
+        None => Err(corrupt("empty keypoint blob".to_string())),
+    }
+}
+
+fn decode_keypoints_delta(mut bytes: &[u8]) -> Result<Vec<KeyPointData>> {
+    let count = read_varint(&mut bytes)?;
+    // Every keypoint takes at least four bytes, one per column; checked before allocating
+    if count.saturating_mul(4) > bytes.len() as u64 {
+        return Err(corrupt(format!("keypoint blob too short for {count} keypoints")));
+    }
+    let count = count as usize;
+    let mut columns = [vec![0f32; count], vec![0f32; count], vec![0f32; count], vec![0f32; count]];
+    for column in &mut columns {
+        let mut previous = 0u32;
+        for value in column.iter_mut() {
+            let zigzag = u32::try_from(read_varint(&mut bytes)?)
+                .map_err(|_| corrupt("keypoint delta does not fit 32 bits".to_string()))?;
+            let delta = ((zigzag >> 1) as i32) ^ -((zigzag & 1) as i32);
+            previous = previous.wrapping_add(delta as u32);
+            *value = f32::from_bits(previous);
+        }
+    }
+    let [x, y, size, angle] = columns;
+    Ok((0..count)
+        .map(|i| KeyPointData {
+            x: x[i],
+            y: y[i],
+            size: size[i],
+            angle: angle[i],
+        })
+        .collect())
+}
+
+// Function to encode `descriptors` against a base matrix of the same shape, e.g. the
+// previous frame of a static scene: varint rows and cols, then the XOR of the two byte
+// arrays as alternating varint runs of zero bytes and of literal (non-zero) bytes, each
+// literal run followed by its bytes. Identical matrices shrink to a few bytes.
+pub fn encode_descriptor_delta(descriptors: &DescriptorMatrix, base: &DescriptorMatrix) -> Result<Vec<u8>> {
+    if (descriptors.rows, descriptors.cols) != (base.rows, base.cols) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot delta-encode {}x{} descriptors against a {}x{} base",
+            descriptors.rows, descriptors.cols, base.rows, base.cols
+        )));
+    }
+    let mut blob = Vec::new();
+    write_varint(&mut blob, descriptors.rows as u64);
+    write_varint(&mut blob, descriptors.cols as u64);
+    let xor: Vec<u8> = descriptors.data.iter().zip(&base.data).map(|(a, b)| a ^ b).collect();
+    let mut rest = &xor[..];
+    while !rest.is_empty() {
+        let zeros = rest.iter().take_while(|&&byte| byte == 0).count();
+        let literals = rest[zeros..].iter().take_while(|&&byte| byte != 0).count();
+        write_varint(&mut blob, zeros as u64);
+        write_varint(&mut blob, literals as u64);
+        blob.extend_from_slice(&rest[zeros..zeros + literals]);
+        rest = &rest[zeros + literals..];
+    }
+    Ok(blob)
+}
+
+// Function to rebuild descriptors from `encode_descriptor_delta` output and the same base
+pub fn decode_descriptor_delta(mut bytes: &[u8], base: &DescriptorMatrix) -> Result<DescriptorMatrix> {
+    let rows = read_varint(&mut bytes)?;
+    let cols = read_varint(&mut bytes)?;
+    if (rows, cols) != (base.rows as u64, base.cols as u64) {
+        return Err(corrupt(format!(
+            "{rows}x{cols} descriptor delta does not fit its {}x{} base",
+            base.rows, base.cols
+        )));
+    }
+    let mut data = base.data.clone();
+    let mut offset = 0usize;
+    while !bytes.is_empty() {
+        let zeros = read_varint(&mut bytes)? as usize;
+        let literals = read_varint(&mut bytes)? as usize;
+        let start = offset.saturating_add(zeros);
+        let end = start.saturating_add(literals);
+        if end > data.len() || literals > bytes.len() {
+            return Err(corrupt("descriptor delta runs past its matrix".to_string()));
+        }
+        for (byte, diff) in data[start..end].iter_mut().zip(&bytes[..literals]) {
+            *byte ^= diff;
+        }
+        bytes = &bytes[literals..];
+        offset = end;
+    }
+    DescriptorMatrix::new(base.rows, base.cols, data)
+}
+
+// LEB128: seven bits per byte, low bits first, high bit set on all but the last byte
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(|| corrupt("truncated varint".to_string()))?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(corrupt("varint longer than 64 bits".to_string()))
+}
+
+fn corrupt(message: String) -> VyuwerError {
+    VyuwerError::Codec(Box::new(bincode::ErrorKind::Custom(message)))
+}
@@ -0,0 +1,317 @@
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::ptr::NonNull;
+
+use rusqlite::serialize::OwnedData;
+use rusqlite::{ffi, Connection, DatabaseName, OpenFlags};
+
+use crate::cache::FeatureCache;
+use crate::error::{Result, VyuwerError};
+use crate::model::{self, BatchInsertReport, ImageDescription, ImageFeature, OnConflict};
+use crate::schema;
+
+// How hard SQLite works to keep commits on disk, traded against write speed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DurabilityMode {
+    // Rollback journal, `synchronous = FULL` (SQLite's own defaults): a commit survives
+    // both a crash and a power cut. The slowest, with an fsync per commit.
+    #[default]
+    Safe,
+    // WAL, `synchronous = NORMAL`: the file is never corrupted, but a power cut can lose
+    // the last few commits. Readers no longer block the writer. Suits edge devices on flash.
+    Balanced,
+    // WAL, `synchronous = OFF`: survives the process crashing, but a power cut or OS crash
+    // can corrupt the file. Only for data that can be rebuilt.
+    Fast,
+}
+
+impl DurabilityMode {
+    pub fn journal_mode(self) -> &'static str {
+        match self {
+            DurabilityMode::Safe => "delete",
+            DurabilityMode::Balanced | DurabilityMode::Fast => "wal",
+        }
+    }
+
+    pub fn synchronous(self) -> &'static str {
+        match self {
+            DurabilityMode::Safe => "FULL",
+            DurabilityMode::Balanced => "NORMAL",
+            DurabilityMode::Fast => "OFF",
+        }
+    }
+}
+
+// Callback run after an anomalous description is stored, see `Database::on_anomaly`
+pub type AnomalyHook = Box<dyn Fn(&ImageDescription) + Send + Sync>;
+
+// Long-lived handle over one SQLite file, with an optional feature cache
+pub struct Database {
+    conn: Connection,
+    cache: Option<FeatureCache>,
+    // `PRAGMA data_version` when the cache was last known to be current
+    data_version: i64,
+    anomaly_hooks: Vec<AnomalyHook>,
+    read_only: bool,
+}
+
+impl Database {
+    // Function to open a database, creating any missing tables and running migrations
+    pub fn open(db_name: &str) -> Result<Self> {
+        let db = Database::open_without_setup(db_name)?;
+        schema::apply(&db.conn)?;
+        Ok(db)
+    }
+
+    // Function to open a database with the given durability. The journal mode is stored in
+    // the file, so WAL stays on for later connections; `synchronous` only applies to this one.
+    pub fn open_with_durability(db_name: &str, mode: DurabilityMode) -> Result<Self> {
+        let db = Database::open_without_setup(db_name)?;
+        // Set before the schema writes anything; switching to WAL needs no open transaction
+        db.conn.pragma_update_and_check(None, "journal_mode", mode.journal_mode(), |row| row.get::<_, String>(0))?;
+        db.conn.pragma_update(None, "synchronous", mode.synchronous())?;
+        schema::apply(&db.conn)?;
+        Ok(db)
+    }
+
+    // Function to open a database exactly as it is on disk, skipping schema setup. Missing
+    // parent directories of a file path are created first.
+    pub fn open_without_setup(db_name: &str) -> Result<Self> {
+        create_parent_dir(db_name)?;
+        Ok(Database {
+            conn: Connection::open(db_name)?,
+            cache: None,
+            data_version: 0,
+            anomaly_hooks: Vec::new(),
+            read_only: false,
+        })
+    }
+
+    // Function to open an existing database that this handle can only read, e.g. for a
+    // dashboard. The mutating methods fail with `VyuwerError::ReadOnly`, and SQLite itself
+    // rejects writes through `connection()`. No schema setup runs, so the file must already
+    // have been opened once by a writer.
+    pub fn open_read_only(db_name: &str) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(db_name, flags)?;
+        let found = schema::user_version(&conn)?;
+        if found > schema::SCHEMA_VERSION {
+            return Err(VyuwerError::IncompatibleSchema {
+                found,
+                supported: schema::SCHEMA_VERSION,
+            });
+        }
+        Ok(Database {
+            conn,
+            cache: None,
+            data_version: 0,
+            anomaly_hooks: Vec::new(),
+            read_only: true,
+        })
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Function to refuse `operation` on a read-only handle before SQLite sees it
+    fn check_writable(&self, operation: &'static str) -> Result<()> {
+        if self.read_only {
+            return Err(VyuwerError::ReadOnly(operation));
+        }
+        Ok(())
+    }
+
+    // Function to open (or create) a SQLCipher database encrypted with `key`. Every
+    // `Database` method works on the handle; the free functions taking a `db_name` open
+    // their own unkeyed connections and cannot read it.
+    #[cfg(feature = "sqlcipher")]
+    pub fn open_encrypted(db_name: &str, key: &str) -> Result<Self> {
+        let db = Database::open_without_setup(db_name)?;
+        // The key must be set before anything else touches the file
+        db.conn.pragma_update(None, "key", key)?;
+        schema::apply(&db.conn)?;
+        Ok(db)
+    }
+
+    // Function to open a database that caches up to `cache_capacity` cameras
+    pub fn open_with_cache(db_name: &str, cache_capacity: usize) -> Result<Self> {
+        let mut db = Database::open(db_name)?;
+        db.set_cache_capacity(cache_capacity);
+        Ok(db)
+    }
+
+    // Function to snapshot the whole main database into one SQLite file image, suitable for
+    // sending over the network or embedding; the handle stays usable afterwards
+    pub fn serialize_to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.conn.serialize(DatabaseName::Main)?.to_vec())
+    }
+
+    // Function to load a snapshot made by `serialize_to_bytes` into a new in-memory
+    // database. Older snapshots are migrated like any file opened with `open`.
+    pub fn deserialize_from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut db = Database::open_without_setup(":memory:")?;
+        let len = u64::try_from(bytes.len()).map_err(|_| VyuwerError::InvalidInput("snapshot too large".into()))?;
+        // SQLite takes ownership of the image and frees it with `sqlite3_free`, so it has
+        // to live in memory from `sqlite3_malloc64`
+        let ptr = NonNull::new(unsafe { ffi::sqlite3_malloc64(len.max(1)) }.cast::<u8>())
+            .ok_or_else(|| VyuwerError::InvalidInput("out of memory loading snapshot".into()))?;
+        // SAFETY: `ptr` points at `len` freshly allocated bytes that nothing else aliases,
+        // and it came from `sqlite3_malloc64` as `OwnedData` requires
+        let data = unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len());
+            OwnedData::from_raw_nonnull(ptr, bytes.len())
+        };
+        db.conn.deserialize(DatabaseName::Main, data, false)?;
+        schema::apply(&db.conn)?;
+        Ok(db)
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    // Function to resize the cache; a capacity of 0 disables caching
+    pub fn set_cache_capacity(&mut self, cache_capacity: usize) {
+        self.cache = NonZeroUsize::new(cache_capacity).map(FeatureCache::new);
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache.as_ref().map_or(0, FeatureCache::capacity)
+    }
+
+    pub fn cache_hits(&self) -> u64 {
+        self.cache.as_ref().map_or(0, FeatureCache::hits)
+    }
+
+    pub fn clear_cache(&mut self) {
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    // Function to drop the whole cache if another connection committed since the last check.
+    // Writes made through this handle invalidate their camera directly; free functions such
+    // as `prune_older_than` or `clone_camera` open their own connection, and SQLite bumps
+    // `data_version` for those (in this process or any other).
+    fn sync_cache(&mut self) -> Result<()> {
+        let Some(cache) = self.cache.as_mut() else {
+            return Ok(());
+        };
+        let data_version: i64 = self.conn.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        if data_version != self.data_version {
+            cache.clear();
+            self.data_version = data_version;
+        }
+        Ok(())
+    }
+
+    fn invalidate(&mut self, camera_id: &str) {
+        if let Some(cache) = self.cache.as_mut() {
+            cache.invalidate(camera_id);
+        }
+    }
+
+    // Function to register a callback for every anomalous description stored through this
+    // handle, e.g. to send a notification; hooks run in registration order once the row is
+    // written. Descriptions stored by the free functions do not reach them.
+    pub fn on_anomaly(&mut self, hook: AnomalyHook) {
+        self.anomaly_hooks.push(hook);
+    }
+
+    pub fn insert_image_description(&mut self, image_description: &ImageDescription) -> Result<()> {
+        self.check_writable("insert an image description")?;
+        model::insert_description(&self.conn, image_description)?;
+        if image_description.anomaly.is_some() {
+            for hook in &self.anomaly_hooks {
+                hook(image_description);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert_image_feature(&mut self, image_feature: &ImageFeature) -> Result<()> {
+        self.check_writable("insert an image feature")?;
+        model::insert_feature(&self.conn, image_feature)?;
+        self.invalidate(&image_feature.camera_id);
+        Ok(())
+    }
+
+    pub fn insert_image_feature_timed(&mut self, image_feature: &ImageFeature, extract_ms: u64) -> Result<()> {
+        self.check_writable("insert an image feature")?;
+        let tx = self.conn.transaction()?;
+        model::insert_feature_timed(&tx, image_feature, extract_ms)?;
+        tx.commit()?;
+        self.invalidate(&image_feature.camera_id);
+        Ok(())
+    }
+
+    pub fn insert_image_features_batch(
+        &mut self,
+        image_features: &[ImageFeature],
+        on_conflict: OnConflict,
+    ) -> Result<BatchInsertReport> {
+        self.check_writable("insert image features")?;
+        let tx = self.conn.transaction()?;
+        let report = model::insert_features_batch(&tx, image_features, on_conflict)?;
+        tx.commit()?;
+        for image_feature in image_features {
+            self.invalidate(&image_feature.camera_id);
+        }
+        Ok(report)
+    }
+
+    pub fn delete_image_feature(&mut self, camera_id: &str) -> Result<()> {
+        self.check_writable("delete image features")?;
+        model::delete_features(&self.conn, camera_id)?;
+        self.invalidate(camera_id);
+        Ok(())
+    }
+
+    pub fn reset_image_feature(&mut self, camera_id: &str, image_feature: &ImageFeature) -> Result<()> {
+        self.check_writable("reset image features")?;
+        let tx = self.conn.transaction()?;
+        model::reset_features(&tx, camera_id, image_feature)?;
+        tx.commit()?;
+        self.invalidate(camera_id);
+        self.invalidate(&image_feature.camera_id);
+        Ok(())
+    }
+
+    // Function to get a camera's latest feature, from the cache when it holds one
+    pub fn get_image_feature(&mut self, camera_id: &str) -> Result<Option<ImageFeature>> {
+        self.sync_cache()?;
+        if let Some(feature) = self.cache.as_mut().and_then(|cache| cache.get(camera_id)) {
+            #[cfg(feature = "metrics")]
+            crate::metrics::query_served();
+            return Ok(Some(feature));
+        }
+        let feature = model::fetch_feature(&self.conn, camera_id)?;
+        if let (Some(cache), Some(feature)) = (self.cache.as_mut(), feature.as_ref()) {
+            cache.put(camera_id, feature.clone());
+        }
+        Ok(feature)
+    }
+}
+
+// Function to create the directory a database file will live in, so a first run against
+// e.g. `data/cams/vyuwer.db` works. In-memory, temporary and `file:` URI names are left to SQLite.
+fn create_parent_dir(db_name: &str) -> Result<()> {
+    if db_name.is_empty() || db_name == ":memory:" || db_name.starts_with("file:") {
+        return Ok(());
+    }
+    let Some(parent) = Path::new(db_name).parent() else {
+        return Ok(());
+    };
+    if parent.as_os_str().is_empty() || parent.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(parent).map_err(|err| {
+        VyuwerError::Io(io::Error::new(
+            err.kind(),
+            format!("could not create database directory {}: {err}", parent.display()),
+        ))
+    })
+}
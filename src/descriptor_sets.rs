@@ -0,0 +1,183 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, VyuwerError};
+use crate::matching::{self, hamming_distance, MatchParams, MatchResult};
+use crate::model::{self, DescriptorMatrix};
+
+// Extractor a descriptor set came from, which also fixes how its rows are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DescriptorKind {
+    // Binary ORB rows, compared by Hamming distance. A feature's own descriptors are ORB.
+    Orb,
+    // SIFT rows converted to 8 bits per value, compared by L2 distance
+    Sift,
+    // Binary AKAZE (MLDB) rows, compared by Hamming distance
+    Akaze,
+}
+
+impl DescriptorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "orb" => Some(DescriptorKind::Orb),
+            "sift" => Some(DescriptorKind::Sift),
+            "akaze" => Some(DescriptorKind::Akaze),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DescriptorKind::Orb => "orb",
+            DescriptorKind::Sift => "sift",
+            DescriptorKind::Akaze => "akaze",
+        }
+    }
+
+    pub(crate) fn distance(self) -> fn(&[u8], &[u8]) -> f64 {
+        match self {
+            DescriptorKind::Orb | DescriptorKind::Akaze => |a, b| f64::from(hamming_distance(a, b)),
+            DescriptorKind::Sift => matching::l2_distance,
+        }
+    }
+}
+
+// Function to guess a descriptor kind from the row width alone: 32 bytes is ORB, 61 AKAZE
+// (full MLDB) and 128 SIFT. Best-effort only, for features stored before their kind was
+// recorded: other extractors can produce the same widths, and any width not listed is
+// taken to be a binary ORB-like descriptor.
+pub fn infer_descriptor_kind(cols: usize) -> DescriptorKind {
+    match cols {
+        61 => DescriptorKind::Akaze,
+        128 => DescriptorKind::Sift,
+        _ => DescriptorKind::Orb,
+    }
+}
+
+// Function to record the kind of a feature's own descriptors, e.g. the kind
+// `extract::extract_features` reported
+pub fn set_descriptor_kind(feature_id: &str, kind: DescriptorKind, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    let updated = conn.execute(
+        "UPDATE image_features SET descriptor_kind = ?1 WHERE id = ?2",
+        params![kind.as_str(), feature_id],
+    )?;
+    if updated == 0 {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    Ok(())
+}
+
+// Function to get the kind of a feature's own descriptors: the recorded one, else
+// `infer_descriptor_kind` on their width. None if the feature does not exist.
+pub fn descriptor_kind(feature_id: &str, db_name: &str) -> Result<Option<DescriptorKind>> {
+    let conn = Connection::open(db_name)?;
+    match model::fetch_feature_by_id(&conn, feature_id)? {
+        Some(feature) => Ok(Some(own_kind(&conn, feature_id, &feature.descriptors)?)),
+        None => Ok(None),
+    }
+}
+
+fn own_kind(conn: &Connection, feature_id: &str, descriptors: &DescriptorMatrix) -> Result<DescriptorKind> {
+    let recorded: Option<String> = conn.query_row(
+        "SELECT descriptor_kind FROM image_features WHERE id = ?1",
+        params![feature_id],
+        |row| row.get(0),
+    )?;
+    Ok(recorded
+        .as_deref()
+        .and_then(DescriptorKind::from_name)
+        .unwrap_or_else(|| infer_descriptor_kind(descriptors.cols)))
+}
+
+// Function to create the table of extra descriptor sets, at most one per feature and kind.
+// Deleting a feature cascades to its sets through a trigger.
+pub(crate) fn create_descriptor_set_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feature_descriptors (
+            feature_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            data BLOB NOT NULL,
+            rows INTEGER NOT NULL,
+            cols INTEGER NOT NULL,
+            PRIMARY KEY (feature_id, kind)
+        );
+        CREATE TRIGGER IF NOT EXISTS feature_descriptors_cascade_delete AFTER DELETE ON image_features
+        BEGIN
+            DELETE FROM feature_descriptors WHERE feature_id = OLD.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+// Function to store a feature's descriptors of `kind`, replacing any previous set of that kind
+pub fn store_descriptors(
+    feature_id: &str,
+    kind: DescriptorKind,
+    descriptors: &DescriptorMatrix,
+    db_name: &str,
+) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    if model::fetch_feature_by_id(&conn, feature_id)?.is_none() {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO feature_descriptors (feature_id, kind, data, rows, cols) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            feature_id,
+            kind.as_str(),
+            descriptors.data,
+            descriptors.rows as i64,
+            descriptors.cols as i64
+        ],
+    )?;
+    Ok(())
+}
+
+// Function to get a feature's descriptors of `kind`. Without a separate set of that kind,
+// the feature's own descriptors are returned if they are of that kind (see
+// `descriptor_kind`), so features stored before kinds were recorded stay matchable.
+pub fn get_descriptors(feature_id: &str, kind: DescriptorKind, db_name: &str) -> Result<Option<DescriptorMatrix>> {
+    let conn = Connection::open(db_name)?;
+    fetch_descriptors(&conn, feature_id, kind)
+}
+
+pub(crate) fn fetch_descriptors(
+    conn: &Connection,
+    feature_id: &str,
+    kind: DescriptorKind,
+) -> Result<Option<DescriptorMatrix>> {
+    let stored = conn
+        .query_row(
+            "SELECT data, rows, cols FROM feature_descriptors WHERE feature_id = ?1 AND kind = ?2",
+            params![feature_id, kind.as_str()],
+            |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)),
+        )
+        .optional()?;
+    match stored {
+        Some((data, rows, cols)) => Ok(Some(DescriptorMatrix::new(rows as usize, cols as usize, data)?)),
+        None => match model::fetch_feature_by_id(conn, feature_id)? {
+            Some(feature) if own_kind(conn, feature_id, &feature.descriptors)? == kind => Ok(Some(feature.descriptors)),
+            _ => Ok(None),
+        },
+    }
+}
+
+// Function to match two stored features on their descriptors of `kind`. Fails if either
+// feature has no set of that kind.
+pub fn match_stored_descriptors(
+    query_id: &str,
+    train_id: &str,
+    kind: DescriptorKind,
+    params: &MatchParams,
+    db_name: &str,
+) -> Result<MatchResult> {
+    let conn = Connection::open(db_name)?;
+    let load = |feature_id: &str| {
+        fetch_descriptors(&conn, feature_id, kind)?.ok_or_else(|| {
+            VyuwerError::InvalidInput(format!("{feature_id} has no {} descriptors", kind.as_str()))
+        })
+    };
+    let (query, train) = (load(query_id)?, load(train_id)?);
+    Ok(matching::match_descriptors(&query, &train, kind, params))
+}
@@ -0,0 +1,46 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VyuwerError {
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("failed to encode or decode blob: {0}")]
+    Codec(#[from] bincode::Error),
+    #[error("failed to encode or decode JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "opencv")]
+    #[error("opencv error: {0}")]
+    OpenCv(#[from] opencv::Error),
+    #[error("video error: {0}")]
+    Video(String),
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    #[error("invalid timestamp {0:?}, expected ISO-8601 UTC")]
+    InvalidTimestamp(String),
+    #[error("feature blob version {0} is not supported by this build")]
+    UnsupportedFeatureVersion(i64),
+    #[error("feature {0} not found")]
+    FeatureNotFound(String),
+    #[error("feature {0} already exists")]
+    FeatureExists(String),
+    #[error("no frame is stored for feature {0}")]
+    MissingFrame(String),
+    #[error("stored frame is corrupt: {0}")]
+    CorruptFrame(String),
+    #[error("camera {0} has no baseline feature")]
+    MissingBaseline(String),
+    #[error("camera {0} has no calibration")]
+    MissingCalibration(String),
+    #[error("database schema version {found} is newer than this build supports ({supported}); upgrade vyuwer")]
+    IncompatibleSchema { found: i64, supported: i64 },
+    #[error("only {found} matches found, at least {required} required")]
+    InsufficientMatches { found: usize, required: usize },
+    #[error("only {found} keypoints found, at least {required} required")]
+    InsufficientKeypoints { found: usize, required: usize },
+    #[error("cannot {0}: the database was opened read-only")]
+    ReadOnly(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, VyuwerError>;
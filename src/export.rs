@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::error::{Result, VyuwerError};
+#[cfg(feature = "opencv")]
+use crate::geometry::alignment_quality;
+use crate::matching::{good_matches, hamming_distance, MatchParams};
+use crate::model::{fetch_feature_by_id, get_feature_by_id, DescriptorMatrix, ImageFeature};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+// Function to write a stored feature's descriptor matrix as a NumPy .npy file (uint8, rows x cols)
+pub fn export_descriptors_npy(feature_id: &str, db_name: &str, path: &str) -> Result<()> {
+    let feature =
+        get_feature_by_id(feature_id, db_name)?.ok_or_else(|| VyuwerError::FeatureNotFound(feature_id.to_string()))?;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&npy_bytes(&feature.descriptors))?;
+    out.flush()?;
+    Ok(())
+}
+
+// Function to encode a descriptor matrix in .npy format version 1.0
+pub fn npy_bytes(descriptors: &DescriptorMatrix) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '|u1', 'fortran_order': False, 'shape': ({}, {}), }}",
+        descriptors.rows, descriptors.cols
+    );
+    // magic (6) + version (2) + header length (2) + header must be a multiple of 64, ending in '\n'
+    let preamble = NPY_MAGIC.len() + 4;
+    let padded = (preamble + header.len() + 1).div_ceil(64) * 64;
+    header.push_str(&" ".repeat(padded - preamble - header.len() - 1));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(padded + descriptors.data.len());
+    bytes.extend_from_slice(NPY_MAGIC);
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(&descriptors.data);
+    bytes
+}
+
+// Function to build a GeoJSON FeatureCollection with one Point per anomalous description,
+// placed at its camera's location. Cameras without coordinates are skipped.
+pub fn anomalies_geojson(db_name: &str) -> Result<String> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT d.camera_id, d.datetime, d.anomaly, c.latitude, c.longitude
+        FROM image_description d JOIN cameras c ON c.camera_id = d.camera_id
+        WHERE d.anomaly IS NOT NULL AND c.latitude IS NOT NULL AND c.longitude IS NOT NULL
+        ORDER BY d.datetime, d.image_name",
+    )?;
+    let features = stmt
+        .query_map([], |row| {
+            let (camera_id, datetime, anomaly): (String, String, String) = (row.get(0)?, row.get(1)?, row.get(2)?);
+            let (latitude, longitude): (f64, f64) = (row.get(3)?, row.get(4)?);
+            // GeoJSON positions are [longitude, latitude]
+            Ok(json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [longitude, latitude] },
+                "properties": { "camera_id": camera_id, "datetime": datetime, "anomaly": anomaly },
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(json!({ "type": "FeatureCollection", "features": features }).to_string())
+}
+
+// Header of the CSV `export_match_dataset` writes; empty cells are features that do not
+// apply to the pair (no good matches, or no inlier ratio without the `opencv` feature)
+pub const MATCH_DATASET_COLUMNS: &str = "feature_a,feature_b,good_matches,descriptors_a,descriptors_b,match_ratio,\
+    good_over_total,min_distance,mean_distance,max_distance,inlier_ratio,same_scene";
+
+// Function to write labeled match data for learning a similarity function: one CSV row per
+// (feature_a, feature_b, same_scene) of `pairs`, in order, with the match counts, good
+// matches over `feature_a`'s descriptors, the Hamming distances of the good matches and
+// the homography inlier ratio, then the label as 0/1. Every feature must be stored.
+pub fn export_match_dataset(pairs: &[(String, String, bool)], db_name: &str, out: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    let fetch = |feature_id: &str| {
+        fetch_feature_by_id(&conn, feature_id)?.ok_or_else(|| VyuwerError::FeatureNotFound(feature_id.to_string()))
+    };
+    let mut writer = BufWriter::new(File::create(out)?);
+    writeln!(writer, "{MATCH_DATASET_COLUMNS}")?;
+    for (a_id, b_id, same_scene) in pairs {
+        let (a, b) = (fetch(a_id)?, fetch(b_id)?);
+        writeln!(writer, "{},{},{},{}", csv_field(a_id), csv_field(b_id), match_row(&a, &b)?, u8::from(*same_scene))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Function to compute the feature columns of one `export_match_dataset` row
+fn match_row(a: &ImageFeature, b: &ImageFeature) -> Result<String> {
+    let matches = good_matches(a, b, &MatchParams::default());
+    let (rows_a, rows_b) = (a.descriptors.rows, b.descriptors.rows);
+    let ratio = |num: usize, denom: usize| if denom == 0 { 0.0 } else { num as f64 / denom as f64 };
+    let distances: Vec<u32> = matches
+        .iter()
+        .map(|&(qi, ti)| hamming_distance(a.descriptors.row(qi), b.descriptors.row(ti)))
+        .collect();
+    let distance_stats = match (distances.iter().min(), distances.iter().max()) {
+        (Some(min), Some(max)) => {
+            let mean = distances.iter().map(|&d| f64::from(d)).sum::<f64>() / distances.len() as f64;
+            format!("{min},{mean},{max}")
+        }
+        _ => ",,".to_string(),
+    };
+    Ok(format!(
+        "{},{rows_a},{rows_b},{},{},{distance_stats},{}",
+        matches.len(),
+        ratio(matches.len(), rows_a.min(rows_b)),
+        ratio(matches.len(), rows_a),
+        inlier_ratio(a, b)?.map(|r| r.to_string()).unwrap_or_default()
+    ))
+}
+
+// Function to find the share of good matches consistent with one homography; None when
+// there are too few matches to fit one
+#[cfg(feature = "opencv")]
+fn inlier_ratio(a: &ImageFeature, b: &ImageFeature) -> Result<Option<f64>> {
+    match alignment_quality(a, b) {
+        Ok(quality) => Ok(Some(quality.inliers as f64 / quality.inlier_mask.len() as f64)),
+        Err(VyuwerError::InsufficientMatches { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(feature = "opencv"))]
+fn inlier_ratio(_a: &ImageFeature, _b: &ImageFeature) -> Result<Option<f64>> {
+    Ok(None)
+}
+
+// Function to quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
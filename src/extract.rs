@@ -0,0 +1,527 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{self, KeyPoint, Mat, Vector},
+    features2d::{FastFeatureDetector, FastFeatureDetector_DetectorType, ORB_ScoreType, AKAZE, ORB, SIFT},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+
+#[cfg(feature = "opencv")]
+use uuid::Uuid;
+
+#[cfg(feature = "opencv")]
+use crate::descriptor_sets::DescriptorKind;
+use crate::error::{Result, VyuwerError};
+use crate::model::{DescriptorMatrix, KeyPointData};
+#[cfg(feature = "opencv")]
+use crate::{
+    model::{FrameSize, ImageFeature, Roi},
+    timestamp::now_utc_iso8601,
+};
+
+// Mean absolute frame difference below which a frame is considered static
+pub const MOTION_GATE_THRESHOLD: f64 = 1.0;
+
+// Image the detector runs on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    // Luminance of a BGR frame
+    #[default]
+    Gray,
+    // V channel of HSV (the per-pixel max of B, G and R), which keeps more texture in low light
+    HsvValue,
+    // Each of the B, G and R channels separately, keeping edges between colours of equal
+    // luminance; yields up to three times `nfeatures` keypoints
+    Rgb,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrbParams {
+    pub nfeatures: i32,
+    pub scale_factor: f32,
+    pub nlevels: i32,
+    pub fast_threshold: i32,
+    // Params recorded before colour modes existed were extracted on grayscale
+    #[serde(default)]
+    pub color_mode: ColorMode,
+}
+
+impl Default for OrbParams {
+    fn default() -> Self {
+        OrbParams {
+            nfeatures: 500,
+            scale_factor: 1.2,
+            nlevels: 8,
+            fast_threshold: 20,
+            color_mode: ColorMode::Gray,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AkazeParams {
+    // Detector response threshold; lower finds more, weaker keypoints
+    pub threshold: f64,
+    pub n_octaves: i32,
+    pub n_octave_layers: i32,
+}
+
+impl Default for AkazeParams {
+    fn default() -> Self {
+        AkazeParams {
+            threshold: 0.001,
+            n_octaves: 4,
+            n_octave_layers: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SiftParams {
+    // Keypoints kept, strongest first; 0 keeps all of them
+    pub nfeatures: i32,
+    pub n_octave_layers: i32,
+    pub contrast_threshold: f64,
+    pub edge_threshold: f64,
+    pub sigma: f64,
+}
+
+impl Default for SiftParams {
+    fn default() -> Self {
+        SiftParams {
+            nfeatures: 0,
+            n_octave_layers: 3,
+            contrast_threshold: 0.04,
+            edge_threshold: 10.0,
+            sigma: 1.6,
+        }
+    }
+}
+
+// FAST corners, described with ORB's rotated BRIEF since FAST itself has no descriptor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FastParams {
+    pub threshold: i32,
+    pub nonmax_suppression: bool,
+}
+
+impl Default for FastParams {
+    fn default() -> Self {
+        FastParams {
+            threshold: 10,
+            nonmax_suppression: true,
+        }
+    }
+}
+
+// Keypoint detector and descriptor to extract with, chosen per camera
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Detector {
+    Orb(OrbParams),
+    Akaze(AkazeParams),
+    Sift(SiftParams),
+    Fast(FastParams),
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Detector::Orb(OrbParams::default())
+    }
+}
+
+// What `check_opencv` found about the OpenCV this build links against
+#[cfg(feature = "opencv")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenCvInfo {
+    // Version of the headers the bindings were generated from (`core::CV_VERSION`)
+    pub version: String,
+    // Version reported by the shared library actually loaded
+    pub runtime_version: String,
+    // Whether a small Mat could be allocated and reduced
+    pub ok: bool,
+    // Why the smoke test failed, when it did
+    pub error: Option<String>,
+}
+
+// Function to check at startup that OpenCV is usable, so a broken install is reported
+// cleanly instead of failing inside the first extraction. Only a library that cannot even
+// report its version is an error; a failing smoke test comes back as `ok: false`.
+#[cfg(feature = "opencv")]
+pub fn check_opencv() -> Result<OpenCvInfo> {
+    let runtime_version = core::get_version_string()?;
+    let smoke_test = || -> Result<()> {
+        let mat = Mat::new_rows_cols_with_default(8, 8, core::CV_8UC1, core::Scalar::all(3.0))?;
+        let total = core::sum_elems(&mat)?[0];
+        if total != 8.0 * 8.0 * 3.0 {
+            return Err(VyuwerError::InvalidInput(format!("8x8 Mat of 3s summed to {total}")));
+        }
+        Ok(())
+    };
+    let error = smoke_test().err().map(|err| err.to_string());
+    Ok(OpenCvInfo {
+        version: core::CV_VERSION.to_string(),
+        runtime_version,
+        ok: error.is_none(),
+        error,
+    })
+}
+
+// Function to check whether a frame moved enough to be worth storing
+pub fn passes_motion_gate(motion_mean: f64) -> bool {
+    motion_mean >= MOTION_GATE_THRESHOLD
+}
+
+// Function to convert a BGR frame to grayscale (frames already single-channel are copied)
+#[cfg(feature = "opencv")]
+pub fn to_gray(image: &Mat) -> Result<Mat> {
+    let mut gray = Mat::default();
+    if image.channels() == 1 {
+        image.copy_to(&mut gray)?;
+    } else {
+        imgproc::cvt_color_def(image, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+    }
+    Ok(gray)
+}
+
+// Function to extract ORB keypoints and the flattened descriptor matrix from an image
+#[cfg(feature = "opencv")]
+pub fn extract_orb_features(image: &Mat, params: &OrbParams) -> Result<(Vec<KeyPointData>, DescriptorMatrix)> {
+    detect_orb(image, &core::no_array(), params)
+}
+
+// Function to extract keypoints and descriptors with any supported detector, returning
+// the kind of the descriptors so they can be stored with `descriptor_sets::store_descriptors`
+// and matched with the right distance. Orb honours its colour mode; the others run on the
+// grayscale frame.
+#[cfg(feature = "opencv")]
+pub fn extract_features(
+    image: &Mat,
+    detector: &Detector,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix, DescriptorKind)> {
+    match detector {
+        Detector::Orb(params) => {
+            let (keypoints, descriptors) = extract_orb_features(image, params)?;
+            Ok((keypoints, descriptors, DescriptorKind::Orb))
+        }
+        Detector::Akaze(params) => {
+            let mut akaze = AKAZE::create_def()?;
+            akaze.set_threshold(params.threshold)?;
+            akaze.set_n_octaves(params.n_octaves)?;
+            akaze.set_n_octave_layers(params.n_octave_layers)?;
+            let (keypoints, descriptors) = detect_and_describe(&mut akaze, &to_gray(image)?)?;
+            Ok((keypoints, DescriptorMatrix::from_mat(&descriptors)?, DescriptorKind::Akaze))
+        }
+        Detector::Sift(params) => {
+            let mut sift = SIFT::create(
+                params.nfeatures,
+                params.n_octave_layers,
+                params.contrast_threshold,
+                params.edge_threshold,
+                params.sigma,
+                false,
+            )?;
+            let (keypoints, descriptors) = detect_and_describe(&mut sift, &to_gray(image)?)?;
+            // SIFT values are clamped to 0..=255 before normalisation, so the cast loses little
+            let mut bytes = Mat::default();
+            if !descriptors.empty() {
+                descriptors.convert_to(&mut bytes, core::CV_8U, 1.0, 0.0)?;
+            }
+            Ok((keypoints, DescriptorMatrix::from_mat(&bytes)?, DescriptorKind::Sift))
+        }
+        Detector::Fast(params) => {
+            let gray = to_gray(image)?;
+            let mut fast = FastFeatureDetector::create(
+                params.threshold,
+                params.nonmax_suppression,
+                FastFeatureDetector_DetectorType::TYPE_9_16,
+            )?;
+            let mut keypoints = Vector::<KeyPoint>::new();
+            fast.detect(&gray, &mut keypoints, &core::no_array())?;
+            // ORB drops corners too close to the border to describe, keeping the rest aligned
+            let mut orb = ORB::create_def()?;
+            let mut descriptors = Mat::default();
+            orb.compute(&gray, &mut keypoints, &mut descriptors)?;
+            let keypoints = keypoints.iter().map(|kp| KeyPointData::from_keypoint(&kp)).collect();
+            Ok((keypoints, DescriptorMatrix::from_mat(&descriptors)?, DescriptorKind::Orb))
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+fn detect_and_describe(detector: &mut impl Feature2DTrait, gray: &Mat) -> Result<(Vec<KeyPointData>, Mat)> {
+    let mut keypoints = Vector::<KeyPoint>::new();
+    let mut descriptors = Mat::default();
+    detector.detect_and_compute(gray, &core::no_array(), &mut keypoints, &mut descriptors, false)?;
+    Ok((keypoints.iter().map(|kp| KeyPointData::from_keypoint(&kp)).collect(), descriptors))
+}
+
+// Function to split a frame into a grid of `tiles.0` columns by `tiles.1` rows and extract
+// each tile on its own, so a high-resolution frame keeps local detail a global detector
+// spends elsewhere. Returns one feature per tile, left to right then top to bottom, with
+// `roi` set to the tile; edge tiles take the pixels left over by the division. Keypoints are
+// shifted into frame coordinates and `frame_size` is the whole frame. Tiles without any
+// keypoints are skipped, so fewer features than tiles may come back. `camera_id` is left
+// empty for the caller to fill in; descriptors are of the detector's kind.
+#[cfg(feature = "opencv")]
+pub fn extract_tiled(image: &Mat, tiles: (u32, u32), params: &Detector) -> Result<Vec<ImageFeature>> {
+    let (columns, rows) = tiles;
+    let (width, height) = (image.cols() as u32, image.rows() as u32);
+    if columns == 0 || rows == 0 || columns > width || rows > height {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot split a {width}x{height} frame into {columns}x{rows} tiles"
+        )));
+    }
+    let (tile_width, tile_height) = (width / columns, height / rows);
+    let created_at_utc = now_utc_iso8601();
+    let mut features = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = (column * tile_width, row * tile_height);
+            let roi = Roi::new(
+                x,
+                y,
+                if column + 1 == columns { width - x } else { tile_width },
+                if row + 1 == rows { height - y } else { tile_height },
+            );
+            let rect = core::Rect::new(x as i32, y as i32, roi.width as i32, roi.height as i32);
+            let tile = Mat::roi(image, rect)?.try_clone()?;
+            let (mut keypoints, descriptors, _) = extract_features(&tile, params)?;
+            if keypoints.is_empty() {
+                continue;
+            }
+            for keypoint in &mut keypoints {
+                keypoint.x += x as f32;
+                keypoint.y += y as f32;
+            }
+            features.push(ImageFeature {
+                id: Uuid::new_v4().to_string(),
+                keypoints,
+                descriptors,
+                motion_mean: 0.0,
+                motion_std: 0.0,
+                created_at_utc: created_at_utc.clone(),
+                img_filename: None,
+                camera_id: String::new(),
+                phash: None,
+                frame_size: Some(FrameSize::new(width, height)),
+                extraction_params: match params {
+                    Detector::Orb(orb) => Some(orb.clone()),
+                    _ => None,
+                },
+                sharpness: Some(sharpness(&tile)?),
+                roi: Some(roi),
+            });
+        }
+    }
+    Ok(features)
+}
+
+// Function to extract ORB features only where `mask` is non-zero (e.g. to ignore a public
+// sidewalk). The mask must be a single-channel 8-bit image the same size as `image`.
+#[cfg(feature = "opencv")]
+pub fn extract_orb_features_masked(
+    image: &Mat,
+    mask: &Mat,
+    params: &OrbParams,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix)> {
+    if mask.size()? != image.size()? {
+        return Err(VyuwerError::InvalidInput(format!(
+            "mask is {}x{} but image is {}x{}",
+            mask.cols(),
+            mask.rows(),
+            image.cols(),
+            image.rows()
+        )));
+    }
+    if mask.typ() != core::CV_8UC1 {
+        return Err(VyuwerError::InvalidInput("mask must be a single-channel 8-bit image".to_string()));
+    }
+    detect_orb(image, mask, params)
+}
+
+// Function to load a binary (black = ignored) PNG mask as a grayscale Mat
+#[cfg(feature = "opencv")]
+pub fn load_mask(path: &str) -> Result<Mat> {
+    let mask = imgcodecs::imread(path, imgcodecs::IMREAD_GRAYSCALE)?;
+    if mask.empty() {
+        return Err(VyuwerError::InvalidInput(format!("could not read mask image {path}")));
+    }
+    Ok(mask)
+}
+
+// Function to extract ORB features and thin them with `spatial_nms`, keeping the
+// strongest corner within each `radius`, at most `max_kp` in total
+#[cfg(feature = "opencv")]
+pub fn extract_orb_features_spread(
+    image: &Mat,
+    params: &OrbParams,
+    radius: f32,
+    max_kp: usize,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix)> {
+    let (keypoints, descriptors, responses) = detect_orb_with_responses(image, &core::no_array(), params)?;
+    spatial_nms(keypoints, descriptors, &responses, radius, max_kp)
+}
+
+#[cfg(feature = "opencv")]
+fn detect_orb(
+    image: &Mat,
+    mask: &impl core::ToInputArray,
+    params: &OrbParams,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix)> {
+    let (keypoints, descriptors, _) = detect_orb_with_responses(image, mask, params)?;
+    Ok((keypoints, descriptors))
+}
+
+// Function to run ORB and also return each keypoint's detector response, which
+// KeyPointData does not store
+#[cfg(feature = "opencv")]
+fn detect_orb_with_responses(
+    image: &Mat,
+    mask: &impl core::ToInputArray,
+    params: &OrbParams,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix, Vec<f32>)> {
+    let mut orb = ORB::create(
+        params.nfeatures,
+        params.scale_factor,
+        params.nlevels,
+        31,
+        0,
+        2,
+        ORB_ScoreType::HARRIS_SCORE,
+        31,
+        params.fast_threshold,
+    )?;
+    let mut all_keypoints = Vec::new();
+    let mut all_descriptors = DescriptorMatrix::default();
+    let mut all_responses = Vec::new();
+    for plane in color_mode_planes(image, params.color_mode)? {
+        let mut keypoints = Vector::<KeyPoint>::new();
+        let mut descriptors = Mat::default();
+        orb.detect_and_compute(&plane, mask, &mut keypoints, &mut descriptors, false)?;
+        all_keypoints.extend(keypoints.iter().map(|kp| KeyPointData::from_keypoint(&kp)));
+        all_responses.extend(keypoints.iter().map(|kp| kp.response()));
+        let descriptors = DescriptorMatrix::from_mat(&descriptors)?;
+        if !descriptors.is_empty() {
+            all_descriptors.cols = descriptors.cols;
+            all_descriptors.rows += descriptors.rows;
+            all_descriptors.data.extend(descriptors.data);
+        }
+    }
+    Ok((all_keypoints, all_descriptors, all_responses))
+}
+
+// Function to spread keypoints evenly over the frame: in response order, a keypoint is kept
+// unless a stronger kept one lies closer than `radius`, stopping after `max_kp`. Survivors
+// keep their original order and descriptor rows. `responses` holds one detector score per
+// keypoint (KeyPointData has no response field); ties go to the earlier keypoint.
+pub fn spatial_nms(
+    keypoints: Vec<KeyPointData>,
+    descriptors: DescriptorMatrix,
+    responses: &[f32],
+    radius: f32,
+    max_kp: usize,
+) -> Result<(Vec<KeyPointData>, DescriptorMatrix)> {
+    if responses.len() != keypoints.len() {
+        return Err(VyuwerError::InvalidInput(format!(
+            "{} responses for {} keypoints",
+            responses.len(),
+            keypoints.len()
+        )));
+    }
+    if !descriptors.is_empty() && descriptors.rows != keypoints.len() {
+        return Err(VyuwerError::InvalidInput(format!(
+            "{} keypoints but {} descriptor rows",
+            keypoints.len(),
+            descriptors.rows
+        )));
+    }
+    let mut order: Vec<usize> = (0..keypoints.len()).collect();
+    order.sort_by(|&a, &b| responses[b].total_cmp(&responses[a]).then(a.cmp(&b)));
+    let radius_sq = radius * radius;
+    let mut kept: Vec<usize> = Vec::new();
+    for i in order {
+        if kept.len() >= max_kp {
+            break;
+        }
+        let (x, y) = (keypoints[i].x, keypoints[i].y);
+        let suppressed = kept.iter().any(|&k| {
+            let (dx, dy) = (keypoints[k].x - x, keypoints[k].y - y);
+            dx * dx + dy * dy < radius_sq
+        });
+        if !suppressed {
+            kept.push(i);
+        }
+    }
+    kept.sort_unstable();
+    let descriptors = if descriptors.is_empty() {
+        descriptors
+    } else {
+        let data = kept.iter().flat_map(|&i| descriptors.row(i).iter().copied()).collect();
+        DescriptorMatrix::new(kept.len(), descriptors.cols, data)?
+    };
+    let keypoints = kept.iter().map(|&i| keypoints[i].clone()).collect();
+    Ok((keypoints, descriptors))
+}
+
+// Function to convert a frame to the single-channel images the detector runs on: one for
+// Gray and HsvValue, one per channel for Rgb. Single-channel frames are used as they are
+// in every mode.
+#[cfg(feature = "opencv")]
+pub fn color_mode_planes(image: &Mat, mode: ColorMode) -> Result<Vec<Mat>> {
+    if image.channels() == 1 {
+        return Ok(vec![image.try_clone()?]);
+    }
+    match mode {
+        ColorMode::Gray => Ok(vec![to_gray(image)?]),
+        ColorMode::HsvValue => {
+            let mut hsv = Mat::default();
+            imgproc::cvt_color_def(image, &mut hsv, imgproc::COLOR_BGR2HSV)?;
+            let mut value = Mat::default();
+            core::extract_channel(&hsv, &mut value, 2)?;
+            Ok(vec![value])
+        }
+        ColorMode::Rgb => {
+            let mut channels = Vector::<Mat>::new();
+            core::split(image, &mut channels)?;
+            Ok(channels.iter().take(3).collect())
+        }
+    }
+}
+
+// Function to compute (mean, std) of the absolute difference between two grayscale frames
+#[cfg(feature = "opencv")]
+pub fn motion_stats(prev_gray: &Mat, gray: &Mat) -> Result<(f64, f64)> {
+    let mut diff = Mat::default();
+    core::absdiff(prev_gray, gray, &mut diff)?;
+    let mut mean = Mat::default();
+    let mut stddev = Mat::default();
+    core::mean_std_dev_def(&diff, &mut mean, &mut stddev)?;
+    Ok((*mean.at::<f64>(0)?, *stddev.at::<f64>(0)?))
+}
+
+// Function to score a frame's sharpness as the variance of its Laplacian: blurred frames
+// have few strong edges and score low. Values depend on content and resolution, so compare
+// frames of the same camera rather than using one threshold everywhere.
+#[cfg(feature = "opencv")]
+pub fn sharpness(image: &Mat) -> Result<f64> {
+    let gray = to_gray(image)?;
+    let mut laplacian = Mat::default();
+    imgproc::laplacian_def(&gray, &mut laplacian, core::CV_64F)?;
+    let mut mean = Mat::default();
+    let mut stddev = Mat::default();
+    core::mean_std_dev_def(&laplacian, &mut mean, &mut stddev)?;
+    let stddev = *stddev.at::<f64>(0)?;
+    Ok(stddev * stddev)
+}
+
+// Function to measure an exposure change as the mean luminance of `current` minus that of
+// `prev` (0-255 scale, positive when the frame got brighter). Lighting changes and
+// auto-exposure shift this a lot while leaving the scene itself in place.
+#[cfg(feature = "opencv")]
+pub fn brightness_delta(prev: &Mat, current: &Mat) -> Result<f64> {
+    let prev_mean = core::mean_def(&to_gray(prev)?)?[0];
+    let current_mean = core::mean_def(&to_gray(current)?)?[0];
+    Ok(current_mean - prev_mean)
+}
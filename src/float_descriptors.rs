@@ -0,0 +1,255 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::codec;
+use crate::error::{Result, VyuwerError};
+use crate::matching::{MatchParams, MatchResult, SimilarityMetric};
+use crate::model;
+
+// Row-major matrix of float descriptors (SIFT: 128 f32 per keypoint, AKAZE/KAZE: 64).
+// Binary ORB descriptors stay in `DescriptorMatrix` and are never quantized.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "FloatDescriptorParts")]
+pub struct FloatDescriptors {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<f32>,
+}
+
+// Wire form of FloatDescriptors, before the shape is checked
+#[derive(Deserialize)]
+struct FloatDescriptorParts {
+    rows: usize,
+    cols: usize,
+    data: Vec<f32>,
+}
+
+impl TryFrom<FloatDescriptorParts> for FloatDescriptors {
+    type Error = VyuwerError;
+
+    fn try_from(parts: FloatDescriptorParts) -> Result<Self> {
+        FloatDescriptors::new(parts.rows, parts.cols, parts.data)
+    }
+}
+
+impl FloatDescriptors {
+    pub fn new(rows: usize, cols: usize, data: Vec<f32>) -> Result<Self> {
+        if rows.checked_mul(cols) != Some(data.len()) {
+            return Err(VyuwerError::InvalidInput(format!(
+                "float descriptor matrix {rows}x{cols} does not match {} values",
+                data.len()
+            )));
+        }
+        if data.iter().any(|v| !v.is_finite()) {
+            return Err(VyuwerError::InvalidInput("float descriptors must be finite".to_string()));
+        }
+        Ok(FloatDescriptors { rows, cols, data })
+    }
+
+    pub fn row(&self, i: usize) -> &[f32] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    // Function to quantize every row to u8 over its own [min, max] range: a quarter of the
+    // f32 size plus 8 bytes per row. Each value then carries an error of at most half a
+    // step, (max - min) / 510, which barely moves L2 distances between distinct descriptors;
+    // only near-ties in nearest-neighbour matching can flip.
+    pub fn quantize(&self) -> QuantizedDescriptors {
+        let mut mins = Vec::with_capacity(self.rows);
+        let mut scales = Vec::with_capacity(self.rows);
+        let mut data = Vec::with_capacity(self.data.len());
+        for i in 0..self.rows {
+            let row = self.row(i);
+            let min = row.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            // A constant row quantizes to all zeros with any scale
+            let scale = if max > min { (max - min) / 255.0 } else { 1.0 };
+            data.extend(row.iter().map(|v| ((v - min) / scale).round().clamp(0.0, 255.0) as u8));
+            mins.push(min);
+            scales.push(scale);
+        }
+        QuantizedDescriptors {
+            rows: self.rows,
+            cols: self.cols,
+            mins,
+            scales,
+            data,
+        }
+    }
+}
+
+// Float descriptors stored as one u8 per value, with a per-row offset and step
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "QuantizedParts")]
+pub struct QuantizedDescriptors {
+    pub rows: usize,
+    pub cols: usize,
+    pub mins: Vec<f32>,
+    pub scales: Vec<f32>,
+    pub data: Vec<u8>,
+}
+
+// Wire form of QuantizedDescriptors, before the shape is checked
+#[derive(Deserialize)]
+struct QuantizedParts {
+    rows: usize,
+    cols: usize,
+    mins: Vec<f32>,
+    scales: Vec<f32>,
+    data: Vec<u8>,
+}
+
+impl TryFrom<QuantizedParts> for QuantizedDescriptors {
+    type Error = VyuwerError;
+
+    fn try_from(parts: QuantizedParts) -> Result<Self> {
+        let quantized = QuantizedDescriptors {
+            rows: parts.rows,
+            cols: parts.cols,
+            mins: parts.mins,
+            scales: parts.scales,
+            data: parts.data,
+        };
+        quantized.check()?;
+        Ok(quantized)
+    }
+}
+
+impl QuantizedDescriptors {
+    // Function to restore approximate float descriptors, e.g. before L2 matching.
+    // Fails if the per-row parameters or the data disagree with the shape.
+    pub fn dequantize(&self) -> Result<FloatDescriptors> {
+        self.check()?;
+        let mut data = Vec::with_capacity(self.data.len());
+        for i in 0..self.rows {
+            let (min, scale) = (self.mins[i], self.scales[i]);
+            let row = &self.data[i * self.cols..(i + 1) * self.cols];
+            data.extend(row.iter().map(|&q| min + f32::from(q) * scale));
+        }
+        FloatDescriptors::new(self.rows, self.cols, data)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.rows.checked_mul(self.cols) != Some(self.data.len())
+            || self.mins.len() != self.rows
+            || self.scales.len() != self.rows
+        {
+            return Err(VyuwerError::InvalidInput(format!(
+                "quantized matrix {}x{} does not match {} values, {} mins and {} scales",
+                self.rows,
+                self.cols,
+                self.data.len(),
+                self.mins.len(),
+                self.scales.len()
+            )));
+        }
+        Ok(())
+    }
+
+    // Largest per-value error `dequantize` can introduce in row `i`
+    pub fn max_error(&self, i: usize) -> f32 {
+        self.scales[i] / 2.0
+    }
+}
+
+// Function to create the table of quantized float descriptors, one matrix per feature.
+// Deleting a feature cascades to its row through a trigger.
+pub(crate) fn create_float_descriptor_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS float_descriptors (
+            feature_id TEXT PRIMARY KEY,
+            quantized BLOB NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS float_descriptors_cascade_delete AFTER DELETE ON image_features
+        BEGIN
+            DELETE FROM float_descriptors WHERE feature_id = OLD.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+// Function to store a feature's float descriptors (e.g. SIFT alongside its ORB ones),
+// quantized to u8 per value, replacing any previous matrix. Reads return the dequantized
+// values, each within `QuantizedDescriptors::max_error` of what was stored.
+pub fn store_float_descriptors(feature_id: &str, descriptors: &FloatDescriptors, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    if model::fetch_feature_by_id(&conn, feature_id)?.is_none() {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO float_descriptors (feature_id, quantized) VALUES (?1, ?2)",
+        params![feature_id, codec::encode(&descriptors.quantize())?],
+    )?;
+    Ok(())
+}
+
+// Function to load a feature's float descriptors, dequantized for matching
+pub fn load_float_descriptors(feature_id: &str, db_name: &str) -> Result<Option<FloatDescriptors>> {
+    let conn = Connection::open(db_name)?;
+    let blob: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT quantized FROM float_descriptors WHERE feature_id = ?1",
+            params![feature_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match blob {
+        Some(blob) => Ok(Some(codec::decode::<QuantizedDescriptors>(&blob)?.dequantize()?)),
+        None => Ok(None),
+    }
+}
+
+// Function to brute-force match float descriptors by L2 distance with Lowe's ratio test
+// (and the cross-check if `params` asks for it; there are no keypoints for an inlier
+// ratio), the float counterpart of `match_features`. Descriptors of different widths come
+// from different extractors and are refused.
+pub fn match_float_descriptors(
+    query: &FloatDescriptors,
+    train: &FloatDescriptors,
+    params: &MatchParams,
+) -> Result<MatchResult> {
+    if query.rows > 0 && train.rows > 0 && query.cols != train.cols {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot match {}-wide descriptors against {}-wide ones",
+            query.cols, train.cols
+        )));
+    }
+    let sampled = params.sampled_rows(query.rows);
+    let mut good_matches = 0;
+    if train.rows >= 2 {
+        for &qi in &sampled {
+            let (best, best_index, second) = two_nearest_l2(query.row(qi), train);
+            // Squared distances, so the ratio is squared too
+            let passes = best < params.lowe_ratio().powi(2) * second
+                && (params.metric() != SimilarityMetric::CrossCheck
+                    || two_nearest_l2(train.row(best_index), query).1 == qi);
+            if passes {
+                good_matches += 1;
+            }
+        }
+    }
+    Ok(MatchResult {
+        good_matches,
+        query_descriptors: sampled.len(),
+        train_descriptors: train.rows,
+    })
+}
+
+// Function to find the best squared L2 distance (and its row) and the second-best from `q`
+// to `train`'s rows
+fn two_nearest_l2(q: &[f32], train: &FloatDescriptors) -> (f64, usize, f64) {
+    let mut best = f64::INFINITY;
+    let mut best_index = 0;
+    let mut second = f64::INFINITY;
+    for i in 0..train.rows {
+        let d: f64 = q.iter().zip(train.row(i)).map(|(a, b)| f64::from(a - b).powi(2)).sum();
+        if d < best {
+            second = best;
+            best = d;
+            best_index = i;
+        } else if d < second {
+            second = d;
+        }
+    }
+    (best, best_index, second)
+}
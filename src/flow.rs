@@ -0,0 +1,137 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{self, Mat, Vector},
+    prelude::*,
+    video,
+};
+
+use crate::error::{Result, VyuwerError};
+use crate::model;
+#[cfg(feature = "opencv")]
+use crate::extract::to_gray;
+
+// Number of direction bins `flow_stats` votes into; bin 0 is centred on rightward motion
+pub const FLOW_DIRECTION_BINS: usize = 8;
+// Flow vectors shorter than this many pixels are sensor noise and cast no direction vote
+pub const FLOW_MIN_MAGNITUDE: f64 = 0.5;
+
+// Summary of a dense optical-flow field between two frames
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlowStats {
+    // Mean flow vector length over every pixel, in pixels per frame
+    pub mean_magnitude: f64,
+    // Centre of the winning direction bin in degrees: 0 is rightward, 90 downward (image
+    // rows grow downwards). 0 when nothing moved more than FLOW_MIN_MAGNITUDE.
+    pub dominant_direction: f64,
+}
+
+// Function to compute Farneback dense optical flow from `prev` to `current`: a CV_32FC2
+// Mat holding each pixel's (dx, dy). Colour frames are converted to grayscale first.
+#[cfg(feature = "opencv")]
+pub fn optical_flow(prev: &Mat, current: &Mat) -> Result<Mat> {
+    if prev.size()? != current.size()? {
+        return Err(VyuwerError::InvalidInput(format!(
+            "frames are {}x{} and {}x{}",
+            prev.cols(),
+            prev.rows(),
+            current.cols(),
+            current.rows()
+        )));
+    }
+    let mut flow = Mat::default();
+    video::calc_optical_flow_farneback(&to_gray(prev)?, &to_gray(current)?, &mut flow, 0.5, 3, 15, 3, 5, 1.2, 0)?;
+    Ok(flow)
+}
+
+// Function to summarise a flow field from `optical_flow`. Each vector of at least
+// FLOW_MIN_MAGNITUDE votes for its direction bin with its length, so coherent motion (a door
+// swinging) wins a bin while noise spreads its votes thinly.
+#[cfg(feature = "opencv")]
+pub fn flow_stats(flow: &Mat) -> Result<FlowStats> {
+    if flow.empty() {
+        return Ok(FlowStats {
+            mean_magnitude: 0.0,
+            dominant_direction: 0.0,
+        });
+    }
+    if flow.typ() != core::CV_32FC2 {
+        return Err(VyuwerError::InvalidInput("flow must be a two-channel 32-bit float Mat".to_string()));
+    }
+    let mut components = Vector::<Mat>::new();
+    core::split(flow, &mut components)?;
+    let mut magnitude = Mat::default();
+    let mut angle = Mat::default();
+    core::cart_to_polar(&components.get(0)?, &components.get(1)?, &mut magnitude, &mut angle, true)?;
+
+    let bin_width = 360.0 / FLOW_DIRECTION_BINS as f64;
+    let mut votes = [0.0f64; FLOW_DIRECTION_BINS];
+    let mut total = 0.0;
+    for (&length, &degrees) in magnitude.data_typed::<f32>()?.iter().zip(angle.data_typed::<f32>()?) {
+        let length = f64::from(length);
+        total += length;
+        if length >= FLOW_MIN_MAGNITUDE {
+            let bin = ((f64::from(degrees) + bin_width / 2.0) / bin_width) as usize % FLOW_DIRECTION_BINS;
+            votes[bin] += length;
+        }
+    }
+    let pixels = magnitude.total() as f64;
+    let dominant_direction = match votes.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)) {
+        Some((bin, &weight)) if weight > 0.0 => bin as f64 * bin_width,
+        _ => 0.0,
+    };
+    Ok(FlowStats {
+        mean_magnitude: total / pixels,
+        dominant_direction,
+    })
+}
+
+// Function to create the table of per-feature flow stats, which are optional: only
+// features whose ingest computed flow have a row
+pub(crate) fn create_flow_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS flow_stats (
+            feature_id TEXT PRIMARY KEY,
+            mean_magnitude REAL NOT NULL,
+            dominant_direction REAL NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS flow_stats_cascade_delete AFTER DELETE ON image_features
+        BEGIN
+            DELETE FROM flow_stats WHERE feature_id = OLD.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+// Function to record the flow stats of the frame a feature was extracted from, replacing
+// any stored before
+pub fn store_flow_stats(feature_id: &str, stats: &FlowStats, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    if !model::feature_exists(&conn, feature_id)? {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO flow_stats (feature_id, mean_magnitude, dominant_direction) VALUES (?1, ?2, ?3)",
+        params![feature_id, stats.mean_magnitude, stats.dominant_direction],
+    )?;
+    Ok(())
+}
+
+pub fn load_flow_stats(feature_id: &str, db_name: &str) -> Result<Option<FlowStats>> {
+    let conn = Connection::open(db_name)?;
+    let stats = conn
+        .query_row(
+            "SELECT mean_magnitude, dominant_direction FROM flow_stats WHERE feature_id = ?1",
+            params![feature_id],
+            |row| {
+                Ok(FlowStats {
+                    mean_magnitude: row.get(0)?,
+                    dominant_direction: row.get(1)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(stats)
+}
@@ -0,0 +1,275 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs,
+    prelude::*,
+};
+
+use crate::error::{Result, VyuwerError};
+use crate::schema;
+#[cfg(feature = "opencv")]
+use crate::{
+    extract::{extract_orb_features, OrbParams},
+    model,
+};
+
+const FRAME_DIRECTORY_SETTING: &str = "frame_directory";
+
+// Function to create the table of original frames, one encoded image (PNG unless migrated) per feature.
+// A frame is either held inline in `image`, or, once a frame directory is configured,
+// written to a content-addressed file whose relative path and SHA-256 are stored instead.
+// Deleting a feature cascades to its frame row through a trigger, as for `descriptors`;
+// files are left in place since identical frames share one.
+pub(crate) fn create_frame_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS frames (
+            feature_id TEXT PRIMARY KEY,
+            image BLOB
+        );
+        {FRAME_TRIGGER}"
+    ))?;
+    // Tables created before frame files existed declared `image` NOT NULL, which file-backed
+    // rows violate. SQLite cannot drop a constraint in place, so the table is rebuilt.
+    if schema::is_not_null(conn, "frames", "image")? {
+        conn.execute_batch(&format!(
+            "SAVEPOINT relax_frames;
+            DROP TRIGGER frames_cascade_delete;
+            CREATE TABLE frames_rebuilt (feature_id TEXT PRIMARY KEY, image BLOB);
+            INSERT INTO frames_rebuilt (feature_id, image) SELECT feature_id, image FROM frames;
+            DROP TABLE frames;
+            ALTER TABLE frames_rebuilt RENAME TO frames;
+            {FRAME_TRIGGER}
+            RELEASE relax_frames;"
+        ))?;
+    }
+    schema::add_column_if_missing(conn, "frames", "frame_path", "TEXT")?;
+    schema::add_column_if_missing(conn, "frames", "frame_sha256", "TEXT")?;
+    Ok(())
+}
+
+const FRAME_TRIGGER: &str = "CREATE TRIGGER IF NOT EXISTS frames_cascade_delete AFTER DELETE ON image_features
+    BEGIN
+        DELETE FROM frames WHERE feature_id = OLD.id;
+    END;";
+
+// Function to keep new frames as files under `directory` instead of BLOBs.
+// Frames stored earlier stay where they are.
+pub fn set_frame_directory(directory: &str, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    schema::set_setting(&conn, FRAME_DIRECTORY_SETTING, directory)
+}
+
+fn frame_directory(conn: &Connection) -> Result<Option<PathBuf>> {
+    Ok(schema::get_setting(conn, FRAME_DIRECTORY_SETTING)?.map(PathBuf::from))
+}
+
+// Encodings a stored frame can be in, recognised from their leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn detect(encoded: &[u8]) -> Option<Self> {
+        if encoded.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if encoded.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if encoded.len() >= 12 && &encoded[..4] == b"RIFF" && &encoded[8..12] == b"WEBP" {
+            Some(ImageFormat::WebP)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::WebP => "webp",
+        }
+    }
+}
+
+// Function to store an already-encoded frame for a feature, replacing any previous one
+pub fn store_frame_bytes(feature_id: &str, encoded: &[u8], db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    write_frame(&conn, feature_id, encoded)
+}
+
+fn write_frame(conn: &Connection, feature_id: &str, encoded: &[u8]) -> Result<()> {
+    let Some(directory) = frame_directory(conn)? else {
+        conn.execute(
+            "INSERT OR REPLACE INTO frames (feature_id, image) VALUES (?1, ?2)",
+            params![feature_id, encoded],
+        )?;
+        return Ok(());
+    };
+
+    let sha256 = sha256_hex(encoded);
+    let extension = ImageFormat::detect(encoded).map_or("png", ImageFormat::extension);
+    // Fan out by hash prefix so no single directory grows unbounded
+    let relative = format!("{}/{sha256}.{extension}", &sha256[..2]);
+    let path = directory.join(&relative);
+    if !path.exists() {
+        fs::create_dir_all(directory.join(&sha256[..2]))?;
+        fs::write(&path, encoded)?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO frames (feature_id, image, frame_path, frame_sha256) VALUES (?1, NULL, ?2, ?3)",
+        params![feature_id, relative, sha256],
+    )?;
+    Ok(())
+}
+
+pub fn load_frame_bytes(feature_id: &str, db_name: &str) -> Result<Option<Vec<u8>>> {
+    let conn = Connection::open(db_name)?;
+    fetch_frame_bytes(&conn, feature_id)
+}
+
+// Function to read a feature's frame from its BLOB or file; file contents are checked
+// against the stored hash so a modified or swapped file is reported, not returned
+pub(crate) fn fetch_frame_bytes(conn: &Connection, feature_id: &str) -> Result<Option<Vec<u8>>> {
+    let mut stmt = conn.prepare("SELECT image, frame_path, frame_sha256 FROM frames WHERE feature_id = ?1")?;
+    let mut rows = stmt.query(params![feature_id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    if let Some(image) = row.get::<_, Option<Vec<u8>>>(0)? {
+        return Ok(Some(image));
+    }
+    let (relative, expected): (String, String) = (row.get(1)?, row.get(2)?);
+    let directory = frame_directory(conn)?.ok_or_else(|| {
+        VyuwerError::CorruptFrame(format!("{feature_id} is stored at {relative} but no frame directory is set"))
+    })?;
+    let encoded = fs::read(directory.join(&relative))?;
+    if sha256_hex(&encoded) != expected {
+        return Err(VyuwerError::CorruptFrame(format!("{relative} does not match its SHA-256 {expected}")));
+    }
+    Ok(Some(encoded))
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Function to store a frame losslessly (PNG) so the feature can be reprocessed later
+#[cfg(feature = "opencv")]
+pub fn store_frame(feature_id: &str, image: &Mat, db_name: &str) -> Result<()> {
+    let mut encoded = Vector::<u8>::new();
+    imgcodecs::imencode_def(".png", image, &mut encoded)?;
+    store_frame_bytes(feature_id, encoded.as_slice(), db_name)
+}
+
+#[cfg(feature = "opencv")]
+pub fn load_frame(feature_id: &str, db_name: &str) -> Result<Option<Mat>> {
+    match load_frame_bytes(feature_id, db_name)? {
+        Some(encoded) => Ok(Some(decode_frame(&encoded)?)),
+        None => Ok(None),
+    }
+}
+
+// Function to re-run extraction on a feature's stored frame, e.g. after tuning `params`,
+// and update its keypoints and descriptors in place (id and timestamp are kept)
+#[cfg(feature = "opencv")]
+pub fn reprocess_feature(feature_id: &str, params: &OrbParams, db_name: &str) -> Result<()> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let encoded = fetch_frame_bytes(&tx, feature_id)?.ok_or_else(|| VyuwerError::MissingFrame(feature_id.to_string()))?;
+    let (keypoints, descriptors) = extract_orb_features(&decode_frame(&encoded)?, params)?;
+    if !model::update_feature_extraction(&tx, feature_id, &keypoints, &descriptors, params)? {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// Number of frames `migrate_frame_format` re-encodes per transaction
+#[cfg(feature = "opencv")]
+pub const FRAME_MIGRATION_BATCH_SIZE: usize = 100;
+
+// Function to re-encode every stored frame in `from` format as `to`, e.g. JPEG to WebP to
+// save space, committing in batches of FRAME_MIGRATION_BATCH_SIZE. Frames in other formats
+// are left alone. After each batch, old frame files no row references any more (by path
+// or hash) are deleted; identical frames share a file, so one may outlive its batch.
+// Returns how many frames were migrated.
+#[cfg(feature = "opencv")]
+pub fn migrate_frame_format(from: ImageFormat, to: ImageFormat, db_name: &str) -> Result<usize> {
+    if from == to {
+        return Ok(0);
+    }
+    let mut conn = Connection::open(db_name)?;
+    let feature_ids = conn
+        .prepare("SELECT feature_id FROM frames ORDER BY feature_id")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    let extension = format!(".{}", to.extension());
+
+    let mut migrated = 0;
+    for batch in feature_ids.chunks(FRAME_MIGRATION_BATCH_SIZE) {
+        let tx = conn.transaction()?;
+        let mut replaced_files = Vec::new();
+        for feature_id in batch {
+            let Some(encoded) = fetch_frame_bytes(&tx, feature_id)? else {
+                continue;
+            };
+            if ImageFormat::detect(&encoded) != Some(from) {
+                continue;
+            }
+            let old_file: Option<(String, String)> = tx.query_row(
+                "SELECT frame_path, frame_sha256 FROM frames WHERE feature_id = ?1",
+                params![feature_id],
+                |row| Ok(row.get::<_, Option<String>>(0)?.zip(row.get::<_, Option<String>>(1)?)),
+            )?;
+            let mut reencoded = Vector::<u8>::new();
+            imgcodecs::imencode_def(&extension, &decode_frame(&encoded)?, &mut reencoded)?;
+            write_frame(&tx, feature_id, reencoded.as_slice())?;
+            replaced_files.extend(old_file);
+            migrated += 1;
+        }
+        tx.commit()?;
+        remove_unreferenced_files(&conn, &replaced_files)?;
+    }
+    Ok(migrated)
+}
+
+// Function to delete frame files, given as (relative path, SHA-256), that no frame row
+// references any more. Returns how many files were removed.
+pub(crate) fn remove_unreferenced_files(conn: &Connection, files: &[(String, String)]) -> Result<usize> {
+    let Some(directory) = frame_directory(conn)? else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for (relative, sha256) in files {
+        let references: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM frames WHERE frame_path = ?1 OR frame_sha256 = ?2",
+            params![relative, sha256],
+            |row| row.get(0),
+        )?;
+        if references == 0 {
+            match fs::remove_file(directory.join(relative)) {
+                Ok(()) => removed += 1,
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                Err(_) => {}
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(feature = "opencv")]
+pub(crate) fn decode_frame(encoded: &[u8]) -> Result<Mat> {
+    let image = imgcodecs::imdecode(&Vector::<u8>::from_slice(encoded), imgcodecs::IMREAD_UNCHANGED)?;
+    if image.empty() {
+        return Err(VyuwerError::Video("stored frame could not be decoded".to_string()));
+    }
+    Ok(image)
+}
@@ -0,0 +1,157 @@
+use opencv::{
+    calib3d,
+    core::{Mat, Point2f, Vector},
+    prelude::*,
+};
+
+use crate::camera;
+use crate::error::{Result, VyuwerError};
+use crate::matching::{good_matches, MatchParams};
+use crate::model::ImageFeature;
+
+// RANSAC needs 4 correspondences; fewer inliers than this is treated as no reliable transform
+pub const MIN_HOMOGRAPHY_INLIERS: usize = 8;
+
+// Matches required before alignment quality is meaningful
+pub const MIN_ALIGNMENT_MATCHES: usize = 10;
+
+const RANSAC_REPROJ_THRESHOLD: f64 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentQuality {
+    pub inliers: usize,
+    // Mean distance in pixels between projected and matched points, over inliers only
+    pub mean_reprojection_error: f64,
+    // One entry per good match, true when RANSAC kept it
+    pub inlier_mask: Vec<bool>,
+}
+
+// Function to estimate the homography mapping `a`'s keypoints onto `b`'s, flattened row-major.
+// Returns None when there are too few matches or RANSAC inliers.
+pub fn estimate_homography(a: &ImageFeature, b: &ImageFeature) -> Result<Option<[f64; 9]>> {
+    let pairs = good_matches(a, b, &MatchParams::default());
+    let (src, dst) = point_pairs(a, b, &pairs);
+    match fit_homography(&src, &dst)? {
+        Some((homography, mask)) if mask.iter().filter(|&&m| m).count() >= MIN_HOMOGRAPHY_INLIERS => {
+            Ok(Some(homography))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Function to measure how well `a` aligns onto `b`, erroring when there are too few matches
+pub fn alignment_quality(a: &ImageFeature, b: &ImageFeature) -> Result<AlignmentQuality> {
+    let pairs = good_matches(a, b, &MatchParams::default());
+    let (src, dst) = point_pairs(a, b, &pairs);
+    if src.len() < MIN_ALIGNMENT_MATCHES {
+        return Err(VyuwerError::InsufficientMatches {
+            found: src.len(),
+            required: MIN_ALIGNMENT_MATCHES,
+        });
+    }
+    let Some((homography, inlier_mask)) = fit_homography(&src, &dst)? else {
+        return Ok(AlignmentQuality {
+            inliers: 0,
+            mean_reprojection_error: f64::INFINITY,
+            inlier_mask: vec![false; src.len()],
+        });
+    };
+
+    let mut total_error = 0.0;
+    let mut inliers = 0;
+    for (i, &inlier) in inlier_mask.iter().enumerate() {
+        if inlier {
+            let (p, q) = (src.get(i)?, dst.get(i)?);
+            let (x, y) = project(&homography, f64::from(p.x), f64::from(p.y));
+            total_error += ((x - f64::from(q.x)).powi(2) + (y - f64::from(q.y)).powi(2)).sqrt();
+            inliers += 1;
+        }
+    }
+    let mean_reprojection_error = if inliers == 0 {
+        f64::INFINITY
+    } else {
+        total_error / inliers as f64
+    };
+    Ok(AlignmentQuality {
+        inliers,
+        mean_reprojection_error,
+        inlier_mask,
+    })
+}
+
+// Function to remove lens distortion from `image` using the camera's stored calibration
+// (see `camera::set_calibration`), keeping the same camera matrix and image size
+pub fn undistort(image: &Mat, camera_id: &str, db_name: &str) -> Result<Mat> {
+    let calibration = camera::get_calibration(camera_id, db_name)?
+        .ok_or_else(|| VyuwerError::MissingCalibration(camera_id.to_string()))?;
+    let rows: Vec<&[f64]> = calibration.intrinsics.chunks(3).collect();
+    let camera_matrix = Mat::from_slice_2d(&rows)?;
+    let distortion = Vector::<f64>::from_slice(&calibration.distortion);
+    let mut undistorted = Mat::default();
+    calib3d::undistort(image, &mut undistorted, &camera_matrix, &distortion, &Mat::default())?;
+    Ok(undistorted)
+}
+
+// Function to run RANSAC, returning the flattened homography and the per-point inlier mask
+fn fit_homography(src: &Vector<Point2f>, dst: &Vector<Point2f>) -> Result<Option<([f64; 9], Vec<bool>)>> {
+    if src.len() < 4 {
+        return Ok(None);
+    }
+    let mut mask = Mat::default();
+    let homography = calib3d::find_homography(src, dst, &mut mask, calib3d::RANSAC, RANSAC_REPROJ_THRESHOLD)?;
+    if homography.empty() {
+        return Ok(None);
+    }
+
+    let mut flat = [0.0; 9];
+    for (i, value) in flat.iter_mut().enumerate() {
+        *value = *homography.at_2d::<f64>((i / 3) as i32, (i % 3) as i32)?;
+    }
+    let inlier_mask = mask.data_bytes()?.iter().map(|&m| m != 0).collect();
+    Ok(Some((flat, inlier_mask)))
+}
+
+// Function to keep the matches whose keypoints RANSAC fits to one homography, for
+// `SimilarityMetric::InlierRatio`. Matches without keypoints are dropped, and no fit (too
+// few points, or OpenCV failing on degenerate ones) keeps nothing.
+pub(crate) fn homography_inliers(
+    a: &ImageFeature,
+    b: &ImageFeature,
+    pairs: Vec<(usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let pairs: Vec<(usize, usize)> = pairs
+        .into_iter()
+        .filter(|&(qi, ti)| qi < a.keypoints.len() && ti < b.keypoints.len())
+        .collect();
+    let (src, dst) = point_pairs(a, b, &pairs);
+    match fit_homography(&src, &dst) {
+        Ok(Some((_, mask))) => pairs
+            .into_iter()
+            .zip(mask)
+            .filter_map(|(pair, inlier)| inlier.then_some(pair))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn project(h: &[f64; 9], x: f64, y: f64) -> (f64, f64) {
+    let w = h[6] * x + h[7] * y + h[8];
+    ((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w)
+}
+
+// Function to collect the matched keypoint coordinates as OpenCV point vectors
+pub(crate) fn point_pairs(
+    a: &ImageFeature,
+    b: &ImageFeature,
+    pairs: &[(usize, usize)],
+) -> (Vector<Point2f>, Vector<Point2f>) {
+    let mut src = Vector::<Point2f>::new();
+    let mut dst = Vector::<Point2f>::new();
+    for &(qi, ti) in pairs {
+        if let (Some(p), Some(q)) = (a.keypoints.get(qi), b.keypoints.get(ti)) {
+            src.push(Point2f::new(p.x, p.y));
+            dst.push(Point2f::new(q.x, q.y));
+        }
+    }
+    (src, dst)
+}
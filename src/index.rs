@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fs;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::codec;
+use crate::error::{Result, VyuwerError};
+use crate::matching::hamming_distance;
+use crate::model::{fetch_features, DescriptorMatrix};
+
+// LSH parameters: more tables raise recall, longer keys make each bucket smaller
+const TABLE_COUNT: usize = 8;
+const KEY_BITS: usize = 16;
+
+// One hash table: a key is the descriptor's values at `bits`, sampled positions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LshTable {
+    bits: Vec<usize>,
+    buckets: HashMap<u32, Vec<usize>>,
+}
+
+impl LshTable {
+    fn key(&self, descriptor: &[u8]) -> u32 {
+        self.bits
+            .iter()
+            .enumerate()
+            .filter(|(_, &bit)| descriptor[bit / 8] & (1 << (bit % 8)) != 0)
+            .fold(0, |key, (i, _)| key | (1 << i))
+    }
+}
+
+// Bit-sampling LSH index over every descriptor row of a camera's history, implemented
+// here rather than through OpenCV's FLANN. Each row is hashed into several tables; a
+// query only compares against rows that share a bucket with it in some table, so a true
+// nearest row in no shared bucket is missed. `save`/`load` persist it instead of rebuilding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LshIndex {
+    tables: Vec<LshTable>,
+    descriptors: DescriptorMatrix,
+    // owners[row] indexes `feature_ids`
+    owners: Vec<usize>,
+    feature_ids: Vec<String>,
+}
+
+impl LshIndex {
+    pub fn len(&self) -> usize {
+        self.feature_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.feature_ids.is_empty()
+    }
+
+    // Function to write the index to `path`. It is a snapshot: features stored later are
+    // not in it until it is rebuilt.
+    pub fn save(&self, path: &str) -> Result<()> {
+        fs::write(path, codec::encode(self)?)?;
+        Ok(())
+    }
+
+    // Function to read an index written by `save`, checking it is internally consistent
+    pub fn load(path: &str) -> Result<Self> {
+        let index: LshIndex = codec::decode(&fs::read(path)?)?;
+        index.check()?;
+        Ok(index)
+    }
+
+    fn check(&self) -> Result<()> {
+        let rows = self.descriptors.rows;
+        let total_bits = self.descriptors.cols * 8;
+        let consistent = self.owners.len() == rows
+            && self.owners.iter().all(|&owner| owner < self.feature_ids.len())
+            && self.tables.iter().all(|table| {
+                table.bits.iter().all(|&bit| bit < total_bits)
+                    && table.buckets.values().flatten().all(|&row| row < rows)
+            });
+        if !consistent {
+            return Err(VyuwerError::InvalidInput("index file is inconsistent".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Function to index every descriptor stored for `camera_id`
+pub fn build_lsh_index(camera_id: &str, db_name: &str) -> Result<LshIndex> {
+    let conn = Connection::open(db_name)?;
+    let features = fetch_features(&conn, camera_id, None)?;
+    let cols = features.iter().find(|f| !f.descriptors.is_empty()).map_or(0, |f| f.descriptors.cols);
+    if features.iter().any(|f| !f.descriptors.is_empty() && f.descriptors.cols != cols) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "camera {camera_id} mixes descriptors of different widths"
+        )));
+    }
+
+    let mut data = Vec::new();
+    let mut owners = Vec::new();
+    let mut feature_ids = Vec::new();
+    for feature in features.iter().filter(|f| !f.descriptors.is_empty()) {
+        data.extend_from_slice(&feature.descriptors.data);
+        owners.extend(std::iter::repeat_n(feature_ids.len(), feature.descriptors.rows));
+        feature_ids.push(feature.id.clone());
+    }
+    let descriptors = DescriptorMatrix::new(owners.len(), cols, data)?;
+
+    let mut tables: Vec<LshTable> = (0..TABLE_COUNT)
+        .map(|t| LshTable {
+            bits: sample_bits(t as u64, cols * 8),
+            buckets: HashMap::new(),
+        })
+        .collect();
+    if cols > 0 {
+        for table in &mut tables {
+            for (row, descriptor) in descriptors.row_iter().enumerate() {
+                let key = table.key(descriptor);
+                table.buckets.entry(key).or_default().push(row);
+            }
+        }
+    }
+    Ok(LshIndex {
+        tables,
+        descriptors,
+        owners,
+        feature_ids,
+    })
+}
+
+// Function to find the `k` indexed features closest to `descriptors`: each query row votes
+// for the feature owning its nearest candidate row, and features are ranked by votes
+pub fn query_index(index: &LshIndex, descriptors: &DescriptorMatrix, k: usize) -> Vec<String> {
+    if index.descriptors.is_empty() || descriptors.cols != index.descriptors.cols {
+        return Vec::new();
+    }
+    let mut votes = vec![0usize; index.feature_ids.len()];
+    for query in descriptors.row_iter() {
+        let nearest = index
+            .tables
+            .iter()
+            .filter_map(|table| table.buckets.get(&table.key(query)))
+            .flatten()
+            .min_by_key(|&&row| hamming_distance(query, index.descriptors.row(row)));
+        if let Some(&row) = nearest {
+            votes[index.owners[row]] += 1;
+        }
+    }
+
+    let mut ranked: Vec<usize> = (0..votes.len()).filter(|&i| votes[i] > 0).collect();
+    ranked.sort_by(|&a, &b| votes[b].cmp(&votes[a]).then(a.cmp(&b)));
+    ranked.into_iter().take(k).map(|i| index.feature_ids[i].clone()).collect()
+}
+
+// Function to pick KEY_BITS distinct bit positions out of `total_bits`, deterministically per table
+fn sample_bits(table: u64, total_bits: usize) -> Vec<usize> {
+    if total_bits == 0 {
+        return Vec::new();
+    }
+    // xorshift64*, seeded per table so every build of the same data hashes identically
+    let mut state = 0x9E37_79B9_7F4A_7C15u64 ^ (table + 1).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    let mut bits = Vec::with_capacity(KEY_BITS);
+    while bits.len() < KEY_BITS.min(total_bits) {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        let bit = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) % total_bits as u64) as usize;
+        if !bits.contains(&bit) {
+            bits.push(bit);
+        }
+    }
+    bits
+}
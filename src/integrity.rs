@@ -0,0 +1,133 @@
+use rusqlite::Connection;
+
+use crate::audit;
+use crate::error::Result;
+use crate::model::{
+    delete_feature_row, read_feature_row, replace_keypoints_and_descriptors, CorruptFeature, DescriptorMatrix,
+    FeatureReadResult, ImageDescription, FEATURE_SELECT,
+};
+
+// Rows referring to features that no longer exist. Deletes through this crate clean up
+// tags, but merges, manual edits or other tools can leave any of these behind.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    // Descriptions whose image no feature of the same camera was stored from
+    pub descriptions_without_feature: Vec<ImageDescription>,
+    // (feature_id, key) of tags on missing features
+    pub tags_without_feature: Vec<(String, String)>,
+    // (camera_id, feature_id) of baselines promoting a missing feature
+    pub baselines_without_feature: Vec<(String, String)>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.descriptions_without_feature.is_empty()
+            && self.tags_without_feature.is_empty()
+            && self.baselines_without_feature.is_empty()
+    }
+}
+
+// Function to find dangling references across tables. Descriptions are linked to features
+// by image name and camera, the same join `features_without_description` uses.
+pub fn check_referential_integrity(db_name: &str) -> Result<IntegrityReport> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT d.image_name, d.datetime, d.camera_id, d.anomaly FROM image_description d
+        WHERE NOT EXISTS (
+            SELECT 1 FROM image_features f WHERE f.img_filename = d.image_name AND f.camera_id = d.camera_id
+        )
+        ORDER BY d.camera_id, d.image_name",
+    )?;
+    let descriptions_without_feature = stmt
+        .query_map([], |row| {
+            Ok(ImageDescription {
+                image_name: row.get(0)?,
+                datetime: row.get(1)?,
+                camera_id: row.get(2)?,
+                anomaly: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(IntegrityReport {
+        descriptions_without_feature,
+        tags_without_feature: dangling_pairs(&conn, "feature_tags", "feature_id, key")?,
+        baselines_without_feature: dangling_pairs(&conn, "baselines", "camera_id, feature_id")?,
+    })
+}
+
+// Function to list two columns of `table`'s rows whose feature_id matches no feature
+fn dangling_pairs(conn: &Connection, table: &str, columns: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {columns} FROM {table}
+        WHERE feature_id NOT IN (SELECT id FROM image_features)
+        ORDER BY {columns}"
+    ))?;
+    let pairs = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(pairs)
+}
+
+// Function to decode every stored feature and list those that fail, by camera and id, e.g.
+// rows left truncated by a crash mid-insert. The scan carries on past each corrupt row;
+// the listed features can then be deleted or re-extracted.
+pub fn scan_corrupt_features(db_name: &str) -> Result<Vec<CorruptFeature>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!("{FEATURE_SELECT} ORDER BY camera_id, image_features.id"))?;
+    let mut rows = stmt.query([])?;
+    let mut corrupt = Vec::new();
+    while let Some(row) = rows.next()? {
+        if let FeatureReadResult::Corrupt(feature) = read_feature_row(row)? {
+            corrupt.push(feature);
+        }
+    }
+    Ok(corrupt)
+}
+
+// What `repair_inconsistent` does with a feature whose keypoint and descriptor-row counts differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairStrategy {
+    // Keep the first min(keypoints, rows) of both
+    Truncate,
+    Delete,
+}
+
+// Function to heal features breaking `validate_feature`'s one-descriptor-row-per-keypoint
+// invariant, e.g. written by a past bug, all in one transaction. Features without
+// descriptors are exempt as there, and rows that do not decode are left to
+// `scan_corrupt_features`. Each repair is audited. Returns how many features were repaired.
+pub fn repair_inconsistent(db_name: &str, strategy: RepairStrategy) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let inconsistent = {
+        let mut stmt = tx.prepare(&format!("{FEATURE_SELECT} ORDER BY camera_id, image_features.id"))?;
+        let mut rows = stmt.query([])?;
+        let mut inconsistent = Vec::new();
+        while let Some(row) = rows.next()? {
+            if let FeatureReadResult::Ok(feature) = read_feature_row(row)? {
+                if !feature.descriptors.is_empty() && feature.keypoints.len() != feature.descriptors.rows {
+                    inconsistent.push(feature);
+                }
+            }
+        }
+        inconsistent
+    };
+    for feature in &inconsistent {
+        match strategy {
+            RepairStrategy::Truncate => {
+                let count = feature.keypoints.len().min(feature.descriptors.rows);
+                let cols = feature.descriptors.cols;
+                let data = feature.descriptors.data[..count * cols].to_vec();
+                let descriptors = DescriptorMatrix::new(count, cols, data)?;
+                replace_keypoints_and_descriptors(&tx, &feature.id, &feature.keypoints[..count], &descriptors)?;
+                audit::record(&tx, "repair", &feature.id, Some(&format!("truncated to {count} keypoint(s)")))?;
+            }
+            RepairStrategy::Delete => {
+                delete_feature_row(&tx, &feature.id)?;
+                audit::record(&tx, "repair", &feature.id, Some("deleted"))?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(inconsistent.len())
+}
@@ -0,0 +1,230 @@
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension};
+
+use crate::error::{Result, VyuwerError};
+use crate::matching::ORB_DESCRIPTOR_BYTES;
+use crate::model::{decode_descriptors, materialize_deltas, DescriptorMatrix, DELTA_DESCRIPTORS_FEATURE_VERSION};
+use crate::schema;
+
+const LAYOUT_SETTING: &str = "descriptor_layout";
+
+// Where descriptor matrices are stored.
+// Embedded keeps them in image_features.descriptors; Normalized moves them to the
+// `descriptors` table so metadata scans of image_features never touch the large blobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorLayout {
+    Embedded,
+    Normalized,
+}
+
+// Function to create the normalized descriptor table.
+// Deleting a feature cascades to its descriptor row through a trigger, so it holds
+// regardless of whether a connection enabled `PRAGMA foreign_keys`.
+pub(crate) fn create_descriptor_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS descriptors (
+            feature_id TEXT PRIMARY KEY,
+            data BLOB NOT NULL,
+            rows INTEGER NOT NULL,
+            cols INTEGER NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS descriptors_cascade_delete AFTER DELETE ON image_features
+        BEGIN
+            DELETE FROM descriptors WHERE feature_id = OLD.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+pub(crate) fn layout(conn: &Connection) -> Result<DescriptorLayout> {
+    match schema::get_setting(conn, LAYOUT_SETTING)?.as_deref() {
+        Some("normalized") => Ok(DescriptorLayout::Normalized),
+        _ => Ok(DescriptorLayout::Embedded),
+    }
+}
+
+// Function to report which descriptor layout a database uses
+pub fn descriptor_layout(db_name: &str) -> Result<DescriptorLayout> {
+    let conn = Connection::open(db_name)?;
+    layout(&conn)
+}
+
+// REPLACE on image_features does not fire the cascade trigger, so a replaced feature's
+// old descriptor row is overwritten here instead
+pub(crate) fn insert_descriptor_row(conn: &Connection, feature_id: &str, descriptors: &DescriptorMatrix) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO descriptors (feature_id, data, rows, cols) VALUES (?1, ?2, ?3, ?4)",
+        params![feature_id, descriptors.data, descriptors.rows as i64, descriptors.cols as i64],
+    )?;
+    Ok(())
+}
+
+// Function to move every embedded descriptor blob into the `descriptors` table and
+// switch the database to the normalized layout. Returns how many rows were moved.
+pub fn migrate_to_normalized_descriptors(db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    // The descriptors table has no delta form
+    materialize_deltas(&tx, None)?;
+
+    let mut embedded = Vec::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT id, descriptors, feature_version FROM image_features WHERE descriptors IS NOT NULL",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let blob: Vec<u8> = row.get(1)?;
+            embedded.push((row.get::<_, String>(0)?, decode_descriptors(&blob, row.get(2)?)?));
+        }
+    }
+    for (feature_id, descriptors) in &embedded {
+        insert_descriptor_row(&tx, feature_id, descriptors)?;
+        tx.execute("UPDATE image_features SET descriptors = NULL WHERE id = ?1", params![feature_id])?;
+    }
+    schema::set_setting(&tx, LAYOUT_SETTING, "normalized")?;
+    tx.commit()?;
+    Ok(embedded.len())
+}
+
+// Descriptor rows `DescriptorRows` reads per incremental blob read
+pub const DESCRIPTOR_READ_CHUNK_ROWS: usize = 256;
+
+// Iterator over a feature's descriptor rows, read from the blob in chunks of
+// DESCRIPTOR_READ_CHUNK_ROWS through SQLite's incremental blob I/O, so the whole matrix
+// is never in memory at once. The feature must not be modified while iterating.
+pub struct DescriptorRows {
+    conn: Connection,
+    table: &'static str,
+    column: &'static str,
+    rowid: i64,
+    // Byte offset of the next unread row within the blob
+    offset: usize,
+    cols: usize,
+    remaining: usize,
+    chunk: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl DescriptorRows {
+    pub fn rows_remaining(&self) -> usize {
+        self.remaining + self.chunk.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn read_chunk(&mut self) -> Result<()> {
+        let rows = self.remaining.min(DESCRIPTOR_READ_CHUNK_ROWS);
+        let mut buffer = vec![0; rows * self.cols];
+        let blob = self.conn.blob_open(DatabaseName::Main, self.table, self.column, self.rowid, true)?;
+        blob.read_at_exact(&mut buffer, self.offset)?;
+        self.offset += buffer.len();
+        self.remaining -= rows;
+        self.chunk = buffer.chunks(self.cols.max(1)).map(<[u8]>::to_vec).collect::<Vec<_>>().into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for DescriptorRows {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.len() == 0 && self.remaining > 0 {
+            if let Err(err) = self.read_chunk() {
+                self.remaining = 0;
+                return Some(Err(err));
+            }
+        }
+        self.chunk.next().map(Ok)
+    }
+}
+
+// Function to stream a feature's descriptor rows one at a time without loading the blob,
+// e.g. into a matcher on a memory-constrained device. Reads whichever layout the feature
+// is stored in; the blob's header is checked against its size before any row is read.
+pub fn descriptor_rows(feature_id: &str, db_name: &str) -> Result<DescriptorRows> {
+    let conn = Connection::open(db_name)?;
+    let normalized = conn
+        .query_row(
+            "SELECT rowid, rows, cols, length(data) FROM descriptors WHERE feature_id = ?1",
+            params![feature_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?)),
+        )
+        .optional()?;
+    let (table, column, rowid, offset, rows, cols) = match normalized {
+        Some((rowid, rows, cols, len)) => {
+            check_shape(feature_id, rows as usize, cols as usize, len as usize)?;
+            ("descriptors", "data", rowid, 0, rows as usize, cols as usize)
+        }
+        None => {
+            let (rowid, version, len): (i64, i64, Option<i64>) = conn
+                .query_row(
+                    "SELECT rowid, feature_version, length(descriptors) FROM image_features WHERE id = ?1",
+                    params![feature_id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?
+                .ok_or_else(|| VyuwerError::FeatureNotFound(feature_id.to_string()))?;
+            let len = len.unwrap_or(0) as usize;
+            let (offset, rows, cols) = embedded_header(&conn, feature_id, rowid, version, len)?;
+            ("image_features", "descriptors", rowid, offset, rows, cols)
+        }
+    };
+    Ok(DescriptorRows {
+        conn,
+        table,
+        column,
+        rowid,
+        offset,
+        cols,
+        remaining: rows,
+        chunk: Vec::new().into_iter(),
+    })
+}
+
+// Function to read where the rows of an embedded descriptor blob start and its shape,
+// from the codec's u64 length prefixes: v1 is one flat Vec of 32-byte ORB rows, v2 a
+// DescriptorMatrix (rows, cols, then the data Vec)
+fn embedded_header(
+    conn: &Connection,
+    feature_id: &str,
+    rowid: i64,
+    version: i64,
+    len: usize,
+) -> Result<(usize, usize, usize)> {
+    let header_len = match version {
+        1 => 8,
+        2 | 3 => 24,
+        DELTA_DESCRIPTORS_FEATURE_VERSION => {
+            return Err(VyuwerError::InvalidInput(format!(
+                "descriptors of {feature_id} are stored as a delta; read the feature whole"
+            )))
+        }
+        _ => return Err(VyuwerError::UnsupportedFeatureVersion(version)),
+    };
+    if len < header_len {
+        return Ok((header_len, 0, ORB_DESCRIPTOR_BYTES));
+    }
+    let mut header = vec![0; header_len];
+    conn.blob_open(DatabaseName::Main, "image_features", "descriptors", rowid, true)?
+        .read_at_exact(&mut header, 0)?;
+    let field = |i: usize| u64::from_le_bytes(header[i * 8..(i + 1) * 8].try_into().expect("8-byte field")) as usize;
+    match version {
+        // Trailing bytes short of a whole row are ignored, as when decoding
+        1 => Ok((8, field(0).min(len - 8) / ORB_DESCRIPTOR_BYTES, ORB_DESCRIPTOR_BYTES)),
+        _ => {
+            check_shape(feature_id, field(0), field(1), field(2))?;
+            check_shape(feature_id, field(0), field(1), len - 24)?;
+            Ok((24, field(0), field(1)))
+        }
+    }
+}
+
+fn check_shape(feature_id: &str, rows: usize, cols: usize, len: usize) -> Result<()> {
+    if rows.checked_mul(cols) != Some(len) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "descriptors of {feature_id} claim {rows}x{cols} but hold {len} bytes"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+pub mod analytics;
+pub mod anomaly;
+pub mod audit;
+pub mod backup;
+pub mod cache;
+pub mod camera;
+pub mod cluster;
+pub mod codec;
+pub mod db;
+pub mod descriptor_sets;
+pub mod error;
+pub mod export;
+pub mod extract;
+pub mod float_descriptors;
+pub mod flow;
+pub mod frames;
+#[cfg(feature = "opencv")]
+pub mod geometry;
+pub mod index;
+pub mod integrity;
+pub mod layout;
+pub mod maintenance;
+pub mod matching;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod model;
+pub mod phash;
+pub mod progress;
+pub mod ratelimit;
+pub mod schema;
+pub mod storage;
+pub mod store;
+pub mod sync;
+pub mod stream;
+pub mod tags;
+pub mod timestamp;
+#[cfg(feature = "opencv")]
+pub mod video;
+pub mod view;
+pub mod vocabulary;
+pub mod window;
+
+pub use db::Database;
+pub use error::{Result, VyuwerError};
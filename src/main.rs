@@ -1,3 +1,51 @@
-fn main() {
-    println!("Hello, world!");
+use vyuwer_rust::model::{
+    get_image_feature, insert_image_description, insert_image_feature, setup_schema, DescriptorMatrix,
+    ImageDescription, ImageFeature, KeyPointData, PROD_DB, TEST_DB,
+};
+use vyuwer_rust::Result;
+
+// Main function to test the setup
+fn main() -> Result<()> {
+    setup_schema(PROD_DB)?;
+    setup_schema(TEST_DB)?;
+
+    let image_description = ImageDescription {
+        image_name: String::from("test_image.jpg"),
+        datetime: String::from("2024-06-12T12:34:56Z"),
+        camera_id: String::from("camera_1"),
+        anomaly: None,
+    };
+    insert_image_description(&image_description, PROD_DB)?;
+
+    let keypoints = vec![
+        KeyPointData { x: 0.0, y: 0.0, size: 1.0, angle: 0.0 },
+        KeyPointData { x: 1.0, y: 1.0, size: 2.0, angle: 45.0 },
+    ];
+    let descriptors = DescriptorMatrix::new(2, 5, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])?;
+
+    let image_feature = ImageFeature {
+        id: String::from("1"),
+        keypoints,
+        descriptors,
+        motion_mean: 0.5,
+        motion_std: 0.1,
+        created_at_utc: String::from("2024-06-12T12:34:56Z"),
+        img_filename: Some(String::from("image_1.jpg")),
+        camera_id: String::from("camera_1"),
+        phash: None,
+        frame_size: None,
+        extraction_params: None,
+        sharpness: None,
+        roi: None,
+    };
+
+    insert_image_feature(&image_feature, PROD_DB)?;
+
+    if let Some(feature) = get_image_feature("camera_1", PROD_DB)? {
+        println!("Retrieved image feature: {:?}", feature);
+    } else {
+        println!("No image feature found for the given camera_id.");
+    }
+
+    Ok(())
 }
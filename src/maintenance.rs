@@ -0,0 +1,131 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+
+use crate::error::Result;
+use crate::model;
+use crate::timestamp::utc_iso8601_ago;
+
+// Shortest interval a task runs at; shorter ones, including zero, are raised to it so the
+// background loop cannot spin
+pub const MIN_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(1);
+
+// What one maintenance pass did; tasks that are not configured stay at their defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    pub pruned: usize,
+    pub checkpointed: bool,
+    pub vacuumed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Task {
+    interval: Duration,
+    last_run: Option<Instant>,
+}
+
+impl Task {
+    fn new(interval: Duration) -> Self {
+        Task {
+            interval: interval.max(MIN_MAINTENANCE_INTERVAL),
+            last_run: None,
+        }
+    }
+
+    fn due_in(&self, now: Instant) -> Duration {
+        self.last_run
+            .map_or(Duration::ZERO, |last| (last + self.interval).saturating_duration_since(now))
+    }
+}
+
+// Periodic prune / WAL checkpoint / vacuum for one database. Each task is off until
+// configured, e.g. `Maintenance::new(PROD_DB).prune_every(day, 30 * day).vacuum_every(week)`.
+pub struct Maintenance {
+    db_name: String,
+    prune: Option<(Task, Duration)>,
+    checkpoint: Option<Task>,
+    vacuum: Option<Task>,
+}
+
+impl Maintenance {
+    pub fn new(db_name: &str) -> Self {
+        Maintenance {
+            db_name: db_name.to_string(),
+            prune: None,
+            checkpoint: None,
+            vacuum: None,
+        }
+    }
+
+    // Function to delete features older than `retention` every `interval`
+    pub fn prune_every(mut self, interval: Duration, retention: Duration) -> Self {
+        self.prune = Some((Task::new(interval), retention));
+        self
+    }
+
+    // Function to truncate the write-ahead log every `interval` (a no-op outside WAL mode)
+    pub fn checkpoint_every(mut self, interval: Duration) -> Self {
+        self.checkpoint = Some(Task::new(interval));
+        self
+    }
+
+    pub fn vacuum_every(mut self, interval: Duration) -> Self {
+        self.vacuum = Some(Task::new(interval));
+        self
+    }
+
+    // Function to run every configured task immediately, regardless of its interval
+    pub fn run_once(&mut self) -> Result<MaintenanceReport> {
+        self.run(true)
+    }
+
+    // Function to run the tasks on a background thread until `shutdown` receives a
+    // message or its sender is dropped. The thread returns the first error it hits.
+    pub fn spawn(mut self, shutdown: Receiver<()>) -> JoinHandle<Result<()>> {
+        thread::spawn(move || loop {
+            self.run(false)?;
+            match shutdown.recv_timeout(self.next_due()) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        })
+    }
+
+    fn run(&mut self, force: bool) -> Result<MaintenanceReport> {
+        let now = Instant::now();
+        let due = |task: &Task| force || task.due_in(now).is_zero();
+        let conn = Connection::open(&self.db_name)?;
+        let mut report = MaintenanceReport::default();
+
+        if let Some((task, retention)) = self.prune.as_mut().filter(|(task, _)| due(task)) {
+            report.pruned = model::prune_features(&conn, &utc_iso8601_ago(*retention))?;
+            task.last_run = Some(now);
+        }
+        if let Some(task) = self.checkpoint.as_mut().filter(|task| due(task)) {
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+            report.checkpointed = true;
+            task.last_run = Some(now);
+        }
+        if let Some(task) = self.vacuum.as_mut().filter(|task| due(task)) {
+            conn.execute_batch("VACUUM")?;
+            report.vacuumed = true;
+            task.last_run = Some(now);
+        }
+        Ok(report)
+    }
+
+    // Function to find how long to sleep until the next task is due (an hour when none are configured)
+    fn next_due(&self) -> Duration {
+        let now = Instant::now();
+        self.prune
+            .iter()
+            .map(|(task, _)| task)
+            .chain(self.checkpoint.iter())
+            .chain(self.vacuum.iter())
+            .map(|task| task.due_in(now))
+            .min()
+            .unwrap_or(Duration::from_secs(3600))
+    }
+}
@@ -0,0 +1,341 @@
+use crate::descriptor_sets::DescriptorKind;
+use crate::error::{Result, VyuwerError};
+use crate::model::{DescriptorMatrix, ImageFeature, KeyPointData};
+use crate::stream::SplitMix64;
+
+// Size in bytes of one ORB descriptor row
+pub const ORB_DESCRIPTOR_BYTES: usize = 32;
+
+// Largest number of cells `distance_matrix` builds (4 MB of distances)
+pub const MAX_DISTANCE_MATRIX_CELLS: usize = 1 << 20;
+
+// Lowe's ratio: a match is kept if its best distance is below this fraction of the second best
+pub const DEFAULT_LOWE_RATIO: f64 = 0.7;
+
+// Which matches count as good. All start from the nearest neighbour of each query row;
+// the raw count of what survives is `MatchResult::good_matches`, the normalized score
+// `MatchResult::match_ratio`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SimilarityMetric {
+    // Lowe's ratio test, query to train only
+    #[default]
+    RatioTest,
+    // The ratio test plus a symmetric check: the train row's own nearest query row must be
+    // the one it was matched from. Fewer, more reliable matches, at twice the comparisons.
+    CrossCheck,
+    // The ratio test, keeping only the matches whose keypoints RANSAC fits to one homography
+    // (as `geometry::alignment_quality` does), so `match_ratio` becomes an inlier ratio.
+    // Needs keypoints: `match_descriptors` and `match_float_descriptors` only see
+    // descriptors and apply the plain ratio test instead.
+    #[cfg(feature = "opencv")]
+    InlierRatio,
+}
+
+// Query rows are subsampled to this fraction before matching when below 1.0, see
+// `MatchParams::with_sampling`. Rows are picked by a seeded PRNG, so a seed always picks
+// the same rows of the same matrix. Comparisons shrink in proportion. Each kept row is
+// matched exactly as without sampling, so `good_matches` shrinks by about the fraction,
+// while `match_ratio` (over the sampled `query_descriptors`) estimates the full ratio with
+// a standard error of about sqrt(r(1 - r) / kept rows), e.g. ±0.045 at r = 0.5 with 125
+// rows kept. Thresholds near the ratio therefore flip more often, and a handful of
+// distinctive matches can be missed entirely at small fractions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchParams {
+    lowe_ratio: f64,
+    metric: SimilarityMetric,
+    sample_fraction: f64,
+    sample_seed: u64,
+}
+
+impl MatchParams {
+    // Lower ratios keep fewer, more distinctive matches (precision); higher ratios keep more (recall)
+    pub fn new(lowe_ratio: f64) -> Result<Self> {
+        if !(lowe_ratio > 0.0 && lowe_ratio < 1.0) {
+            return Err(VyuwerError::InvalidInput(format!(
+                "lowe_ratio must be within (0, 1), got {lowe_ratio}"
+            )));
+        }
+        Ok(MatchParams {
+            lowe_ratio,
+            ..MatchParams::default()
+        })
+    }
+
+    pub fn with_metric(self, metric: SimilarityMetric) -> Self {
+        MatchParams { metric, ..self }
+    }
+
+    // Function to match only `sample_fraction` (within (0, 1]) of the query rows, chosen by
+    // `seed`; 1.0 matches every row
+    pub fn with_sampling(self, sample_fraction: f64, seed: u64) -> Result<Self> {
+        if !(sample_fraction > 0.0 && sample_fraction <= 1.0) {
+            return Err(VyuwerError::InvalidInput(format!(
+                "sample_fraction must be within (0, 1], got {sample_fraction}"
+            )));
+        }
+        Ok(MatchParams {
+            sample_fraction,
+            sample_seed: seed,
+            ..self
+        })
+    }
+
+    pub fn lowe_ratio(&self) -> f64 {
+        self.lowe_ratio
+    }
+
+    pub fn metric(&self) -> SimilarityMetric {
+        self.metric
+    }
+
+    pub fn sample_fraction(&self) -> f64 {
+        self.sample_fraction
+    }
+
+    pub fn sample_seed(&self) -> u64 {
+        self.sample_seed
+    }
+
+    // Function to pick the query rows to match, in ascending order; at least one of a
+    // non-empty matrix
+    pub(crate) fn sampled_rows(&self, rows: usize) -> Vec<usize> {
+        let keep = ((rows as f64 * self.sample_fraction).ceil() as usize).clamp(rows.min(1), rows);
+        let mut indices: Vec<usize> = (0..rows).collect();
+        if keep < rows {
+            // Partial Fisher-Yates: the first `keep` slots end up a uniform sample
+            let mut rng = SplitMix64(self.sample_seed);
+            for i in 0..keep {
+                let j = i + rng.below((rows - i) as u64) as usize;
+                indices.swap(i, j);
+            }
+            indices.truncate(keep);
+            indices.sort_unstable();
+        }
+        indices
+    }
+}
+
+impl Default for MatchParams {
+    fn default() -> Self {
+        MatchParams {
+            lowe_ratio: DEFAULT_LOWE_RATIO,
+            metric: SimilarityMetric::default(),
+            sample_fraction: 1.0,
+            sample_seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchResult {
+    pub good_matches: usize,
+    // Query rows compared: all of them unless `MatchParams::with_sampling` kept fewer
+    pub query_descriptors: usize,
+    pub train_descriptors: usize,
+}
+
+impl MatchResult {
+    // Good matches as a fraction of the smaller descriptor set (0.0 when either side is empty)
+    pub fn match_ratio(&self) -> f64 {
+        let denom = self.query_descriptors.min(self.train_descriptors);
+        if denom == 0 {
+            0.0
+        } else {
+            self.good_matches as f64 / denom as f64
+        }
+    }
+}
+
+pub fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+// Function to take the per-bit majority of equally sized binary descriptors, the mean in
+// Hamming space (ties resolve to 0)
+pub(crate) fn bitwise_majority(rows: &[&[u8]], cols: usize) -> Vec<u8> {
+    (0..cols)
+        .map(|byte_index| {
+            (0..8).fold(0u8, |byte, bit| {
+                let votes = rows.iter().filter(|row| row[byte_index] & (1 << bit) != 0).count();
+                if votes * 2 > rows.len() {
+                    byte | (1 << bit)
+                } else {
+                    byte
+                }
+            })
+        })
+        .collect()
+}
+
+// Function to brute-force match `query` descriptors against `train` with Lowe's ratio test.
+// Either side having no descriptors gives 0 good matches and a match ratio of 0.0.
+pub fn match_features(query: &ImageFeature, train: &ImageFeature, params: &MatchParams) -> MatchResult {
+    MatchResult {
+        good_matches: good_matches(query, train, params).len(),
+        query_descriptors: params.sampled_rows(query.descriptors.rows).len(),
+        train_descriptors: train.descriptors.rows,
+    }
+}
+
+// Function to measure how one-sided the overlap of two features is: the good matches
+// from `a` into `b` as a fraction of `a`'s descriptors, and the same from `b` into `a`,
+// returned as their absolute difference (0.0 for a symmetric pair). A partly occluded view
+// keeps fewer descriptors, most of which still find the full view, so it scores high
+// where a full scene change scores low in both directions; use it alongside the match
+// ratio the classifier thresholds. Features with descriptors of different widths are refused.
+pub fn match_asymmetry(a: &ImageFeature, b: &ImageFeature) -> Result<f64> {
+    if !a.descriptors.is_empty() && !b.descriptors.is_empty() && a.descriptors.cols != b.descriptors.cols {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot compare {}-byte descriptors of {} with {}-byte descriptors of {}",
+            a.descriptors.cols, a.id, b.descriptors.cols, b.id
+        )));
+    }
+    let params = MatchParams::default();
+    let directional = |query: &ImageFeature, train: &ImageFeature| {
+        if query.descriptors.is_empty() {
+            0.0
+        } else {
+            good_matches(query, train, &params).len() as f64 / query.descriptors.rows as f64
+        }
+    };
+    Ok((directional(a, b) - directional(b, a)).abs())
+}
+
+// Counts plus the correspondences themselves, for geometry or optical flow
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetailedMatchResult {
+    pub counts: MatchResult,
+    // (query row, train row) of each good match
+    pub index_pairs: Vec<(usize, usize)>,
+    // (query keypoint, train keypoint) of each good match
+    pub point_pairs: Vec<(KeyPointData, KeyPointData)>,
+}
+
+// Function to match like `match_features` and also return the matched keypoints.
+// Matches whose row has no keypoint (an inconsistent feature) are left out of the pairs.
+pub fn match_features_detailed(query: &ImageFeature, train: &ImageFeature, params: &MatchParams) -> DetailedMatchResult {
+    let matches = good_matches(query, train, params);
+    let counts = MatchResult {
+        good_matches: matches.len(),
+        query_descriptors: params.sampled_rows(query.descriptors.rows).len(),
+        train_descriptors: train.descriptors.rows,
+    };
+    let (index_pairs, point_pairs) = matches
+        .into_iter()
+        .filter_map(|(qi, ti)| {
+            let points = (query.keypoints.get(qi)?.clone(), train.keypoints.get(ti)?.clone());
+            Some(((qi, ti), points))
+        })
+        .unzip();
+    DetailedMatchResult {
+        counts,
+        index_pairs,
+        point_pairs,
+    }
+}
+
+// Function to compute the Euclidean distance between two byte descriptors, e.g. SIFT
+// converted to 8-bit
+pub fn l2_distance(a: &[u8], b: &[u8]) -> f64 {
+    let squared: u64 = a.iter().zip(b).map(|(&x, &y)| u64::from(x.abs_diff(y)).pow(2)).sum();
+    (squared as f64).sqrt()
+}
+
+// Function to match two descriptor matrices of one kind like `match_features`, using the
+// kind's distance (Hamming for binary ORB, L2 for SIFT). Without keypoints an inlier ratio
+// metric falls back to the ratio test.
+pub fn match_descriptors(
+    query: &DescriptorMatrix,
+    train: &DescriptorMatrix,
+    kind: DescriptorKind,
+    params: &MatchParams,
+) -> MatchResult {
+    MatchResult {
+        good_matches: good_descriptor_matches(query, train, params, kind.distance()).len(),
+        query_descriptors: params.sampled_rows(query.rows).len(),
+        train_descriptors: train.rows,
+    }
+}
+
+// Function to compute every pairwise distance between the rows of `a` and `b`, an
+// `a.rows` x `b.rows` matrix for visualizing how descriptors relate. Uses the kind's
+// distance like `match_descriptors`; L2 distances are rounded to the nearest integer.
+// Refuses descriptors of different widths and matrices over `MAX_DISTANCE_MATRIX_CELLS`.
+pub fn distance_matrix(a: &DescriptorMatrix, b: &DescriptorMatrix, kind: DescriptorKind) -> Result<Vec<Vec<u32>>> {
+    if !a.is_empty() && !b.is_empty() && a.cols != b.cols {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot compare {}-byte descriptors with {}-byte descriptors",
+            a.cols, b.cols
+        )));
+    }
+    if a.rows.saturating_mul(b.rows) > MAX_DISTANCE_MATRIX_CELLS {
+        return Err(VyuwerError::InvalidInput(format!(
+            "a {}x{} distance matrix exceeds the limit of {MAX_DISTANCE_MATRIX_CELLS} cells",
+            a.rows, b.rows
+        )));
+    }
+    let distance = kind.distance();
+    let b_rows: Vec<&[u8]> = b.row_iter().collect();
+    Ok(a.row_iter()
+        .map(|q| b_rows.iter().map(|t| distance(q, t).round() as u32).collect())
+        .collect())
+}
+
+// Function to list the (query row, train row) pairs that pass the ratio test (and the
+// metric's extra check)
+pub(crate) fn good_matches(query: &ImageFeature, train: &ImageFeature, params: &MatchParams) -> Vec<(usize, usize)> {
+    let hamming = |a: &[u8], b: &[u8]| f64::from(hamming_distance(a, b));
+    let matches = good_descriptor_matches(&query.descriptors, &train.descriptors, params, hamming);
+    #[cfg(feature = "opencv")]
+    if params.metric == SimilarityMetric::InlierRatio {
+        return crate::geometry::homography_inliers(query, train, matches);
+    }
+    matches
+}
+
+fn good_descriptor_matches(
+    query: &DescriptorMatrix,
+    train: &DescriptorMatrix,
+    params: &MatchParams,
+    distance: fn(&[u8], &[u8]) -> f64,
+) -> Vec<(usize, usize)> {
+    // Descriptors of different widths come from different extractors and never match
+    if train.rows < 2 || query.cols != train.cols {
+        return Vec::new();
+    }
+    let train_rows: Vec<&[u8]> = train.row_iter().collect();
+    let matches = params.sampled_rows(query.rows).into_iter().filter_map(|qi| {
+        let (best, best_index, second) = two_nearest(query.row(qi), &train_rows, distance);
+        (best < params.lowe_ratio * second).then_some((qi, best_index))
+    });
+    match params.metric {
+        SimilarityMetric::RatioTest => matches.collect(),
+        // The geometric check needs keypoints, see `good_matches`
+        #[cfg(feature = "opencv")]
+        SimilarityMetric::InlierRatio => matches.collect(),
+        SimilarityMetric::CrossCheck => {
+            let query_rows: Vec<&[u8]> = query.row_iter().collect();
+            matches
+                .filter(|&(qi, ti)| two_nearest(train_rows[ti], &query_rows, distance).1 == qi)
+                .collect()
+        }
+    }
+}
+
+// Function to find the best distance (and its row) and the second-best distance from `q` to `rows`
+fn two_nearest(q: &[u8], rows: &[&[u8]], distance: fn(&[u8], &[u8]) -> f64) -> (f64, usize, f64) {
+    let mut best = f64::INFINITY;
+    let mut best_index = 0;
+    let mut second = f64::INFINITY;
+    for (i, row) in rows.iter().enumerate() {
+        let d = distance(q, row);
+        if d < best {
+            second = best;
+            best = d;
+            best_index = i;
+        } else if d < second {
+            second = d;
+        }
+    }
+    (best, best_index, second)
+}
@@ -0,0 +1,45 @@
+use rusqlite::Connection;
+
+use crate::anomaly::AnomalyKind;
+use crate::error::Result;
+
+// Metric names, as seen by whichever `metrics` exporter (e.g. Prometheus) the
+// application installs. Without an installed recorder every update is a no-op.
+pub const FEATURES_INSERTED: &str = "features_inserted_total";
+pub const QUERIES_SERVED: &str = "queries_served_total";
+// Labelled with `kind`, e.g. kind="scene_change"
+pub const ANOMALIES_DETECTED: &str = "anomalies_detected_total";
+pub const DB_SIZE_BYTES: &str = "db_size_bytes";
+
+// Counted per feature row written, including rows of a transaction later rolled back
+pub(crate) fn feature_inserted() {
+    ::metrics::counter!(FEATURES_INSERTED).increment(1);
+}
+
+// Counted per feature lookup, whether served from the database or a handle's cache
+pub(crate) fn query_served() {
+    ::metrics::counter!(QUERIES_SERVED).increment(1);
+}
+
+pub(crate) fn anomaly_detected(kind: AnomalyKind) {
+    let kind = match kind {
+        AnomalyKind::SceneChange => "scene_change",
+        AnomalyKind::Combined => "combined",
+        AnomalyKind::Custom => "custom",
+    };
+    ::metrics::counter!(ANOMALIES_DETECTED, "kind" => kind).increment(1);
+}
+
+// Function to measure the database file (page count times page size), publish it as the
+// `db_size_bytes` gauge and return it. Size is not tracked continuously; call this from
+// the scrape handler or a periodic task.
+pub fn record_db_size(db_name: &str) -> Result<u64> {
+    let conn = Connection::open(db_name)?;
+    let bytes: i64 = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?;
+    ::metrics::gauge!(DB_SIZE_BYTES).set(bytes as f64);
+    Ok(bytes as u64)
+}
@@ -1,58 +1,542 @@
-use rusqlite::{params, Connection, Result};
+use std::time::Duration;
+
+#[cfg(feature = "opencv")]
+use opencv::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use opencv::core::{KeyPoint, Vector};
-use std::time::{SystemTime, UNIX_EPOCH};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::audit;
+use crate::camera::{self, LowKeypointAction};
+use crate::codec;
+use crate::error::{Result, VyuwerError};
+use crate::extract::OrbParams;
+use crate::layout::{self, DescriptorLayout};
+use crate::matching::ORB_DESCRIPTOR_BYTES;
+use crate::schema;
+use crate::timestamp;
+
+pub const PROD_DB: &str = "vyuwer.db";
+pub const TEST_DB: &str = "vyuwer_test.db";
+
+// Blob layout written by this build; see `feature_from_row` for the decoders.
+// v1: keypoints = Vec<KeyPointData>, descriptors = flat Vec<u8> of ORB rows
+// v2: keypoints = Vec<KeyPointData>, descriptors = DescriptorMatrix
+// v3: keypoints = a `codec::KEYPOINT_FORMAT_*` byte and that encoding, descriptors as v2.
+//     Written instead of v2 while the database uses `KeypointCodec::Delta`.
+// v4: keypoints as v3, descriptors = `codec::encode_descriptor_delta` against the v2/v3
+//     feature named in `base_feature_id`. Written by `insert_image_feature_delta`.
+pub const FEATURE_VERSION: i64 = 2;
+pub const FLAGGED_KEYPOINTS_FEATURE_VERSION: i64 = 3;
+pub const DELTA_DESCRIPTORS_FEATURE_VERSION: i64 = 4;
+
+const KEYPOINT_CODEC_SETTING: &str = "keypoint_codec";
+
+// How newly written keypoint blobs are encoded. Either way old rows stay readable, since
+// each row records its own `feature_version`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeypointCodec {
+    // Plain bincode (v2), readable by every build
+    #[default]
+    Bincode,
+    // `codec::encode_keypoints_delta` (v3), typically well under the bincode size but
+    // unreadable by builds from before v3
+    Delta,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageFeature {
+    pub id: String,
+    pub keypoints: Vec<KeyPointData>,
+    pub descriptors: DescriptorMatrix,
+    pub motion_mean: f64,
+    pub motion_std: f64,
+    pub created_at_utc: String,
+    pub img_filename: Option<String>,
+    pub camera_id: String,
+    // DCT perceptual hash of the source frame, when one was computed
+    pub phash: Option<u64>,
+    // Size of the original frame; keypoints may be in the coordinates of a resized copy
+    pub frame_size: Option<FrameSize>,
+    // ORB settings the keypoints were extracted with, stored as JSON for reproducibility
+    pub extraction_params: Option<OrbParams>,
+    // Variance of the Laplacian of the source frame (see `extract::sharpness`); low means blurry
+    pub sharpness: Option<f64>,
+    // Tile of a larger frame the keypoints were extracted from, in frame pixels
+    #[serde(default)]
+    pub roi: Option<Roi>,
+}
+
+// Keypoint count at which a frame's texture is considered fully sufficient (ORB's default budget)
+pub const QUALITY_FULL_KEYPOINTS: usize = 500;
+
+impl ImageFeature {
+    // Function to rate how reliable the feature is for matching, from 0.0 to 1.0: the
+    // descriptor entropy as a fraction of its 8-bit maximum, scaled down when there are
+    // fewer than QUALITY_FULL_KEYPOINTS keypoints
+    pub fn quality_score(&self) -> f64 {
+        let coverage = (self.keypoints.len() as f64 / QUALITY_FULL_KEYPOINTS as f64).min(1.0);
+        descriptor_entropy(&self.descriptors) / 8.0 * coverage
+    }
+
+    // Function to hash the feature's content for diffing, dedup and sync: SHA-256 over the
+    // camera, the capture time, then the keypoints and descriptor rows in stored order.
+    // Every field is written little-endian behind a length or tag, floats by their bits,
+    // so the bytes are canonical. The time is hashed as parsed Unix milliseconds, so one
+    // instant written in two formats hashes alike (an unparseable one hashes as its text).
+    // The id and the derived or per-frame metadata (motion, phash, sizes, params) are left out.
+    pub fn content_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let field = |hasher: &mut Sha256, bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(&mut hasher, self.camera_id.as_bytes());
+        match timestamp::parse_utc_millis(&self.created_at_utc) {
+            Ok(millis) => {
+                hasher.update([0]);
+                hasher.update(millis.to_le_bytes());
+            }
+            Err(_) => {
+                hasher.update([1]);
+                field(&mut hasher, self.created_at_utc.as_bytes());
+            }
+        }
+        hasher.update((self.keypoints.len() as u64).to_le_bytes());
+        for kp in &self.keypoints {
+            for value in [kp.x, kp.y, kp.size, kp.angle] {
+                hasher.update(value.to_bits().to_le_bytes());
+            }
+        }
+        hasher.update((self.descriptors.rows as u64).to_le_bytes());
+        hasher.update((self.descriptors.cols as u64).to_le_bytes());
+        hasher.update(&self.descriptors.data);
+        hasher.finalize().into()
+    }
+}
+
+// Function to compute the Shannon entropy, in bits, of the byte distribution of a
+// descriptor matrix: 0.0 for a constant matrix, up to 8.0 for uniformly random bytes.
+// Low-texture frames score low and make unreliable matches.
+pub fn descriptor_entropy(descriptors: &DescriptorMatrix) -> f64 {
+    if descriptors.data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in &descriptors.data {
+        counts[usize::from(byte)] += 1;
+    }
+    let total = descriptors.data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FrameSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        FrameSize { width, height }
+    }
+}
+
+// Axis-aligned rectangle of a frame: top-left corner plus size, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Roi {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Roi { x, y, width, height }
+    }
+
+    // Function to check whether two rectangles share at least one pixel; merely touching
+    // edges do not count
+    pub fn overlaps(&self, other: &Roi) -> bool {
+        // Widened so a rectangle reaching the edge of the u32 range cannot overflow
+        let span = |start: u32, len: u32| (u64::from(start), u64::from(start) + u64::from(len));
+        let ((left, right), (other_left, other_right)) = (span(self.x, self.width), span(other.x, other.width));
+        let ((top, bottom), (other_top, other_bottom)) = (span(self.y, self.height), span(other.y, other.height));
+        left < other_right && other_left < right && top < other_bottom && other_top < bottom
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyPointData {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    pub angle: f32,
+}
+
+// Function to map keypoints extracted on a `from`-sized frame onto a `to`-sized one,
+// e.g. back onto the original frame for drawing. Positions scale per axis; the
+// keypoint diameter scales by the mean of the two factors. Angles are unchanged.
+pub fn rescale_keypoints(keypoints: &mut [KeyPointData], from: FrameSize, to: FrameSize) -> Result<()> {
+    if from.width == 0 || from.height == 0 {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot rescale from a {}x{} frame",
+            from.width, from.height
+        )));
+    }
+    let sx = to.width as f32 / from.width as f32;
+    let sy = to.height as f32 / from.height as f32;
+    for keypoint in keypoints {
+        keypoint.x *= sx;
+        keypoint.y *= sy;
+        keypoint.size *= (sx + sy) / 2.0;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "opencv")]
+impl KeyPointData {
+    pub fn from_keypoint(keypoint: &opencv::core::KeyPoint) -> Self {
+        let pt = keypoint.pt();
+        KeyPointData {
+            x: pt.x,
+            y: pt.y,
+            size: keypoint.size(),
+            angle: keypoint.angle(),
+        }
+    }
+}
+
+// Row-major descriptor matrix: one `cols`-byte descriptor per keypoint.
+// Frames without keypoints (blank walls, dark scenes) carry an empty matrix with
+// `rows == 0`; it is stored, loaded and matched like any other (always 0 matches),
+// and converts to and from an empty `Mat` without error.
+// Deserializing checks the shape like `new`, so a corrupt blob fails to decode instead of
+// panicking later in `row`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DescriptorMatrixParts")]
+pub struct DescriptorMatrix {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<u8>,
+}
+
+// Wire form of DescriptorMatrix, before the shape is checked
+#[derive(Deserialize)]
+struct DescriptorMatrixParts {
+    rows: usize,
+    cols: usize,
+    data: Vec<u8>,
+}
+
+impl TryFrom<DescriptorMatrixParts> for DescriptorMatrix {
+    type Error = VyuwerError;
+
+    fn try_from(parts: DescriptorMatrixParts) -> Result<Self> {
+        DescriptorMatrix::new(parts.rows, parts.cols, parts.data)
+    }
+}
+
+impl DescriptorMatrix {
+    pub fn new(rows: usize, cols: usize, data: Vec<u8>) -> Result<Self> {
+        if rows.checked_mul(cols) != Some(data.len()) {
+            return Err(VyuwerError::InvalidInput(format!(
+                "descriptor matrix {rows}x{cols} does not match {} data bytes",
+                data.len()
+            )));
+        }
+        Ok(DescriptorMatrix { rows, cols, data })
+    }
+
+    // Function to wrap a flat buffer of ORB descriptors (32 bytes per row)
+    pub fn from_orb_bytes(data: Vec<u8>) -> Self {
+        let rows = data.len() / ORB_DESCRIPTOR_BYTES;
+        let mut data = data;
+        data.truncate(rows * ORB_DESCRIPTOR_BYTES);
+        DescriptorMatrix {
+            rows,
+            cols: ORB_DESCRIPTOR_BYTES,
+            data,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    pub fn row(&self, i: usize) -> &[u8] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn row_iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.rows).map(move |i| self.row(i))
+    }
+
+    #[cfg(feature = "opencv")]
+    pub fn from_mat(mat: &opencv::core::Mat) -> Result<Self> {
+        // detect_and_compute leaves the output Mat unallocated when nothing was found
+        if mat.empty() || mat.rows() == 0 {
+            return Ok(DescriptorMatrix::default());
+        }
+        DescriptorMatrix::new(mat.rows() as usize, mat.cols() as usize, mat.data_bytes()?.to_vec())
+    }
+
+    #[cfg(feature = "opencv")]
+    pub fn to_mat(&self) -> Result<opencv::core::Mat> {
+        if self.is_empty() {
+            return Ok(opencv::core::Mat::default());
+        }
+        let flat = opencv::core::Mat::from_slice(&self.data)?;
+        Ok(flat.reshape(1, self.rows as i32)?.try_clone()?)
+    }
+}
+
+// Function to merge several frames' features into one composite record, e.g. a
+// multi-exposure or panorama reference. Keypoints are concatenated and descriptor rows
+// stacked in input order; motion stats are averaged. The composite gets a fresh id, the
+// first feature's camera and the latest timestamp. All non-empty descriptor matrices
+// must have the same width.
+pub fn combine_features(image_features: &[ImageFeature]) -> Result<ImageFeature> {
+    let first = image_features
+        .first()
+        .ok_or_else(|| VyuwerError::InvalidInput("cannot combine an empty list of features".to_string()))?;
+    let mut cols = None;
+    for image_feature in image_features.iter().filter(|f| !f.descriptors.is_empty()) {
+        match cols {
+            None => cols = Some(image_feature.descriptors.cols),
+            Some(cols) if cols != image_feature.descriptors.cols => {
+                return Err(VyuwerError::InvalidInput(format!(
+                    "feature {} has {}-byte descriptors, expected {cols}",
+                    image_feature.id, image_feature.descriptors.cols
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut latest = (timestamp::parse_utc_millis(&first.created_at_utc)?, &first.created_at_utc);
+    let mut keypoints = Vec::new();
+    let mut descriptors = DescriptorMatrix::default();
+    for image_feature in image_features {
+        let millis = timestamp::parse_utc_millis(&image_feature.created_at_utc)?;
+        if millis > latest.0 {
+            latest = (millis, &image_feature.created_at_utc);
+        }
+        keypoints.extend(image_feature.keypoints.iter().cloned());
+        descriptors.rows += image_feature.descriptors.rows;
+        descriptors.data.extend_from_slice(&image_feature.descriptors.data);
+    }
+    descriptors.cols = cols.unwrap_or(0);
+
+    let n = image_features.len() as f64;
+    let combined = ImageFeature {
+        id: Uuid::new_v4().to_string(),
+        keypoints,
+        descriptors,
+        motion_mean: image_features.iter().map(|f| f.motion_mean).sum::<f64>() / n,
+        motion_std: image_features.iter().map(|f| f.motion_std).sum::<f64>() / n,
+        created_at_utc: latest.1.clone(),
+        img_filename: None,
+        camera_id: first.camera_id.clone(),
+        // A composite has no single source frame
+        phash: None,
+        frame_size: None,
+        extraction_params: first
+            .extraction_params
+            .clone()
+            .filter(|params| image_features.iter().all(|f| f.extraction_params.as_ref() == Some(params))),
+        sharpness: None,
+        roi: None,
+    };
+    validate_feature(&combined)?;
+    Ok(combined)
+}
+
+// How a batch insert treats a feature whose id is already stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    // Fail the whole batch
+    Abort,
+    // Keep the stored feature and skip the new one
+    Ignore,
+    // Overwrite the stored feature with the new one
+    Replace,
+}
+
+impl OnConflict {
+    fn insert_verb(self) -> &'static str {
+        match self {
+            OnConflict::Abort => "INSERT",
+            OnConflict::Ignore => "INSERT OR IGNORE",
+            OnConflict::Replace => "INSERT OR REPLACE",
+        }
+    }
+}
+
+// Outcome of a batch insert; replaced features count as inserted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchInsertReport {
+    pub inserted: usize,
+    pub skipped: usize,
+}
 
-#[derive(Serialize, Deserialize)]
-struct ImageFeature {
-    id: String,
-    keypoints: Vec<KeyPointData>,
-    descriptors: Vec<u8>,
-    motion_mean: f64,
-    motion_std: f64,
-    created_at_utc: String,
-    img_filename: Option<String>,
-    camera_id: String,
+// A stored feature as read back, for scans that must survive damaged rows
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureReadResult {
+    Ok(ImageFeature),
+    // Its blobs could not be decoded, e.g. one truncated by a crash mid-insert without WAL
+    Corrupt(CorruptFeature),
 }
 
-#[derive(Serialize, Deserialize)]
-struct KeyPointData {
-    x: f32,
-    y: f32,
-    size: f32,
-    angle: f32,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptFeature {
+    pub id: String,
+    pub camera_id: String,
+    // Why decoding failed
+    pub error: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ImageDescription {
-    image_name: String,
-    datetime: String,
-    camera_id: String,
-    anomaly: Option<String>,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageDescription {
+    pub image_name: String,
+    pub datetime: String,
+    pub camera_id: String,
+    pub anomaly: Option<String>,
+}
+
+// A feature's identifying columns, without decoding its blobs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureMeta {
+    pub id: String,
+    pub camera_id: String,
+    pub created_at_utc: String,
+    pub img_filename: Option<String>,
+}
+
+// Function to setup every table and apply migrations in one call
+pub fn setup_schema(db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    schema::apply(&conn)
 }
 
 // Function to setup database
-fn setup_database(db_name: &str) -> Result<()> {
+pub fn setup_database(db_name: &str) -> Result<()> {
     let conn = Connection::open(db_name)?;
+    create_feature_tables(&conn)
+}
+
+pub(crate) fn create_feature_tables(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS image_features (
             id TEXT PRIMARY KEY,
             keypoints BLOB,
             descriptors BLOB,
-            motion_mean REAL,
-            motion_std REAL,
+            motion_mean REAL NOT NULL DEFAULT 0,
+            motion_std REAL NOT NULL DEFAULT 0,
             created_at_utc TEXT NOT NULL,
             img_filename TEXT,
-            camera_id TEXT NOT NULL
+            camera_id TEXT NOT NULL,
+            feature_version INTEGER NOT NULL DEFAULT 1,
+            phash INTEGER,
+            frame_width INTEGER,
+            frame_height INTEGER,
+            extraction_params TEXT,
+            seq INTEGER,
+            sharpness REAL,
+            roi_x INTEGER,
+            roi_y INTEGER,
+            roi_w INTEGER,
+            roi_h INTEGER,
+            content_sha256 TEXT,
+            descriptor_kind TEXT,
+            low_quality INTEGER NOT NULL DEFAULT 0,
+            bow_histogram BLOB,
+            base_feature_id TEXT,
+            extract_ms INTEGER,
+            content_hash BLOB
         )",
         [],
     )?;
+    // Rows written before versioning existed use the v1 layout
+    schema::add_column_if_missing(conn, "image_features", "feature_version", "INTEGER NOT NULL DEFAULT 1")?;
+    schema::add_column_if_missing(conn, "image_features", "phash", "INTEGER")?;
+    schema::add_column_if_missing(conn, "image_features", "frame_width", "INTEGER")?;
+    schema::add_column_if_missing(conn, "image_features", "frame_height", "INTEGER")?;
+    schema::add_column_if_missing(conn, "image_features", "extraction_params", "TEXT")?;
+    // Per-camera insertion counter breaking ties between equal timestamps; NULL on older rows
+    schema::add_column_if_missing(conn, "image_features", "seq", "INTEGER")?;
+    schema::add_column_if_missing(conn, "image_features", "sharpness", "REAL")?;
+    for column in ["roi_x", "roi_y", "roi_w", "roi_h"] {
+        schema::add_column_if_missing(conn, "image_features", column, "INTEGER")?;
+    }
+    // Hash of the source frame's pixels, set by `video::ingest_idempotent`; NULL otherwise
+    schema::add_column_if_missing(conn, "image_features", "content_sha256", "TEXT")?;
+    // Name of the extractor behind `descriptors` (see `descriptor_sets::DescriptorKind`);
+    // NULL on rows stored before it was recorded, whose kind is inferred from the width
+    schema::add_column_if_missing(conn, "image_features", "descriptor_kind", "TEXT")?;
+    // Set when the feature was stored below its camera's keypoint gate (`LowKeypointAction::Flag`)
+    schema::add_column_if_missing(conn, "image_features", "low_quality", "INTEGER NOT NULL DEFAULT 0")?;
+    // Visual-word counts from `vocabulary::index_bow_histograms`; NULL until indexed
+    schema::add_column_if_missing(conn, "image_features", "bow_histogram", "BLOB")?;
+    // Feature a v4 row's descriptors are a delta against; NULL for fully stored rows
+    schema::add_column_if_missing(conn, "image_features", "base_feature_id", "TEXT")?;
+    // Milliseconds extraction took, from `insert_image_feature_timed`; NULL when not measured
+    schema::add_column_if_missing(conn, "image_features", "extract_ms", "INTEGER")?;
+    // `ImageFeature::content_hash`, set on every write; NULL on older rows until
+    // `backfill_content_hashes` runs
+    schema::add_column_if_missing(conn, "image_features", "content_hash", "BLOB")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS image_features_camera_seq ON image_features (camera_id, seq);
+        CREATE UNIQUE INDEX IF NOT EXISTS image_features_camera_content ON image_features (camera_id, content_sha256);
+        CREATE INDEX IF NOT EXISTS image_features_base ON image_features (base_feature_id)
+            WHERE base_feature_id IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS image_features_content_hash ON image_features (content_hash);",
+    )?;
+    schema::create_settings_table(conn)?;
+    layout::create_descriptor_table(conn)?;
+    audit::create_audit_log(conn)?;
     Ok(())
 }
 
+// Function to rewrite every stored feature timestamp in the form inserts use (see
+// `timestamp::normalize_utc`). Rows whose timestamp does not parse are left as they are.
+// Returns how many rows changed.
+pub(crate) fn normalize_feature_timestamps(conn: &Connection) -> Result<usize> {
+    let mut stmt = conn.prepare("SELECT id, created_at_utc FROM image_features")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut changed = 0;
+    for (id, created_at_utc) in rows {
+        match timestamp::normalize_utc(&created_at_utc) {
+            Ok(normalized) if normalized != created_at_utc => {
+                changed += conn.execute(
+                    "UPDATE image_features SET created_at_utc = ?1 WHERE id = ?2",
+                    params![normalized, id],
+                )?;
+            }
+            _ => {}
+        }
+    }
+    Ok(changed)
+}
+
 // Function to setup image description table
-fn image_description_table(db_name: &str) -> Result<()> {
+pub fn image_description_table(db_name: &str) -> Result<()> {
     let conn = Connection::open(db_name)?;
+    create_description_table(&conn)
+}
+
+pub(crate) fn create_description_table(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS image_description (
             image_name TEXT PRIMARY KEY,
@@ -62,133 +546,1086 @@ fn image_description_table(db_name: &str) -> Result<()> {
         )",
         [],
     )?;
+    // Repeats folded into the row by `insert_image_description_with_cooldown`
+    schema::add_column_if_missing(conn, "image_description", "count", "INTEGER NOT NULL DEFAULT 1")?;
+    schema::add_column_if_missing(conn, "image_description", "last_seen", "TEXT")?;
+    // The described feature, once known; see `relink_by_filename`
+    schema::add_column_if_missing(conn, "image_description", "feature_id", "TEXT")?;
+    // The anomaly detail's confidence, for ordering by severity; NULL for other anomalies
+    if !schema::has_column(conn, "image_description", "severity")? {
+        conn.execute("ALTER TABLE image_description ADD COLUMN severity REAL", [])?;
+        conn.execute(
+            "UPDATE image_description SET severity = json_extract(anomaly, '$.confidence')
+            WHERE json_valid(anomaly) AND json_type(anomaly, '$.confidence') IN ('integer', 'real')",
+            [],
+        )?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS image_description_camera_severity ON image_description (camera_id, severity)",
+        [],
+    )?;
     Ok(())
 }
 
 // Function to insert image description
-fn insert_image_description(image_description: &ImageDescription, db_name: &str) -> Result<()> {
+pub fn insert_image_description(image_description: &ImageDescription, db_name: &str) -> Result<()> {
     let conn = Connection::open(db_name)?;
+    insert_description(&conn, image_description)
+}
+
+pub(crate) fn insert_description(conn: &Connection, image_description: &ImageDescription) -> Result<()> {
     conn.execute(
-        "INSERT INTO image_description (image_name, datetime, camera_id, anomaly)
-        VALUES (?1, ?2, ?3, ?4)",
+        "INSERT INTO image_description (image_name, datetime, camera_id, anomaly, severity)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
         params![
             image_description.image_name,
             image_description.datetime,
             image_description.camera_id,
-            image_description.anomaly
+            image_description.anomaly,
+            image_description.anomaly.as_deref().and_then(anomaly_severity)
         ],
     )?;
     Ok(())
 }
 
+// Function to insert a description unless it repeats the camera's previous one: same
+// anomaly kind, and dated within `cooldown` of that one's last sighting. A repeat bumps the
+// previous row's `count` and `last_seen` instead, collapsing a stream of identical alerts,
+// and raises its `severity` to the repeat's if that is higher.
+// Descriptions without an anomaly are always inserted. Returns whether a row was added.
+pub fn insert_image_description_with_cooldown(
+    image_description: &ImageDescription,
+    cooldown: Duration,
+    db_name: &str,
+) -> Result<bool> {
+    let Some(anomaly) = image_description.anomaly.as_deref() else {
+        insert_image_description(image_description, db_name)?;
+        return Ok(true);
+    };
+    let datetime = timestamp::parse_utc_millis(&image_description.datetime)?;
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let previous: Option<(i64, Option<String>, String)> = tx
+        .query_row(
+            "SELECT rowid, anomaly, COALESCE(last_seen, datetime) FROM image_description
+            WHERE camera_id = ?1 ORDER BY rowid DESC LIMIT 1",
+            params![image_description.camera_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    if let Some((rowid, Some(previous_anomaly), last_seen)) = previous {
+        let last_seen_millis = timestamp::parse_utc_millis(&last_seen)?;
+        let within_cooldown = u128::from(datetime.abs_diff(last_seen_millis)) <= cooldown.as_millis();
+        if within_cooldown && anomaly_kind(&previous_anomaly) == anomaly_kind(anomaly) {
+            let last_seen = if datetime >= last_seen_millis {
+                image_description.datetime.clone()
+            } else {
+                last_seen
+            };
+            // The row ranks by its worst sighting; MAX is NULL if either side is
+            tx.execute(
+                "UPDATE image_description SET count = count + 1, last_seen = ?1,
+                    severity = COALESCE(MAX(severity, ?3), severity, ?3)
+                WHERE rowid = ?2",
+                params![last_seen, rowid, anomaly_severity(anomaly)],
+            )?;
+            tx.commit()?;
+            return Ok(false);
+        }
+    }
+    insert_description(&tx, image_description)?;
+    tx.commit()?;
+    Ok(true)
+}
+
+// Function to get how many descriptions a row stands for and when it was last seen (its
+// own datetime unless repeats were folded into it)
+pub fn description_repeats(image_name: &str, db_name: &str) -> Result<Option<(u32, String)>> {
+    let conn = Connection::open(db_name)?;
+    Ok(conn
+        .query_row(
+            "SELECT count, COALESCE(last_seen, datetime) FROM image_description WHERE image_name = ?1",
+            params![image_name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+// Function to reduce an anomaly to what makes two of them the same alert: the `kind` of a
+// JSON anomaly detail (as `classify_pending` writes), otherwise the whole text
+fn anomaly_kind(anomaly: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(anomaly) {
+        Ok(serde_json::Value::Object(detail)) => match detail.get("kind") {
+            Some(kind) => kind.to_string(),
+            None => anomaly.to_string(),
+        },
+        _ => anomaly.to_string(),
+    }
+}
+
+// Function to read the severity of an anomaly: the `confidence` of a JSON anomaly detail
+// (as `classify_pending` writes), None for anything else
+fn anomaly_severity(anomaly: &str) -> Option<f64> {
+    match serde_json::from_str::<serde_json::Value>(anomaly) {
+        Ok(serde_json::Value::Object(detail)) => detail.get("confidence").and_then(serde_json::Value::as_f64),
+        _ => None,
+    }
+}
+
+// Function to list a camera's anomalous descriptions worst first, for triage: by
+// severity descending, then oldest first. Anomalies without a severity come last.
+pub fn anomalies_by_severity(camera_id: &str, db_name: &str) -> Result<Vec<ImageDescription>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT image_name, datetime, camera_id, anomaly FROM image_description
+        WHERE camera_id = ?1 AND anomaly IS NOT NULL
+        ORDER BY severity DESC NULLS LAST, datetime, rowid",
+    )?;
+    let descriptions = stmt
+        .query_map(params![camera_id], |row| {
+            Ok(ImageDescription {
+                image_name: row.get(0)?,
+                datetime: row.get(1)?,
+                camera_id: row.get(2)?,
+                anomaly: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(descriptions)
+}
+
+// Position in a camera's anomaly stream: the newest delivered description's time and
+// rowid. The rowid orders descriptions sharing a timestamp, so one written in the same
+// millisecond as the cursor after a poll is still delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnomalyCursor {
+    pub datetime: String,
+    pub rowid: i64,
+}
+
+// One poll of `poll_new_anomalies`: the new anomalies, oldest first, and the cursor to pass next time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnomalyPoll {
+    pub anomalies: Vec<ImageDescription>,
+    pub cursor: Option<AnomalyCursor>,
+}
+
+// Function to fetch a camera's anomalous descriptions after `after` (everything when
+// None), for a push loop such as a Server-Sent Events handler. Feed the returned cursor
+// back in to receive each anomaly once; it stays put when nothing is new. Descriptions
+// back-dated to before the cursor are not picked up.
+pub fn poll_new_anomalies(camera_id: &str, after: Option<&AnomalyCursor>, db_name: &str) -> Result<AnomalyPoll> {
+    let after_position = match after {
+        Some(cursor) => Some((timestamp::parse_utc_millis(&cursor.datetime)?, cursor.rowid)),
+        None => None,
+    };
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT image_name, datetime, camera_id, anomaly, rowid FROM image_description
+        WHERE camera_id = ?1 AND anomaly IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map(params![camera_id], |row| {
+            let description = ImageDescription {
+                image_name: row.get(0)?,
+                datetime: row.get(1)?,
+                camera_id: row.get(2)?,
+                anomaly: row.get(3)?,
+            };
+            Ok((description, row.get::<_, i64>(4)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Compared after parsing, since stored strings may differ in format
+    let mut anomalies = Vec::new();
+    for (description, rowid) in rows {
+        let position = (timestamp::parse_utc_millis(&description.datetime)?, rowid);
+        if after_position.is_none_or(|after| position > after) {
+            anomalies.push((position, description));
+        }
+    }
+    anomalies.sort_by_key(|(position, _)| *position);
+    let cursor = match anomalies.last() {
+        Some(((_, rowid), newest)) => Some(AnomalyCursor {
+            datetime: newest.datetime.clone(),
+            rowid: *rowid,
+        }),
+        None => after.cloned(),
+    };
+    Ok(AnomalyPoll {
+        anomalies: anomalies.into_iter().map(|(_, description)| description).collect(),
+        cursor,
+    })
+}
+
+// Function to list a camera's features whose recorded sharpness is below `threshold`, in
+// time order. Features stored without a sharpness score are not listed.
+pub fn blurry_features(camera_id: &str, threshold: f64, db_name: &str) -> Result<Vec<FeatureMeta>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, camera_id, created_at_utc, img_filename FROM image_features
+        WHERE camera_id = ?1 AND sharpness < ?2
+        ORDER BY created_at_utc, seq, id",
+    )?;
+    let features = stmt
+        .query_map(params![camera_id, threshold], feature_meta_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(features)
+}
+
+// Function to list a camera's features stored below its keypoint gate with
+// `LowKeypointAction::Flag`, in time order
+pub fn low_quality_features(camera_id: &str, db_name: &str) -> Result<Vec<FeatureMeta>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, camera_id, created_at_utc, img_filename FROM image_features
+        WHERE camera_id = ?1 AND low_quality = 1
+        ORDER BY created_at_utc, seq, id",
+    )?;
+    let features = stmt
+        .query_map(params![camera_id], feature_meta_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(features)
+}
+
+fn feature_meta_from_row(row: &rusqlite::Row) -> rusqlite::Result<FeatureMeta> {
+    Ok(FeatureMeta {
+        id: row.get(0)?,
+        camera_id: row.get(1)?,
+        created_at_utc: row.get(2)?,
+        img_filename: row.get(3)?,
+    })
+}
+
+// Function to list a camera's features awaiting classification, in time order: those
+// whose image has no description row yet. Descriptions are linked by image name, so
+// features stored without an `img_filename` are always listed.
+pub fn features_without_description(camera_id: &str, db_name: &str) -> Result<Vec<FeatureMeta>> {
+    let conn = Connection::open(db_name)?;
+    fetch_features_without_description(&conn, camera_id)
+}
+
+pub(crate) fn fetch_features_without_description(conn: &Connection, camera_id: &str) -> Result<Vec<FeatureMeta>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.camera_id, f.created_at_utc, f.img_filename FROM image_features f
+        LEFT JOIN image_description d ON d.image_name = f.img_filename AND d.camera_id = f.camera_id
+        WHERE f.camera_id = ?1 AND d.image_name IS NULL
+        ORDER BY f.created_at_utc, f.seq, f.id",
+    )?;
+    let features = stmt
+        .query_map(params![camera_id], feature_meta_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(features)
+}
+
+// Function to link descriptions to their features after a bulk ingest stored them apart:
+// every description without a `feature_id` gets the feature of the same camera whose
+// `img_filename` is its image name, the latest one if several share it. Descriptions
+// without such a feature are left unlinked. Returns how many were linked.
+pub fn relink_by_filename(db_name: &str) -> Result<usize> {
+    let conn = Connection::open(db_name)?;
+    let linked = conn.execute(
+        "UPDATE image_description SET feature_id = (
+            SELECT f.id FROM image_features f
+            WHERE f.img_filename = image_description.image_name AND f.camera_id = image_description.camera_id
+            ORDER BY f.created_at_utc DESC, f.seq DESC, f.id DESC LIMIT 1
+        )
+        WHERE feature_id IS NULL AND EXISTS (
+            SELECT 1 FROM image_features f
+            WHERE f.img_filename = image_description.image_name AND f.camera_id = image_description.camera_id
+        )",
+        [],
+    )?;
+    Ok(linked)
+}
+
 // Function to clear test database
-fn clear_test_db() -> Result<()> {
+pub fn clear_test_db() -> Result<()> {
     let conn = Connection::open(TEST_DB)?;
     conn.execute("DROP TABLE IF EXISTS image_features", [])?;
     Ok(())
 }
 
 // Function to insert image feature
-fn insert_image_feature(image_feature: &ImageFeature, db_name: &str) -> Result<()> {
+pub fn insert_image_feature(image_feature: &ImageFeature, db_name: &str) -> Result<()> {
     let conn = Connection::open(db_name)?;
-    let keypoints = bincode::serialize(&image_feature.keypoints).unwrap();
-    let descriptors = bincode::serialize(&image_feature.descriptors).unwrap();
+    insert_feature(&conn, image_feature)
+}
+
+// Function to insert image feature along with how long its extraction took, for
+// `analytics::extraction_latency_stats`
+pub fn insert_image_feature_timed(image_feature: &ImageFeature, extract_ms: u64, db_name: &str) -> Result<()> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    insert_feature_timed(&tx, image_feature, extract_ms)?;
+    tx.commit()?;
+    Ok(())
+}
 
+// Callers wrap this in a transaction, so the latency is never stored apart from its row
+pub(crate) fn insert_feature_timed(conn: &Connection, image_feature: &ImageFeature, extract_ms: u64) -> Result<()> {
+    insert_feature(conn, image_feature)?;
     conn.execute(
-        "INSERT INTO image_features (id, keypoints, descriptors, motion_mean, motion_std, created_at_utc, img_filename, camera_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "UPDATE image_features SET extract_ms = ?1 WHERE id = ?2",
+        params![i64::try_from(extract_ms).unwrap_or(i64::MAX), image_feature.id],
+    )?;
+    Ok(())
+}
+
+// Function to delete image feature
+pub fn delete_image_feature(camera_id: &str, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    delete_features(&conn, camera_id)
+}
+
+// Function to reset image feature
+pub fn reset_image_feature(camera_id: &str, image_feature: &ImageFeature, db_name: &str) -> Result<()> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    reset_features(&tx, camera_id, image_feature)?;
+    tx.commit()?;
+    Ok(())
+}
+
+// Function to get a camera's latest feature
+pub fn get_image_feature(camera_id: &str, db_name: &str) -> Result<Option<ImageFeature>> {
+    let conn = Connection::open(db_name)?;
+    fetch_feature(&conn, camera_id)
+}
+
+// Function to get a camera's latest feature like `get_image_feature`, except that a row
+// whose blobs cannot be decoded comes back as `FeatureReadResult::Corrupt` instead of
+// failing the call, so the caller can skip or repair it. Database errors still fail.
+pub fn get_image_feature_checked(camera_id: &str, db_name: &str) -> Result<Option<FeatureReadResult>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1
+        ORDER BY created_at_utc DESC, seq DESC, id DESC LIMIT 1"
+    ))?;
+    let mut rows = stmt.query(params![camera_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(read_feature_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Connection-level CRUD shared by the free functions above and `Database`.
+// Every mutation appends to the audit log.
+pub(crate) fn insert_feature(conn: &Connection, image_feature: &ImageFeature) -> Result<()> {
+    insert_feature_row(conn, image_feature)?;
+    audit::record(conn, "insert", &image_feature.id, Some(&image_feature.camera_id))
+}
+
+pub(crate) fn delete_features(conn: &Connection, camera_id: &str) -> Result<()> {
+    let deleted = delete_feature_rows(conn, camera_id)?;
+    audit::record(conn, "delete", camera_id, Some(&format!("{deleted} feature(s)")))
+}
+
+pub(crate) fn reset_features(conn: &Connection, camera_id: &str, image_feature: &ImageFeature) -> Result<()> {
+    let deleted = delete_feature_rows(conn, camera_id)?;
+    insert_feature_row(conn, image_feature)?;
+    audit::record(
+        conn,
+        "reset",
+        camera_id,
+        Some(&format!("replaced {deleted} feature(s) with {}", image_feature.id)),
+    )
+}
+
+// Function to check a feature's invariants before it is stored: a descriptor buffer of
+// exactly rows x cols bytes, one descriptor row per keypoint (frames without descriptors
+// are exempt) and finite motion statistics
+pub fn validate_feature(image_feature: &ImageFeature) -> Result<()> {
+    let descriptors = &image_feature.descriptors;
+    if descriptors.rows.checked_mul(descriptors.cols) != Some(descriptors.data.len()) {
+        return Err(VyuwerError::InvalidInput(format!(
+            "feature {} has a {}x{} descriptor matrix holding {} bytes",
+            image_feature.id,
+            descriptors.rows,
+            descriptors.cols,
+            descriptors.data.len()
+        )));
+    }
+    if !descriptors.is_empty() && descriptors.rows != image_feature.keypoints.len() {
+        return Err(VyuwerError::InvalidInput(format!(
+            "feature {} has {} keypoints but {} descriptor rows",
+            image_feature.id,
+            image_feature.keypoints.len(),
+            descriptors.rows
+        )));
+    }
+    // NaN/inf would silently poison every average and z-score over the column
+    for (column, value) in [("motion_mean", image_feature.motion_mean), ("motion_std", image_feature.motion_std)] {
+        if !value.is_finite() {
+            return Err(VyuwerError::InvalidInput(format!(
+                "feature {} has non-finite {column} {value}",
+                image_feature.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Function to scan every stored feature and list the ids failing `validate_feature`,
+// e.g. rows written by older builds or partial writes. Rows whose blobs no longer decode
+// (such as a descriptor shape that disagrees with its data) are listed too.
+pub fn find_inconsistent_features(db_name: &str) -> Result<Vec<String>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!("{FEATURE_SELECT} ORDER BY image_features.id"))?;
+    let mut rows = stmt.query([])?;
+    let mut inconsistent = Vec::new();
+    while let Some(row) = rows.next()? {
+        let consistent = feature_from_row(row).is_ok_and(|feature| validate_feature(&feature).is_ok());
+        if !consistent {
+            inconsistent.push(row.get(0)?);
+        }
+    }
+    Ok(inconsistent)
+}
+
+// Function to insert many features in one transaction, resolving duplicate ids with
+// `on_conflict`. With `Abort` a duplicate fails the call and nothing is inserted.
+pub fn insert_image_features_batch(
+    image_features: &[ImageFeature],
+    on_conflict: OnConflict,
+    db_name: &str,
+) -> Result<BatchInsertReport> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let report = insert_features_batch(&tx, image_features, on_conflict)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+pub(crate) fn insert_features_batch(
+    conn: &Connection,
+    image_features: &[ImageFeature],
+    on_conflict: OnConflict,
+) -> Result<BatchInsertReport> {
+    let mut report = BatchInsertReport::default();
+    for image_feature in image_features {
+        if insert_feature_row_with(conn, image_feature, on_conflict)? {
+            audit::record(conn, "insert", &image_feature.id, Some(&image_feature.camera_id))?;
+            report.inserted += 1;
+        } else {
+            report.skipped += 1;
+        }
+    }
+    Ok(report)
+}
+
+// Function to insert a feature whose descriptors are stored as a delta against
+// `base_feature_id`, e.g. the previous frame of a near-static scene, so only the bytes
+// that changed take space; reads rebuild the full matrix. The base must be a fully stored
+// (v2/v3) feature of the same camera, with descriptors of the same shape, in the embedded
+// layout. Otherwise the feature is stored in full as by `insert_image_feature`. Deleting,
+// replacing or re-extracting a base first rewrites its deltas in full. Returns whether
+// the feature was stored as a delta.
+pub fn insert_image_feature_delta(image_feature: &ImageFeature, base_feature_id: &str, db_name: &str) -> Result<bool> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    insert_feature(&tx, image_feature)?;
+    let base: Option<Vec<u8>> = tx
+        .query_row(
+            "SELECT descriptors FROM image_features
+            WHERE id = ?1 AND id != ?2 AND camera_id = ?3 AND feature_version IN (2, 3)
+                AND base_feature_id IS NULL AND descriptors IS NOT NULL",
+            params![base_feature_id, image_feature.id, image_feature.camera_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let base = base.map(|blob| decode_descriptors(&blob, FEATURE_VERSION)).transpose()?;
+    let delta = match base {
+        Some(base) if (base.rows, base.cols) == (image_feature.descriptors.rows, image_feature.descriptors.cols) => {
+            Some(codec::encode_descriptor_delta(&image_feature.descriptors, &base)?)
+        }
+        _ => None,
+    };
+    if let Some(delta) = &delta {
+        tx.execute(
+            "UPDATE image_features SET keypoints = ?1, descriptors = ?2, feature_version = ?3, base_feature_id = ?4
+            WHERE id = ?5",
+            params![
+                flagged_keypoints(&tx, &image_feature.keypoints)?,
+                delta,
+                DELTA_DESCRIPTORS_FEATURE_VERSION,
+                base_feature_id,
+                image_feature.id
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(delta.is_some())
+}
+
+// Function to rewrite v4 rows in full (as v3, whose keypoint layout they share), so they
+// no longer depend on their base: those based on `base_feature_id`, or all of them for None.
+// Returns how many rows were rewritten.
+pub(crate) fn materialize_deltas(conn: &Connection, base_feature_id: Option<&str>) -> Result<usize> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM image_features
+        WHERE base_feature_id IS NOT NULL AND (?1 IS NULL OR base_feature_id = ?1)",
+    )?;
+    let ids = stmt
+        .query_map(params![base_feature_id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for id in &ids {
+        let feature = fetch_feature_by_id(conn, id)?.ok_or_else(|| VyuwerError::FeatureNotFound(id.clone()))?;
+        conn.execute(
+            "UPDATE image_features SET descriptors = ?1, feature_version = ?2, base_feature_id = NULL WHERE id = ?3",
+            params![codec::encode(&feature.descriptors)?, FLAGGED_KEYPOINTS_FEATURE_VERSION, id],
+        )?;
+    }
+    Ok(ids.len())
+}
+
+fn insert_feature_row(conn: &Connection, image_feature: &ImageFeature) -> Result<()> {
+    insert_feature_row_with(conn, image_feature, OnConflict::Abort)?;
+    Ok(())
+}
+
+// Returns false when the row was skipped by `OnConflict::Ignore`
+// Features below the camera's keypoint gate are refused or flagged as the gate says
+fn insert_feature_row_with(conn: &Connection, image_feature: &ImageFeature, on_conflict: OnConflict) -> Result<bool> {
+    validate_feature(image_feature)?;
+    let low_quality = match camera::fetch_keypoint_gate(conn, &image_feature.camera_id)? {
+        Some(gate) if image_feature.keypoints.len() < gate.min_keypoints => match gate.action {
+            LowKeypointAction::Reject => {
+                return Err(VyuwerError::InsufficientKeypoints {
+                    found: image_feature.keypoints.len(),
+                    required: gate.min_keypoints,
+                })
+            }
+            LowKeypointAction::Flag => true,
+        },
+        _ => false,
+    };
+    if on_conflict == OnConflict::Replace {
+        // The replaced row may be the base of deltas read against its old descriptors
+        materialize_deltas(conn, Some(&image_feature.id))?;
+    }
+    let (keypoints, feature_version) = encode_keypoints(conn, &image_feature.keypoints)?;
+    let layout = layout::layout(conn)?;
+    let descriptors = match layout {
+        DescriptorLayout::Embedded => Some(codec::encode(&image_feature.descriptors)?),
+        DescriptorLayout::Normalized => None,
+    };
+
+    let extraction_params = image_feature.extraction_params.as_ref().map(serde_json::to_string).transpose()?;
+    // One text form for every row, so ordering by created_at_utc is time order
+    let created_at_utc = timestamp::normalize_utc(&image_feature.created_at_utc)?;
+
+    let inserted = conn.execute(
+        &format!(
+            "{} INTO image_features (id, keypoints, descriptors, motion_mean, motion_std, created_at_utc, img_filename, camera_id, feature_version, phash, frame_width, frame_height, extraction_params, sharpness, roi_x, roi_y, roi_w, roi_h, low_quality, content_hash, seq)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+                (SELECT COALESCE(MAX(seq), 0) + 1 FROM image_features WHERE camera_id = ?8))",
+            on_conflict.insert_verb()
+        ),
         params![
             image_feature.id,
             keypoints,
             descriptors,
             image_feature.motion_mean,
             image_feature.motion_std,
-            image_feature.created_at_utc,
+            created_at_utc,
             image_feature.img_filename,
-            image_feature.camera_id
+            image_feature.camera_id,
+            feature_version,
+            // SQLite integers are signed; the hash bits are stored as-is
+            image_feature.phash.map(|hash| hash as i64),
+            image_feature.frame_size.map(|size| size.width),
+            image_feature.frame_size.map(|size| size.height),
+            extraction_params,
+            image_feature.sharpness,
+            image_feature.roi.map(|roi| roi.x),
+            image_feature.roi.map(|roi| roi.y),
+            image_feature.roi.map(|roi| roi.width),
+            image_feature.roi.map(|roi| roi.height),
+            low_quality,
+            image_feature.content_hash()
         ],
     )?;
-    Ok(())
+    if inserted == 0 {
+        return Ok(false);
+    }
+    if layout == DescriptorLayout::Normalized {
+        layout::insert_descriptor_row(conn, &image_feature.id, &image_feature.descriptors)?;
+    }
+    #[cfg(feature = "metrics")]
+    crate::metrics::feature_inserted();
+    Ok(true)
 }
 
-// Function to delete image feature
-fn delete_image_feature(camera_id: &str, db_name: &str) -> Result<()> {
-    let conn = Connection::open(db_name)?;
-    conn.execute("DELETE FROM image_features WHERE camera_id = ?", params![camera_id])?;
+// Function to overwrite a stored feature's keypoints and descriptors, keeping its id and
+// timestamp; its BoW histogram no longer applies and is cleared. Returns false if no
+// feature has that id.
+#[cfg(feature = "opencv")]
+pub(crate) fn update_feature_extraction(
+    conn: &Connection,
+    feature_id: &str,
+    keypoints: &[KeyPointData],
+    descriptors: &DescriptorMatrix,
+    extraction_params: &OrbParams,
+) -> Result<bool> {
+    if !replace_keypoints_and_descriptors(conn, feature_id, keypoints, descriptors)? {
+        return Ok(false);
+    }
+    conn.execute(
+        "UPDATE image_features SET extraction_params = ?1 WHERE id = ?2",
+        params![serde_json::to_string(extraction_params)?, feature_id],
+    )?;
+    audit::record(conn, "reprocess", feature_id, Some(&format!("{} keypoint(s)", keypoints.len())))?;
+    Ok(true)
+}
+
+// Function to store new keypoints and descriptors for a feature in full, under the
+// current layout and keypoint codec. Its BoW histogram is cleared, and deltas based on it
+// are rewritten first. Returns false if no feature has that id.
+pub(crate) fn replace_keypoints_and_descriptors(
+    conn: &Connection,
+    feature_id: &str,
+    keypoints: &[KeyPointData],
+    descriptors: &DescriptorMatrix,
+) -> Result<bool> {
+    materialize_deltas(conn, Some(feature_id))?;
+    let (keypoints, feature_version) = encode_keypoints(conn, keypoints)?;
+    let layout = layout::layout(conn)?;
+    let embedded = match layout {
+        DescriptorLayout::Embedded => Some(codec::encode(descriptors)?),
+        DescriptorLayout::Normalized => None,
+    };
+    let updated = conn.execute(
+        "UPDATE image_features SET keypoints = ?1, descriptors = ?2, feature_version = ?3, bow_histogram = NULL,
+            base_feature_id = NULL
+        WHERE id = ?4",
+        params![keypoints, embedded, feature_version, feature_id],
+    )?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    if layout == DescriptorLayout::Normalized {
+        layout::insert_descriptor_row(conn, feature_id, descriptors)?;
+    }
+    set_content_hash(conn, feature_id)?;
+    Ok(true)
+}
+
+// Function to recompute a stored feature's content hash from what now reads back
+pub(crate) fn set_content_hash(conn: &Connection, feature_id: &str) -> Result<()> {
+    let feature =
+        fetch_feature_by_id(conn, feature_id)?.ok_or_else(|| VyuwerError::FeatureNotFound(feature_id.to_string()))?;
+    conn.execute(
+        "UPDATE image_features SET content_hash = ?1 WHERE id = ?2",
+        params![feature.content_hash(), feature_id],
+    )?;
     Ok(())
 }
 
-// Function to reset image feature
-fn reset_image_feature(camera_id: &str, image_feature: &ImageFeature, db_name: &str) -> Result<()> {
-    delete_image_feature(camera_id, db_name)?;
-    insert_image_feature(image_feature, db_name)?;
+// Function to fill in the content hash of rows stored before it was recorded, in one
+// transaction. Rows that do not decode are skipped (see `integrity::scan_corrupt_features`).
+// Returns how many rows were hashed.
+pub fn backfill_content_hashes(db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let mut stmt = tx.prepare("SELECT id FROM image_features WHERE content_hash IS NULL ORDER BY id")?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+    let mut hashed = 0;
+    for id in &ids {
+        let mut stmt = tx.prepare_cached(&format!("{FEATURE_SELECT} WHERE image_features.id = ?1"))?;
+        let mut rows = stmt.query(params![id])?;
+        let Some(row) = rows.next()? else {
+            continue;
+        };
+        if let FeatureReadResult::Ok(feature) = read_feature_row(row)? {
+            tx.execute(
+                "UPDATE image_features SET content_hash = ?1 WHERE id = ?2",
+                params![feature.content_hash(), id],
+            )?;
+            hashed += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(hashed)
+}
+
+pub(crate) fn feature_exists(conn: &Connection, feature_id: &str) -> Result<bool> {
+    Ok(conn
+        .query_row("SELECT 1 FROM image_features WHERE id = ?1", params![feature_id], |_| Ok(()))
+        .optional()?
+        .is_some())
+}
+
+// Tables whose `feature_id` column refers to image_features.id
+pub(crate) const FEATURE_ID_TABLES: [&str; 9] = [
+    "descriptors",
+    "frames",
+    "feature_tags",
+    "baselines",
+    "float_descriptors",
+    "feature_descriptors",
+    "flow_stats",
+    "camera_fingerprints",
+    "image_description",
+];
+
+// Function to change a feature's id, e.g. when adopting a new id scheme, carrying its
+// descriptors, frame, tags, baseline promotions, extra descriptor sets, flow stats, cached
+// fingerprint and description links along in one transaction. Fails if `old_id` does not
+// exist or `new_id` already does.
+pub fn rekey_feature(old_id: &str, new_id: &str, db_name: &str) -> Result<()> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    if feature_exists(&tx, new_id)? {
+        return Err(VyuwerError::FeatureExists(new_id.to_string()));
+    }
+    if !feature_exists(&tx, old_id)? {
+        return Err(VyuwerError::FeatureNotFound(old_id.to_string()));
+    }
+    for table in FEATURE_ID_TABLES {
+        tx.execute(&format!("UPDATE {table} SET feature_id = ?1 WHERE feature_id = ?2"), params![new_id, old_id])?;
+    }
+    tx.execute("UPDATE image_features SET base_feature_id = ?1 WHERE base_feature_id = ?2", params![new_id, old_id])?;
+    tx.execute("UPDATE image_features SET id = ?1 WHERE id = ?2", params![new_id, old_id])?;
+    audit::record(&tx, "rekey", new_id, Some(old_id))?;
+    tx.commit()?;
     Ok(())
 }
 
-// Function to get image feature
-fn get_image_feature(camera_id: &str, db_name: &str) -> Result<Option<ImageFeature>> {
+// Function to copy every feature of `src_id` under `dst_id` with fresh ids, in one
+// transaction. Fails if `dst_id` already has features unless `append` is set.
+// Returns how many features were copied.
+pub fn clone_camera(src_id: &str, dst_id: &str, append: bool, db_name: &str) -> Result<usize> {
+    if src_id == dst_id {
+        return Err(VyuwerError::InvalidInput(format!("cannot clone camera {src_id} onto itself")));
+    }
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    if !append && !fetch_features(&tx, dst_id, Some(1))?.is_empty() {
+        return Err(VyuwerError::InvalidInput(format!(
+            "camera {dst_id} already has features; pass append to add to them"
+        )));
+    }
+    let features = fetch_features(&tx, src_id, None)?;
+    for feature in &features {
+        let copy = ImageFeature {
+            id: Uuid::new_v4().to_string(),
+            camera_id: dst_id.to_string(),
+            ..feature.clone()
+        };
+        insert_feature_row(&tx, &copy)?;
+    }
+    audit::record(&tx, "clone", dst_id, Some(&format!("{} feature(s) from {src_id}", features.len())))?;
+    tx.commit()?;
+    Ok(features.len())
+}
+
+// Function to delete every feature, across cameras, created before `cutoff_utc`.
+// Returns how many features were removed.
+pub fn prune_features_before(cutoff_utc: &str, db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let pruned = prune_features(&tx, cutoff_utc)?;
+    tx.commit()?;
+    Ok(pruned)
+}
+
+// Function to delete every feature older than `age`, e.g. `parse_duration("30d")?` to
+// keep the last 30 days. Returns how many features were removed.
+pub fn prune_older_than(age: Duration, db_name: &str) -> Result<usize> {
+    prune_features_before(&timestamp::utc_iso8601_ago(age), db_name)
+}
+
+pub(crate) fn prune_features(conn: &Connection, cutoff_utc: &str) -> Result<usize> {
+    let cutoff = timestamp::parse_utc_millis(cutoff_utc)?;
+    let pruned = delete_features_where(conn, None, |millis| millis < cutoff)?;
+    audit::record(conn, "prune", cutoff_utc, Some(&format!("{pruned} feature(s)")))?;
+    Ok(pruned)
+}
+
+// Function to delete a camera's features created between `start_utc` and `end_utc`
+// (both inclusive), e.g. a known bad recording segment. Returns how many were removed.
+pub fn delete_features_in_range(camera_id: &str, start_utc: &str, end_utc: &str, db_name: &str) -> Result<usize> {
+    let start = timestamp::parse_utc_millis(start_utc)?;
+    let end = timestamp::parse_utc_millis(end_utc)?;
+    if start > end {
+        return Err(VyuwerError::InvalidInput(format!("range start {start_utc} is after its end {end_utc}")));
+    }
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let deleted = delete_features_where(&tx, Some(camera_id), |millis| (start..=end).contains(&millis))?;
+    audit::record(
+        &tx,
+        "delete",
+        camera_id,
+        Some(&format!("{deleted} feature(s) from {start_utc} to {end_utc}")),
+    )?;
+    tx.commit()?;
+    Ok(deleted)
+}
+
+// Function to delete features (optionally of one camera) whose parsed creation time,
+// in Unix milliseconds, satisfies `matches`. Timestamps are compared after parsing,
+// since stored strings may differ in format.
+fn delete_features_where(conn: &Connection, camera_id: Option<&str>, matches: impl Fn(i64) -> bool) -> Result<usize> {
+    let mut stmt = conn.prepare("SELECT id, created_at_utc FROM image_features WHERE ?1 IS NULL OR camera_id = ?1")?;
+    let rows = stmt
+        .query_map(params![camera_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut deleted = 0;
+    for (id, created_at_utc) in rows {
+        if matches(timestamp::parse_utc_millis(&created_at_utc)?) {
+            deleted += delete_feature_row(conn, &id)?;
+        }
+    }
+    Ok(deleted)
+}
+
+// Function to delete one feature by id; triggers remove its descriptors, frame and tags.
+// Deltas based on it are rewritten in full first. Callers record the audit entry for the
+// operation as a whole.
+pub(crate) fn delete_feature_row(conn: &Connection, feature_id: &str) -> Result<usize> {
+    materialize_deltas(conn, Some(feature_id))?;
+    Ok(conn.execute("DELETE FROM image_features WHERE id = ?", params![feature_id])?)
+}
+
+// Deltas only ever have a base in their own camera, so none outlive this
+fn delete_feature_rows(conn: &Connection, camera_id: &str) -> Result<usize> {
+    let deleted = conn.execute("DELETE FROM image_features WHERE camera_id = ?", params![camera_id])?;
+    Ok(deleted)
+}
+
+pub(crate) fn fetch_feature(conn: &Connection, camera_id: &str) -> Result<Option<ImageFeature>> {
+    Ok(fetch_latest_features(conn, camera_id, 1)?.pop())
+}
+
+// Function to get a single feature by its id
+pub fn get_feature_by_id(feature_id: &str, db_name: &str) -> Result<Option<ImageFeature>> {
     let conn = Connection::open(db_name)?;
-    let mut stmt = conn.prepare("SELECT * FROM image_features WHERE camera_id = ?")?;
-    let mut rows = stmt.query(params![camera_id])?;
+    fetch_feature_by_id(&conn, feature_id)
+}
 
-    if let Some(row) = rows.next()? {
-        let keypoints: Vec<KeyPointData> = bincode::deserialize(&row.get::<_, Vec<u8>>(1)?)?;
-        let descriptors: Vec<u8> = bincode::deserialize(&row.get::<_, Vec<u8>>(2)?)?;
-        let image_feature = ImageFeature {
-            id: row.get(0)?,
-            keypoints,
-            descriptors,
-            motion_mean: row.get(3)?,
-            motion_std: row.get(4)?,
-            created_at_utc: row.get(5)?,
-            img_filename: row.get(6)?,
-            camera_id: row.get(7)?,
-        };
-        Ok(Some(image_feature))
-    } else {
-        Ok(None)
+pub(crate) fn fetch_feature_by_id(conn: &Connection, feature_id: &str) -> Result<Option<ImageFeature>> {
+    #[cfg(feature = "metrics")]
+    crate::metrics::query_served();
+    let mut stmt = conn.prepare(&format!("{FEATURE_SELECT} WHERE image_features.id = ?"))?;
+    let mut rows = stmt.query(params![feature_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(feature_from_row(row)?)),
+        None => Ok(None),
     }
 }
 
-// Main function to test the setup
-fn main() -> Result<()> {
-    setup_database(PROD_DB)?;
-    setup_database(TEST_DB)?;
-    image_description_table(PROD_DB)?;
-    image_description_table(TEST_DB)?;
+// Function to get all of a camera's features in time order
+pub fn get_camera_features(camera_id: &str, db_name: &str) -> Result<Vec<ImageFeature>> {
+    let conn = Connection::open(db_name)?;
+    fetch_features(&conn, camera_id, None)
+}
 
-    let image_description = ImageDescription {
-        image_name: String::from("test_image.jpg"),
-        datetime: String::from("2024-06-12T12:34:56Z"),
-        camera_id: String::from("camera_1"),
-        anomaly: None,
-    };
-    insert_image_description(&image_description, PROD_DB)?;
+// Function to get a camera's features whose stored ROI overlaps `roi`, in time order.
+// Features stored without a ROI are not returned.
+pub fn features_in_roi(camera_id: &str, roi: Roi, db_name: &str) -> Result<Vec<ImageFeature>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1
+            AND roi_x < ?2 + ?4 AND ?2 < roi_x + roi_w AND roi_y < ?3 + ?5 AND ?3 < roi_y + roi_h
+        ORDER BY created_at_utc, seq, id"
+    ))?;
+    let mut rows = stmt.query(params![camera_id, roi.x, roi.y, roi.width, roi.height])?;
+    let mut features = Vec::new();
+    while let Some(row) = rows.next()? {
+        features.push(feature_from_row(row)?);
+    }
+    Ok(features)
+}
+
+pub(crate) fn fetch_features(conn: &Connection, camera_id: &str, limit: Option<usize>) -> Result<Vec<ImageFeature>> {
+    #[cfg(feature = "metrics")]
+    crate::metrics::query_served();
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1
+        ORDER BY created_at_utc, seq, id LIMIT ?2"
+    ))?;
+    let limit = limit.map_or(-1, |n| n as i64);
+    let mut rows = stmt.query(params![camera_id, limit])?;
+
+    let mut features = Vec::new();
+    while let Some(row) = rows.next()? {
+        features.push(feature_from_row(row)?);
+    }
+    Ok(features)
+}
 
-    let keypoints = vec![
-        KeyPointData { x: 0.0, y: 0.0, size: 1.0, angle: 0.0 },
-        KeyPointData { x: 1.0, y: 1.0, size: 2.0, angle: 45.0 },
-    ];
-    let descriptors = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+// Function to fetch a camera's `n` most recent features, returned oldest first
+pub(crate) fn fetch_latest_features(conn: &Connection, camera_id: &str, n: usize) -> Result<Vec<ImageFeature>> {
+    #[cfg(feature = "metrics")]
+    crate::metrics::query_served();
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1
+        ORDER BY created_at_utc DESC, seq DESC, id DESC LIMIT ?2"
+    ))?;
+    let mut rows = stmt.query(params![camera_id, n as i64])?;
 
-    let image_feature = ImageFeature {
-        id: String::from("1"),
+    let mut features = Vec::new();
+    while let Some(row) = rows.next()? {
+        features.push(feature_from_row(row)?);
+    }
+    features.reverse();
+    Ok(features)
+}
+
+// Feature rows joined with their normalized descriptors (NULL for the embedded layout);
+// callers append WHERE / ORDER BY clauses. Databases created before the motion columns
+// were NOT NULL may hold NULLs there, which read back as 0.
+pub(crate) const FEATURE_SELECT: &str = "SELECT image_features.id, keypoints, image_features.descriptors,
+        COALESCE(motion_mean, 0), COALESCE(motion_std, 0), created_at_utc, img_filename, camera_id, feature_version,
+        descriptors.data, descriptors.rows, descriptors.cols, phash, frame_width, frame_height,
+        extraction_params, sharpness, roi_x, roi_y, roi_w, roi_h,
+        (SELECT base.descriptors FROM image_features base WHERE base.id = image_features.base_feature_id)
+    FROM image_features LEFT JOIN descriptors ON descriptors.feature_id = image_features.id";
+
+// Function to decode a row selected with FEATURE_SELECT, dispatching on its feature_version
+pub(crate) fn feature_from_row(row: &rusqlite::Row) -> Result<ImageFeature> {
+    let version: i64 = row.get(8)?;
+    let keypoints = decode_keypoints(&row.get::<_, Vec<u8>>(1)?, version)?;
+    let descriptors = match row.get::<_, Option<Vec<u8>>>(2)? {
+        Some(blob) if version == DELTA_DESCRIPTORS_FEATURE_VERSION => {
+            decode_delta_descriptors(&blob, row.get(21)?, &row.get::<_, String>(0)?)?
+        }
+        Some(blob) => decode_descriptors(&blob, version)?,
+        None => DescriptorMatrix::new(
+            row.get::<_, i64>(10)? as usize,
+            row.get::<_, i64>(11)? as usize,
+            row.get(9)?,
+        )?,
+    };
+    Ok(ImageFeature {
+        id: row.get(0)?,
         keypoints,
         descriptors,
-        motion_mean: 0.5,
-        motion_std: 0.1,
-        created_at_utc: String::from("2024-06-12T12:34:56Z"),
-        img_filename: Some(String::from("image_1.jpg")),
-        camera_id: String::from("camera_1"),
+        motion_mean: row.get(3)?,
+        motion_std: row.get(4)?,
+        created_at_utc: row.get(5)?,
+        img_filename: row.get(6)?,
+        camera_id: row.get(7)?,
+        phash: row.get::<_, Option<i64>>(12)?.map(|hash| hash as u64),
+        frame_size: match (row.get(13)?, row.get(14)?) {
+            (Some(width), Some(height)) => Some(FrameSize { width, height }),
+            _ => None,
+        },
+        extraction_params: decode_extraction_params(row.get(15)?)?,
+        sharpness: row.get(16)?,
+        roi: match (row.get(17)?, row.get(18)?, row.get(19)?, row.get(20)?) {
+            (Some(x), Some(y), Some(width), Some(height)) => Some(Roi { x, y, width, height }),
+            _ => None,
+        },
+    })
+}
+
+// Function to decode a FEATURE_SELECT row like `feature_from_row`, turning a decoding
+// failure into `FeatureReadResult::Corrupt`; only errors reading the row itself propagate
+pub(crate) fn read_feature_row(row: &rusqlite::Row) -> Result<FeatureReadResult> {
+    match feature_from_row(row) {
+        Ok(feature) => Ok(FeatureReadResult::Ok(feature)),
+        Err(VyuwerError::Sqlite(err)) => Err(err.into()),
+        Err(err) => Ok(FeatureReadResult::Corrupt(CorruptFeature {
+            id: row.get(0)?,
+            camera_id: row.get(7)?,
+            error: err.to_string(),
+        })),
+    }
+}
+
+fn decode_extraction_params(json: Option<String>) -> Result<Option<OrbParams>> {
+    Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+}
+
+// Function to get the ORB settings a feature was extracted with; None when they were not recorded
+pub fn get_extraction_params(feature_id: &str, db_name: &str) -> Result<Option<OrbParams>> {
+    let conn = Connection::open(db_name)?;
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT extraction_params FROM image_features WHERE id = ?1",
+            params![feature_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| VyuwerError::FeatureNotFound(feature_id.to_string()))?;
+    decode_extraction_params(json)
+}
+
+// Function to choose how features written from now on encode their keypoints
+pub fn set_keypoint_codec(keypoint_codec: KeypointCodec, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    let value = match keypoint_codec {
+        KeypointCodec::Bincode => "bincode",
+        KeypointCodec::Delta => "delta",
     };
+    schema::set_setting(&conn, KEYPOINT_CODEC_SETTING, value)
+}
 
-    insert_image_feature(&image_feature, PROD_DB)?;
+pub fn keypoint_codec(db_name: &str) -> Result<KeypointCodec> {
+    let conn = Connection::open(db_name)?;
+    fetch_keypoint_codec(&conn)
+}
 
-    if let Some(feature) = get_image_feature("camera_1", PROD_DB)? {
-        println!("Retrieved image feature: {:?}", feature);
-    } else {
-        println!("No image feature found for the given camera_id.");
+fn fetch_keypoint_codec(conn: &Connection) -> Result<KeypointCodec> {
+    match schema::get_setting(conn, KEYPOINT_CODEC_SETTING)?.as_deref() {
+        Some("delta") => Ok(KeypointCodec::Delta),
+        _ => Ok(KeypointCodec::Bincode),
     }
+}
 
-    Ok(())
+// Function to encode keypoints under the database's codec, returning the blob and the
+// feature_version to store with it
+pub(crate) fn encode_keypoints(conn: &Connection, keypoints: &[KeyPointData]) -> Result<(Vec<u8>, i64)> {
+    match fetch_keypoint_codec(conn)? {
+        KeypointCodec::Bincode => Ok((codec::encode(&keypoints)?, FEATURE_VERSION)),
+        KeypointCodec::Delta => Ok((codec::encode_keypoints_delta(keypoints), FLAGGED_KEYPOINTS_FEATURE_VERSION)),
+    }
+}
+
+// Function to encode keypoints in the flagged (v3/v4) layout whatever the database's codec
+fn flagged_keypoints(conn: &Connection, keypoints: &[KeyPointData]) -> Result<Vec<u8>> {
+    let (blob, version) = encode_keypoints(conn, keypoints)?;
+    if version == FLAGGED_KEYPOINTS_FEATURE_VERSION {
+        return Ok(blob);
+    }
+    let mut flagged = Vec::with_capacity(blob.len() + 1);
+    flagged.push(codec::KEYPOINT_FORMAT_BINCODE);
+    flagged.extend(blob);
+    Ok(flagged)
+}
+
+// The keypoint layout is shared by v1 and v2, and by v3 and v4
+pub(crate) fn decode_keypoints(blob: &[u8], version: i64) -> Result<Vec<KeyPointData>> {
+    match version {
+        1 | 2 => codec::decode(blob),
+        3 | 4 => codec::decode_flagged_keypoints(blob),
+        _ => Err(VyuwerError::UnsupportedFeatureVersion(version)),
+    }
+}
+
+pub(crate) fn decode_descriptors(blob: &[u8], version: i64) -> Result<DescriptorMatrix> {
+    match version {
+        1 => Ok(DescriptorMatrix::from_orb_bytes(codec::decode(blob)?)),
+        2 | 3 => codec::decode(blob),
+        _ => Err(VyuwerError::UnsupportedFeatureVersion(version)),
+    }
+}
+
+// Function to rebuild v4 descriptors from their delta and the base's (v2/v3) blob
+pub(crate) fn decode_delta_descriptors(
+    blob: &[u8],
+    base: Option<Vec<u8>>,
+    feature_id: &str,
+) -> Result<DescriptorMatrix> {
+    let base = base.ok_or_else(|| {
+        VyuwerError::InvalidInput(format!("descriptors of {feature_id} are a delta against a missing base"))
+    })?;
+    codec::decode_descriptor_delta(blob, &decode_descriptors(&base, FEATURE_VERSION)?)
 }
@@ -0,0 +1,118 @@
+#[cfg(feature = "opencv")]
+use opencv::{core::Mat, prelude::*};
+use rusqlite::{params, Connection};
+
+use crate::error::Result;
+#[cfg(feature = "opencv")]
+use crate::extract::to_gray;
+
+// Side of the downsampled image the DCT runs on
+const DCT_SIZE: usize = 32;
+// Side of the low-frequency block kept for the 64-bit hash
+const HASH_SIZE: usize = 8;
+
+// Function to count differing bits between two perceptual hashes (0 = identical, 64 = opposite)
+pub fn phash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Function to find a camera's features whose stored pHash is within `max_distance` bits of
+// `target`, as (feature id, distance) sorted by distance, then time. A cheap pre-filter
+// before descriptor matching; SQLite cannot index Hamming distance, so every hash is scanned.
+pub fn find_by_phash(camera_id: &str, target: u64, max_distance: u32, db_name: &str) -> Result<Vec<(String, u32)>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, phash FROM image_features WHERE camera_id = ?1 AND phash IS NOT NULL
+        ORDER BY created_at_utc, seq, id",
+    )?;
+    let mut matches = Vec::new();
+    let mut rows = stmt.query(params![camera_id])?;
+    while let Some(row) = rows.next()? {
+        let distance = phash_distance(row.get::<_, i64>(1)? as u64, target);
+        if distance <= max_distance {
+            matches.push((row.get(0)?, distance));
+        }
+    }
+    // Stable, so equal distances stay in time order
+    matches.sort_by_key(|(_, distance)| *distance);
+    Ok(matches)
+}
+
+// Function to compute a DCT-based perceptual hash from a row-major 8-bit grayscale buffer.
+// The image is area-averaged to 32x32, transformed with a 2D DCT-II, and each of the
+// 8x8 lowest-frequency coefficients sets one bit when above their median.
+pub fn phash_from_gray(pixels: &[u8], width: usize, height: usize) -> u64 {
+    if width == 0 || height == 0 || pixels.len() < width * height {
+        return 0;
+    }
+    let small = downsample(pixels, width, height);
+    let coefficients = dct_2d(&small);
+
+    let mut low = Vec::with_capacity(HASH_SIZE * HASH_SIZE);
+    for row in coefficients.iter().take(HASH_SIZE) {
+        low.extend_from_slice(&row[..HASH_SIZE]);
+    }
+    let mut sorted = low.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = (sorted[31] + sorted[32]) / 2.0;
+
+    low.iter()
+        .enumerate()
+        .filter(|(_, &c)| c > median)
+        .fold(0u64, |hash, (i, _)| hash | (1 << i))
+}
+
+// Function to compute the perceptual hash of a frame
+#[cfg(feature = "opencv")]
+pub fn phash(image: &Mat) -> Result<u64> {
+    let gray = to_gray(image)?;
+    Ok(phash_from_gray(gray.data_bytes()?, gray.cols() as usize, gray.rows() as usize))
+}
+
+fn downsample(pixels: &[u8], width: usize, height: usize) -> Vec<Vec<f64>> {
+    let span = |i: usize, len: usize| {
+        let start = i * len / DCT_SIZE;
+        let end = ((i + 1) * len / DCT_SIZE).max(start + 1).min(len);
+        (start.min(len - 1), end)
+    };
+    (0..DCT_SIZE)
+        .map(|ty| {
+            let (y0, y1) = span(ty, height);
+            (0..DCT_SIZE)
+                .map(|tx| {
+                    let (x0, x1) = span(tx, width);
+                    let sum: u64 = (y0..y1)
+                        .flat_map(|y| pixels[y * width + x0..y * width + x1].iter())
+                        .map(|&p| u64::from(p))
+                        .sum();
+                    sum as f64 / ((y1 - y0) * (x1 - x0)) as f64
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn dct_1d(input: &[f64]) -> Vec<f64> {
+    let n = input.len() as f64;
+    (0..input.len())
+        .map(|k| {
+            input
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| x * (std::f64::consts::PI * (i as f64 + 0.5) * k as f64 / n).cos())
+                .sum()
+        })
+        .collect()
+}
+
+fn dct_2d(input: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let rows: Vec<Vec<f64>> = input.iter().map(|row| dct_1d(row)).collect();
+    let mut out = vec![vec![0.0; DCT_SIZE]; DCT_SIZE];
+    for x in 0..DCT_SIZE {
+        let column: Vec<f64> = rows.iter().map(|row| row[x]).collect();
+        for (y, value) in dct_1d(&column).into_iter().enumerate() {
+            out[y][x] = value;
+        }
+    }
+    out
+}
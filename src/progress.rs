@@ -0,0 +1,9 @@
+// Progress of a long-running operation, passed to its `_with_progress` callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    pub done: usize,
+    // None when the size of the input is not known up front, e.g. a frame stream
+    pub total: Option<usize>,
+    // Short name of the current phase, e.g. "matching"
+    pub stage: &'static str,
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::error::{Result, VyuwerError};
+use crate::model::{insert_image_feature, ImageFeature};
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// Per-camera token bucket: each camera may insert `burst` features at once and then
+// `per_second` features per second on average. Inserts over the limit are dropped and
+// counted, as a safety valve against runaway ingestion loops.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: HashMap<String, Bucket>,
+    dropped: HashMap<String, u64>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64, burst: u32) -> Result<Self> {
+        if !(per_second.is_finite() && per_second > 0.0) || burst == 0 {
+            return Err(VyuwerError::InvalidInput(format!(
+                "rate limit needs a positive rate and burst, got {per_second}/s with burst {burst}"
+            )));
+        }
+        Ok(RateLimiter {
+            per_second,
+            burst: f64::from(burst),
+            buckets: HashMap::new(),
+            dropped: HashMap::new(),
+        })
+    }
+
+    // Function to take one token for `camera_id` at time `now`; false means the insert should be dropped
+    pub fn try_acquire_at(&mut self, camera_id: &str, now: Instant) -> bool {
+        let bucket = self.buckets.entry(camera_id.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return true;
+        }
+        *self.dropped.entry(camera_id.to_string()).or_insert(0) += 1;
+        false
+    }
+
+    pub fn try_acquire(&mut self, camera_id: &str) -> bool {
+        self.try_acquire_at(camera_id, Instant::now())
+    }
+
+    pub fn dropped(&self, camera_id: &str) -> u64 {
+        self.dropped.get(camera_id).copied().unwrap_or(0)
+    }
+
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.values().sum()
+    }
+}
+
+// Function to insert a feature unless its camera is over the limiter's rate.
+// Returns whether the feature was stored; dropped features are counted by the limiter.
+pub fn insert_rate_limited(limiter: &mut RateLimiter, image_feature: &ImageFeature, db_name: &str) -> Result<bool> {
+    if !limiter.try_acquire(&image_feature.camera_id) {
+        return Ok(false);
+    }
+    insert_image_feature(image_feature, db_name)?;
+    Ok(true)
+}
@@ -0,0 +1,204 @@
+use rusqlite::Connection;
+
+use crate::anomaly;
+use crate::camera;
+use crate::descriptor_sets;
+use crate::error::{Result, VyuwerError};
+use crate::float_descriptors;
+use crate::flow;
+use crate::frames;
+use crate::model;
+use crate::tags;
+use crate::vocabulary;
+
+// Schema version stamped into `PRAGMA user_version`; bump it whenever a migration is added.
+// 1: feature, description, camera, vocabulary, frame, descriptor and audit tables
+// 2: frames.frame_path and frames.frame_sha256 for content-addressed frame files
+// 3: image_features.frame_width and frame_height
+// 4: image_features.extraction_params
+// 5: image_features.seq and its (camera_id, seq) index
+// 6: feature_tags
+// 7: baselines
+// 8: image_features.sharpness
+// 9: float_descriptors
+// 10: feature_descriptors
+// 11: flow_stats
+// 12: image_description.count and last_seen
+// 13: image_features.roi_x, roi_y, roi_w and roi_h
+// 14: image_features.content_sha256 and its unique (camera_id, content_sha256) index
+// 15: image_features.descriptor_kind
+// 16: camera_calibration table
+// 17: cameras.min_keypoints and low_keypoint_action, image_features.low_quality
+// 18: image_features.bow_histogram
+// 19: camera_fingerprints table
+// 20: image_description.feature_id
+// 21: image_features.base_feature_id
+// 22: image_features.extract_ms
+// 23: image_description.severity
+// 24: image_features.content_hash
+// 25: image_features.created_at_utc rewritten as YYYY-MM-DDTHH:MM:SS.fffZ
+pub const SCHEMA_VERSION: i64 = 25;
+
+// Function to create every table and bring older databases up to the current schema.
+// Each step is idempotent, so this is safe to run on every open. A database stamped by
+// a newer build is refused rather than risk this build corrupting it.
+pub(crate) fn apply(conn: &Connection) -> Result<()> {
+    let found = user_version(conn)?;
+    if found > SCHEMA_VERSION {
+        return Err(VyuwerError::IncompatibleSchema {
+            found,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    model::create_feature_tables(conn)?;
+    model::create_description_table(conn)?;
+    camera::create_camera_table(conn)?;
+    camera::create_calibration_table(conn)?;
+    camera::create_fingerprint_table(conn)?;
+    vocabulary::create_vocabulary_table(conn)?;
+    frames::create_frame_table(conn)?;
+    tags::create_tag_table(conn)?;
+    anomaly::create_baseline_table(conn)?;
+    float_descriptors::create_float_descriptor_table(conn)?;
+    descriptor_sets::create_descriptor_set_table(conn)?;
+    flow::create_flow_table(conn)?;
+    // Rows from before then may be stored whole-second, space-separated or without the Z
+    if found < 25 {
+        model::normalize_feature_timestamps(conn)?;
+    }
+    if found < SCHEMA_VERSION {
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn user_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+// Function to dump the live schema as a SQL script: every CREATE TABLE, then every
+// CREATE INDEX and CREATE TRIGGER, as stored in sqlite_master (SQLite-internal objects excluded)
+pub fn dump_schema(db_name: &str) -> Result<String> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT sql FROM sqlite_master
+        WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+        ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END, name",
+    )?;
+    let statements = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(statements.iter().map(|sql| format!("{sql};\n")).collect::<Vec<_>>().join("\n"))
+}
+
+// Optional capabilities and the (table, columns) each needs, for code that must also run
+// against databases this build has not yet upgraded (see `supports`)
+const CAPABILITIES: [(&str, &str, &[&str]); 18] = [
+    ("phash", "image_features", &["phash"]),
+    ("frame_size", "image_features", &["frame_width", "frame_height"]),
+    ("extraction_params", "image_features", &["extraction_params"]),
+    ("sharpness", "image_features", &["sharpness"]),
+    ("roi", "image_features", &["roi_x", "roi_y", "roi_w", "roi_h"]),
+    ("content_hash", "image_features", &["content_sha256"]),
+    ("feature_content_hash", "image_features", &["content_hash"]),
+    ("descriptor_kind", "image_features", &["descriptor_kind"]),
+    ("low_quality", "image_features", &["low_quality"]),
+    ("bow_search", "image_features", &["bow_histogram"]),
+    ("descriptor_delta", "image_features", &["base_feature_id"]),
+    ("extraction_latency", "image_features", &["extract_ms"]),
+    ("frames", "frames", &["image"]),
+    ("frame_files", "frames", &["frame_path", "frame_sha256"]),
+    ("description_repeats", "image_description", &["count", "last_seen"]),
+    ("description_links", "image_description", &["feature_id"]),
+    ("anomaly_severity", "image_description", &["severity"]),
+    ("keypoint_gate", "cameras", &["min_keypoints", "low_keypoint_action"]),
+];
+
+// Function to list `table`'s columns in declaration order; empty if there is no such table
+pub fn table_columns(table: &str, db_name: &str) -> Result<Vec<String>> {
+    let conn = Connection::open(db_name)?;
+    columns(&conn, table)
+}
+
+fn columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+    let columns = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+// Function to check whether the database has the columns `capability` needs, as named in
+// `CAPABILITIES`. Read-only, so an old file opened without `Database::open` is inspected
+// as it is rather than upgraded. Unknown capability names are an error, not false.
+pub fn supports(capability: &str, db_name: &str) -> Result<bool> {
+    let (_, table, required) = CAPABILITIES
+        .iter()
+        .find(|(name, _, _)| *name == capability)
+        .ok_or_else(|| VyuwerError::InvalidInput(format!("unknown capability {capability:?}")))?;
+    let conn = Connection::open(db_name)?;
+    let present = columns(&conn, table)?;
+    Ok(required.iter().all(|column| present.iter().any(|name| name == column)))
+}
+
+// Function to check whether `table` already has `column`
+pub(crate) fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Function to check whether `table`.`column` is declared NOT NULL
+pub(crate) fn is_not_null(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(row.get::<_, i64>(3)? != 0);
+        }
+    }
+    Ok(false)
+}
+
+// Function to add a column to an existing table, for databases created before it existed
+pub(crate) fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"), [])?;
+    }
+    Ok(())
+}
+
+// Function to create the key/value table holding per-database options
+pub(crate) fn create_settings_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vyuwer_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT value FROM vyuwer_settings WHERE key = ?1")?;
+    let mut rows = stmt.query([key])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO vyuwer_settings (key, value) VALUES (?1, ?2)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        [key, value],
+    )?;
+    Ok(())
+}
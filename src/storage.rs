@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rusqlite::{params, Connection};
+
+use crate::audit;
+use crate::error::Result;
+use crate::frames;
+use crate::model;
+use crate::timestamp::parse_utc_millis;
+
+// What is taking up space in a database, for capacity planning
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageReport {
+    // Size of the main database file (page count times page size); a WAL file is not counted
+    pub file_bytes: u64,
+    // Free pages inside the file that VACUUM would give back, in bytes
+    pub free_bytes: u64,
+    // Row count of every table, by name
+    pub table_rows: BTreeMap<String, u64>,
+    // Payload of the descriptor blobs, embedded in image_features or in the descriptors table
+    pub descriptor_bytes: u64,
+}
+
+// Function to break a database's storage down by table. Row counts are exact, so this
+// scans every table; run it from an admin path rather than per request.
+pub fn storage_report(db_name: &str) -> Result<StorageReport> {
+    let conn = Connection::open(db_name)?;
+    let (file_bytes, free_bytes) = page_usage(&conn)?;
+
+    let mut stmt =
+        conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut table_rows = BTreeMap::new();
+    for table in tables {
+        let rows: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM \"{table}\""), [], |row| row.get(0))?;
+        table_rows.insert(table, rows as u64);
+    }
+
+    Ok(StorageReport {
+        file_bytes,
+        free_bytes,
+        table_rows,
+        descriptor_bytes: descriptor_bytes(&conn)?,
+    })
+}
+
+// Descriptor blobs `compression_advice` looks at, spread evenly over the stored ones
+pub const COMPRESSION_SAMPLE_BLOBS: usize = 200;
+// Estimated compressed/original ratio at or below which compression is recommended
+pub const COMPRESSION_WORTHWHILE_RATIO: f64 = 0.8;
+
+// Shortest repeat the compression estimate encodes as a back-reference
+const MIN_MATCH_LEN: usize = 4;
+// Estimated bytes of one back-reference (offset and length)
+const MATCH_COST_BYTES: f64 = 3.0;
+
+// Whether compressing descriptor blobs would pay off, from a sample
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CompressionAdvice {
+    pub sampled_blobs: usize,
+    pub sampled_bytes: u64,
+    // Payload of every descriptor blob, as in `StorageReport::descriptor_bytes`
+    pub descriptor_bytes: u64,
+    // Estimated compressed size over original size of the sample, 1.0 when nothing was sampled
+    pub estimated_ratio: f64,
+    // `descriptor_bytes` the estimated ratio would save across the database
+    pub projected_savings_bytes: u64,
+    pub recommended: bool,
+}
+
+// Function to estimate how well per-blob compression (zstd, say) would shrink the stored
+// descriptors. Up to COMPRESSION_SAMPLE_BLOBS blobs are sampled evenly by rowid and each
+// is run through a greedy LZ77 model: repeats of MIN_MATCH_LEN bytes or more cost
+// MATCH_COST_BYTES, and the remaining literals are entropy coded at their order-0
+// entropy. That tracks zstd at its fast levels closely enough to decide; random binary
+// descriptors such as ORB's barely compress, while repetitive ones (flat, low-texture
+// scenes) do. Compression is recommended at COMPRESSION_WORTHWHILE_RATIO or better.
+pub fn compression_advice(db_name: &str) -> Result<CompressionAdvice> {
+    let conn = Connection::open(db_name)?;
+    let descriptor_bytes = descriptor_bytes(&conn)?;
+    let stored: i64 = conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM image_features WHERE length(descriptors) > 0)
+            + (SELECT COUNT(*) FROM descriptors)",
+        [],
+        |row| row.get(0),
+    )?;
+    let step = (stored as usize).div_ceil(COMPRESSION_SAMPLE_BLOBS).max(1);
+    let mut stmt = conn.prepare(
+        "SELECT blob FROM (
+            SELECT blob, row_number() OVER (ORDER BY source, id) AS n FROM (
+                SELECT 0 AS source, rowid AS id, descriptors AS blob FROM image_features WHERE length(descriptors) > 0
+                UNION ALL
+                SELECT 1, rowid, data FROM descriptors
+            )
+        )
+        WHERE (n - 1) % ?1 = 0",
+    )?;
+    let blobs = stmt
+        .query_map(params![step as i64], |row| row.get::<_, Vec<u8>>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let sampled_bytes: usize = blobs.iter().map(Vec::len).sum();
+    let compressed: f64 = blobs.iter().map(|blob| estimated_compressed_len(blob)).sum();
+    let estimated_ratio = if sampled_bytes == 0 {
+        1.0
+    } else {
+        (compressed / sampled_bytes as f64).min(1.0)
+    };
+    Ok(CompressionAdvice {
+        sampled_blobs: blobs.len(),
+        sampled_bytes: sampled_bytes as u64,
+        descriptor_bytes,
+        estimated_ratio,
+        projected_savings_bytes: (descriptor_bytes as f64 * (1.0 - estimated_ratio)) as u64,
+        recommended: sampled_bytes > 0 && estimated_ratio <= COMPRESSION_WORTHWHILE_RATIO,
+    })
+}
+
+// Function to total the descriptor blobs, embedded in image_features or in the descriptors table
+fn descriptor_bytes(conn: &Connection) -> Result<u64> {
+    let descriptor_bytes: i64 = conn.query_row(
+        "SELECT (SELECT COALESCE(SUM(length(descriptors)), 0) FROM image_features)
+            + (SELECT COALESCE(SUM(length(data)), 0) FROM descriptors)",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(descriptor_bytes as u64)
+}
+
+// Function to estimate the compressed size of one blob, see `compression_advice`
+fn estimated_compressed_len(blob: &[u8]) -> f64 {
+    // Latest position of every MIN_MATCH_LEN-byte window seen so far
+    let mut last_seen: HashMap<&[u8], usize> = HashMap::new();
+    let mut literals = Vec::new();
+    let mut matches = 0usize;
+    let mut i = 0;
+    while i + MIN_MATCH_LEN <= blob.len() {
+        match last_seen.insert(&blob[i..i + MIN_MATCH_LEN], i) {
+            Some(earlier) => {
+                let len = blob[i..].iter().zip(&blob[earlier..]).take_while(|(a, b)| a == b).count();
+                for start in i + 1..(i + len).min(blob.len() + 1 - MIN_MATCH_LEN) {
+                    last_seen.insert(&blob[start..start + MIN_MATCH_LEN], start);
+                }
+                matches += 1;
+                i += len;
+            }
+            None => {
+                literals.push(blob[i]);
+                i += 1;
+            }
+        }
+    }
+    literals.extend_from_slice(&blob[i..]);
+
+    let mut counts = [0usize; 256];
+    for &byte in &literals {
+        counts[usize::from(byte)] += 1;
+    }
+    let total = literals.len() as f64;
+    let entropy_bits: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    total * entropy_bits / 8.0 + matches as f64 * MATCH_COST_BYTES
+}
+
+// Function to keep the database file within `max_bytes` on storage-limited devices. When
+// the file is larger, features are evicted oldest first, those of cameras missing from
+// `camera_id_priority` before any listed one, and listed cameras in reverse order (the
+// first is kept longest). Eviction stops once the pages still in use fit the cap, and a
+// VACUUM then gives the freed pages back to the file system. If removing every feature is
+// not enough (other tables alone exceed the cap), everything is evicted and the file stays
+// over. Returns how many features were evicted.
+pub fn enforce_max_size(max_bytes: u64, camera_id_priority: &[&str], db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let (file_bytes, free_bytes) = page_usage(&conn)?;
+    if file_bytes <= max_bytes {
+        return Ok(0);
+    }
+    let mut used_bytes = file_bytes.saturating_sub(free_bytes);
+
+    let rows = conn
+        .prepare("SELECT id, camera_id, created_at_utc FROM image_features")?
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let rank = |camera_id: &str| {
+        camera_id_priority
+            .iter()
+            .position(|&kept| kept == camera_id)
+            .map_or(0, |i| camera_id_priority.len() - i)
+    };
+    let mut candidates = rows
+        .into_iter()
+        .map(|(id, camera_id, created_at_utc)| Ok((rank(&camera_id), parse_utc_millis(&created_at_utc)?, id)))
+        .collect::<Result<Vec<_>>>()?;
+    candidates.sort();
+
+    let tx = conn.transaction()?;
+    let mut evicted = 0;
+    let mut files = Vec::new();
+    for (_, _, id) in &candidates {
+        if used_bytes <= max_bytes {
+            break;
+        }
+        files.extend(frame_file(&tx, id)?);
+        evicted += model::delete_feature_row(&tx, id)?;
+        // Deleted pages join the freelist; the file itself only shrinks on VACUUM
+        let (file_bytes, free_bytes) = page_usage(&tx)?;
+        used_bytes = file_bytes.saturating_sub(free_bytes);
+    }
+    audit::record(&tx, "evict", &format!("{max_bytes} bytes"), Some(&format!("{evicted} feature(s)")))?;
+    tx.commit()?;
+    frames::remove_unreferenced_files(&conn, &files)?;
+    conn.execute_batch("VACUUM")?;
+    Ok(evicted)
+}
+
+// Function to measure (file size, bytes of free pages) from the page counts
+fn page_usage(conn: &Connection) -> Result<(u64, u64)> {
+    let (file_bytes, free_bytes): (i64, i64) = conn.query_row(
+        "SELECT page_count * page_size, freelist_count * page_size
+        FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok((file_bytes as u64, free_bytes as u64))
+}
+
+// Function to get the (relative path, SHA-256) of a feature's frame file, if it has one
+fn frame_file(conn: &Connection, feature_id: &str) -> Result<Option<(String, String)>> {
+    let mut stmt =
+        conn.prepare("SELECT frame_path, frame_sha256 FROM frames WHERE feature_id = ?1 AND frame_path IS NOT NULL")?;
+    let mut rows = stmt.query(params![feature_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some((row.get(0)?, row.get(1)?))),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::anomaly;
+use crate::db::Database;
+use crate::error::{Result, VyuwerError};
+use crate::model::{self, ImageFeature};
+use crate::timestamp;
+
+// Feature CRUD independent of the backend. `Database` is the SQLite implementation;
+// `MemoryStore` keeps everything in a HashMap for tests and tooling. Reads take
+// `&mut self` so implementations may cache.
+pub trait FeatureStore {
+    fn insert_image_feature(&mut self, image_feature: &ImageFeature) -> Result<()>;
+
+    fn delete_image_feature(&mut self, camera_id: &str) -> Result<()>;
+
+    // Function to replace all of a camera's features with one, atomically
+    fn reset_image_feature(&mut self, camera_id: &str, image_feature: &ImageFeature) -> Result<()>;
+
+    fn get_image_feature(&mut self, camera_id: &str) -> Result<Option<ImageFeature>>;
+
+    // Function to list a camera's features oldest first; features with the same timestamp
+    // come back in insertion order
+    fn camera_features(&mut self, camera_id: &str) -> Result<Vec<ImageFeature>>;
+
+    // Function to get the camera's baseline: the promoted feature if there is one,
+    // otherwise its earliest feature
+    fn baseline(&mut self, camera_id: &str) -> Result<Option<ImageFeature>>;
+}
+
+impl FeatureStore for Database {
+    fn insert_image_feature(&mut self, image_feature: &ImageFeature) -> Result<()> {
+        Database::insert_image_feature(self, image_feature)
+    }
+
+    fn delete_image_feature(&mut self, camera_id: &str) -> Result<()> {
+        Database::delete_image_feature(self, camera_id)
+    }
+
+    fn reset_image_feature(&mut self, camera_id: &str, image_feature: &ImageFeature) -> Result<()> {
+        Database::reset_image_feature(self, camera_id, image_feature)
+    }
+
+    fn get_image_feature(&mut self, camera_id: &str) -> Result<Option<ImageFeature>> {
+        Database::get_image_feature(self, camera_id)
+    }
+
+    fn camera_features(&mut self, camera_id: &str) -> Result<Vec<ImageFeature>> {
+        model::fetch_features(self.connection(), camera_id, None)
+    }
+
+    fn baseline(&mut self, camera_id: &str) -> Result<Option<ImageFeature>> {
+        anomaly::load_baseline(self.connection(), camera_id)
+    }
+}
+
+// In-memory store keyed by camera. Each feature is tagged with an insertion sequence,
+// mirroring the `seq` column, to order same-timestamp features. Nothing is persisted or audited.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    cameras: HashMap<String, Vec<(u64, ImageFeature)>>,
+    next_seq: u64,
+    baselines: HashMap<String, String>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    // Function to count stored features across all cameras
+    pub fn len(&self) -> usize {
+        self.cameras.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Function to make a stored feature the camera's baseline
+    pub fn set_baseline(&mut self, camera_id: &str, feature_id: &str) -> Result<()> {
+        let stored = self
+            .cameras
+            .get(camera_id)
+            .is_some_and(|features| features.iter().any(|(_, feature)| feature.id == feature_id));
+        if !stored {
+            return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+        }
+        self.baselines.insert(camera_id.to_string(), feature_id.to_string());
+        Ok(())
+    }
+}
+
+impl FeatureStore for MemoryStore {
+    fn insert_image_feature(&mut self, image_feature: &ImageFeature) -> Result<()> {
+        // Stored in the same text form as the SQLite store, so comparing it is time order
+        let stored = ImageFeature {
+            created_at_utc: timestamp::normalize_utc(&image_feature.created_at_utc)?,
+            ..image_feature.clone()
+        };
+        self.next_seq += 1;
+        self.cameras.entry(stored.camera_id.clone()).or_default().push((self.next_seq, stored));
+        Ok(())
+    }
+
+    fn delete_image_feature(&mut self, camera_id: &str) -> Result<()> {
+        self.cameras.remove(camera_id);
+        self.baselines.remove(camera_id);
+        Ok(())
+    }
+
+    fn reset_image_feature(&mut self, camera_id: &str, image_feature: &ImageFeature) -> Result<()> {
+        self.delete_image_feature(camera_id)?;
+        self.insert_image_feature(image_feature)
+    }
+
+    fn get_image_feature(&mut self, camera_id: &str) -> Result<Option<ImageFeature>> {
+        Ok(self.camera_features(camera_id)?.pop())
+    }
+
+    fn camera_features(&mut self, camera_id: &str) -> Result<Vec<ImageFeature>> {
+        let mut features = self.cameras.get(camera_id).cloned().unwrap_or_default();
+        features.sort_by(|(a_seq, a), (b_seq, b)| (&a.created_at_utc, a_seq).cmp(&(&b.created_at_utc, b_seq)));
+        Ok(features.into_iter().map(|(_, feature)| feature).collect())
+    }
+
+    fn baseline(&mut self, camera_id: &str) -> Result<Option<ImageFeature>> {
+        let Some(features) = self.cameras.get(camera_id) else {
+            return Ok(None);
+        };
+        let promoted = self.baselines.get(camera_id);
+        let baseline = promoted
+            .and_then(|id| features.iter().find(|(_, feature)| &feature.id == id))
+            .or_else(|| features.iter().min_by_key(|(seq, feature)| (&feature.created_at_utc, *seq)));
+        Ok(baseline.map(|(_, feature)| feature.clone()))
+    }
+}
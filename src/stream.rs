@@ -0,0 +1,106 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::error::{Result, VyuwerError};
+use crate::model::{feature_from_row, ImageFeature, FEATURE_SELECT};
+use crate::timestamp;
+
+// Function to send a camera's features, in time order, down a channel.
+// Rows are decoded one at a time; a disconnected receiver ends the stream early without error.
+// The sender is dropped on return, which closes the channel for the consumer.
+pub fn stream_features(camera_id: &str, db_name: &str, tx: Sender<ImageFeature>) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1 ORDER BY created_at_utc, seq, id"
+    ))?;
+    let mut rows = stmt.query(params![camera_id])?;
+    while let Some(row) = rows.next()? {
+        if tx.send(feature_from_row(row)?).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Function to replay a camera's stored features to `f` in time order, as a simulated live feed.
+// Between two features it sleeps for their real gap divided by `speed`, so 2.0 plays twice as
+// fast; a speed of 0 delivers everything without sleeping.
+pub fn replay(camera_id: &str, speed: f64, db_name: &str, mut f: impl FnMut(ImageFeature)) -> Result<()> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(VyuwerError::InvalidInput(format!("replay speed must be finite and >= 0, got {speed}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1 ORDER BY created_at_utc, seq, id"
+    ))?;
+    let mut rows = stmt.query(params![camera_id])?;
+    let mut previous_millis: Option<i64> = None;
+    while let Some(row) = rows.next()? {
+        let feature = feature_from_row(row)?;
+        if speed > 0.0 {
+            let millis = timestamp::parse_utc_millis(&feature.created_at_utc)?;
+            if let Some(previous) = previous_millis {
+                let gap = (millis - previous).max(0) as f64 / 1000.0;
+                thread::sleep(Duration::from_secs_f64(gap / speed));
+            }
+            previous_millis = Some(millis);
+        }
+        f(feature);
+    }
+    Ok(())
+}
+
+// Function to pick `k` features uniformly at random in one pass (reservoir sampling), e.g.
+// for a preview gallery. Only the rows kept in the reservoir are decoded. The same `seed`
+// gives the same sample; None seeds from the clock. The sample is returned in time order.
+pub fn sample_features(camera_id: &str, k: usize, seed: Option<u64>, db_name: &str) -> Result<Vec<ImageFeature>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(&format!(
+        "{FEATURE_SELECT} WHERE camera_id = ?1 ORDER BY created_at_utc, seq, id"
+    ))?;
+    let mut rows = stmt.query(params![camera_id])?;
+    let mut rng = SplitMix64(seed.unwrap_or_else(clock_seed));
+    let mut reservoir: Vec<(u64, ImageFeature)> = Vec::with_capacity(k);
+    let mut seen: u64 = 0;
+    while let Some(row) = rows.next()? {
+        if reservoir.len() < k {
+            reservoir.push((seen, feature_from_row(row)?));
+        } else if k > 0 {
+            let slot = rng.below(seen + 1);
+            if slot < k as u64 {
+                reservoir[slot as usize] = (seen, feature_from_row(row)?);
+            }
+        }
+        seen += 1;
+    }
+    reservoir.sort_by_key(|(index, _)| *index);
+    Ok(reservoir.into_iter().map(|(_, feature)| feature).collect())
+}
+
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+// Small, fast PRNG; sampling needs reproducibility, not cryptographic quality
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Function to draw from 0..n (the modulo bias is negligible for n far below 2^64)
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+use crate::codec;
+use crate::error::Result;
+use crate::model::{
+    self, decode_delta_descriptors, decode_descriptors, decode_keypoints, DescriptorMatrix, OnConflict,
+    DELTA_DESCRIPTORS_FEATURE_VERSION,
+};
+use crate::timestamp;
+
+// Feature ids that differ between two databases, each list sorted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    // In both, but with different content
+    pub differing: Vec<String>,
+}
+
+impl DbDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.differing.is_empty()
+    }
+}
+
+// Columns after the descriptors that make up a feature's content. `seq` is left out: it
+// only orders rows within one database.
+const CONTENT_COLUMNS: &str = "motion_mean, motion_std, created_at_utc, img_filename, camera_id, phash, frame_width,
+    frame_height, extraction_params, sharpness, roi_x, roi_y, roi_w, roi_h, content_sha256, descriptor_kind,
+    low_quality, extract_ms";
+
+// Content columns `ImageFeature` does not carry, which `push_missing` copies separately
+const UNINSERTED_COLUMNS: &str = "content_sha256, descriptor_kind, low_quality, extract_ms";
+
+// Function to compare the features of two databases, e.g. a central and an edge copy, by
+// id and by a hash of each row's content. Keypoints and descriptors are hashed after
+// decoding, so the same feature hashes alike under any descriptor layout or keypoint codec.
+// `b_path` is attached to the connection on `a_path`, so both are read in one pass each.
+pub fn diff_databases(a_path: &str, b_path: &str) -> Result<DbDiff> {
+    let conn = Connection::open(a_path)?;
+    conn.execute("ATTACH DATABASE ?1 AS other", params![b_path])?;
+    let a = row_hashes(&conn, "main")?;
+    let b = row_hashes(&conn, "other")?;
+    conn.execute("DETACH DATABASE other", [])?;
+
+    let mut diff = DbDiff::default();
+    for (id, hash) in &a {
+        match b.get(id) {
+            None => diff.only_in_a.push(id.clone()),
+            Some(other) if other != hash => diff.differing.push(id.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.only_in_b = b.keys().filter(|id| !a.contains_key(*id)).cloned().collect();
+    Ok(diff)
+}
+
+// Function to copy the features only `src_path` has into `dst_path` in one transaction,
+// a one-way sync such as edge to central. Features present in both are left alone even if
+// they differ, and tags, frames and other per-feature rows are not copied. Inserts run in
+// time order so the destination's per-camera `seq` follows capture order. Returns how
+// many features were pushed.
+pub fn push_missing(src_path: &str, dst_path: &str) -> Result<usize> {
+    let missing = diff_databases(src_path, dst_path)?.only_in_a;
+    let src = Connection::open(src_path)?;
+    let mut features = Vec::with_capacity(missing.len());
+    for feature_id in &missing {
+        if let Some(feature) = model::fetch_feature_by_id(&src, feature_id)? {
+            let extra = src.query_row(
+                &format!("SELECT {UNINSERTED_COLUMNS} FROM image_features WHERE id = ?1"),
+                params![feature_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, bool>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                    ))
+                },
+            )?;
+            features.push((timestamp::parse_utc_millis(&feature.created_at_utc)?, feature, extra));
+        }
+    }
+    features.sort_by(|a, b| (a.0, &a.1.id).cmp(&(b.0, &b.1.id)));
+
+    let mut dst = Connection::open(dst_path)?;
+    let tx = dst.transaction()?;
+    let to_insert: Vec<_> = features.iter().map(|(_, feature, _)| feature.clone()).collect();
+    let pushed = model::insert_features_batch(&tx, &to_insert, OnConflict::Abort)?.inserted;
+    for (_, feature, (content_sha256, descriptor_kind, low_quality, extract_ms)) in &features {
+        tx.execute(
+            "UPDATE image_features SET content_sha256 = ?1, descriptor_kind = ?2, low_quality = ?3, extract_ms = ?4
+            WHERE id = ?5",
+            params![content_sha256, descriptor_kind, low_quality, extract_ms, feature.id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(pushed)
+}
+
+// Function to hash every feature row of the attached database `schema`, by id
+fn row_hashes(conn: &Connection, schema: &str) -> Result<BTreeMap<String, [u8; 32]>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT f.id, f.keypoints, f.descriptors, f.feature_version, d.data, d.rows, d.cols,
+            (SELECT base.descriptors FROM {schema}.image_features base WHERE base.id = f.base_feature_id),
+            {CONTENT_COLUMNS}
+        FROM {schema}.image_features f LEFT JOIN {schema}.descriptors d ON d.feature_id = f.id"
+    ))?;
+    let columns = stmt.column_count();
+    let mut rows = stmt.query([])?;
+    let mut hashes = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let descriptors = match row.get::<_, Option<Vec<u8>>>(2)? {
+            Some(blob) if row.get::<_, i64>(3)? == DELTA_DESCRIPTORS_FEATURE_VERSION => {
+                decode_delta_descriptors(&blob, row.get(7)?, &row.get::<_, String>(0)?)?
+            }
+            Some(blob) => decode_descriptors(&blob, row.get(3)?)?,
+            None => DescriptorMatrix::new(row.get::<_, i64>(5)? as usize, row.get::<_, i64>(6)? as usize, row.get(4)?)?,
+        };
+        let mut hasher = Sha256::new();
+        // Re-encoded as bincode, so features hash alike under either keypoint codec
+        let keypoints = codec::encode(&decode_keypoints(&row.get::<_, Vec<u8>>(1)?, row.get(3)?)?)?;
+        hash_value(&mut hasher, ValueRef::Blob(&keypoints));
+        hasher.update((descriptors.rows as u64).to_le_bytes());
+        hasher.update((descriptors.cols as u64).to_le_bytes());
+        hasher.update(&descriptors.data);
+        for i in 8..columns {
+            hash_value(&mut hasher, row.get_ref(i)?);
+        }
+        hashes.insert(row.get(0)?, hasher.finalize().into());
+    }
+    Ok(hashes)
+}
+
+// Function to feed one SQL value to the hasher, tagged with its type and length so that
+// adjacent values cannot run into each other
+fn hash_value(hasher: &mut Sha256, value: ValueRef) {
+    match value {
+        ValueRef::Null => hasher.update([0]),
+        ValueRef::Integer(i) => {
+            hasher.update([1]);
+            hasher.update(i.to_le_bytes());
+        }
+        ValueRef::Real(f) => {
+            hasher.update([2]);
+            hasher.update(f.to_le_bytes());
+        }
+        ValueRef::Text(bytes) => {
+            hasher.update([3]);
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+        ValueRef::Blob(bytes) => {
+            hasher.update([4]);
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+    }
+}
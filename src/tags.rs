@@ -0,0 +1,112 @@
+use rusqlite::{params, Connection};
+
+use crate::error::{Result, VyuwerError};
+use crate::model;
+use crate::timestamp;
+
+// Function to create the table of free-form key/value tags on features (e.g. weather=rain).
+// A feature has at most one value per key. Deleting a feature deletes its tags.
+pub(crate) fn create_tag_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feature_tags (
+            feature_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (feature_id, key)
+        );
+        CREATE INDEX IF NOT EXISTS feature_tags_key_value ON feature_tags (key, value);
+        CREATE TRIGGER IF NOT EXISTS feature_tags_cascade_delete AFTER DELETE ON image_features
+        BEGIN
+            DELETE FROM feature_tags WHERE feature_id = OLD.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+// Function to tag a stored feature, replacing its previous value for `key`
+pub fn add_tag(feature_id: &str, key: &str, value: &str, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    if !model::feature_exists(&conn, feature_id)? {
+        return Err(VyuwerError::FeatureNotFound(feature_id.to_string()));
+    }
+    conn.execute(
+        "INSERT INTO feature_tags (feature_id, key, value) VALUES (?1, ?2, ?3)
+        ON CONFLICT(feature_id, key) DO UPDATE SET value = excluded.value",
+        params![feature_id, key, value],
+    )?;
+    Ok(())
+}
+
+// Function to tag every feature a camera created between `start_utc` and `end_utc` (both
+// inclusive) in one transaction, e.g. to label a night shift, replacing earlier values
+// for `key`. Returns how many features were tagged.
+pub fn tag_features_in_range(
+    camera_id: &str,
+    start_utc: &str,
+    end_utc: &str,
+    key: &str,
+    value: &str,
+    db_name: &str,
+) -> Result<usize> {
+    let start = timestamp::parse_utc_millis(start_utc)?;
+    let end = timestamp::parse_utc_millis(end_utc)?;
+    if start > end {
+        return Err(VyuwerError::InvalidInput(format!("range start {start_utc} is after its end {end_utc}")));
+    }
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let mut stmt = tx.prepare("SELECT id, created_at_utc FROM image_features WHERE camera_id = ?1")?;
+    let rows = stmt
+        .query_map(params![camera_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+    let mut tagged = 0;
+    for (feature_id, created_at_utc) in rows {
+        // Stored strings may differ in format, so compare parsed times
+        if (start..=end).contains(&timestamp::parse_utc_millis(&created_at_utc)?) {
+            tx.execute(
+                "INSERT INTO feature_tags (feature_id, key, value) VALUES (?1, ?2, ?3)
+                ON CONFLICT(feature_id, key) DO UPDATE SET value = excluded.value",
+                params![feature_id, key, value],
+            )?;
+            tagged += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(tagged)
+}
+
+// Function to remove a feature's tag; returns false if it had no such tag
+pub fn remove_tag(feature_id: &str, key: &str, db_name: &str) -> Result<bool> {
+    let conn = Connection::open(db_name)?;
+    let removed = conn.execute(
+        "DELETE FROM feature_tags WHERE feature_id = ?1 AND key = ?2",
+        params![feature_id, key],
+    )?;
+    Ok(removed > 0)
+}
+
+// Function to get a feature's tags as (key, value) pairs sorted by key
+pub fn get_tags(feature_id: &str, db_name: &str) -> Result<Vec<(String, String)>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare("SELECT key, value FROM feature_tags WHERE feature_id = ?1 ORDER BY key")?;
+    let tags = stmt
+        .query_map(params![feature_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(tags)
+}
+
+// Function to list the ids of features tagged `key=value`, in time order
+pub fn features_with_tag(key: &str, value: &str, db_name: &str) -> Result<Vec<String>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT image_features.id FROM feature_tags
+        JOIN image_features ON image_features.id = feature_tags.feature_id
+        WHERE key = ?1 AND value = ?2
+        ORDER BY created_at_utc, seq, image_features.id",
+    )?;
+    let ids = stmt
+        .query_map(params![key, value], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(ids)
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, SecondsFormat};
+use chrono_tz::Tz;
+
+use crate::error::{Result, VyuwerError};
+
+// Function to get the current time as an ISO-8601 UTC string with milliseconds,
+// e.g. 2024-06-12T12:34:56.789Z, so frames within one second still sort in order
+pub fn now_utc_iso8601() -> String {
+    format_unix_millis(now_unix_millis())
+}
+
+// Function to get the ISO-8601 UTC timestamp `age` before now, e.g. a retention cutoff,
+// with milliseconds, so a zero age cuts off everything written up to this instant
+pub fn utc_iso8601_ago(age: Duration) -> String {
+    format_unix_millis(now_unix_millis().saturating_sub(age.as_millis().min(i64::MAX as u128) as i64))
+}
+
+fn now_unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+// Function to parse a human duration such as "30d", "12h" or "1d12h" (units s, m, h, d, w)
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || VyuwerError::InvalidInput(format!("invalid duration {text:?}, expected e.g. 30d or 12h"));
+    let mut total: u64 = 0;
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let amount: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let mut chars = rest[digits..].chars();
+        let unit = match chars.next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86_400,
+            Some('w') => 604_800,
+            _ => return Err(invalid()),
+        };
+        total = amount
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        rest = chars.as_str();
+    }
+    Ok(Duration::from_secs(total))
+}
+
+// Function to format seconds since the Unix epoch as ISO-8601 UTC
+pub fn format_unix_secs(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// Function to format milliseconds since the Unix epoch as ISO-8601 UTC with a .fff fraction
+pub fn format_unix_millis(millis: i64) -> String {
+    let secs = format_unix_secs(millis.div_euclid(1000));
+    format!("{}.{:03}Z", &secs[..secs.len() - 1], millis.rem_euclid(1000))
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Function to parse an ISO-8601 UTC timestamp (YYYY-MM-DDTHH:MM:SS[.fff]Z) into Unix milliseconds
+pub fn parse_utc_millis(ts: &str) -> Result<i64> {
+    let invalid = || VyuwerError::InvalidTimestamp(ts.to_string());
+    let body = ts.strip_suffix('Z').unwrap_or(ts);
+    let (date, time) = body.split_once(['T', ' ']).ok_or_else(invalid)?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let next_num = |parts: &mut std::str::SplitN<'_, char>| -> Result<i64> {
+        parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)
+    };
+    let year = next_num(&mut date_parts)?;
+    let month = next_num(&mut date_parts)?;
+    let day = next_num(&mut date_parts)?;
+
+    let (hms, fraction) = match time.split_once('.') {
+        Some((hms, fraction)) => (hms, fraction),
+        None => (time, ""),
+    };
+    let mut time_parts = hms.splitn(3, ':');
+    let hour = next_num(&mut time_parts)?;
+    let minute = next_num(&mut time_parts)?;
+    let second = next_num(&mut time_parts)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+    let millis = if fraction.is_empty() {
+        0
+    } else {
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        format!("{fraction:0<3}")[..3].parse::<i64>().map_err(|_| invalid())?
+    };
+
+    let days = days_from_civil(year, month as u32, day as u32);
+    Ok(((days * 86_400 + hour * 3600 + minute * 60 + second) * 1000) + millis)
+}
+
+// Function to rewrite any timestamp `parse_utc_millis` accepts in the stored form,
+// YYYY-MM-DDTHH:MM:SS.fffZ, whose text order is its time order
+pub fn normalize_utc(ts: &str) -> Result<String> {
+    Ok(format_unix_millis(parse_utc_millis(ts)?))
+}
+
+// Function to render a stored UTC timestamp in the IANA zone `tz` (e.g. America/New_York)
+// for display, as RFC 3339 with the zone's offset at that instant, e.g.
+// 2024-06-12T08:34:56-04:00. Milliseconds are kept when non-zero. Storage stays UTC.
+pub fn to_local_display(utc: &str, tz: &str) -> Result<String> {
+    let zone: Tz = tz.parse().map_err(|_| VyuwerError::InvalidInput(format!("unknown time zone {tz:?}")))?;
+    let instant = DateTime::from_timestamp_millis(parse_utc_millis(utc)?)
+        .ok_or_else(|| VyuwerError::InvalidTimestamp(utc.to_string()))?;
+    Ok(instant.with_timezone(&zone).to_rfc3339_opts(SecondsFormat::AutoSi, false))
+}
+
+// (year, month, day) to days since 1970-01-01, the inverse of civil_from_days
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -0,0 +1,415 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{Mat, Size},
+    imgcodecs, imgproc,
+    prelude::*,
+    videoio,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use crate::error::{Result, VyuwerError};
+use crate::extract::{extract_orb_features, motion_stats, passes_motion_gate, sharpness, to_gray, OrbParams};
+use crate::frames::{self, sha256_hex};
+use crate::model::{self, FrameSize, ImageFeature, OnConflict};
+use crate::phash::phash;
+use crate::progress::ProgressUpdate;
+use crate::timestamp::{format_unix_millis, now_utc_iso8601, parse_utc_millis};
+
+// Number of features the ingest functions buffer before writing them in one transaction
+pub const INGEST_CHUNK_SIZE: usize = 64;
+
+// Outcome of a batch ingest. Items that could not be read or extracted are listed in
+// `errors` as (item, reason) instead of aborting the job; database errors still abort.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub errors: Vec<(String, String)>,
+    pub duration: Duration,
+}
+
+impl IngestReport {
+    fn record_failure(&mut self, item: String, err: VyuwerError) {
+        self.failed += 1;
+        self.errors.push((item, err.to_string()));
+    }
+}
+
+// Function to extract and store features from a lazy source of (frame, ISO-8601 timestamp)
+// pairs, e.g. a network stream. Frames are consumed one at a time and written in
+// transactions of INGEST_CHUNK_SIZE, so nothing is collected up front. Unlike
+// `process_video` every frame is kept. Failed frames are reported by their timestamp.
+pub fn ingest_stream(
+    items: impl Iterator<Item = (Mat, String)>,
+    camera_id: &str,
+    params: &OrbParams,
+    db_name: &str,
+) -> Result<IngestReport> {
+    ingest_stream_with_progress(items, camera_id, params, db_name, |_| {})
+}
+
+// Function to ingest like `ingest_stream`, reporting after each frame. The total is only
+// known when the iterator reports an exact size.
+pub fn ingest_stream_with_progress(
+    items: impl Iterator<Item = (Mat, String)>,
+    camera_id: &str,
+    params: &OrbParams,
+    db_name: &str,
+    mut progress: impl FnMut(ProgressUpdate),
+) -> Result<IngestReport> {
+    let total = match items.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(lower),
+        _ => None,
+    };
+    let mut ingest = Ingest::new(camera_id, params, db_name)?;
+    for (done, (frame, created_at_utc)) in items.enumerate() {
+        ingest.push(&frame, created_at_utc.clone(), None, created_at_utc)?;
+        progress(ProgressUpdate {
+            done: done + 1,
+            total,
+            stage: "extracting",
+        });
+    }
+    ingest.finish()
+}
+
+// Function to extract and store features from image files, e.g. a folder export, timestamped
+// at ingest. Unreadable or undecodable files are reported by path and skipped.
+pub fn ingest_files<P: AsRef<Path>>(
+    paths: impl IntoIterator<Item = P>,
+    camera_id: &str,
+    params: &OrbParams,
+    db_name: &str,
+) -> Result<IngestReport> {
+    ingest_files_with_progress(paths, camera_id, params, db_name, |_| {})
+}
+
+// Function to ingest like `ingest_files`, reporting after each file
+pub fn ingest_files_with_progress<P: AsRef<Path>>(
+    paths: impl IntoIterator<Item = P>,
+    camera_id: &str,
+    params: &OrbParams,
+    db_name: &str,
+    mut progress: impl FnMut(ProgressUpdate),
+) -> Result<IngestReport> {
+    let paths: Vec<P> = paths.into_iter().collect();
+    let mut ingest = Ingest::new(camera_id, params, db_name)?;
+    for (done, path) in paths.iter().enumerate() {
+        let path = path.as_ref().to_string_lossy().into_owned();
+        match read_image(&path) {
+            Ok(frame) => ingest.push(&frame, now_utc_iso8601(), Some(path.clone()), path)?,
+            Err(err) => ingest.report.record_failure(path, err),
+        }
+        progress(ProgressUpdate {
+            done: done + 1,
+            total: Some(paths.len()),
+            stage: "extracting",
+        });
+    }
+    ingest.finish()
+}
+
+// Function to ingest one frame unless the camera already has a feature extracted from the
+// same pixels, so a re-run job does not store duplicates. The frame is hashed (SHA-256 over
+// its size, type and pixel data) into the feature's `content_sha256`. Returns whether a
+// feature was inserted. Two processes racing on the same frame cannot both insert it: the
+// loser fails on the unique (camera_id, content_sha256) index.
+pub fn ingest_idempotent(image: &Mat, camera_id: &str, db_name: &str) -> Result<bool> {
+    let content_sha256 = content_sha256(image)?;
+    let params = OrbParams::default();
+    let mut ingest = Ingest::new(camera_id, &params, db_name)?;
+    let exists = ingest
+        .conn
+        .query_row(
+            "SELECT 1 FROM image_features WHERE camera_id = ?1 AND content_sha256 = ?2",
+            params![camera_id, content_sha256],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if exists {
+        return Ok(false);
+    }
+    let image_feature = ingest.extract(image, now_utc_iso8601(), None)?;
+    let tx = ingest.conn.transaction()?;
+    model::insert_feature(&tx, &image_feature)?;
+    tx.execute(
+        "UPDATE image_features SET content_sha256 = ?1 WHERE id = ?2",
+        params![content_sha256, image_feature.id],
+    )?;
+    tx.commit()?;
+    Ok(true)
+}
+
+fn content_sha256(image: &Mat) -> Result<String> {
+    // data_bytes needs one contiguous buffer, which ROI views do not have
+    let copy;
+    let image = if image.is_continuous() {
+        image
+    } else {
+        copy = image.try_clone()?;
+        &copy
+    };
+    let mut bytes = Vec::new();
+    for value in [image.rows(), image.cols(), image.typ()] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(image.data_bytes()?);
+    Ok(sha256_hex(&bytes))
+}
+
+fn read_image(path: &str) -> Result<Mat> {
+    let frame = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
+    if frame.empty() {
+        return Err(VyuwerError::Video(format!("could not read image {path}")));
+    }
+    Ok(frame)
+}
+
+// Shared state of a chunked ingest run
+struct Ingest<'a> {
+    conn: Connection,
+    camera_id: &'a str,
+    params: &'a OrbParams,
+    prev_gray: Option<Mat>,
+    chunk: Vec<ImageFeature>,
+    report: IngestReport,
+    started: Instant,
+}
+
+impl<'a> Ingest<'a> {
+    fn new(camera_id: &'a str, params: &'a OrbParams, db_name: &str) -> Result<Self> {
+        Ok(Ingest {
+            conn: Connection::open(db_name)?,
+            camera_id,
+            params,
+            prev_gray: None,
+            chunk: Vec::with_capacity(INGEST_CHUNK_SIZE),
+            report: IngestReport::default(),
+            started: Instant::now(),
+        })
+    }
+
+    // Function to extract one frame into the pending chunk, recording a failure under `item`
+    fn push(&mut self, frame: &Mat, created_at_utc: String, img_filename: Option<String>, item: String) -> Result<()> {
+        match self.extract(frame, created_at_utc, img_filename) {
+            Ok(image_feature) => self.chunk.push(image_feature),
+            Err(err) => self.report.record_failure(item, err),
+        }
+        if self.chunk.len() == INGEST_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn extract(&mut self, frame: &Mat, created_at_utc: String, img_filename: Option<String>) -> Result<ImageFeature> {
+        let gray = to_gray(frame)?;
+        // Motion is only meaningful against a previous frame of the same size
+        let (motion_mean, motion_std) = match self.prev_gray.as_ref() {
+            Some(prev) if prev.size()? == gray.size()? => motion_stats(prev, &gray)?,
+            _ => (0.0, 0.0),
+        };
+        let (keypoints, descriptors) = extract_orb_features(frame, self.params)?;
+        let image_feature = ImageFeature {
+            id: Uuid::new_v4().to_string(),
+            keypoints,
+            descriptors,
+            motion_mean,
+            motion_std,
+            created_at_utc,
+            img_filename,
+            camera_id: self.camera_id.to_string(),
+            phash: Some(phash(&gray)?),
+            frame_size: Some(FrameSize::new(frame.cols() as u32, frame.rows() as u32)),
+            extraction_params: Some(self.params.clone()),
+            sharpness: Some(sharpness(&gray)?),
+            roi: None,
+        };
+        self.prev_gray = Some(gray);
+        Ok(image_feature)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        let inserted = model::insert_features_batch(&tx, &self.chunk, OnConflict::Abort)?.inserted;
+        tx.commit()?;
+        self.chunk.clear();
+        self.report.succeeded += inserted;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<IngestReport> {
+        self.flush()?;
+        self.report.duration = self.started.elapsed();
+        Ok(self.report)
+    }
+}
+
+// Function to ingest every `every_n`th frame of a video file, skipping static frames.
+// The recording is taken to start now; see `process_video_starting_at` for footage with a
+// known start time. Returns how many features were stored.
+pub fn process_video(path: &str, camera_id: &str, every_n: u32, db_name: &str) -> Result<usize> {
+    process_video_starting_at(path, camera_id, every_n, &now_utc_iso8601(), db_name)
+}
+
+// Function to ingest a video like `process_video`, stamping each frame with `start_utc`
+// plus its position in the video, so features keep the footage's own timeline
+pub fn process_video_starting_at(
+    path: &str,
+    camera_id: &str,
+    every_n: u32,
+    start_utc: &str,
+    db_name: &str,
+) -> Result<usize> {
+    if every_n == 0 {
+        return Err(VyuwerError::InvalidInput("every_n must be at least 1".to_string()));
+    }
+    let start_millis = parse_utc_millis(start_utc)?;
+    let mut capture = videoio::VideoCapture::from_file(path, videoio::CAP_ANY)?;
+    if !capture.is_opened()? {
+        return Err(VyuwerError::Video(format!("could not open video file {path}")));
+    }
+
+    let conn = Connection::open(db_name)?;
+    let params = OrbParams::default();
+    let mut frame = Mat::default();
+    let mut prev_gray: Option<Mat> = None;
+    let mut frame_index: u64 = 0;
+    let mut decoded_any = false;
+    let mut stored = 0;
+
+    while capture.read(&mut frame)? {
+        if frame.empty() {
+            break;
+        }
+        decoded_any = true;
+        let index = frame_index;
+        frame_index += 1;
+        if index % u64::from(every_n) != 0 {
+            continue;
+        }
+
+        let gray = to_gray(&frame)?;
+        // The first sampled frame has nothing to compare against and is always kept
+        let (motion_mean, motion_std) = match prev_gray.as_ref() {
+            Some(prev) => {
+                let stats = motion_stats(prev, &gray)?;
+                if !passes_motion_gate(stats.0) {
+                    continue;
+                }
+                stats
+            }
+            None => (0.0, 0.0),
+        };
+
+        let (keypoints, descriptors) = extract_orb_features(&frame, &params)?;
+        let offset_millis = frame_position_millis(&capture, index)?;
+        let image_feature = ImageFeature {
+            id: Uuid::new_v4().to_string(),
+            keypoints,
+            descriptors,
+            motion_mean,
+            motion_std,
+            created_at_utc: format_unix_millis(start_millis + offset_millis),
+            img_filename: Some(format!("{path}#{index}")),
+            camera_id: camera_id.to_string(),
+            phash: Some(phash(&gray)?),
+            frame_size: Some(FrameSize::new(frame.cols() as u32, frame.rows() as u32)),
+            extraction_params: Some(params.clone()),
+            sharpness: Some(sharpness(&gray)?),
+            roi: None,
+        };
+        model::insert_feature(&conn, &image_feature)?;
+        stored += 1;
+        prev_gray = Some(gray);
+    }
+
+    if !decoded_any {
+        return Err(VyuwerError::Video(format!(
+            "no frames could be decoded from {path}; the file may be corrupt or use an unsupported codec"
+        )));
+    }
+    Ok(stored)
+}
+
+// Function to write a camera's stored frames, in time order, to a Motion-JPEG video at `fps`
+// for a quick scrub through its history (`out_path` should end in .avi). Features without a
+// stored frame are skipped; frames of another size are resized to the first one's. Returns
+// how many frames were written, and creates no file when the camera has no frames.
+pub fn export_timelapse(camera_id: &str, fps: f64, out_path: &str, db_name: &str) -> Result<usize> {
+    if !(fps.is_finite() && fps > 0.0) {
+        return Err(VyuwerError::InvalidInput(format!("fps must be positive, got {fps}")));
+    }
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare("SELECT id FROM image_features WHERE camera_id = ?1 ORDER BY created_at_utc, seq, id")?;
+    let feature_ids = stmt
+        .query_map(params![camera_id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut writer: Option<(videoio::VideoWriter, Size)> = None;
+    let mut written = 0;
+    for feature_id in feature_ids {
+        let Some(encoded) = frames::fetch_frame_bytes(&conn, &feature_id)? else {
+            continue;
+        };
+        let frame = to_bgr(frames::decode_frame(&encoded)?)?;
+        let (writer, size) = match writer.as_mut() {
+            Some(open) => open,
+            None => writer.insert(open_writer(out_path, fps, frame.size()?)?),
+        };
+        if frame.size()? == *size {
+            writer.write(&frame)?;
+        } else {
+            let mut resized = Mat::default();
+            imgproc::resize(&frame, &mut resized, *size, 0.0, 0.0, imgproc::INTER_AREA)?;
+            writer.write(&resized)?;
+        }
+        written += 1;
+    }
+    if let Some((mut writer, _)) = writer {
+        writer.release()?;
+    }
+    Ok(written)
+}
+
+fn open_writer(out_path: &str, fps: f64, size: Size) -> Result<(videoio::VideoWriter, Size)> {
+    let fourcc = videoio::VideoWriter::fourcc('M', 'J', 'P', 'G')?;
+    let writer = videoio::VideoWriter::new(out_path, fourcc, fps, size, true)?;
+    if !writer.is_opened()? {
+        return Err(VyuwerError::Video(format!("could not open {out_path} for writing Motion-JPEG")));
+    }
+    Ok((writer, size))
+}
+
+// Function to bring a decoded frame to the 3-channel BGR the colour writer expects
+fn to_bgr(frame: Mat) -> Result<Mat> {
+    let code = match frame.channels() {
+        1 => imgproc::COLOR_GRAY2BGR,
+        4 => imgproc::COLOR_BGRA2BGR,
+        _ => return Ok(frame),
+    };
+    let mut bgr = Mat::default();
+    imgproc::cvt_color_def(&frame, &mut bgr, code)?;
+    Ok(bgr)
+}
+
+// Function to get the position of the frame just read, in milliseconds from the start.
+// Some backends report no position; the frame index over the frame rate stands in then.
+fn frame_position_millis(capture: &videoio::VideoCapture, index: u64) -> Result<i64> {
+    let position = capture.get(videoio::CAP_PROP_POS_MSEC)?;
+    if position > 0.0 || index == 0 {
+        return Ok(position.round() as i64);
+    }
+    let fps = capture.get(videoio::CAP_PROP_FPS)?;
+    if fps > 0.0 {
+        Ok((index as f64 * 1000.0 / fps).round() as i64)
+    } else {
+        Ok(0)
+    }
+}
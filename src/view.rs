@@ -0,0 +1,46 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::model::ImageFeature;
+
+// Serializes a feature like `ImageFeature` itself, but leaves out the (large) descriptor
+// matrix entirely unless `include_descriptors` is set; intended for lightweight API responses
+#[derive(Debug, Clone, Copy)]
+pub struct FeatureView<'a> {
+    pub feature: &'a ImageFeature,
+    pub include_descriptors: bool,
+}
+
+impl<'a> FeatureView<'a> {
+    pub fn new(feature: &'a ImageFeature, include_descriptors: bool) -> Self {
+        FeatureView {
+            feature,
+            include_descriptors,
+        }
+    }
+}
+
+impl Serialize for FeatureView<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let feature = self.feature;
+        let len = if self.include_descriptors { 13 } else { 12 };
+        let mut state = serializer.serialize_struct("ImageFeature", len)?;
+        state.serialize_field("id", &feature.id)?;
+        state.serialize_field("keypoints", &feature.keypoints)?;
+        if self.include_descriptors {
+            state.serialize_field("descriptors", &feature.descriptors)?;
+        } else {
+            state.skip_field("descriptors")?;
+        }
+        state.serialize_field("motion_mean", &feature.motion_mean)?;
+        state.serialize_field("motion_std", &feature.motion_std)?;
+        state.serialize_field("created_at_utc", &feature.created_at_utc)?;
+        state.serialize_field("img_filename", &feature.img_filename)?;
+        state.serialize_field("camera_id", &feature.camera_id)?;
+        state.serialize_field("phash", &feature.phash)?;
+        state.serialize_field("frame_size", &feature.frame_size)?;
+        state.serialize_field("extraction_params", &feature.extraction_params)?;
+        state.serialize_field("sharpness", &feature.sharpness)?;
+        state.serialize_field("roi", &feature.roi)?;
+        state.end()
+    }
+}
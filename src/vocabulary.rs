@@ -0,0 +1,183 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::codec;
+use crate::error::{Result, VyuwerError};
+use crate::matching::{bitwise_majority, hamming_distance};
+use crate::model::{fetch_features, DescriptorMatrix};
+use crate::timestamp::now_utc_iso8601;
+
+// Upper bound on k-majority refinement passes; most vocabularies settle well before this
+const MAX_ITERATIONS: usize = 20;
+
+// Bag-of-words vocabulary: row i of `words` is the center of visual word i
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vocabulary {
+    pub words: DescriptorMatrix,
+}
+
+impl Vocabulary {
+    pub fn len(&self) -> usize {
+        self.words.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // Function to find the visual word nearest to one descriptor row
+    pub fn nearest_word(&self, descriptor: &[u8]) -> usize {
+        self.words
+            .row_iter()
+            .enumerate()
+            .min_by_key(|(_, word)| hamming_distance(descriptor, word))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+// Function to create the table of named, serialized vocabularies
+pub(crate) fn create_vocabulary_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vocabularies (
+            name TEXT PRIMARY KEY,
+            data BLOB NOT NULL,
+            created_at_utc TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Function to cluster every descriptor of `camera_ids` into `k` visual words.
+// ORB descriptors are binary, so this runs k-majority (k-means under Hamming distance,
+// with bitwise-majority centers) from an evenly spaced deterministic initialisation.
+pub fn build_vocabulary(camera_ids: &[&str], k: usize, db_name: &str) -> Result<Vocabulary> {
+    let conn = Connection::open(db_name)?;
+    let mut features = Vec::new();
+    for camera_id in camera_ids {
+        features.extend(fetch_features(&conn, camera_id, None)?);
+    }
+    let descriptors: Vec<&[u8]> = features.iter().flat_map(|f| f.descriptors.row_iter()).collect();
+    let cols = features.iter().find(|f| !f.descriptors.is_empty()).map_or(0, |f| f.descriptors.cols);
+    if features.iter().any(|f| !f.descriptors.is_empty() && f.descriptors.cols != cols) {
+        return Err(VyuwerError::InvalidInput("descriptors of different widths cannot share a vocabulary".to_string()));
+    }
+    if k == 0 || descriptors.len() < k {
+        return Err(VyuwerError::InvalidInput(format!(
+            "cannot build {k} visual words from {} descriptors",
+            descriptors.len()
+        )));
+    }
+
+    let mut centers: Vec<Vec<u8>> = (0..k).map(|i| descriptors[i * descriptors.len() / k].to_vec()).collect();
+    let mut assignment = vec![usize::MAX; descriptors.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (slot, descriptor) in assignment.iter_mut().zip(&descriptors) {
+            let nearest = (0..k).min_by_key(|&c| hamming_distance(descriptor, &centers[c])).unwrap_or(0);
+            changed |= *slot != nearest;
+            *slot = nearest;
+        }
+        if !changed {
+            break;
+        }
+        for (c, center) in centers.iter_mut().enumerate() {
+            let members: Vec<&[u8]> =
+                descriptors.iter().zip(&assignment).filter(|(_, &a)| a == c).map(|(d, _)| *d).collect();
+            // An empty cluster keeps its previous center
+            if !members.is_empty() {
+                *center = bitwise_majority(&members, cols);
+            }
+        }
+    }
+
+    Ok(Vocabulary {
+        words: DescriptorMatrix::new(k, cols, centers.concat())?,
+    })
+}
+
+// Function to turn a descriptor matrix into a histogram of visual-word counts (one bin per word)
+pub fn quantize(descriptors: &DescriptorMatrix, vocabulary: &Vocabulary) -> Vec<u32> {
+    let mut histogram = vec![0; vocabulary.len()];
+    if vocabulary.is_empty() || descriptors.cols != vocabulary.words.cols {
+        return histogram;
+    }
+    for descriptor in descriptors.row_iter() {
+        histogram[vocabulary.nearest_word(descriptor)] += 1;
+    }
+    histogram
+}
+
+// Function to store a vocabulary under `name`, replacing any previous one
+pub fn save_vocabulary(name: &str, vocabulary: &Vocabulary, db_name: &str) -> Result<()> {
+    let conn = Connection::open(db_name)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO vocabularies (name, data, created_at_utc) VALUES (?1, ?2, ?3)",
+        params![name, codec::encode(vocabulary)?, now_utc_iso8601()],
+    )?;
+    Ok(())
+}
+
+pub fn load_vocabulary(name: &str, db_name: &str) -> Result<Option<Vocabulary>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare("SELECT data FROM vocabularies WHERE name = ?1")?;
+    let mut rows = stmt.query(params![name])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(codec::decode(&row.get::<_, Vec<u8>>(0)?)?)),
+        None => Ok(None),
+    }
+}
+
+// Function to compute and store the histogram of each of a camera's features under
+// `vocabulary`, for `search_by_bow`. Earlier histograms are overwritten, so re-run this
+// after rebuilding the vocabulary. Returns how many features were indexed.
+pub fn index_bow_histograms(camera_id: &str, vocabulary: &Vocabulary, db_name: &str) -> Result<usize> {
+    let mut conn = Connection::open(db_name)?;
+    let tx = conn.transaction()?;
+    let features = fetch_features(&tx, camera_id, None)?;
+    for feature in &features {
+        tx.execute(
+            "UPDATE image_features SET bow_histogram = ?1 WHERE id = ?2",
+            params![codec::encode(&quantize(&feature.descriptors, vocabulary))?, feature.id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(features.len())
+}
+
+// Function to rank a camera's indexed features by the cosine similarity of their stored
+// histogram to `query_hist`, best first, returning up to `top_k` (feature id, similarity)
+// pairs. Only the id and histogram columns are read, so no descriptors are decoded.
+// Features without a histogram, or with one from a vocabulary of another size, are skipped.
+pub fn search_by_bow(query_hist: &[u32], camera_id: &str, top_k: usize, db_name: &str) -> Result<Vec<(String, f64)>> {
+    let conn = Connection::open(db_name)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, bow_histogram FROM image_features
+        WHERE camera_id = ?1 AND bow_histogram IS NOT NULL
+        ORDER BY created_at_utc, seq, id",
+    )?;
+    let mut rows = stmt.query(params![camera_id])?;
+    let mut ranked = Vec::new();
+    while let Some(row) = rows.next()? {
+        let histogram: Vec<u32> = codec::decode(&row.get::<_, Vec<u8>>(1)?)?;
+        if histogram.len() == query_hist.len() {
+            ranked.push((row.get::<_, String>(0)?, cosine_similarity(query_hist, &histogram)));
+        }
+    }
+    // Stable, so equal scores stay in time order
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(top_k);
+    Ok(ranked)
+}
+
+// 0.0 when either histogram is empty
+fn cosine_similarity(a: &[u32], b: &[u32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(&x, &y)| f64::from(x) * f64::from(y)).sum();
+    let norm = |h: &[u32]| h.iter().map(|&x| f64::from(x).powi(2)).sum::<f64>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::{HashMap, VecDeque};
+
+use rusqlite::Connection;
+
+use crate::error::{Result, VyuwerError};
+use crate::model::{fetch_latest_features, insert_features_batch, BatchInsertReport, ImageFeature, OnConflict};
+
+// In-memory working set for online processing: the last `capacity` features of each
+// camera, so consecutive frames can be compared without a database round-trip
+#[derive(Debug, Clone)]
+pub struct CameraWindow {
+    capacity: usize,
+    windows: HashMap<String, VecDeque<ImageFeature>>,
+}
+
+impl CameraWindow {
+    pub fn new(capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err(VyuwerError::InvalidInput(
+                "camera window capacity must be at least 1".to_string(),
+            ));
+        }
+        Ok(CameraWindow {
+            capacity,
+            windows: HashMap::new(),
+        })
+    }
+
+    // Function to build a window for one camera from its latest `capacity` stored features,
+    // e.g. to restart the pipeline where `persist` left it
+    pub fn load(camera_id: &str, capacity: usize, db_name: &str) -> Result<Self> {
+        let mut window = CameraWindow::new(capacity)?;
+        window.hydrate(camera_id, db_name)?;
+        Ok(window)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Function to append a feature to its camera's window, evicting the oldest once full
+    pub fn push(&mut self, image_feature: ImageFeature) {
+        let window = self
+            .windows
+            .entry(image_feature.camera_id.clone())
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+        if window.len() == self.capacity {
+            window.pop_front();
+        }
+        window.push_back(image_feature);
+    }
+
+    // Function to get up to `n` of a camera's most recent features, newest first
+    pub fn recent(&self, camera_id: &str, n: usize) -> Vec<&ImageFeature> {
+        self.windows
+            .get(camera_id)
+            .map(|window| window.iter().rev().take(n).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self, camera_id: &str) -> usize {
+        self.windows.get(camera_id).map_or(0, VecDeque::len)
+    }
+
+    pub fn is_empty(&self, camera_id: &str) -> bool {
+        self.len(camera_id) == 0
+    }
+
+    // Function to refill a camera's window from its latest stored features, e.g. on startup.
+    // Anything already in memory for the camera is replaced. Returns how many were loaded.
+    pub fn hydrate(&mut self, camera_id: &str, db_name: &str) -> Result<usize> {
+        let conn = Connection::open(db_name)?;
+        let features = fetch_latest_features(&conn, camera_id, self.capacity)?;
+        let loaded = features.len();
+        self.windows.insert(camera_id.to_string(), features.into());
+        Ok(loaded)
+    }
+
+    // Function to store every windowed feature that is not in the database yet, in one
+    // transaction and oldest first per camera, so a later `load` sees the same windows.
+    // Features already stored (by id) are left as they are and counted as skipped.
+    pub fn persist(&self, db_name: &str) -> Result<BatchInsertReport> {
+        let mut cameras: Vec<&String> = self.windows.keys().collect();
+        cameras.sort();
+        let mut conn = Connection::open(db_name)?;
+        let tx = conn.transaction()?;
+        let mut report = BatchInsertReport::default();
+        for camera_id in cameras {
+            let features: Vec<ImageFeature> = self.windows[camera_id].iter().cloned().collect();
+            let camera_report = insert_features_batch(&tx, &features, OnConflict::Ignore)?;
+            report.inserted += camera_report.inserted;
+            report.skipped += camera_report.skipped;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::analytics::{
+    activity_histogram, anomaly_timeseries, camera_centroid, camera_gaps, correlate_activity, estimated_fps,
+    extraction_latency_stats, handoff_candidates, keypoint_heatmap, peak_activity_window, Bucket,
+};
+use vyuwer_rust::model::{
+    insert_image_description, insert_image_feature, insert_image_feature_timed, FrameSize, ImageDescription,
+};
+use vyuwer_rust::VyuwerError;
+use vyuwer_rust::timestamp::format_unix_millis;
+
+#[test]
+fn centroid_of_identical_descriptors_is_that_descriptor() {
+    let db = TempDb::new();
+    let reference = feature("a", "cam", 1_700_000_000, 7);
+    for (i, id) in ["a", "b", "c"].iter().enumerate() {
+        insert_image_feature(&feature(id, "cam", 1_700_000_000 + i as i64, 7), db.path()).unwrap();
+    }
+
+    let centroid = camera_centroid("cam", db.path()).unwrap().unwrap();
+    assert_eq!(centroid, reference.descriptors);
+    assert_eq!(camera_centroid("missing", db.path()).unwrap(), None);
+}
+
+#[test]
+fn histogram_buckets_features_by_day() {
+    let db = TempDb::new();
+    // 2023-11-14T22:13:20Z onwards: two features that day, one two days later
+    for (i, offset) in [0, 3600, 2 * 86_400].iter().enumerate() {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", 1_700_000_000 + offset, 0), db.path()).unwrap();
+    }
+
+    let days = activity_histogram("cam", Bucket::Day, false, db.path()).unwrap();
+    assert_eq!(
+        days,
+        vec![("2023-11-14T00:00:00Z".to_string(), 2), ("2023-11-16T00:00:00Z".to_string(), 1)]
+    );
+    let filled = activity_histogram("cam", Bucket::Day, true, db.path()).unwrap();
+    assert_eq!(filled.iter().map(|(_, n)| *n).collect::<Vec<_>>(), vec![2, 0, 1]);
+    assert_eq!(activity_histogram("cam", Bucket::Hour, false, db.path()).unwrap().len(), 3);
+}
+
+#[test]
+fn identical_activity_patterns_correlate() {
+    let db = TempDb::new();
+    // Bursts of 3, 0, 1 and 4 features in consecutive hours from 2023-11-14T22:00:00Z
+    let base = 1_699_999_200;
+    for camera in ["a", "b"] {
+        for (hour, burst) in [3, 0, 1, 4].iter().enumerate() {
+            for i in 0..*burst {
+                let at = base + hour as i64 * 3600 + i * 60;
+                insert_image_feature(&feature(&format!("{camera}-{hour}-{i}"), camera, at, 0), db.path()).unwrap();
+            }
+        }
+    }
+
+    let (start, end) = ("2023-11-14T22:00:00Z", "2023-11-15T01:59:59Z");
+    let r = correlate_activity("a", "b", start, end, Bucket::Hour, db.path()).unwrap();
+    assert!((r - 1.0).abs() < 1e-9, "correlation was {r}");
+    assert_eq!(correlate_activity("a", "missing", start, end, Bucket::Hour, db.path()).unwrap(), 0.0);
+    assert!(correlate_activity("a", "b", end, start, Bucket::Hour, db.path()).is_err());
+}
+
+#[test]
+fn peak_window_covers_the_cluster() {
+    let db = TempDb::new();
+    // Sparse features an hour apart, with a burst of five within two minutes in the middle
+    let mut times = vec![1_700_000_000, 1_700_003_600, 1_700_010_800, 1_700_014_400];
+    times.extend((0..5).map(|i| 1_700_007_200 + i * 30));
+    for (i, at) in times.iter().enumerate() {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", *at, 0), db.path()).unwrap();
+    }
+
+    let (start, count) = peak_activity_window("cam", 300, db.path()).unwrap().unwrap();
+    assert_eq!(start, "2023-11-15T00:13:20.000Z");
+    assert_eq!(count, 5);
+    assert_eq!(peak_activity_window("missing", 300, db.path()).unwrap(), None);
+}
+
+#[test]
+fn an_outage_is_reported_as_one_gap() {
+    let db = TempDb::new();
+    // 10 s cadence with a deliberate 5 minute outage after the third frame
+    for (i, secs) in [0, 10, 20, 320, 330].iter().enumerate() {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", 1_700_000_000 + secs, 3), db.path()).unwrap();
+    }
+    assert_eq!(
+        camera_gaps("cam", 60.0, db.path()).unwrap(),
+        [("2023-11-14T22:13:40.000Z".to_string(), "2023-11-14T22:18:40.000Z".to_string())]
+    );
+    assert!(camera_gaps("cam", 600.0, db.path()).unwrap().is_empty());
+
+    db.seed_features(1, "single");
+    assert!(camera_gaps("single", 0.0, db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn gaps_follow_parsed_time_across_timestamp_formats() {
+    let db = TempDb::new();
+    // Whole-second and millisecond timestamps within one second sort wrongly as strings
+    let stamps = [("a", "2024-01-01T00:00:00.500Z"), ("b", "2024-01-01T00:00:00Z"), ("c", "2024-01-01T00:00:30Z")];
+    for (id, created_at_utc) in stamps {
+        let mut stored = feature(id, "cam", 0, 3);
+        stored.created_at_utc = created_at_utc.to_string();
+        insert_image_feature(&stored, db.path()).unwrap();
+    }
+    assert_eq!(
+        camera_gaps("cam", 10.0, db.path()).unwrap(),
+        [("2024-01-01T00:00:00.500Z".to_string(), "2024-01-01T00:00:30.000Z".to_string())]
+    );
+}
+
+#[test]
+fn object_reappearing_in_a_second_camera_is_a_handoff_candidate() {
+    let db = TempDb::new();
+    let seen = feature("entry", "lobby", 1_700_000_000, 3);
+    insert_image_feature(&seen, db.path()).unwrap();
+    // The same scene shortly after in the corridor, something else in the car park, and a
+    // repeat in the corridor long after the window
+    insert_image_feature(&feature("hall-1", "corridor", 1_700_000_020, 3), db.path()).unwrap();
+    insert_image_feature(&feature("park-1", "car_park", 1_700_000_030, 200), db.path()).unwrap();
+    insert_image_feature(&feature("hall-2", "corridor", 1_700_100_000, 3), db.path()).unwrap();
+
+    let window = ("2023-11-14T22:13:20Z", "2023-11-14T22:15:00Z");
+    let candidates = handoff_candidates(&seen, "lobby", window, db.path()).unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!((candidates[0].0.as_str(), candidates[0].1.as_str()), ("corridor", "hall-1"));
+    assert!(candidates[0].2 >= 0.9);
+
+    // Only the excluded camera is skipped
+    assert!(handoff_candidates(&seen, "corridor", window, db.path()).unwrap().iter().any(|c| c.0 == "lobby"));
+    assert!(handoff_candidates(&seen, "lobby", (window.1, window.0), db.path()).is_err());
+}
+
+#[test]
+fn keypoints_in_one_corner_fill_one_cell() {
+    let db = TempDb::new();
+    for i in 0..2 {
+        let mut corner = feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 3);
+        corner.frame_size = Some(FrameSize::new(640, 480));
+        // The fixture's keypoints sit at (0,0) .. (3,3): the top-left corner
+        insert_image_feature(&corner, db.path()).unwrap();
+    }
+    let mut bottom_right = feature("far", "cam", 1_700_000_010, 3);
+    bottom_right.frame_size = Some(FrameSize::new(640, 480));
+    for keypoint in &mut bottom_right.keypoints {
+        keypoint.x = 630.0;
+        keypoint.y = 700.0;
+    }
+    insert_image_feature(&bottom_right, db.path()).unwrap();
+    // No frame size, so not placed
+    insert_image_feature(&feature("unsized", "cam", 1_700_000_020, 3), db.path()).unwrap();
+
+    let heatmap = keypoint_heatmap("cam", (3, 4), db.path()).unwrap();
+    assert_eq!(heatmap.len(), 3);
+    assert!(heatmap.iter().all(|row| row.len() == 4));
+    assert_eq!(heatmap[0][0], 8);
+    assert_eq!(heatmap[2][3], 4);
+    assert_eq!(heatmap.iter().flatten().sum::<u32>(), 12);
+    assert!(keypoint_heatmap("cam", (0, 4), db.path()).is_err());
+}
+
+#[test]
+fn features_100ms_apart_estimate_10_fps() {
+    let db = TempDb::new();
+    assert!(estimated_fps("cam", 10, db.path()).is_err());
+    for i in 0..8 {
+        let mut f = feature(&format!("f{i}"), "cam", 0, 1);
+        f.created_at_utc = format_unix_millis(1_700_000_000_000 + i * 100);
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+    let fps = estimated_fps("cam", 5, db.path()).unwrap();
+    assert!((fps - 10.0).abs() < 1e-9, "got {fps}");
+}
+
+#[test]
+fn anomaly_timeseries_zero_fills_quiet_buckets() {
+    let db = TempDb::new();
+    let describe = |image_name: &str, datetime: &str, anomaly: Option<&str>| {
+        let description = ImageDescription {
+            image_name: image_name.to_string(),
+            datetime: datetime.to_string(),
+            camera_id: "cam".to_string(),
+            anomaly: anomaly.map(str::to_string),
+        };
+        insert_image_description(&description, db.path()).unwrap();
+    };
+    describe("a.png", "2024-06-12T10:05:00Z", Some("scene_change"));
+    describe("b.png", "2024-06-12T10:40:00Z", Some("scene_change"));
+    describe("c.png", "2024-06-12T11:30:00Z", None);
+    describe("d.png", "2024-06-12T12:15:00Z", Some("tampering"));
+    describe("late.png", "2024-06-12T14:00:00Z", Some("tampering"));
+
+    let series =
+        anomaly_timeseries("cam", Bucket::Hour, "2024-06-12T10:00:00Z", "2024-06-12T12:59:59Z", db.path()).unwrap();
+    assert_eq!(
+        series,
+        [
+            ("2024-06-12T10:00:00Z".to_string(), 2),
+            ("2024-06-12T11:00:00Z".to_string(), 0),
+            ("2024-06-12T12:00:00Z".to_string(), 1),
+        ]
+    );
+    assert!(anomaly_timeseries("cam", Bucket::Day, "2024-06-13T00:00:00Z", "2024-06-12T00:00:00Z", db.path()).is_err());
+}
+
+#[test]
+fn extraction_latency_stats_use_nearest_rank_percentiles() {
+    let db = TempDb::new();
+    // Untimed features and other cameras are left out
+    insert_image_feature(&feature("untimed", "cam", 1_700_000_000, 0), db.path()).unwrap();
+    insert_image_feature_timed(&feature("other", "other", 1_700_000_000, 0), 1, db.path()).unwrap();
+    assert!(matches!(extraction_latency_stats("cam", db.path()), Err(VyuwerError::InvalidInput(_))));
+
+    // 1..=20 ms, inserted out of order
+    for i in 0..20u8 {
+        let ms = (u64::from(i) * 7) % 20 + 1;
+        insert_image_feature_timed(&feature(&format!("f{i}"), "cam", 1_700_000_001 + i64::from(i), i), ms, db.path())
+            .unwrap();
+    }
+    assert_eq!(extraction_latency_stats("cam", db.path()).unwrap(), (1, 10, 19, 20));
+    assert_eq!(extraction_latency_stats("other", db.path()).unwrap(), (1, 1, 1, 1));
+}
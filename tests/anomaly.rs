@@ -0,0 +1,388 @@
+mod common;
+
+use common::{feature, TempDb};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rusqlite::Connection;
+use vyuwer_rust::anomaly::{
+    auto_rotate_baseline, calibrate_threshold, classify_anomaly_with_lighting, classify_pending, classify_with_store,
+    detect_frozen, detect_tampering, evaluate_classifier, load_baseline, set_baseline, suggest_baseline, AnomalyDetail,
+    AnomalyKind, AnomalyRule, AnomalyScorer, AnomalySignals, AnomalyTracker, AnomalyWeights, FrameContext, Hysteresis,
+    RuleClassifier, DEFAULT_MIN_MATCH_RATIO, LIGHTING_CHANGE_MIN_DELTA,
+};
+use vyuwer_rust::store::{FeatureStore, MemoryStore};
+use vyuwer_rust::{Database, VyuwerError};
+use vyuwer_rust::matching::{match_features, MatchParams};
+use vyuwer_rust::model::{features_without_description, insert_image_feature, ImageDescription, ImageFeature};
+
+fn insert_static(db: &TempDb, id: &str, unix_secs: i64, seed: u8) {
+    let mut static_frame = feature(id, "cam", unix_secs, seed);
+    static_frame.motion_mean = 0.0;
+    static_frame.motion_std = 0.0;
+    insert_image_feature(&static_frame, db.path()).unwrap();
+}
+
+#[test]
+fn identical_static_window_is_frozen() {
+    let db = TempDb::new();
+    for i in 0..4 {
+        insert_static(&db, &format!("f{i}"), 1_700_000_000 + i, 3);
+    }
+    assert!(detect_frozen("cam", 4, db.path()).unwrap());
+}
+
+#[test]
+fn window_with_variation_is_not_frozen() {
+    let db = TempDb::new();
+    insert_static(&db, "f0", 1_700_000_000, 3);
+    insert_static(&db, "f1", 1_700_000_001, 3);
+    insert_static(&db, "f2", 1_700_000_002, 200);
+    assert!(!detect_frozen("cam", 3, db.path()).unwrap());
+
+    // Matching descriptors are not enough if the frames moved
+    for i in 0..3 {
+        insert_image_feature(&feature(&format!("m{i}"), "moving", 1_700_000_000 + i, 3), db.path()).unwrap();
+    }
+    assert!(!detect_frozen("moving", 3, db.path()).unwrap());
+}
+
+#[test]
+fn classifier_runs_against_the_memory_store() {
+    let mut store = MemoryStore::new();
+    let current = feature("now", "cam", 1_700_000_100, 3);
+    assert!(matches!(
+        classify_with_store(&mut store, &current, DEFAULT_MIN_MATCH_RATIO),
+        Err(VyuwerError::MissingBaseline(_))
+    ));
+
+    store.insert_image_feature(&feature("later", "cam", 1_700_000_050, 200)).unwrap();
+    store.insert_image_feature(&feature("baseline", "cam", 1_700_000_000, 3)).unwrap();
+    // The earliest feature is the baseline, and `current` repeats its descriptors
+    assert_eq!(classify_with_store(&mut store, &current, DEFAULT_MIN_MATCH_RATIO).unwrap(), None);
+
+    let changed = feature("changed", "cam", 1_700_000_200, 200);
+    let detail = classify_with_store(&mut store, &changed, DEFAULT_MIN_MATCH_RATIO).unwrap().unwrap();
+    assert_eq!(detail.kind, AnomalyKind::SceneChange);
+
+    // Once promoted, the later feature is the baseline instead
+    store.set_baseline("cam", "later").unwrap();
+    assert_eq!(classify_with_store(&mut store, &changed, DEFAULT_MIN_MATCH_RATIO).unwrap(), None);
+    assert!(store.set_baseline("cam", "missing").is_err());
+}
+
+#[test]
+fn classifier_uses_the_promoted_baseline_in_the_database() {
+    let db = TempDb::new();
+    insert_image_feature(&feature("baseline", "cam", 1_700_000_000, 3), db.path()).unwrap();
+    insert_image_feature(&feature("later", "cam", 1_700_000_050, 200), db.path()).unwrap();
+    let mut handle = Database::open(db.path()).unwrap();
+    let changed = feature("changed", "cam", 1_700_000_200, 200);
+    assert!(classify_with_store(&mut handle, &changed, DEFAULT_MIN_MATCH_RATIO).unwrap().is_some());
+
+    set_baseline("cam", "later", db.path()).unwrap();
+    assert_eq!(classify_with_store(&mut handle, &changed, DEFAULT_MIN_MATCH_RATIO).unwrap(), None);
+}
+
+#[test]
+fn hysteresis_reduces_alert_flapping() {
+    let ratios = [0.5, 0.29, 0.31, 0.28, 0.32, 0.29, 0.31, 0.45, 0.5];
+    let state_changes = |hysteresis: Hysteresis| {
+        let mut tracker = AnomalyTracker::new(hysteresis);
+        let mut alerting = false;
+        let mut changes = 0;
+        for ratio in ratios {
+            let now = tracker.observe("cam", ratio).is_some();
+            changes += usize::from(now != alerting);
+            alerting = now;
+        }
+        changes
+    };
+
+    let single_threshold = state_changes(Hysteresis::new(0.3, 0.3).unwrap());
+    let with_dead_band = state_changes(Hysteresis::new(0.3, 0.4).unwrap());
+    assert_eq!(single_threshold, 6);
+    assert_eq!(with_dead_band, 2);
+    assert!(Hysteresis::new(0.4, 0.3).is_err());
+}
+
+#[test]
+fn collapse_after_a_calm_frame_is_tampering() {
+    let prev = feature("prev", "cam", 1_700_000_000, 3);
+    let unrelated = feature("now", "cam", 1_700_000_001, 200);
+    assert!(detect_tampering(&prev, &unrelated, 0.1));
+    assert!(!detect_tampering(&prev, &feature("same", "cam", 1_700_000_001, 3), 0.1));
+
+    let mut busy = prev.clone();
+    busy.motion_mean = 50.0;
+    assert!(!detect_tampering(&busy, &unrelated, 0.1));
+}
+
+#[test]
+fn drifted_baseline_rotates_to_a_recent_normal_frame() {
+    let db = TempDb::new();
+    let day = Duration::from_secs(86_400);
+    // The scene has since changed: recent frames agree with each other but not the baseline
+    insert_image_feature(&feature("baseline", "cam", 1_700_000_000, 3), db.path()).unwrap();
+    for i in 1..=4 {
+        insert_image_feature(&feature(&format!("new{i}"), "cam", 1_700_000_000 + i, 200), db.path()).unwrap();
+    }
+
+    assert_eq!(auto_rotate_baseline("cam", day, db.path()).unwrap().as_deref(), Some("new4"));
+    let conn = Connection::open(db.path()).unwrap();
+    assert_eq!(load_baseline(&conn, "cam").unwrap().unwrap().id, "new4");
+    // The promotion just happened, so the next check is not due yet
+    assert_eq!(auto_rotate_baseline("cam", day, db.path()).unwrap(), None);
+}
+
+#[test]
+fn matching_baseline_is_kept() {
+    let db = TempDb::new();
+    for i in 0..4 {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 3), db.path()).unwrap();
+    }
+    assert_eq!(auto_rotate_baseline("cam", Duration::from_secs(60), db.path()).unwrap(), None);
+    let conn = Connection::open(db.path()).unwrap();
+    assert_eq!(load_baseline(&conn, "cam").unwrap().unwrap().id, "f0");
+}
+
+#[test]
+fn calibrated_threshold_separates_the_clusters() {
+    let params = MatchParams::default();
+    let positives: Vec<_> = (0..4u8)
+        .map(|seed| (feature("a", "cam", 1_700_000_000, seed), feature("b", "cam", 1_700_000_001, seed)))
+        .collect();
+    let negatives = vec![
+        (feature("a", "cam", 1_700_000_000, 3), feature("b", "cam", 1_700_000_001, 200)),
+        (feature("a", "cam", 1_700_000_000, 200), feature("b", "cam", 1_700_000_001, 3)),
+    ];
+    let ratio = |(a, b): &(ImageFeature, ImageFeature)| match_features(a, b, &params).match_ratio();
+    let lowest_positive = positives.iter().map(ratio).fold(f64::INFINITY, f64::min);
+    let highest_negative = negatives.iter().map(ratio).fold(0.0, f64::max);
+    assert!(highest_negative < lowest_positive);
+
+    let threshold = calibrate_threshold(&positives, &negatives).unwrap();
+    assert!(highest_negative < threshold && threshold < lowest_positive, "threshold {threshold}");
+    assert!(calibrate_threshold(&positives, &[]).is_err());
+}
+
+#[test]
+fn lighting_change_discounts_a_moderate_drop() {
+    let baseline = feature("baseline", "cam", 1_700_000_000, 3);
+    let mut current = feature("now", "cam", 1_700_000_001, 3);
+    // Half the descriptors no longer match anything
+    for byte in &mut current.descriptors.data[64..] {
+        *byte = !*byte;
+    }
+    let ratio = match_features(&current, &baseline, &MatchParams::default()).match_ratio();
+    assert!(ratio > 0.0 && ratio < 1.0);
+    let min_ratio = (ratio * 1.5).min(1.0);
+
+    assert!(classify_anomaly_with_lighting(&baseline, &current, min_ratio, 5.0).is_some());
+    assert_eq!(classify_anomaly_with_lighting(&baseline, &current, min_ratio, LIGHTING_CHANGE_MIN_DELTA), None);
+    assert_eq!(classify_anomaly_with_lighting(&baseline, &current, min_ratio, -LIGHTING_CHANGE_MIN_DELTA), None);
+}
+
+#[test]
+fn classify_pending_describes_the_backlog() {
+    let db = TempDb::new();
+    for (i, seed) in [3u8, 3, 200].into_iter().enumerate() {
+        let mut pending = feature(&format!("f{i}"), "cam", 1_700_000_000 + i as i64, seed);
+        pending.img_filename = Some(format!("frame-{i}.png"));
+        insert_image_feature(&pending, db.path()).unwrap();
+    }
+    // No image file, so it cannot be described
+    insert_image_feature(&feature("bare", "cam", 1_700_000_010, 3), db.path()).unwrap();
+
+    assert_eq!(classify_pending("cam", DEFAULT_MIN_MATCH_RATIO, db.path()).unwrap(), 3);
+    let pending = features_without_description("cam", db.path()).unwrap();
+    assert_eq!(pending.iter().map(|f| f.id.as_str()).collect::<Vec<_>>(), ["bare"]);
+    let conn = Connection::open(db.path()).unwrap();
+    let anomalies: Vec<(String, Option<String>)> = conn
+        .prepare("SELECT image_name, anomaly FROM image_description ORDER BY image_name")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(anomalies[0], ("frame-0.png".to_string(), None));
+    assert_eq!(anomalies[1], ("frame-1.png".to_string(), None));
+    assert!(anomalies[2].1.as_deref().unwrap().contains("SceneChange"));
+    // Nothing is left to classify
+    assert_eq!(classify_pending("cam", DEFAULT_MIN_MATCH_RATIO, db.path()).unwrap(), 0);
+}
+
+#[test]
+fn classify_pending_describes_a_shared_image_once() {
+    let db = TempDb::new();
+    for i in 0..3 {
+        let mut pending = feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 3);
+        pending.img_filename = Some(if i < 2 { "shared.png" } else { "own.png" }.to_string());
+        insert_image_feature(&pending, db.path()).unwrap();
+    }
+
+    assert_eq!(classify_pending("cam", DEFAULT_MIN_MATCH_RATIO, db.path()).unwrap(), 2);
+    let conn = Connection::open(db.path()).unwrap();
+    let names: Vec<String> = conn
+        .prepare("SELECT image_name FROM image_description ORDER BY image_name")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(names, ["own.png", "shared.png"]);
+    assert_eq!(classify_pending("cam", DEFAULT_MIN_MATCH_RATIO, db.path()).unwrap(), 0);
+}
+
+#[test]
+fn zero_weight_signal_does_not_move_the_score() {
+    let calm = AnomalySignals {
+        motion_z: 0.2,
+        match_ratio: 0.9,
+        sharpness_ratio: 1.0,
+        brightness_delta: 2.0,
+    };
+    let blurred = AnomalySignals {
+        sharpness_ratio: 0.1,
+        ..calm
+    };
+    let weighted = AnomalyScorer::new(AnomalyWeights::default(), 0.4).unwrap();
+    assert!(weighted.score(&blurred) > weighted.score(&calm));
+
+    let ignore_sharpness = AnomalyWeights {
+        sharpness: 0.0,
+        ..AnomalyWeights::default()
+    };
+    let scorer = AnomalyScorer::new(ignore_sharpness, 0.4).unwrap();
+    assert_eq!(scorer.score(&blurred), scorer.score(&calm));
+
+    // A collapsed match ratio together with a motion spike crosses the threshold
+    let changed = AnomalySignals {
+        motion_z: 4.0,
+        match_ratio: 0.05,
+        ..calm
+    };
+    assert!(scorer.classify(&calm).is_none());
+    let detail = scorer.classify(&changed).unwrap();
+    assert_eq!((detail.kind, detail.match_ratio), (AnomalyKind::Combined, 0.05));
+    assert!(detail.confidence > 0.0 && detail.confidence <= 1.0);
+
+    let nothing = AnomalyWeights {
+        motion: 0.0,
+        match_ratio: 0.0,
+        sharpness: 0.0,
+        brightness: 0.0,
+    };
+    assert!(AnomalyScorer::new(nothing, 0.4).is_err());
+    assert!(AnomalyScorer::new(AnomalyWeights::default(), 1.0).is_err());
+}
+
+#[test]
+fn anomaly_hook_fires_once_per_anomalous_description() {
+    let db = TempDb::new();
+    let mut handle = Database::open(db.path()).unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&calls);
+    handle.on_anomaly(Box::new(move |desc| {
+        assert_eq!(desc.image_name, "f1");
+        seen.fetch_add(1, Ordering::SeqCst);
+    }));
+
+    let mut desc = ImageDescription {
+        image_name: "f0".to_string(),
+        datetime: "2023-11-14T22:13:20Z".to_string(),
+        camera_id: "cam".to_string(),
+        anomaly: None,
+    };
+    handle.insert_image_description(&desc).unwrap();
+    desc.image_name = "f1".to_string();
+    desc.anomaly = Some("tampering".to_string());
+    handle.insert_image_description(&desc).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn separated_labels_evaluate_to_an_auc_of_one() {
+    let baseline = feature("baseline", "cam", 1_700_000_000, 3);
+    let mut labeled: Vec<(ImageFeature, bool)> =
+        (0..4).map(|i| (feature(&format!("n{i}"), "cam", i, 3), false)).collect();
+    labeled.extend([120u8, 200].iter().map(|&seed| (feature(&format!("a{seed}"), "cam", 10, seed), true)));
+
+    let report = evaluate_classifier(&labeled, &baseline, &[0.0, 0.5, 1.01]);
+    assert!((report.auc.unwrap() - 1.0).abs() < 1e-9, "auc {:?}", report.auc);
+
+    let [none, middle, all] = report.thresholds[..] else {
+        panic!("one entry per threshold");
+    };
+    assert_eq!((none.counts.true_positives, none.counts.false_positives), (0, 0));
+    assert_eq!((none.precision, none.recall), (1.0, 0.0));
+    assert_eq!(middle.counts.true_positives, 2);
+    assert_eq!(middle.counts.true_negatives, 4);
+    assert_eq!((middle.precision, middle.recall), (1.0, 1.0));
+    assert_eq!(all.counts.false_positives, 4);
+    assert!((all.precision - 2.0 / 6.0).abs() < 1e-9);
+
+    assert_eq!(evaluate_classifier(&labeled[..4], &baseline, &[0.5]).auc, None);
+}
+
+#[test]
+fn most_central_normal_frame_is_suggested() {
+    let db = TempDb::new();
+    assert_eq!(suggest_baseline("cam", db.path()).unwrap(), None);
+
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut noise = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+    };
+    let central: Vec<u8> = (0..4 * 32).map(|_| noise()).collect();
+    // Each outlier shares one descriptor with the central frame and nothing with the others
+    for k in 0..3 {
+        let mut outlier = feature(&format!("outlier-{k}"), "cam", 1_700_000_000 + k as i64, 0);
+        for (j, byte) in outlier.descriptors.data.iter_mut().enumerate() {
+            *byte = if j / 32 == k { central[j] } else { noise() };
+        }
+        insert_image_feature(&outlier, db.path()).unwrap();
+    }
+    let mut centre = feature("central", "cam", 1_700_000_010, 0);
+    centre.descriptors.data = central;
+    insert_image_feature(&centre, db.path()).unwrap();
+
+    assert_eq!(suggest_baseline("cam", db.path()).unwrap().as_deref(), Some("central"));
+}
+
+struct AlwaysFires;
+
+impl AnomalyRule for AlwaysFires {
+    fn evaluate(&self, ctx: &FrameContext) -> Option<AnomalyDetail> {
+        Some(AnomalyDetail {
+            kind: AnomalyKind::Custom,
+            match_ratio: ctx.motion_mean,
+            confidence: 0.25,
+        })
+    }
+}
+
+#[test]
+fn registered_rules_add_their_anomalies_to_the_classification() {
+    let baseline = feature("baseline", "cam", 1_700_000_000, 3);
+    let same = feature("same", "cam", 1_700_000_001, 3);
+    let mut classifier = RuleClassifier::default();
+    assert!(classifier.classify(&FrameContext::new(&same, &baseline)).is_empty());
+
+    classifier.register(Box::new(AlwaysFires));
+    assert_eq!(classifier.rules().len(), 1);
+    let details = classifier.classify(&FrameContext::new(&same, &baseline));
+    assert_eq!(details.len(), 1);
+    assert_eq!((details[0].kind, details[0].match_ratio), (AnomalyKind::Custom, same.motion_mean));
+
+    // Combined with the built-in test; the scene change is the more confident one
+    let changed = feature("changed", "cam", 1_700_000_002, 200);
+    let ctx = FrameContext::new(&changed, &baseline);
+    let kinds: Vec<_> = classifier.classify(&ctx).iter().map(|detail| detail.kind).collect();
+    assert_eq!(kinds, [AnomalyKind::SceneChange, AnomalyKind::Custom]);
+    assert_eq!(classifier.classify_top(&ctx).unwrap().kind, AnomalyKind::SceneChange);
+}
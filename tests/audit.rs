@@ -0,0 +1,33 @@
+mod common;
+
+use common::TempDb;
+use vyuwer_rust::audit::audit_since;
+use vyuwer_rust::model::delete_image_feature;
+
+#[test]
+fn deleting_writes_one_delete_entry() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    db.seed_features(1, "other");
+    delete_image_feature("cam", db.path()).unwrap();
+
+    let entries = audit_since("1970-01-01T00:00:00Z", db.path()).unwrap();
+    let deletes: Vec<_> = entries.iter().filter(|entry| entry.operation == "delete").collect();
+    assert_eq!(deletes.len(), 1);
+    assert_eq!(deletes[0].target_id, "cam");
+    assert_eq!(deletes[0].details.as_deref(), Some("2 feature(s)"));
+    assert_eq!(entries.iter().filter(|entry| entry.operation == "insert").count(), 3);
+}
+
+#[test]
+fn since_a_whole_second_includes_entries_within_it() {
+    let db = TempDb::new();
+    db.seed_features(1, "cam");
+    let recorded = audit_since("1970-01-01T00:00:00Z", db.path()).unwrap().remove(0).timestamp;
+
+    // 2024-06-12T12:34:56.789Z -> 2024-06-12T12:34:56Z, which sorts after it as text
+    let second = format!("{}Z", &recorded[..19]);
+    assert_eq!(audit_since(&second, db.path()).unwrap().len(), 1);
+    assert!(audit_since("2999-01-01T00:00:00Z", db.path()).unwrap().is_empty());
+    assert!(audit_since("yesterday", db.path()).is_err());
+}
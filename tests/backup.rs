@@ -0,0 +1,42 @@
+mod common;
+
+use common::TempDb;
+use rusqlite::Connection;
+use vyuwer_rust::backup::{backup_to, backup_to_with_progress};
+use vyuwer_rust::model::get_image_feature;
+use vyuwer_rust::tags::add_tag;
+use vyuwer_rust::VyuwerError;
+
+// Function to count the rows of `table` in the database at `path`
+fn row_count(path: &str, table: &str) -> i64 {
+    let conn = Connection::open(path).unwrap();
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn backup_has_the_same_rows() {
+    let db = TempDb::new();
+    db.seed_features(20, "cam");
+    add_tag("cam-3", "weather", "rain", db.path()).unwrap();
+    let dst = tempfile::tempdir().unwrap();
+    let dst_path = dst.path().join("backup.db").to_string_lossy().into_owned();
+
+    let mut updates = Vec::new();
+    backup_to_with_progress(&dst_path, 1, db.path(), |update| updates.push(update)).unwrap();
+    for table in ["image_features", "feature_tags", "audit_log"] {
+        assert_eq!(row_count(&dst_path, table), row_count(db.path(), table), "{table}");
+    }
+    assert_eq!(get_image_feature("cam", &dst_path).unwrap(), get_image_feature("cam", db.path()).unwrap());
+    // One page per step, so several updates, ending with every page copied
+    assert!(updates.len() > 1);
+    let last = updates.last().unwrap();
+    assert_eq!(Some(last.done), last.total);
+}
+
+#[test]
+fn zero_pages_per_step_is_rejected() {
+    let db = TempDb::new();
+    let dst = tempfile::tempdir().unwrap();
+    let dst_path = dst.path().join("backup.db").to_string_lossy().into_owned();
+    assert!(matches!(backup_to(&dst_path, 0, db.path()), Err(VyuwerError::InvalidInput(_))));
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::model::{get_camera_features, insert_image_features_batch, BatchInsertReport, OnConflict};
+
+// Seeds "cam-0" with seed 0, then batches a new "cam-1" and a conflicting "cam-0" with seed 9
+fn run_batch(on_conflict: OnConflict) -> (TempDb, vyuwer_rust::Result<BatchInsertReport>) {
+    let db = TempDb::new();
+    db.seed_features(1, "cam");
+    let batch = [feature("cam-1", "cam", 1_700_000_100, 1), feature("cam-0", "cam", 1_700_000_200, 9)];
+    let report = insert_image_features_batch(&batch, on_conflict, db.path());
+    (db, report)
+}
+
+#[test]
+fn abort_rejects_the_whole_batch() {
+    let (db, report) = run_batch(OnConflict::Abort);
+    assert!(report.is_err());
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 1);
+}
+
+#[test]
+fn ignore_keeps_the_stored_feature() {
+    let (db, report) = run_batch(OnConflict::Ignore);
+    assert_eq!(report.unwrap(), BatchInsertReport { inserted: 1, skipped: 1 });
+    let stored = get_camera_features("cam", db.path()).unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[0], feature("cam-0", "cam", 1_700_000_000, 0));
+}
+
+#[test]
+fn replace_overwrites_the_stored_feature() {
+    let (db, report) = run_batch(OnConflict::Replace);
+    assert_eq!(report.unwrap(), BatchInsertReport { inserted: 2, skipped: 0 });
+    let stored = get_camera_features("cam", db.path()).unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[1], feature("cam-0", "cam", 1_700_000_200, 9));
+}
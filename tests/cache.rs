@@ -0,0 +1,36 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::model::{delete_features_in_range, insert_image_feature};
+use vyuwer_rust::Database;
+
+#[test]
+fn repeated_fetch_hits_and_insert_invalidates() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    let mut handle = Database::open_with_cache(db.path(), 4).unwrap();
+
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "cam-1");
+    assert_eq!(handle.cache_hits(), 0);
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "cam-1");
+    assert_eq!(handle.cache_hits(), 1);
+
+    handle.insert_image_feature(&feature("cam-2", "cam", 1_700_000_100, 2)).unwrap();
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "cam-2");
+    assert_eq!(handle.cache_hits(), 1);
+}
+
+#[test]
+fn writes_through_other_connections_invalidate() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    let mut handle = Database::open_with_cache(db.path(), 4).unwrap();
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "cam-1");
+
+    insert_image_feature(&feature("cam-2", "cam", 1_700_000_100, 2), db.path()).unwrap();
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "cam-2");
+
+    delete_features_in_range("cam", "2023-11-14T22:13:20Z", "2023-11-14T22:15:00Z", db.path()).unwrap();
+    assert!(handle.get_image_feature("cam").unwrap().is_none());
+    assert_eq!(handle.cache_hits(), 0);
+}
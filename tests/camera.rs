@@ -0,0 +1,189 @@
+mod common;
+
+use common::{feature, TempDb};
+use rusqlite::Connection;
+use vyuwer_rust::anomaly::set_baseline;
+use vyuwer_rust::camera::{
+    camera_location, find_duplicate_cameras, forget_camera, get_calibration, keypoint_gate, merge_cameras,
+    set_calibration, set_camera_location, set_keypoint_gate, CameraCalibration, KeypointGate, LowKeypointAction,
+};
+use vyuwer_rust::descriptor_sets::{store_descriptors, DescriptorKind};
+use vyuwer_rust::float_descriptors::{store_float_descriptors, FloatDescriptors};
+use vyuwer_rust::flow::{store_flow_stats, FlowStats};
+use vyuwer_rust::frames::{set_frame_directory, store_frame_bytes};
+use vyuwer_rust::tags::add_tag;
+use vyuwer_rust::model::{
+    get_camera_features, get_feature_by_id, insert_image_description, insert_image_feature, low_quality_features,
+    DescriptorMatrix, ImageDescription,
+};
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn case_variant_cameras_are_found_and_merged() {
+    let db = TempDb::new();
+    db.seed_features(2, "camera_1");
+    db.seed_features(3, "Camera_1");
+    db.seed_features(1, "camera_2");
+    set_camera_location("Camera_1", 12.97, 77.59, db.path()).unwrap();
+
+    assert_eq!(find_duplicate_cameras(db.path()).unwrap(), [vec!["Camera_1", "camera_1"]]);
+    assert_eq!(merge_cameras("camera_1", &["Camera_1"], db.path()).unwrap(), 3);
+
+    assert!(find_duplicate_cameras(db.path()).unwrap().is_empty());
+    assert!(get_camera_features("Camera_1", db.path()).unwrap().is_empty());
+    let ids: Vec<String> = get_camera_features("camera_1", db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(ids, ["camera_1-0", "Camera_1-0", "camera_1-1", "Camera_1-1", "Camera_1-2"]);
+    assert_eq!(camera_location("camera_1", db.path()).unwrap(), Some((12.97, 77.59)));
+    assert_eq!(camera_location("Camera_1", db.path()).unwrap(), None);
+
+    assert!(merge_cameras("camera_2", &["camera_2"], db.path()).is_err());
+}
+
+#[test]
+fn merged_features_are_rehashed_under_their_new_camera() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    let moved = db.seed_features(2, "Cam");
+    merge_cameras("cam", &["Cam"], db.path()).unwrap();
+
+    let conn = Connection::open(db.path()).unwrap();
+    for before in &moved {
+        let after = get_feature_by_id(&before.id, db.path()).unwrap().unwrap();
+        let stored: Vec<u8> = conn
+            .query_row("SELECT content_hash FROM image_features WHERE id = ?1", [&before.id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, after.content_hash());
+        assert_ne!(stored, before.content_hash());
+    }
+}
+
+#[test]
+fn calibration_round_trips_and_follows_a_merge() {
+    let db = TempDb::new();
+    let calibration = CameraCalibration {
+        intrinsics: [812.5, 0.0, 640.25, 0.0, 810.0, 359.75, 0.0, 0.0, 1.0],
+        distortion: vec![-0.31, 0.12, 0.001, -0.0005, -0.02],
+    };
+    assert_eq!(get_calibration("Cam", db.path()).unwrap(), None);
+    set_calibration("Cam", &calibration, db.path()).unwrap();
+    assert_eq!(get_calibration("Cam", db.path()).unwrap(), Some(calibration.clone()));
+
+    let bad = CameraCalibration {
+        distortion: vec![0.0; 3],
+        ..calibration.clone()
+    };
+    assert!(set_calibration("Cam", &bad, db.path()).is_err());
+
+    db.seed_features(1, "cam");
+    assert_eq!(find_duplicate_cameras(db.path()).unwrap(), [vec!["Cam", "cam"]]);
+    merge_cameras("cam", &["Cam"], db.path()).unwrap();
+    assert_eq!(get_calibration("cam", db.path()).unwrap(), Some(calibration));
+    assert_eq!(get_calibration("Cam", db.path()).unwrap(), None);
+}
+
+#[test]
+fn keypoint_gate_rejects_or_flags_sparse_features() {
+    let db = TempDb::new();
+    let sparse = |id: &str, camera_id: &str| {
+        let mut sparse = feature(id, camera_id, 1_700_000_000, 1);
+        sparse.keypoints.truncate(3);
+        sparse.descriptors = DescriptorMatrix::new(3, 32, vec![5; 3 * 32]).unwrap();
+        sparse
+    };
+    // No gate: anything goes
+    insert_image_feature(&sparse("open", "ungated"), db.path()).unwrap();
+
+    let reject = KeypointGate {
+        min_keypoints: 10,
+        action: LowKeypointAction::Reject,
+    };
+    set_keypoint_gate("strict", Some(reject), db.path()).unwrap();
+    assert_eq!(keypoint_gate("strict", db.path()).unwrap(), Some(reject));
+    assert!(matches!(
+        insert_image_feature(&sparse("refused", "strict"), db.path()),
+        Err(VyuwerError::InsufficientKeypoints { found: 3, required: 10 })
+    ));
+    assert!(get_camera_features("strict", db.path()).unwrap().is_empty());
+
+    let flag = KeypointGate {
+        action: LowKeypointAction::Flag,
+        ..reject
+    };
+    set_keypoint_gate("lenient", Some(flag), db.path()).unwrap();
+    insert_image_feature(&sparse("flagged", "lenient"), db.path()).unwrap();
+    let flagged: Vec<String> = low_quality_features("lenient", db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(flagged, ["flagged"]);
+    assert!(low_quality_features("ungated", db.path()).unwrap().is_empty());
+
+    set_keypoint_gate("strict", None, db.path()).unwrap();
+    assert_eq!(keypoint_gate("strict", db.path()).unwrap(), None);
+    insert_image_feature(&sparse("allowed", "strict"), db.path()).unwrap();
+}
+
+// Function to count rows of every table but the audit log with any value mentioning `needle`
+fn rows_mentioning(db_name: &str, needle: &str) -> usize {
+    let conn = Connection::open(db_name).unwrap();
+    let mut tables =
+        conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name != 'audit_log'").unwrap();
+    let tables: Vec<String> = tables.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+    let mut total = 0;
+    for table in tables {
+        let mut columns = conn.prepare("SELECT name FROM pragma_table_info(?1)").unwrap();
+        let columns: Vec<String> = columns.query_map([&table], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        let any = columns.iter().map(|c| format!("CAST({c} AS TEXT) LIKE ?1")).collect::<Vec<_>>().join(" OR ");
+        let sql = format!("SELECT COUNT(*) FROM {table} WHERE {any}");
+        total += conn.query_row(&sql, [format!("%{needle}%")], |row| row.get::<_, i64>(0)).unwrap() as usize;
+    }
+    total
+}
+
+#[test]
+fn forgotten_camera_leaves_no_trace() {
+    let db = TempDb::new();
+    let frames = tempfile::tempdir().unwrap();
+    set_frame_directory(frames.path().to_str().unwrap(), db.path()).unwrap();
+    for camera_id in ["gone", "kept"] {
+        let seeded = db.seed_features(3, camera_id);
+        let first = &seeded[0].id;
+        add_tag(first, "site", "gate", db.path()).unwrap();
+        set_baseline(camera_id, first, db.path()).unwrap();
+        store_frame_bytes(first, camera_id.as_bytes(), db.path()).unwrap();
+        store_descriptors(first, DescriptorKind::Sift, &seeded[0].descriptors, db.path()).unwrap();
+        let floats = FloatDescriptors::new(1, 2, vec![0.5, 1.5]).unwrap();
+        store_float_descriptors(first, &floats, db.path()).unwrap();
+        let stats = FlowStats {
+            mean_magnitude: 1.0,
+            dominant_direction: 90.0,
+        };
+        store_flow_stats(first, &stats, db.path()).unwrap();
+        let description = ImageDescription {
+            image_name: format!("{camera_id}.png"),
+            datetime: "2023-11-14T22:13:20Z".to_string(),
+            camera_id: camera_id.to_string(),
+            anomaly: None,
+        };
+        insert_image_description(&description, db.path()).unwrap();
+        set_camera_location(camera_id, 12.97, 77.59, db.path()).unwrap();
+        let calibration = CameraCalibration {
+            intrinsics: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            distortion: vec![0.0; 4],
+        };
+        set_calibration(camera_id, &calibration, db.path()).unwrap();
+    }
+    assert!(rows_mentioning(db.path(), "gone") > 0);
+    let kept_before = rows_mentioning(db.path(), "kept");
+
+    let report = forget_camera("gone", db.path()).unwrap();
+    assert_eq!(report.rows_removed["image_features"], 3);
+    assert_eq!(report.rows_removed["feature_tags"], 1);
+    assert_eq!(report.rows_removed["baselines"], 1);
+    assert_eq!(report.frame_files_removed, 1);
+    assert_eq!(rows_mentioning(db.path(), "gone"), 0);
+    assert_eq!(rows_mentioning(db.path(), "kept"), kept_before);
+    // Frame files sit in directories named by their hash prefix
+    let files: usize = std::fs::read_dir(frames.path())
+        .unwrap()
+        .map(|dir| std::fs::read_dir(dir.unwrap().path()).unwrap().count())
+        .sum();
+    assert_eq!(files, 1);
+}
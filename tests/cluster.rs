@@ -0,0 +1,56 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::audit::audit_since;
+use vyuwer_rust::cluster::{cluster_features, deduplicate_camera, deduplicate_camera_with_progress};
+use vyuwer_rust::model::{get_camera_features, insert_image_feature};
+use vyuwer_rust::Database;
+
+#[test]
+fn two_similar_features_and_one_different_form_two_clusters() {
+    let db = TempDb::new();
+    for (id, seed) in [("a", 3), ("b", 200), ("c", 3)] {
+        let secs = 1_700_000_000 + i64::from(id.as_bytes()[0]);
+        insert_image_feature(&feature(id, "cam", secs, seed), db.path()).unwrap();
+    }
+
+    let clusters = cluster_features("cam", 0.5, None, db.path()).unwrap();
+    assert_eq!(clusters, [vec!["a", "c"], vec!["b"]]);
+    // Capped to the two oldest features, which differ
+    assert_eq!(cluster_features("cam", 0.5, Some(2), db.path()).unwrap(), [vec!["a"], vec!["b"]]);
+}
+
+#[test]
+fn deduplicate_reports_increasing_progress() {
+    let db = TempDb::new();
+    // Two runs of identical frames separated by a scene change
+    for (i, seed) in [3, 3, 3, 200, 200].iter().enumerate() {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", 1_700_000_000 + i as i64, *seed), db.path()).unwrap();
+    }
+
+    let mut updates = Vec::new();
+    let removed = deduplicate_camera_with_progress("cam", 0.9, db.path(), |update| updates.push(update)).unwrap();
+    assert_eq!(removed, 3);
+    let ids: Vec<String> = get_camera_features("cam", db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(ids, ["f0", "f3"]);
+
+    let done: Vec<usize> = updates.iter().map(|update| update.done).collect();
+    assert_eq!(done, [1, 2, 3, 4, 5]);
+    assert!(updates.iter().all(|update| update.total == Some(5)));
+}
+
+#[test]
+fn deduplicating_is_audited_and_seen_by_open_handles() {
+    let db = TempDb::new();
+    for i in 0..3 {
+        insert_image_feature(&feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 3), db.path()).unwrap();
+    }
+    let mut handle = Database::open(db.path()).unwrap();
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "f2");
+
+    assert_eq!(deduplicate_camera("cam", 0.9, db.path()).unwrap(), 2);
+    assert_eq!(handle.get_image_feature("cam").unwrap().unwrap().id, "f0");
+    let entries = audit_since("1970-01-01T00:00:00Z", db.path()).unwrap();
+    let dedup = entries.iter().find(|entry| entry.operation == "deduplicate").unwrap();
+    assert_eq!((dedup.target_id.as_str(), dedup.details.as_deref()), ("cam", Some("2 duplicate feature(s)")));
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use common::TempDb;
+use rusqlite::{params, Connection};
+use vyuwer_rust::codec;
+use vyuwer_rust::model::{
+    delete_features_in_range, get_feature_by_id, insert_image_feature, insert_image_feature_delta, keypoint_codec,
+    set_keypoint_codec, DescriptorMatrix, KeyPointData, KeypointCodec,
+};
+use vyuwer_rust::VyuwerError;
+
+// Fixtures hold the expected bytes of the blob layout: little-endian, fixed-width
+// integers, u64 length prefixes. A change here breaks every database already written.
+const DESCRIPTOR_FIXTURE: &[u8] = include_bytes!("fixtures/descriptor_matrix.bin");
+const KEYPOINT_FIXTURE: &[u8] = include_bytes!("fixtures/keypoints.bin");
+
+fn fixture_descriptors() -> DescriptorMatrix {
+    DescriptorMatrix::new(2, 3, vec![1, 2, 3, 4, 5, 6]).unwrap()
+}
+
+fn fixture_keypoints() -> Vec<KeyPointData> {
+    vec![
+        KeyPointData { x: 1.5, y: -2.0, size: 31.0, angle: 90.0 },
+        KeyPointData { x: 640.25, y: 0.0, size: 7.0, angle: -1.0 },
+    ]
+}
+
+#[test]
+fn encoding_matches_the_golden_bytes() {
+    assert_eq!(codec::encode(&fixture_descriptors()).unwrap(), DESCRIPTOR_FIXTURE);
+    assert_eq!(codec::encode(&fixture_keypoints()).unwrap(), KEYPOINT_FIXTURE);
+    assert_eq!(codec::decode::<DescriptorMatrix>(DESCRIPTOR_FIXTURE).unwrap(), fixture_descriptors());
+    assert_eq!(codec::decode::<Vec<KeyPointData>>(KEYPOINT_FIXTURE).unwrap(), fixture_keypoints());
+}
+
+#[test]
+fn stored_features_round_trip_exactly() {
+    let feature = common::feature("f", "cam", 1_700_000_000, 9);
+    let keypoints: Vec<KeyPointData> = codec::decode(&codec::encode(&feature.keypoints).unwrap()).unwrap();
+    let descriptors: DescriptorMatrix = codec::decode(&codec::encode(&feature.descriptors).unwrap()).unwrap();
+    assert_eq!(keypoints, feature.keypoints);
+    assert_eq!(descriptors, feature.descriptors);
+}
+
+#[test]
+fn rows_tagged_v1_decode_through_the_v1_layout() {
+    let db = TempDb::new();
+    // v1 stored descriptors as one flat byte vector of 32-byte ORB rows
+    let flat: Vec<u8> = (0..64).collect();
+    let conn = Connection::open(db.path()).unwrap();
+    for (id, version) in [("old", 1), ("future", 99)] {
+        conn.execute(
+            "INSERT INTO image_features (id, keypoints, descriptors, created_at_utc, camera_id, feature_version)
+            VALUES (?1, ?2, ?3, '2023-01-01T00:00:00Z', 'cam', ?4)",
+            params![id, codec::encode(&fixture_keypoints()).unwrap(), codec::encode(&flat).unwrap(), version],
+        )
+        .unwrap();
+    }
+
+    let old = get_feature_by_id("old", db.path()).unwrap().unwrap();
+    assert_eq!(old.descriptors, DescriptorMatrix::new(2, 32, flat).unwrap());
+    assert_eq!(old.keypoints, fixture_keypoints());
+    assert!(matches!(
+        get_feature_by_id("future", db.path()),
+        Err(VyuwerError::UnsupportedFeatureVersion(99))
+    ));
+}
+
+#[test]
+fn blob_declaring_an_absurd_length_fails_gracefully() {
+    // A keypoint Vec claiming 2^60 elements, followed by almost nothing
+    let mut keypoints = (1u64 << 60).to_le_bytes().to_vec();
+    keypoints.extend_from_slice(&[0; 16]);
+    assert!(matches!(codec::decode::<Vec<KeyPointData>>(&keypoints), Err(VyuwerError::Codec(_))));
+
+    // rows, cols, then a data length far beyond MAX_BLOB_BYTES
+    let mut descriptors = Vec::new();
+    for value in [2u64, 32, u64::MAX / 2] {
+        descriptors.extend_from_slice(&value.to_le_bytes());
+    }
+    assert!(codec::decode::<DescriptorMatrix>(&descriptors).is_err());
+}
+
+#[test]
+fn well_formed_blob_with_an_inconsistent_shape_is_rejected() {
+    // 3x32 declared, but only 10 data bytes
+    let mut blob = Vec::new();
+    for value in [3u64, 32, 10] {
+        blob.extend_from_slice(&value.to_le_bytes());
+    }
+    blob.extend_from_slice(&[7; 10]);
+    assert!(matches!(codec::decode::<DescriptorMatrix>(&blob), Err(VyuwerError::Codec(_))));
+
+    // Stored under v2 it fails to load instead of panicking in matching
+    let db = TempDb::new();
+    Connection::open(db.path())
+        .unwrap()
+        .execute(
+            "INSERT INTO image_features (id, keypoints, descriptors, created_at_utc, camera_id, feature_version)
+            VALUES ('bad', ?1, ?2, '2023-01-01T00:00:00Z', 'cam', 2)",
+            params![codec::encode(&fixture_keypoints()).unwrap(), blob],
+        )
+        .unwrap();
+    assert!(get_feature_by_id("bad", db.path()).is_err());
+}
+
+// Function to build keypoints the way ORB lays them out: level by level over a 5-level
+// pyramid, in scan order within a level, at sub-pixel positions with arbitrary angles
+fn orb_like_keypoints() -> Vec<KeyPointData> {
+    let mut keypoints = Vec::new();
+    for level in 0..5 {
+        let scale = 1.2f32.powi(level);
+        for i in 0..100u32 {
+            let (row, col) = (i / 20, i % 20);
+            keypoints.push(KeyPointData {
+                x: (col as f32 * 31.0 + (i * 7 % 13) as f32 * 0.37) * scale,
+                y: (row as f32 * 47.0 + (i * 5 % 11) as f32 * 0.21) * scale,
+                size: 31.0 * scale,
+                angle: (i.wrapping_mul(2_654_435_761) % 36_000) as f32 / 100.0,
+            });
+        }
+    }
+    keypoints
+}
+
+#[test]
+fn delta_keypoints_round_trip_and_are_smaller() {
+    let keypoints = orb_like_keypoints();
+    let delta = codec::encode_keypoints_delta(&keypoints);
+    assert_eq!(delta[0], codec::KEYPOINT_FORMAT_DELTA);
+    assert_eq!(codec::decode_flagged_keypoints(&delta).unwrap(), keypoints);
+    let plain = codec::encode(&keypoints).unwrap();
+    assert!(delta.len() < plain.len(), "{} >= {}", delta.len(), plain.len());
+
+    // Exact bit patterns survive, including negative zero and non-finite values
+    let odd = vec![KeyPointData { x: -0.0, y: f32::MAX, size: f32::INFINITY, angle: -1.0 }];
+    let decoded = codec::decode_flagged_keypoints(&codec::encode_keypoints_delta(&odd)).unwrap();
+    assert_eq!(decoded[0].x.to_bits(), (-0.0f32).to_bits());
+    assert_eq!((decoded[0].y, decoded[0].size), (f32::MAX, f32::INFINITY));
+
+    assert!(codec::decode_flagged_keypoints(&delta[..delta.len() / 2]).is_err());
+    assert!(codec::decode_flagged_keypoints(&[9, 0]).is_err());
+}
+
+#[test]
+fn features_stored_under_the_delta_codec_read_back_unchanged() {
+    let db = TempDb::new();
+    let old = common::feature("old", "cam", 1_700_000_000, 1);
+    insert_image_feature(&old, db.path()).unwrap();
+    assert_eq!(keypoint_codec(db.path()).unwrap(), KeypointCodec::Bincode);
+    set_keypoint_codec(KeypointCodec::Delta, db.path()).unwrap();
+
+    let mut new = common::feature("new", "cam", 1_700_000_001, 2);
+    new.keypoints = orb_like_keypoints();
+    new.descriptors = DescriptorMatrix::new(500, 32, vec![3; 500 * 32]).unwrap();
+    insert_image_feature(&new, db.path()).unwrap();
+    assert_eq!(get_feature_by_id("new", db.path()).unwrap(), Some(new));
+    assert_eq!(get_feature_by_id("old", db.path()).unwrap(), Some(old));
+    let version: i64 = Connection::open(db.path())
+        .unwrap()
+        .query_row("SELECT feature_version FROM image_features WHERE id = 'new'", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, 3);
+}
+
+#[test]
+fn descriptor_deltas_round_trip_and_reject_other_shapes() {
+    let base = DescriptorMatrix::new(3, 32, (0..96).map(|i| i as u8).collect()).unwrap();
+    let mut data = base.data.clone();
+    data[40] ^= 0xff;
+    let changed = DescriptorMatrix::new(3, 32, data).unwrap();
+    let delta = codec::encode_descriptor_delta(&changed, &base).unwrap();
+    assert_eq!(codec::decode_descriptor_delta(&delta, &base).unwrap(), changed);
+    assert!(delta.len() < 16, "{} bytes", delta.len());
+
+    let other = DescriptorMatrix::new(2, 32, vec![0; 64]).unwrap();
+    assert!(matches!(codec::encode_descriptor_delta(&other, &base), Err(VyuwerError::InvalidInput(_))));
+    assert!(codec::decode_descriptor_delta(&delta[..delta.len() - 1], &base).is_err());
+}
+
+#[test]
+fn features_stored_as_deltas_read_back_exactly_and_outlive_their_base() {
+    let db = TempDb::new();
+    let base = common::feature("base", "cam", 1_700_000_000, 5);
+    insert_image_feature(&base, db.path()).unwrap();
+    let mut same = common::feature("same", "cam", 1_700_000_001, 5);
+    same.keypoints[0].x = 9.5;
+    assert!(insert_image_feature_delta(&same, "base", db.path()).unwrap());
+    assert_eq!(get_feature_by_id("same", db.path()).unwrap(), Some(same.clone()));
+    let conn = Connection::open(db.path()).unwrap();
+    let size = |id: &str| -> i64 {
+        conn.query_row("SELECT length(descriptors) FROM image_features WHERE id = ?1", params![id], |row| row.get(0))
+            .unwrap()
+    };
+    assert!(size("same") < size("base"), "{} >= {}", size("same"), size("base"));
+
+    // No suitable base: another shape, another camera, or none at all
+    let mut wide = common::feature("wide", "cam", 1_700_000_002, 5);
+    wide.keypoints.truncate(2);
+    wide.descriptors = DescriptorMatrix::new(2, 32, vec![7; 64]).unwrap();
+    assert!(!insert_image_feature_delta(&wide, "base", db.path()).unwrap());
+    let elsewhere = common::feature("elsewhere", "other", 1_700_000_003, 5);
+    assert!(!insert_image_feature_delta(&elsewhere, "base", db.path()).unwrap());
+    let orphan = common::feature("orphan", "cam", 1_700_000_004, 5);
+    assert!(!insert_image_feature_delta(&orphan, "missing", db.path()).unwrap());
+    assert_eq!(get_feature_by_id("wide", db.path()).unwrap(), Some(wide));
+
+    // Deleting the base rewrites the delta in full first
+    let start = common::feature("x", "cam", 1_700_000_000, 0).created_at_utc;
+    assert_eq!(delete_features_in_range("cam", &start, &start, db.path()).unwrap(), 1);
+    assert_eq!(get_feature_by_id("same", db.path()).unwrap(), Some(same));
+    let (version, base_id): (i64, Option<String>) = conn
+        .query_row("SELECT feature_version, base_feature_id FROM image_features WHERE id = 'same'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((version, base_id), (3, None));
+}
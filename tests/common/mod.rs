@@ -0,0 +1,64 @@
+// Shared fixtures for the integration tests; each test binary uses a different subset
+#![allow(dead_code)]
+
+use tempfile::TempDir;
+use vyuwer_rust::model::{self, DescriptorMatrix, ImageFeature, KeyPointData};
+use vyuwer_rust::timestamp::format_unix_millis;
+
+// A fresh, fully set-up database file in its own temp directory, removed on drop
+pub struct TempDb {
+    // Held for its Drop, which deletes the directory and the database in it
+    _dir: TempDir,
+    path: String,
+}
+
+impl TempDb {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("vyuwer.db").to_string_lossy().into_owned();
+        model::setup_schema(&path).expect("set up schema");
+        TempDb { _dir: dir, path }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Function to insert `n` features for `camera_id`, one second apart, and return them
+    pub fn seed_features(&self, n: usize, camera_id: &str) -> Vec<ImageFeature> {
+        (0..n)
+            .map(|i| {
+                let feature = feature(&format!("{camera_id}-{i}"), camera_id, 1_700_000_000 + i as i64, i as u8);
+                model::insert_image_feature(&feature, &self.path).expect("insert feature");
+                feature
+            })
+            .collect()
+    }
+}
+
+// Function to build a feature with four 32-byte descriptors derived from `seed`
+pub fn feature(id: &str, camera_id: &str, unix_secs: i64, seed: u8) -> ImageFeature {
+    let data = (0..4 * 32).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect();
+    ImageFeature {
+        id: id.to_string(),
+        keypoints: (0..4)
+            .map(|i| KeyPointData {
+                x: i as f32,
+                y: i as f32,
+                size: 31.0,
+                angle: 0.0,
+            })
+            .collect(),
+        descriptors: DescriptorMatrix::new(4, 32, data).expect("4x32 descriptors"),
+        motion_mean: 2.0,
+        motion_std: 0.5,
+        created_at_utc: format_unix_millis(unix_secs * 1000),
+        img_filename: None,
+        camera_id: camera_id.to_string(),
+        phash: None,
+        frame_size: None,
+        extraction_params: None,
+        sharpness: None,
+        roi: None,
+    }
+}
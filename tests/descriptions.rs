@@ -0,0 +1,174 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{feature, TempDb};
+use rusqlite::Connection;
+use vyuwer_rust::model::{
+    anomalies_by_severity, description_repeats, image_description_table, insert_image_description,
+    insert_image_description_with_cooldown, insert_image_feature, poll_new_anomalies, relink_by_filename,
+    ImageDescription,
+};
+
+fn describe(db: &TempDb, image_name: &str, datetime: &str, anomaly: Option<&str>) {
+    let description = ImageDescription {
+        image_name: image_name.to_string(),
+        datetime: datetime.to_string(),
+        camera_id: "cam".to_string(),
+        anomaly: anomaly.map(str::to_string),
+    };
+    insert_image_description(&description, db.path()).unwrap();
+}
+
+#[test]
+fn advancing_the_cursor_skips_seen_anomalies() {
+    let db = TempDb::new();
+    describe(&db, "a.png", "2024-06-12T12:00:00Z", Some("scene_change"));
+    describe(&db, "b.png", "2024-06-12T12:01:00Z", None);
+    describe(&db, "c.png", "2024-06-12T12:02:00Z", Some("tampering"));
+
+    let first = poll_new_anomalies("cam", None, db.path()).unwrap();
+    let names: Vec<&str> = first.anomalies.iter().map(|d| d.image_name.as_str()).collect();
+    assert_eq!(names, ["a.png", "c.png"]);
+    assert_eq!(first.cursor.as_ref().unwrap().datetime, "2024-06-12T12:02:00Z");
+
+    let idle = poll_new_anomalies("cam", first.cursor.as_ref(), db.path()).unwrap();
+    assert!(idle.anomalies.is_empty());
+    assert_eq!(idle.cursor, first.cursor);
+
+    describe(&db, "d.png", "2024-06-12T12:03:00.250Z", Some("scene_change"));
+    let next = poll_new_anomalies("cam", idle.cursor.as_ref(), db.path()).unwrap();
+    let names: Vec<&str> = next.anomalies.iter().map(|d| d.image_name.as_str()).collect();
+    assert_eq!(names, ["d.png"]);
+}
+
+#[test]
+fn anomaly_sharing_the_cursor_timestamp_is_still_delivered() {
+    let db = TempDb::new();
+    describe(&db, "a.png", "2024-06-12T12:00:00.500Z", Some("scene_change"));
+    let first = poll_new_anomalies("cam", None, db.path()).unwrap();
+    assert_eq!(first.anomalies.len(), 1);
+
+    // Same instant, written after the poll, in a different string format
+    describe(&db, "b.png", "2024-06-12T12:00:00.5Z", Some("tampering"));
+    let next = poll_new_anomalies("cam", first.cursor.as_ref(), db.path()).unwrap();
+    let names: Vec<&str> = next.anomalies.iter().map(|d| d.image_name.as_str()).collect();
+    assert_eq!(names, ["b.png"]);
+    assert!(poll_new_anomalies("cam", next.cursor.as_ref(), db.path()).unwrap().anomalies.is_empty());
+}
+
+// Function to describe a "cam" frame through the cooldown path; returns whether a row was added
+fn describe_with_cooldown(db: &TempDb, image_name: &str, datetime: &str, anomaly: Option<&str>) -> bool {
+    let description = ImageDescription {
+        image_name: image_name.to_string(),
+        datetime: datetime.to_string(),
+        camera_id: "cam".to_string(),
+        anomaly: anomaly.map(str::to_string),
+    };
+    insert_image_description_with_cooldown(&description, Duration::from_secs(60), db.path()).unwrap()
+}
+
+#[test]
+fn repeated_anomalies_within_the_cooldown_collapse_into_one_row() {
+    let db = TempDb::new();
+    let detail = |ratio: f64| format!(r#"{{"kind":"SceneChange","match_ratio":{ratio},"confidence":0.5}}"#);
+    assert!(describe_with_cooldown(&db, "a.png", "2024-06-12T12:00:00Z", Some(&detail(0.1))));
+    assert!(!describe_with_cooldown(&db, "b.png", "2024-06-12T12:00:40Z", Some(&detail(0.2))));
+    // Within the cooldown of the last sighting, though not of the first
+    assert!(!describe_with_cooldown(&db, "c.png", "2024-06-12T12:01:30Z", Some(&detail(0.1))));
+
+    let anomalies = poll_new_anomalies("cam", None, db.path()).unwrap().anomalies;
+    assert_eq!(anomalies.iter().map(|d| d.image_name.as_str()).collect::<Vec<_>>(), ["a.png"]);
+    assert_eq!(description_repeats("a.png", db.path()).unwrap(), Some((3, "2024-06-12T12:01:30Z".to_string())));
+}
+
+#[test]
+fn cooldown_keeps_different_or_late_anomalies() {
+    let db = TempDb::new();
+    assert!(describe_with_cooldown(&db, "a.png", "2024-06-12T12:00:00Z", Some("scene_change")));
+    assert!(describe_with_cooldown(&db, "b.png", "2024-06-12T12:00:10Z", Some("tampering")));
+    assert!(describe_with_cooldown(&db, "c.png", "2024-06-12T12:00:20Z", None));
+    assert!(describe_with_cooldown(&db, "d.png", "2024-06-12T12:00:30Z", Some("tampering")));
+    assert!(describe_with_cooldown(&db, "e.png", "2024-06-12T12:05:00Z", Some("tampering")));
+    assert_eq!(description_repeats("d.png", db.path()).unwrap(), Some((1, "2024-06-12T12:00:30Z".to_string())));
+}
+
+#[test]
+fn relinking_fills_in_the_feature_with_the_same_filename() {
+    let db = TempDb::new();
+    let mut stored = feature("f1", "cam", 1_700_000_000, 1);
+    stored.img_filename = Some("a.png".to_string());
+    insert_image_feature(&stored, db.path()).unwrap();
+    describe(&db, "a.png", "2024-06-12T12:00:00Z", None);
+    describe(&db, "orphan.png", "2024-06-12T12:01:00Z", None);
+
+    assert_eq!(relink_by_filename(db.path()).unwrap(), 1);
+    let conn = Connection::open(db.path()).unwrap();
+    let link = |name: &str| -> Option<String> {
+        conn.query_row("SELECT feature_id FROM image_description WHERE image_name = ?1", [name], |row| row.get(0))
+            .unwrap()
+    };
+    assert_eq!(link("a.png").as_deref(), Some("f1"));
+    assert_eq!(link("orphan.png"), None);
+    // Already linked rows are not touched again
+    assert_eq!(relink_by_filename(db.path()).unwrap(), 0);
+}
+
+fn detail(confidence: f64) -> String {
+    format!(r#"{{"kind":"SceneChange","match_ratio":0.1,"confidence":{confidence}}}"#)
+}
+
+#[test]
+fn anomalies_sort_by_severity_worst_first() {
+    let db = TempDb::new();
+    describe(&db, "mild.png", "2024-06-12T12:00:00Z", Some(&detail(0.2)));
+    describe(&db, "normal.png", "2024-06-12T12:00:01Z", None);
+    describe(&db, "manual.png", "2024-06-12T12:00:02Z", Some("operator flagged"));
+    describe(&db, "worst.png", "2024-06-12T12:00:03Z", Some(&detail(0.9)));
+    describe(&db, "medium.png", "2024-06-12T12:00:04Z", Some(&detail(0.5)));
+    describe(&db, "medium-later.png", "2024-06-12T12:00:05Z", Some(&detail(0.5)));
+
+    let names: Vec<String> =
+        anomalies_by_severity("cam", db.path()).unwrap().into_iter().map(|d| d.image_name).collect();
+    assert_eq!(names, ["worst.png", "medium.png", "medium-later.png", "mild.png", "manual.png"]);
+    assert!(anomalies_by_severity("other", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn severity_is_backfilled_when_the_column_is_added() {
+    let db = TempDb::new();
+    let conn = Connection::open(db.path()).unwrap();
+    conn.execute_batch(
+        "DROP INDEX image_description_camera_severity;
+        ALTER TABLE image_description DROP COLUMN severity;",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO image_description (image_name, datetime, camera_id, anomaly) VALUES
+            ('low.png', '2024-06-12T12:00:00Z', 'cam', ?1), ('high.png', '2024-06-12T12:00:01Z', 'cam', ?2)",
+        [detail(0.1), detail(0.7)],
+    )
+    .unwrap();
+
+    image_description_table(db.path()).unwrap();
+    let names: Vec<String> =
+        anomalies_by_severity("cam", db.path()).unwrap().into_iter().map(|d| d.image_name).collect();
+    assert_eq!(names, ["high.png", "low.png"]);
+}
+
+#[test]
+fn an_escalating_repeat_raises_the_severity_of_its_row() {
+    let db = TempDb::new();
+    describe(&db, "steady.png", "2024-06-12T11:00:00Z", Some(&detail(0.6)));
+    assert!(describe_with_cooldown(&db, "storm.png", "2024-06-12T12:00:00Z", Some(&detail(0.2))));
+    let names = |db: &TempDb| -> Vec<String> {
+        anomalies_by_severity("cam", db.path()).unwrap().into_iter().map(|d| d.image_name).collect()
+    };
+    assert_eq!(names(&db), ["steady.png", "storm.png"]);
+
+    assert!(!describe_with_cooldown(&db, "storm-2.png", "2024-06-12T12:00:30Z", Some(&detail(0.9))));
+    assert_eq!(names(&db), ["storm.png", "steady.png"]);
+    // A milder repeat does not lower it again
+    assert!(!describe_with_cooldown(&db, "storm-3.png", "2024-06-12T12:00:50Z", Some(&detail(0.1))));
+    assert_eq!(names(&db), ["storm.png", "steady.png"]);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{feature, TempDb};
+use rusqlite::{params, Connection};
+use vyuwer_rust::codec;
+use vyuwer_rust::layout::{descriptor_rows, migrate_to_normalized_descriptors, DESCRIPTOR_READ_CHUNK_ROWS};
+use vyuwer_rust::model::{insert_image_feature, DescriptorMatrix, ImageFeature};
+use vyuwer_rust::VyuwerError;
+
+// Function to build a feature with `rows` distinct 32-byte descriptors
+fn wide_feature(id: &str, rows: usize) -> ImageFeature {
+    let mut wide = feature(id, "cam", 1_700_000_000, 0);
+    let data = (0..rows * 32).map(|i| (i / 32 + i % 32) as u8).collect();
+    wide.descriptors = DescriptorMatrix::new(rows, 32, data).unwrap();
+    wide.keypoints = vec![wide.keypoints[0].clone(); rows];
+    wide
+}
+
+fn streamed(feature_id: &str, db: &TempDb) -> Vec<Vec<u8>> {
+    descriptor_rows(feature_id, db.path()).unwrap().collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn streaming_yields_every_row_in_both_layouts() {
+    let db = TempDb::new();
+    // Spans several incremental reads
+    let stored = wide_feature("wide", DESCRIPTOR_READ_CHUNK_ROWS * 2 + 5);
+    insert_image_feature(&stored, db.path()).unwrap();
+
+    let rows = descriptor_rows("wide", db.path()).unwrap();
+    assert_eq!((rows.rows_remaining(), rows.cols()), (stored.descriptors.rows, 32));
+    let expected: Vec<Vec<u8>> = stored.descriptors.row_iter().map(<[u8]>::to_vec).collect();
+    assert_eq!(streamed("wide", &db), expected);
+
+    migrate_to_normalized_descriptors(db.path()).unwrap();
+    assert_eq!(streamed("wide", &db), expected);
+    assert!(matches!(descriptor_rows("missing", db.path()), Err(VyuwerError::FeatureNotFound(_))));
+}
+
+#[test]
+fn version_one_blobs_stream_as_orb_rows() {
+    let db = TempDb::new();
+    let flat: Vec<u8> = (0..64).collect();
+    Connection::open(db.path())
+        .unwrap()
+        .execute(
+            "INSERT INTO image_features (id, keypoints, descriptors, created_at_utc, camera_id, feature_version)
+            VALUES ('old', ?1, ?2, '2023-01-01T00:00:00Z', 'cam', 1)",
+            params![codec::encode(&Vec::<u8>::new()).unwrap(), codec::encode(&flat).unwrap()],
+        )
+        .unwrap();
+    assert_eq!(streamed("old", &db), [flat[..32].to_vec(), flat[32..].to_vec()]);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::descriptor_sets::{
+    descriptor_kind, get_descriptors, infer_descriptor_kind, match_stored_descriptors, set_descriptor_kind,
+    store_descriptors, DescriptorKind,
+};
+use vyuwer_rust::matching::MatchParams;
+use vyuwer_rust::model::{insert_image_feature, DescriptorMatrix};
+use vyuwer_rust::VyuwerError;
+
+// Function to build `rows` 128-byte SIFT-like rows, each far from the others
+fn sift(rows: usize, offset: u8) -> DescriptorMatrix {
+    let data = (0..rows * 128).map(|i| ((i / 128) as u8).wrapping_mul(40).wrapping_add(offset)).collect();
+    DescriptorMatrix::new(rows, 128, data).unwrap()
+}
+
+#[test]
+fn orb_and_sift_sets_are_stored_and_read_independently() {
+    let db = TempDb::new();
+    let stored = feature("f", "cam", 1_700_000_000, 3);
+    insert_image_feature(&stored, db.path()).unwrap();
+    insert_image_feature(&feature("g", "cam", 1_700_000_001, 3), db.path()).unwrap();
+
+    // ORB defaults to the feature's own descriptors until a separate set is stored
+    assert_eq!(get_descriptors("f", DescriptorKind::Orb, db.path()).unwrap(), Some(stored.descriptors.clone()));
+    assert_eq!(get_descriptors("f", DescriptorKind::Sift, db.path()).unwrap(), None);
+
+    store_descriptors("f", DescriptorKind::Sift, &sift(4, 0), db.path()).unwrap();
+    let orb = DescriptorMatrix::new(1, 32, vec![7; 32]).unwrap();
+    store_descriptors("f", DescriptorKind::Orb, &orb, db.path()).unwrap();
+    assert_eq!(get_descriptors("f", DescriptorKind::Sift, db.path()).unwrap(), Some(sift(4, 0)));
+    assert_eq!(get_descriptors("f", DescriptorKind::Orb, db.path()).unwrap(), Some(orb));
+
+    store_descriptors("g", DescriptorKind::Sift, &sift(4, 1), db.path()).unwrap();
+    let params = MatchParams::default();
+    let result = match_stored_descriptors("f", "g", DescriptorKind::Sift, &params, db.path()).unwrap();
+    assert_eq!(result.good_matches, 4);
+    assert!(matches!(
+        store_descriptors("missing", DescriptorKind::Sift, &sift(1, 0), db.path()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+}
+
+#[test]
+fn descriptor_kind_is_inferred_from_width_when_not_recorded() {
+    assert_eq!(infer_descriptor_kind(32), DescriptorKind::Orb);
+    assert_eq!(infer_descriptor_kind(61), DescriptorKind::Akaze);
+    assert_eq!(infer_descriptor_kind(128), DescriptorKind::Sift);
+
+    let db = TempDb::new();
+    let mut legacy = feature("s", "cam", 1_700_000_000, 0);
+    legacy.descriptors = sift(4, 0);
+    insert_image_feature(&legacy, db.path()).unwrap();
+    assert_eq!(descriptor_kind("s", db.path()).unwrap(), Some(DescriptorKind::Sift));
+    assert_eq!(descriptor_kind("missing", db.path()).unwrap(), None);
+
+    // The own descriptors serve as the SIFT set without a separate one being stored
+    assert_eq!(get_descriptors("s", DescriptorKind::Sift, db.path()).unwrap(), Some(sift(4, 0)));
+    assert_eq!(get_descriptors("s", DescriptorKind::Orb, db.path()).unwrap(), None);
+
+    // A recorded kind wins over the width
+    set_descriptor_kind("s", DescriptorKind::Akaze, db.path()).unwrap();
+    assert_eq!(descriptor_kind("s", db.path()).unwrap(), Some(DescriptorKind::Akaze));
+    assert!(matches!(
+        set_descriptor_kind("missing", DescriptorKind::Orb, db.path()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::db::DurabilityMode;
+use vyuwer_rust::Database;
+
+// Function to read back (journal_mode, synchronous) for a handle
+fn pragmas(db: &Database) -> (String, i64) {
+    let conn = db.connection();
+    let journal_mode = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+    let synchronous = conn.query_row("PRAGMA synchronous", [], |row| row.get(0)).unwrap();
+    (journal_mode, synchronous)
+}
+
+#[test]
+fn each_mode_applies_its_pragmas() {
+    let expected = [
+        (DurabilityMode::Safe, "delete", 2),
+        (DurabilityMode::Balanced, "wal", 1),
+        (DurabilityMode::Fast, "wal", 0),
+    ];
+    for (mode, journal_mode, synchronous) in expected {
+        let file = TempDb::new();
+        let mut db = Database::open_with_durability(file.path(), mode).unwrap();
+        assert_eq!(pragmas(&db), (journal_mode.to_string(), synchronous), "{mode:?}");
+        db.insert_image_feature(&feature("f", "cam", 1_700_000_000, 1)).unwrap();
+        assert!(db.get_image_feature("cam").unwrap().is_some());
+    }
+}
+
+#[test]
+fn safe_mode_turns_wal_back_off() {
+    let file = TempDb::new();
+    drop(Database::open_with_durability(file.path(), DurabilityMode::Balanced).unwrap());
+    // WAL is a property of the file, so a plain open still sees it
+    assert_eq!(pragmas(&Database::open(file.path()).unwrap()).0, "wal");
+    let db = Database::open_with_durability(file.path(), DurabilityMode::Safe).unwrap();
+    assert_eq!(pragmas(&db), ("delete".to_string(), 2));
+}
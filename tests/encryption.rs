@@ -0,0 +1,21 @@
+#![cfg(feature = "sqlcipher")]
+
+mod common;
+
+use common::feature;
+use vyuwer_rust::Database;
+
+#[test]
+fn encrypted_database_needs_its_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("secret.db").to_string_lossy().into_owned();
+
+    let mut db = Database::open_encrypted(&path, "correct horse").unwrap();
+    db.insert_image_feature(&feature("f", "cam", 1_700_000_000, 0)).unwrap();
+    drop(db);
+
+    assert!(Database::open(&path).is_err());
+    assert!(Database::open_encrypted(&path, "wrong key").is_err());
+    let mut reopened = Database::open_encrypted(&path, "correct horse").unwrap();
+    assert_eq!(reopened.get_image_feature("cam").unwrap().unwrap().id, "f");
+}
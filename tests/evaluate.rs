@@ -0,0 +1,46 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::{feature, TempDb};
+use opencv::{
+    core::{Mat, Scalar, CV_8UC1},
+    prelude::*,
+};
+use vyuwer_rust::anomaly::{evaluate_frame, AnomalyKind};
+use vyuwer_rust::extract::{extract_orb_features, OrbParams};
+use vyuwer_rust::model::{get_camera_features, insert_image_feature, ImageFeature};
+
+// Function to build a 256×256 noise frame; each seed gives an unrelated texture
+fn noise_frame(seed: u64) -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(256, 256, CV_8UC1, Scalar::all(0.0)).unwrap();
+    let mut state = seed | 1;
+    for r in 0..256 {
+        for c in 0..256 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *frame.at_2d_mut::<u8>(r, c).unwrap() = state as u8;
+        }
+    }
+    frame
+}
+
+#[test]
+fn a_different_frame_is_flagged_without_being_stored() {
+    let db = TempDb::new();
+    let scene = noise_frame(0x2545_F491_4F6C_DD1D);
+    let (keypoints, descriptors) = extract_orb_features(&scene, &OrbParams::default()).unwrap();
+    let baseline = ImageFeature {
+        keypoints,
+        descriptors,
+        ..feature("baseline", "cam", 1_700_000_000, 0)
+    };
+    insert_image_feature(&baseline, db.path()).unwrap();
+
+    assert_eq!(evaluate_frame(&scene, "cam", db.path()).unwrap(), None);
+    let detail = evaluate_frame(&noise_frame(0x9E37_79B9_7F4A_7C15), "cam", db.path()).unwrap().unwrap();
+    assert_eq!(detail.kind, AnomalyKind::SceneChange);
+    assert!(detail.confidence > 0.0);
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 1);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::fs;
+
+use common::{feature, TempDb};
+use serde_json::Value;
+use vyuwer_rust::camera::set_camera_location;
+use vyuwer_rust::export::{anomalies_geojson, export_descriptors_npy, export_match_dataset, MATCH_DATASET_COLUMNS};
+use vyuwer_rust::model::{insert_image_description, insert_image_feature, ImageDescription};
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn npy_file_has_a_spec_header_and_the_descriptor_shape() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(1, "cam");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cam-0.npy");
+    export_descriptors_npy("cam-0", db.path(), path.to_str().unwrap()).unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_len = usize::from(u16::from_le_bytes([bytes[8], bytes[9]]));
+    // The data starts 64-byte aligned, right after a newline-terminated header
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    assert!(header.ends_with('\n'));
+    assert!(header.contains("'descr': '|u1'"));
+    assert!(header.contains("'fortran_order': False"));
+    assert!(header.contains("'shape': (4, 32)"));
+    assert_eq!(&bytes[10 + header_len..], seeded[0].descriptors.data.as_slice());
+
+    assert!(matches!(
+        export_descriptors_npy("missing", db.path(), path.to_str().unwrap()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+}
+
+#[test]
+fn geojson_has_one_point_per_located_anomaly() {
+    let db = TempDb::new();
+    set_camera_location("gate", 12.97, 77.59, db.path()).unwrap();
+    set_camera_location("lobby", 12.98, 77.60, db.path()).unwrap();
+    for (image_name, camera_id, anomaly) in [
+        ("g1.png", "gate", Some("scene change")),
+        ("g2.png", "gate", None),
+        ("l1.png", "lobby", Some("tampering")),
+        ("u1.png", "unmapped", Some("scene change")),
+    ] {
+        let description = ImageDescription {
+            image_name: image_name.to_string(),
+            datetime: "2024-01-01T00:00:00Z".to_string(),
+            camera_id: camera_id.to_string(),
+            anomaly: anomaly.map(str::to_string),
+        };
+        insert_image_description(&description, db.path()).unwrap();
+    }
+
+    let geojson: Value = serde_json::from_str(&anomalies_geojson(db.path()).unwrap()).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    let gate = &features[0];
+    assert_eq!(gate["type"], "Feature");
+    assert_eq!(gate["geometry"]["type"], "Point");
+    // Positions are [longitude, latitude]
+    assert_eq!(gate["geometry"]["coordinates"], serde_json::json!([77.59, 12.97]));
+    assert_eq!(gate["properties"]["camera_id"], "gate");
+    assert_eq!(gate["properties"]["anomaly"], "scene change");
+    assert_eq!(features[1]["properties"]["camera_id"], "lobby");
+}
+
+#[test]
+fn match_dataset_has_one_labeled_row_per_pair() {
+    let db = TempDb::new();
+    for (id, seed) in [("a", 3), ("a,again", 3), ("b", 200)] {
+        insert_image_feature(&feature(id, "cam", 1_700_000_000, seed), db.path()).unwrap();
+    }
+    let pairs = [
+        ("a".to_string(), "a,again".to_string(), true),
+        ("a".to_string(), "b".to_string(), false),
+        ("b".to_string(), "b".to_string(), true),
+    ];
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pairs.csv");
+    export_match_dataset(&pairs, db.path(), path.to_str().unwrap()).unwrap();
+
+    let csv = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 1 + pairs.len());
+    assert_eq!(lines[0], MATCH_DATASET_COLUMNS);
+    let header: Vec<&str> = lines[0].split(',').collect();
+    assert_eq!(header.last(), Some(&"same_scene"));
+    // Ids holding a comma are quoted
+    assert!(lines[1].starts_with("a,\"a,again\",4,4,4,1,1,0,0,0,"), "{}", lines[1]);
+    let labels: Vec<&str> = lines[1..].iter().map(|line| line.rsplit(',').next().unwrap()).collect();
+    assert_eq!(labels, ["1", "0", "1"]);
+    for line in &lines[2..] {
+        assert_eq!(line.split(',').count(), header.len(), "{line}");
+    }
+
+    let missing = [("a".to_string(), "missing".to_string(), false)];
+    assert!(matches!(
+        export_match_dataset(&missing, db.path(), path.to_str().unwrap()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "opencv")]
+
+use opencv::{
+    core::{Mat, Rect, Scalar, Size, Vec3b, VecN, Vector, CV_8UC1, CV_8UC3},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+use vyuwer_rust::descriptor_sets::DescriptorKind;
+use vyuwer_rust::extract::{
+    brightness_delta, check_opencv, extract_features, extract_orb_features, extract_orb_features_masked,
+    extract_tiled, load_mask, sharpness, ColorMode, Detector, OrbParams,
+};
+use vyuwer_rust::model::DescriptorMatrix;
+use vyuwer_rust::VyuwerError;
+
+// Function to build a 256×256 BGR frame of coloured noise
+fn color_frame() -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(256, 256, CV_8UC3, Scalar::all(0.0)).unwrap();
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    for r in 0..256 {
+        for c in 0..256 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let [b, g, red, ..] = state.to_le_bytes();
+            *frame.at_2d_mut::<Vec3b>(r, c).unwrap() = VecN([b, g, red]);
+        }
+    }
+    frame
+}
+
+#[test]
+fn every_color_mode_finds_keypoints() {
+    let frame = color_frame();
+    for color_mode in [ColorMode::Gray, ColorMode::HsvValue, ColorMode::Rgb] {
+        let params = OrbParams {
+            color_mode,
+            ..OrbParams::default()
+        };
+        let (keypoints, descriptors) = extract_orb_features(&frame, &params).unwrap();
+        assert!(!keypoints.is_empty(), "no keypoints in {color_mode:?} mode");
+        assert_eq!(descriptors.rows, keypoints.len());
+    }
+}
+
+#[test]
+fn blurring_lowers_sharpness() {
+    let frame = color_frame();
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur_def(&frame, &mut blurred, Size::new(9, 9), 3.0).unwrap();
+    assert!(sharpness(&frame).unwrap() > sharpness(&blurred).unwrap());
+}
+
+#[test]
+fn empty_descriptors_convert_to_and_from_an_empty_mat() {
+    let mat = DescriptorMatrix::default().to_mat().unwrap();
+    assert!(mat.empty());
+    assert_eq!(DescriptorMatrix::from_mat(&mat).unwrap(), DescriptorMatrix::default());
+
+    // A featureless frame extracts to an empty matrix rather than an error
+    let blank = Mat::new_rows_cols_with_default(64, 64, CV_8UC3, Scalar::all(128.0)).unwrap();
+    let (keypoints, descriptors) = extract_orb_features(&blank, &OrbParams::default()).unwrap();
+    assert!(keypoints.is_empty());
+    assert!(descriptors.is_empty());
+}
+
+#[test]
+fn rgb_mode_sees_edges_that_grayscale_loses() {
+    // A checkerboard of pure blue and dark red, which have the same luminance (29)
+    let mut frame = Mat::new_rows_cols_with_default(256, 256, CV_8UC3, Scalar::all(0.0)).unwrap();
+    for r in 0..256 {
+        for c in 0..256 {
+            let pixel = if (r / 32 + c / 32) % 2 == 0 { [255, 0, 0] } else { [0, 0, 97] };
+            *frame.at_2d_mut::<Vec3b>(r, c).unwrap() = VecN(pixel);
+        }
+    }
+    let extract = |color_mode| {
+        let params = OrbParams {
+            color_mode,
+            ..OrbParams::default()
+        };
+        extract_orb_features(&frame, &params).unwrap()
+    };
+    assert!(extract(ColorMode::Gray).0.is_empty());
+    let (keypoints, descriptors) = extract(ColorMode::Rgb);
+    assert!(!keypoints.is_empty());
+    assert_eq!(descriptors.rows, keypoints.len());
+}
+
+#[test]
+fn uniform_brightness_offset_is_a_large_delta() {
+    let frame = color_frame();
+    let mut brighter = Mat::default();
+    frame.convert_to(&mut brighter, -1, 0.5, 100.0).unwrap();
+    let mut darker = Mat::default();
+    frame.convert_to(&mut darker, -1, 0.5, 0.0).unwrap();
+    let delta = brightness_delta(&darker, &brighter).unwrap();
+    assert!((delta - 100.0).abs() < 1.0, "delta {delta}");
+    assert!((brightness_delta(&brighter, &darker).unwrap() + delta).abs() < 1e-9);
+    assert_eq!(brightness_delta(&frame, &frame).unwrap(), 0.0);
+}
+
+#[test]
+fn every_detector_finds_features_of_its_kind() {
+    let frame = color_frame();
+    let detectors = [
+        (Detector::Orb(OrbParams::default()), DescriptorKind::Orb, 32),
+        (Detector::Akaze(Default::default()), DescriptorKind::Akaze, 61),
+        (Detector::Sift(Default::default()), DescriptorKind::Sift, 128),
+        (Detector::Fast(Default::default()), DescriptorKind::Orb, 32),
+    ];
+    for (detector, expected_kind, width) in detectors {
+        let (keypoints, descriptors, kind) = extract_features(&frame, &detector).unwrap();
+        assert_eq!(kind, expected_kind, "{detector:?}");
+        assert!(!keypoints.is_empty(), "{detector:?}");
+        assert_eq!(descriptors.rows, keypoints.len(), "{detector:?}");
+        assert_eq!(descriptors.cols, width, "{detector:?}");
+    }
+}
+
+#[test]
+fn two_by_two_tiling_yields_a_feature_per_textured_tile() {
+    let frame = color_frame();
+    let features = extract_tiled(&frame, (2, 2), &Detector::default()).unwrap();
+    assert!(!features.is_empty() && features.len() <= 4);
+
+    let mut rois: Vec<_> = features.iter().map(|f| f.roi.expect("tile roi")).collect();
+    rois.dedup();
+    assert_eq!(rois.len(), features.len());
+    for feature in &features {
+        let roi = feature.roi.unwrap();
+        assert_eq!((roi.width, roi.height), (128, 128));
+        let inside = |v: f32, start: u32, len: u32| v >= start as f32 && v < (start + len) as f32;
+        assert!(feature.keypoints.iter().all(|kp| inside(kp.x, roi.x, roi.width) && inside(kp.y, roi.y, roi.height)));
+    }
+    assert!(extract_tiled(&frame, (0, 2), &Detector::default()).is_err());
+}
+
+#[test]
+fn linked_opencv_reports_a_version_and_works() {
+    let info = check_opencv().unwrap();
+    assert!(info.version.starts_with(char::is_numeric), "{info:?}");
+    assert!(!info.runtime_version.is_empty());
+    assert!(info.ok, "{info:?}");
+    assert_eq!(info.error, None);
+}
+
+// Function to build a mask of `frame`'s size that keeps only its right half
+fn right_half_mask(frame: &Mat) -> Mat {
+    let mut mask = Mat::new_rows_cols_with_default(frame.rows(), frame.cols(), CV_8UC1, Scalar::all(0.0)).unwrap();
+    let half = Rect::new(frame.cols() / 2, 0, frame.cols() / 2, frame.rows());
+    imgproc::rectangle(&mut mask, half, Scalar::all(255.0), imgproc::FILLED, imgproc::LINE_8, 0).unwrap();
+    mask
+}
+
+#[test]
+fn masking_out_half_the_frame_roughly_halves_the_keypoints() {
+    let frame = color_frame();
+    // A budget the noise cannot fill, so counts follow the unmasked area
+    let params = OrbParams {
+        nfeatures: 100_000,
+        ..OrbParams::default()
+    };
+    let (all, _) = extract_orb_features(&frame, &params).unwrap();
+    let (kept, descriptors) = extract_orb_features_masked(&frame, &right_half_mask(&frame), &params).unwrap();
+    let ratio = kept.len() as f64 / all.len() as f64;
+    assert!((0.3..=0.7).contains(&ratio), "{} of {} keypoints kept", kept.len(), all.len());
+    assert_eq!(descriptors.rows, kept.len());
+    // Coarse pyramid levels see a downscaled mask, so allow a pixel or two at the edge
+    let edge = (frame.cols() / 2) as f32 - 2.0;
+    assert!(kept.iter().all(|kp| kp.x >= edge), "keypoint in the masked half");
+
+    let wrong_size = Mat::new_rows_cols_with_default(128, 128, CV_8UC1, Scalar::all(255.0)).unwrap();
+    let wrong_type = Mat::new_rows_cols_with_default(frame.rows(), frame.cols(), CV_8UC3, Scalar::all(255.0)).unwrap();
+    for mask in [wrong_size, wrong_type] {
+        assert!(matches!(
+            extract_orb_features_masked(&frame, &mask, &params),
+            Err(VyuwerError::InvalidInput(_))
+        ));
+    }
+}
+
+#[test]
+fn masks_load_from_png_as_single_channel() {
+    let frame = color_frame();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mask.png");
+    let path = path.to_str().unwrap();
+    imgcodecs::imwrite(path, &right_half_mask(&frame), &Vector::new()).unwrap();
+
+    let mask = load_mask(path).unwrap();
+    assert_eq!((mask.cols(), mask.rows(), mask.typ()), (frame.cols(), frame.rows(), CV_8UC1));
+    assert!(extract_orb_features_masked(&frame, &mask, &OrbParams::default()).is_ok());
+    assert!(matches!(load_mask(&format!("{path}.missing")), Err(VyuwerError::InvalidInput(_))));
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use common::TempDb;
+use rusqlite::Connection;
+use vyuwer_rust::codec;
+use vyuwer_rust::extract::{ColorMode, OrbParams};
+use vyuwer_rust::model::{
+    backfill_content_hashes, blurry_features, clone_camera, combine_features, features_without_description,
+    find_inconsistent_features, get_camera_features, get_extraction_params, get_image_feature,
+    insert_image_description, insert_image_feature, setup_schema, DescriptorMatrix, ImageDescription,
+};
+use vyuwer_rust::timestamp::now_utc_iso8601;
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn seeded_features_are_counted_per_camera() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(5, "cam_a");
+    db.seed_features(2, "cam_b");
+
+    let stored = get_camera_features("cam_a", db.path()).unwrap();
+    assert_eq!(stored.len(), 5);
+    assert_eq!(stored, seeded);
+    assert_eq!(get_camera_features("cam_b", db.path()).unwrap().len(), 2);
+}
+
+#[test]
+fn non_finite_motion_is_rejected() {
+    let db = TempDb::new();
+    let mut feature = common::feature("nan", "cam", 1_700_000_000, 0);
+    feature.motion_mean = f64::NAN;
+    let err = insert_image_feature(&feature, db.path()).unwrap_err();
+    assert!(matches!(err, VyuwerError::InvalidInput(_)), "{err}");
+
+    feature.motion_mean = 0.0;
+    feature.motion_std = f64::INFINITY;
+    assert!(insert_image_feature(&feature, db.path()).is_err());
+    assert!(get_camera_features("cam", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn cloned_camera_has_same_history_under_fresh_ids() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(3, "src");
+    assert_eq!(clone_camera("src", "dst", false, db.path()).unwrap(), 3);
+
+    let cloned = get_camera_features("dst", db.path()).unwrap();
+    assert_eq!(cloned.len(), 3);
+    for (original, copy) in seeded.iter().zip(&cloned) {
+        assert_ne!(copy.id, original.id);
+        assert_eq!(copy.descriptors, original.descriptors);
+        assert_eq!(copy.created_at_utc, original.created_at_utc);
+    }
+
+    assert!(clone_camera("src", "dst", false, db.path()).is_err());
+    assert_eq!(clone_camera("src", "dst", true, db.path()).unwrap(), 3);
+    assert_eq!(get_camera_features("dst", db.path()).unwrap().len(), 6);
+}
+
+#[test]
+fn mismatched_keypoint_and_descriptor_counts_are_rejected() {
+    let db = TempDb::new();
+    let mut feature = common::feature("bad", "cam", 1_700_000_000, 0);
+    feature.keypoints.pop();
+    assert!(matches!(insert_image_feature(&feature, db.path()), Err(VyuwerError::InvalidInput(_))));
+    assert!(get_camera_features("cam", db.path()).unwrap().is_empty());
+
+    // Rows that bypassed validation are found by the scan
+    db.seed_features(2, "cam");
+    Connection::open(db.path())
+        .unwrap()
+        .execute(
+            "UPDATE image_features SET keypoints = ?1 WHERE id = 'cam-1'",
+            [codec::encode(&feature.keypoints).unwrap()],
+        )
+        .unwrap();
+    assert_eq!(find_inconsistent_features(db.path()).unwrap(), vec!["cam-1".to_string()]);
+}
+
+#[test]
+fn descriptor_buffer_shorter_than_its_shape_is_rejected() {
+    let db = TempDb::new();
+    let mut feature = common::feature("short", "cam", 1_700_000_000, 0);
+    feature.keypoints.truncate(3);
+    feature.descriptors = DescriptorMatrix {
+        rows: 3,
+        cols: 32,
+        data: vec![0; 10],
+    };
+    assert!(matches!(insert_image_feature(&feature, db.path()), Err(VyuwerError::InvalidInput(_))));
+
+    // The same shape written around validation is reported by the scan
+    db.seed_features(2, "cam");
+    let mut blob = Vec::new();
+    for value in [3u64, 32, 10] {
+        blob.extend_from_slice(&value.to_le_bytes());
+    }
+    blob.extend_from_slice(&[0; 10]);
+    Connection::open(db.path())
+        .unwrap()
+        .execute("UPDATE image_features SET descriptors = ?1 WHERE id = 'cam-0'", [blob])
+        .unwrap();
+    assert_eq!(find_inconsistent_features(db.path()).unwrap(), vec!["cam-0".to_string()]);
+}
+
+#[test]
+fn extraction_params_round_trip() {
+    let db = TempDb::new();
+    let params = OrbParams {
+        nfeatures: 1200,
+        scale_factor: 1.5,
+        nlevels: 4,
+        fast_threshold: 12,
+        color_mode: ColorMode::HsvValue,
+    };
+    let mut custom = common::feature("custom", "cam", 1_700_000_000, 0);
+    custom.extraction_params = Some(params.clone());
+    insert_image_feature(&custom, db.path()).unwrap();
+    insert_image_feature(&common::feature("plain", "cam", 1_700_000_001, 0), db.path()).unwrap();
+
+    assert_eq!(get_extraction_params("custom", db.path()).unwrap(), Some(params.clone()));
+    assert_eq!(get_extraction_params("plain", db.path()).unwrap(), None);
+    assert!(matches!(
+        get_extraction_params("missing", db.path()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+    assert_eq!(get_camera_features("cam", db.path()).unwrap()[0].extraction_params, Some(params));
+}
+
+#[test]
+fn equal_timestamps_keep_insertion_order() {
+    let db = TempDb::new();
+    let created_at_utc = now_utc_iso8601();
+    assert!(created_at_utc.contains('.'), "no sub-second precision in {created_at_utc}");
+    // Ids descend so that an id tiebreaker alone would reverse the order
+    let ids: Vec<String> = (0..20).rev().map(|i| format!("f{i:02}")).collect();
+    for id in &ids {
+        let mut f = common::feature(id, "cam", 0, 0);
+        f.created_at_utc = created_at_utc.clone();
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+
+    let stored: Vec<String> = get_camera_features("cam", db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(stored, ids);
+}
+
+#[test]
+fn combining_stacks_descriptors_and_concatenates_keypoints() {
+    let a = common::feature("a", "cam", 1_700_000_000, 1);
+    let mut b = common::feature("b", "cam", 1_700_000_060, 2);
+    b.motion_mean = 4.0;
+
+    let combined = combine_features(&[a.clone(), b.clone()]).unwrap();
+    assert_eq!(combined.descriptors.rows, a.descriptors.rows + b.descriptors.rows);
+    assert_eq!(combined.descriptors.row(a.descriptors.rows), b.descriptors.row(0));
+    assert_eq!(combined.keypoints, [a.keypoints, b.keypoints.clone()].concat());
+    assert_eq!(combined.motion_mean, 3.0);
+    assert_eq!(combined.created_at_utc, b.created_at_utc);
+
+    let mut narrow = b;
+    narrow.descriptors = DescriptorMatrix::new(4, 16, vec![0; 64]).unwrap();
+    assert!(matches!(
+        combine_features(&[combined, narrow]),
+        Err(VyuwerError::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn unclassified_features_are_listed() {
+    let db = TempDb::new();
+    for (i, name) in ["classified.png", "pending.png"].iter().enumerate() {
+        let mut f = common::feature(&format!("f{i}"), "cam", 1_700_000_000 + i as i64, 0);
+        f.img_filename = Some(name.to_string());
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+    let description = ImageDescription {
+        image_name: "classified.png".to_string(),
+        datetime: "2023-11-14T22:13:20Z".to_string(),
+        camera_id: "cam".to_string(),
+        anomaly: None,
+    };
+    insert_image_description(&description, db.path()).unwrap();
+
+    let pending = features_without_description("cam", db.path()).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, "f1");
+    assert_eq!(pending[0].img_filename.as_deref(), Some("pending.png"));
+}
+
+#[test]
+fn blurry_features_are_below_the_threshold() {
+    let db = TempDb::new();
+    for (i, sharpness) in [Some(850.0), Some(12.5), None, Some(40.0)].into_iter().enumerate() {
+        let mut f = common::feature(&format!("f{i}"), "cam", 1_700_000_000 + i as i64, 0);
+        f.sharpness = sharpness;
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+
+    let blurry: Vec<String> = blurry_features("cam", 50.0, db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(blurry, ["f1", "f3"]);
+    assert_eq!(get_camera_features("cam", db.path()).unwrap()[1].sharpness, Some(12.5));
+}
+
+#[test]
+fn content_hash_ignores_the_id_but_not_a_descriptor_byte() {
+    let a = common::feature("a", "cam", 1_700_000_000, 4);
+    let mut b = common::feature("b", "cam", 1_700_000_000, 4);
+    b.motion_mean = 9.0;
+    assert_eq!(a.content_hash(), b.content_hash());
+    // The same instant in another timestamp format is the same content
+    b.created_at_utc = "2023-11-14T22:13:20.000Z".to_string();
+    assert_eq!(a.content_hash(), b.content_hash());
+
+    let mut changed = a.clone();
+    changed.descriptors.data[17] ^= 1;
+    assert_ne!(changed.content_hash(), a.content_hash());
+    let mut moved = a.clone();
+    moved.keypoints[2].x += 0.5;
+    assert_ne!(moved.content_hash(), a.content_hash());
+    let mut elsewhere = a.clone();
+    elsewhere.camera_id = "other".to_string();
+    assert_ne!(elsewhere.content_hash(), a.content_hash());
+}
+
+#[test]
+fn content_hash_is_stored_on_insert_and_backfilled() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(2, "cam");
+    let conn = Connection::open(db.path()).unwrap();
+    let stored = |id: &str| -> Option<Vec<u8>> {
+        conn.query_row("SELECT content_hash FROM image_features WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    };
+    assert_eq!(stored("cam-0"), Some(seeded[0].content_hash().to_vec()));
+
+    conn.execute("UPDATE image_features SET content_hash = NULL WHERE id = 'cam-1'", []).unwrap();
+    assert_eq!(backfill_content_hashes(db.path()).unwrap(), 1);
+    assert_eq!(stored("cam-1"), Some(seeded[1].content_hash().to_vec()));
+    assert_eq!(backfill_content_hashes(db.path()).unwrap(), 0);
+}
+
+#[test]
+fn mixed_timestamp_forms_are_stored_in_time_order() {
+    let db = TempDb::new();
+    // Inserted newest first, so neither the text nor the insertion sequence gives time order
+    let stamps = [
+        ("late", "2024-06-12T12:34:56.500Z"),
+        ("mid", "2024-06-12 12:34:56.250"),
+        ("early", "2024-06-12T12:34:56Z"),
+    ];
+    for (id, created_at_utc) in stamps {
+        let mut stored = common::feature(id, "cam", 0, 3);
+        stored.created_at_utc = created_at_utc.to_string();
+        insert_image_feature(&stored, db.path()).unwrap();
+    }
+    let features = get_camera_features("cam", db.path()).unwrap();
+    let order: Vec<(&str, &str)> = features.iter().map(|f| (f.id.as_str(), f.created_at_utc.as_str())).collect();
+    assert_eq!(
+        order,
+        [
+            ("early", "2024-06-12T12:34:56.000Z"),
+            ("mid", "2024-06-12T12:34:56.250Z"),
+            ("late", "2024-06-12T12:34:56.500Z")
+        ]
+    );
+    assert_eq!(get_image_feature("cam", db.path()).unwrap().unwrap().id, "late");
+
+    let mut unparseable = common::feature("bad", "cam", 0, 3);
+    unparseable.created_at_utc = "yesterday".to_string();
+    assert!(matches!(insert_image_feature(&unparseable, db.path()), Err(VyuwerError::InvalidTimestamp(_))));
+}
+
+#[test]
+fn upgrading_rewrites_older_timestamp_forms() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    let conn = Connection::open(db.path()).unwrap();
+    // As a build before schema 25 could have stored them
+    conn.execute_batch(
+        "UPDATE image_features SET created_at_utc = '2024-06-12T12:34:56.500Z', seq = 1 WHERE id = 'cam-0';
+        UPDATE image_features SET created_at_utc = '2024-06-12 12:34:56', seq = 2 WHERE id = 'cam-1';
+        PRAGMA user_version = 24;",
+    )
+    .unwrap();
+
+    setup_schema(db.path()).unwrap();
+    let stored: Vec<String> =
+        get_camera_features("cam", db.path()).unwrap().into_iter().map(|f| f.created_at_utc).collect();
+    assert_eq!(stored, ["2024-06-12T12:34:56.000Z", "2024-06-12T12:34:56.500Z"]);
+    assert_eq!(get_image_feature("cam", db.path()).unwrap().unwrap().id, "cam-0");
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::TempDb;
+use opencv::{
+    core::{Mat, Scalar, Vec3b, Vector, CV_8UC3},
+    imgcodecs,
+    prelude::*,
+};
+use rusqlite::Connection;
+use vyuwer_rust::camera::{camera_fingerprint, FINGERPRINT_MAX_SIDE};
+use vyuwer_rust::frames::store_frame;
+
+#[test]
+fn fingerprint_is_built_once_and_then_served_from_the_cache() {
+    let db = TempDb::new();
+    assert!(camera_fingerprint("cam", db.path()).is_err());
+    for (i, feature) in db.seed_features(2, "cam").iter().enumerate() {
+        let frame = Mat::new_rows_cols_with_default(480, 640, CV_8UC3, Scalar::all(40.0 + 100.0 * i as f64)).unwrap();
+        store_frame(&feature.id, &frame, db.path()).unwrap();
+    }
+
+    let jpeg = camera_fingerprint("cam", db.path()).unwrap();
+    let decoded = imgcodecs::imdecode(&Vector::<u8>::from_slice(&jpeg), imgcodecs::IMREAD_COLOR).unwrap();
+    assert_eq!((decoded.cols(), decoded.rows()), (FINGERPRINT_MAX_SIDE, 120));
+    // Taken from the latest frame, the brighter one
+    assert!(decoded.at_2d::<Vec3b>(60, 80).unwrap()[0] > 100);
+
+    // With the frames gone, only the cache can answer
+    Connection::open(db.path()).unwrap().execute("DELETE FROM frames", []).unwrap();
+    assert_eq!(camera_fingerprint("cam", db.path()).unwrap(), jpeg);
+}
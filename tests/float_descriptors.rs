@@ -0,0 +1,96 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::codec;
+use vyuwer_rust::float_descriptors::{
+    load_float_descriptors, match_float_descriptors, store_float_descriptors, FloatDescriptors, QuantizedDescriptors,
+};
+use vyuwer_rust::matching::MatchParams;
+use vyuwer_rust::model::insert_image_feature;
+use vyuwer_rust::VyuwerError;
+
+// Function to build `rows` SIFT-like descriptors (128 non-negative floats) from a xorshift stream
+fn sift_like(rows: usize, mut state: u64) -> FloatDescriptors {
+    let data = (0..rows * 128)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 20_000) as f32 / 100.0
+        })
+        .collect();
+    FloatDescriptors::new(rows, 128, data).unwrap()
+}
+
+fn nearest(query: &[f32], train: &FloatDescriptors) -> usize {
+    let distance = |row: &[f32]| query.iter().zip(row).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+    (0..train.rows)
+        .min_by(|&a, &b| distance(train.row(a)).total_cmp(&distance(train.row(b))))
+        .unwrap()
+}
+
+#[test]
+fn quantization_error_is_bounded() {
+    let original = sift_like(16, 0x9E37_79B9_7F4A_7C15);
+    let quantized = original.quantize();
+    let restored = quantized.dequantize().unwrap();
+    for i in 0..original.rows {
+        let bound = quantized.max_error(i) + 1e-4;
+        for (a, b) in original.row(i).iter().zip(restored.row(i)) {
+            assert!((a - b).abs() <= bound, "{a} vs {b} exceeds {bound}");
+        }
+    }
+    // Roughly a quarter of the f32 encoding
+    let full = codec::encode(&original).unwrap().len();
+    assert!(codec::encode(&quantized).unwrap().len() * 3 < full);
+}
+
+#[test]
+fn matches_survive_quantization() {
+    let train = sift_like(50, 42);
+    let restored = train.quantize().dequantize().unwrap();
+    // Queries are slightly perturbed copies of train rows
+    let noise = sift_like(50, 7);
+    for i in 0..train.rows {
+        let query: Vec<f32> = train.row(i).iter().zip(noise.row(i)).map(|(t, n)| t + n / 100.0).collect();
+        assert_eq!(nearest(&query, &restored), nearest(&query, &train));
+    }
+}
+
+#[test]
+fn stored_descriptors_read_back_dequantized_and_still_match() {
+    let db = TempDb::new();
+    insert_image_feature(&feature("f", "cam", 1_700_000_000, 3), db.path()).unwrap();
+    let original = sift_like(20, 11);
+    store_float_descriptors("f", &original, db.path()).unwrap();
+
+    let restored = load_float_descriptors("f", db.path()).unwrap().unwrap();
+    assert_eq!(restored, original.quantize().dequantize().unwrap());
+    let params = MatchParams::default();
+    let exact = match_float_descriptors(&original, &original, &params).unwrap();
+    let lossy = match_float_descriptors(&original, &restored, &params).unwrap();
+    assert_eq!(exact.good_matches, 20);
+    assert_eq!(lossy.good_matches, 20);
+    assert!(match_float_descriptors(&original, &sift_like(20, 99), &params).unwrap().match_ratio() < 0.5);
+
+    assert!(matches!(
+        store_float_descriptors("missing", &original, db.path()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+    assert_eq!(load_float_descriptors("missing", db.path()).unwrap(), None);
+}
+
+#[test]
+fn mismatched_lengths_are_errors_not_panics() {
+    let mut quantized = sift_like(2, 5).quantize();
+    quantized.mins.pop();
+    assert!(matches!(quantized.dequantize(), Err(VyuwerError::InvalidInput(_))));
+    assert!(codec::decode::<QuantizedDescriptors>(&codec::encode(&quantized).unwrap()).is_err());
+
+    let narrow = FloatDescriptors::new(2, 64, vec![0.0; 128]).unwrap();
+    assert!(matches!(
+        match_float_descriptors(&sift_like(2, 5), &narrow, &MatchParams::default()),
+        Err(VyuwerError::InvalidInput(_))
+    ));
+    assert!(FloatDescriptors::new(usize::MAX, 2, Vec::new()).is_err());
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::TempDb;
+use vyuwer_rust::flow::{load_flow_stats, store_flow_stats, FlowStats};
+use vyuwer_rust::model::{delete_image_feature, rekey_feature};
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn flow_stats_round_trip_and_follow_their_feature() {
+    let db = TempDb::new();
+    db.seed_features(1, "cam");
+    let stats = FlowStats {
+        mean_magnitude: 2.5,
+        dominant_direction: 90.0,
+    };
+    assert_eq!(load_flow_stats("cam-0", db.path()).unwrap(), None);
+    store_flow_stats("cam-0", &stats, db.path()).unwrap();
+    assert_eq!(load_flow_stats("cam-0", db.path()).unwrap(), Some(stats));
+
+    rekey_feature("cam-0", "renamed", db.path()).unwrap();
+    assert_eq!(load_flow_stats("renamed", db.path()).unwrap(), Some(stats));
+    delete_image_feature("cam", db.path()).unwrap();
+    assert_eq!(load_flow_stats("renamed", db.path()).unwrap(), None);
+}
+
+#[test]
+fn flow_stats_need_a_stored_feature() {
+    let db = TempDb::new();
+    let stats = FlowStats {
+        mean_magnitude: 1.0,
+        dominant_direction: 0.0,
+    };
+    assert!(matches!(store_flow_stats("missing", &stats, db.path()), Err(VyuwerError::FeatureNotFound(_))));
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use std::fs;
+
+use common::TempDb;
+use opencv::{
+    core::{Mat, Rect, Scalar, Vector, CV_8UC3},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+use vyuwer_rust::frames::{
+    load_frame, load_frame_bytes, migrate_frame_format, set_frame_directory, store_frame_bytes, ImageFormat,
+};
+
+// Function to JPEG-encode a 64×64 frame with an outlined square
+fn jpeg_frame() -> Vec<u8> {
+    let mut frame = Mat::new_rows_cols_with_default(64, 64, CV_8UC3, Scalar::all(40.0)).unwrap();
+    let square = Rect::new(16, 16, 32, 32);
+    imgproc::rectangle_def(&mut frame, square, Scalar::new(200.0, 120.0, 30.0, 0.0)).unwrap();
+    let mut encoded = Vector::<u8>::new();
+    imgcodecs::imencode_def(".jpg", &frame, &mut encoded).unwrap();
+    encoded.to_vec()
+}
+
+#[test]
+fn migrating_jpeg_frames_to_png_keeps_them_decodable() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam");
+    store_frame_bytes("cam-0", &jpeg_frame(), db.path()).unwrap();
+    store_frame_bytes("cam-1", &jpeg_frame(), db.path()).unwrap();
+    store_frame_bytes("cam-2", b"not an image", db.path()).unwrap();
+
+    assert_eq!(
+        migrate_frame_format(ImageFormat::Jpeg, ImageFormat::Png, db.path()).unwrap(),
+        2
+    );
+    for id in ["cam-0", "cam-1"] {
+        let bytes = load_frame_bytes(id, db.path()).unwrap().unwrap();
+        assert_eq!(ImageFormat::detect(&bytes), Some(ImageFormat::Png));
+        let frame = load_frame(id, db.path()).unwrap().unwrap();
+        assert_eq!((frame.cols(), frame.rows()), (64, 64));
+    }
+    assert_eq!(
+        load_frame_bytes("cam-2", db.path()).unwrap(),
+        Some(b"not an image".to_vec())
+    );
+
+    // Nothing is left in the old format
+    assert_eq!(
+        migrate_frame_format(ImageFormat::Jpeg, ImageFormat::Png, db.path()).unwrap(),
+        0
+    );
+}
+
+#[test]
+fn migrated_frame_files_are_removed_once_unreferenced() {
+    let db = TempDb::new();
+    let frames = tempfile::tempdir().unwrap();
+    set_frame_directory(&frames.path().to_string_lossy(), db.path()).unwrap();
+    db.seed_features(2, "cam");
+    // Identical frames share one file
+    store_frame_bytes("cam-0", &jpeg_frame(), db.path()).unwrap();
+    store_frame_bytes("cam-1", &jpeg_frame(), db.path()).unwrap();
+    let files_with = |extension: &str| {
+        fs::read_dir(frames.path())
+            .unwrap()
+            .flat_map(|shard| fs::read_dir(shard.unwrap().path()).unwrap())
+            .filter(|file| file.as_ref().unwrap().path().extension().is_some_and(|e| e == extension))
+            .count()
+    };
+    assert_eq!(files_with("jpg"), 1);
+
+    migrate_frame_format(ImageFormat::Jpeg, ImageFormat::Png, db.path()).unwrap();
+    assert_eq!(files_with("jpg"), 0);
+    assert_eq!(files_with("png"), 1);
+    for id in ["cam-0", "cam-1"] {
+        assert!(load_frame(id, db.path()).unwrap().is_some());
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use std::fs;
+
+use common::TempDb;
+use vyuwer_rust::frames::{load_frame_bytes, set_frame_directory, store_frame_bytes, ImageFormat};
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn filesystem_frames_round_trip_and_are_hash_checked() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+    let frames = tempfile::tempdir().unwrap();
+    set_frame_directory(frames.path().to_str().unwrap(), db.path()).unwrap();
+
+    let png = b"\x89PNG fake frame bytes".to_vec();
+    store_frame_bytes("cam-0", &png, db.path()).unwrap();
+    assert_eq!(load_frame_bytes("cam-0", db.path()).unwrap(), Some(png.clone()));
+    assert_eq!(load_frame_bytes("cam-1", db.path()).unwrap(), None);
+
+    // One hash-named file under a two-character prefix directory
+    let prefix = fs::read_dir(frames.path()).unwrap().next().unwrap().unwrap().path();
+    let file = fs::read_dir(&prefix).unwrap().next().unwrap().unwrap().path();
+    fs::write(&file, b"tampered").unwrap();
+    assert!(matches!(load_frame_bytes("cam-0", db.path()), Err(VyuwerError::CorruptFrame(_))));
+}
+
+#[test]
+fn frames_default_to_blobs() {
+    let db = TempDb::new();
+    db.seed_features(1, "cam");
+    store_frame_bytes("cam-0", b"inline", db.path()).unwrap();
+    assert_eq!(load_frame_bytes("cam-0", db.path()).unwrap(), Some(b"inline".to_vec()));
+}
+
+#[test]
+fn image_format_is_detected_from_magic_bytes() {
+    assert_eq!(ImageFormat::detect(b"\x89PNG\r\n\x1a\nrest"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::detect(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(ImageFormat::Jpeg));
+    assert_eq!(ImageFormat::detect(b"RIFF\0\0\0\0WEBPVP8 "), Some(ImageFormat::WebP));
+    assert_eq!(ImageFormat::detect(b"inline"), None);
+    assert_eq!(ImageFormat::Jpeg.extension(), "jpg");
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use vyuwer_rust::geometry::{alignment_quality, estimate_homography, MIN_ALIGNMENT_MATCHES, MIN_HOMOGRAPHY_INLIERS};
+use vyuwer_rust::matching::{match_features, MatchParams, SimilarityMetric};
+use vyuwer_rust::model::{DescriptorMatrix, ImageFeature, KeyPointData};
+use vyuwer_rust::VyuwerError;
+
+// Function to build a feature with `n` keypoints scattered over a 640×480 frame, each with its own
+// pseudo-random descriptor, so that every keypoint matches only its copy in a shifted frame
+fn scattered(id: &str, n: usize) -> ImageFeature {
+    let mut state = 0x9e37_79b9_u32;
+    let data = (0..n * 32)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    ImageFeature {
+        keypoints: (0..n)
+            .map(|i| KeyPointData {
+                x: (i * 97 % 600) as f32 + 20.0,
+                y: (i * 61 % 440) as f32 + 20.0,
+                size: 31.0,
+                angle: 0.0,
+            })
+            .collect(),
+        descriptors: DescriptorMatrix::new(n, 32, data).unwrap(),
+        ..common::feature(id, "cam", 1_700_000_000, 0)
+    }
+}
+
+// Function to copy `feature` with every keypoint moved by (dx, dy)
+fn shifted(feature: &ImageFeature, id: &str, dx: impl Fn(usize) -> f32, dy: impl Fn(usize) -> f32) -> ImageFeature {
+    let mut moved = ImageFeature {
+        id: id.to_string(),
+        ..feature.clone()
+    };
+    for (i, kp) in moved.keypoints.iter_mut().enumerate() {
+        kp.x += dx(i);
+        kp.y += dy(i);
+    }
+    moved
+}
+
+#[test]
+fn a_pure_translation_is_recovered_as_the_homography() {
+    let a = scattered("a", 40);
+    let b = shifted(&a, "b", |_| 12.0, |_| -7.0);
+    let h = estimate_homography(&a, &b).unwrap().expect("homography");
+    let expected = [1.0, 0.0, 12.0, 0.0, 1.0, -7.0, 0.0, 0.0, 1.0];
+    for (got, want) in h.iter().zip(expected) {
+        assert!((got - want).abs() < 1e-3, "{h:?}");
+    }
+}
+
+#[test]
+fn too_few_matches_give_no_homography() {
+    let a = scattered("a", MIN_HOMOGRAPHY_INLIERS - 2);
+    let b = shifted(&a, "b", |_| 12.0, |_| -7.0);
+    assert_eq!(estimate_homography(&a, &b).unwrap(), None);
+}
+
+#[test]
+fn a_near_identical_pair_aligns_with_low_error() {
+    let a = scattered("a", 40);
+    // Sub-pixel jitter, well inside the RANSAC threshold
+    let b = shifted(&a, "b", |i| if i % 2 == 0 { 0.2 } else { -0.2 }, |i| if i % 3 == 0 { 0.1 } else { -0.1 });
+    let quality = alignment_quality(&a, &b).unwrap();
+    assert_eq!(quality.inlier_mask.len(), 40);
+    assert!(quality.inlier_mask.iter().filter(|&&m| m).count() > 30, "{quality:?}");
+    assert_eq!(quality.inliers, quality.inlier_mask.iter().filter(|&&m| m).count());
+    assert!(quality.mean_reprojection_error < 0.5, "{quality:?}");
+}
+
+#[test]
+fn alignment_needs_enough_matches() {
+    let a = scattered("a", MIN_ALIGNMENT_MATCHES - 1);
+    let b = shifted(&a, "b", |_| 0.0, |_| 0.0);
+    assert!(matches!(
+        alignment_quality(&a, &b),
+        Err(VyuwerError::InsufficientMatches { found, required })
+            if found == MIN_ALIGNMENT_MATCHES - 1 && required == MIN_ALIGNMENT_MATCHES
+    ));
+}
+
+#[test]
+fn the_inlier_ratio_leaves_out_matches_off_the_homography() {
+    let a = scattered("a", 40);
+    // Every fifth keypoint lands far from where the translation puts it
+    let b = shifted(&a, "b", |i| if i % 5 == 0 { 150.0 + i as f32 } else { 12.0 }, |_| -7.0);
+    let ratio_test = match_features(&a, &b, &MatchParams::default());
+    let inliers = match_features(&a, &b, &MatchParams::default().with_metric(SimilarityMetric::InlierRatio));
+    assert_eq!(ratio_test.good_matches, 40);
+    assert_eq!(inliers.good_matches, 32);
+    assert!((inliers.match_ratio() - 0.8).abs() < 1e-9);
+}
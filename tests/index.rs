@@ -0,0 +1,37 @@
+mod common;
+
+use std::fs;
+
+use common::TempDb;
+use vyuwer_rust::index::{build_lsh_index, query_index, LshIndex};
+
+#[test]
+fn features_find_themselves_first() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(6, "cam");
+    let index = build_lsh_index("cam", db.path()).unwrap();
+    assert_eq!(index.len(), 6);
+
+    for feature in &seeded {
+        let nearest = query_index(&index, &feature.descriptors, 3);
+        assert_eq!(nearest.first(), Some(&feature.id));
+    }
+    assert!(build_lsh_index("missing", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn saved_index_loads_and_answers_the_same() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(4, "cam");
+    let index = build_lsh_index("cam", db.path()).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cam.idx").to_string_lossy().into_owned();
+    index.save(&path).unwrap();
+
+    let loaded = LshIndex::load(&path).unwrap();
+    assert_eq!(loaded, index);
+    assert_eq!(query_index(&loaded, &seeded[2].descriptors, 1), [seeded[2].id.clone()]);
+
+    fs::write(&path, b"not an index").unwrap();
+    assert!(LshIndex::load(&path).is_err());
+}
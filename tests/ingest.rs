@@ -0,0 +1,74 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::TempDb;
+use opencv::{
+    core::{Mat, Scalar, Vector, CV_8UC1},
+    imgcodecs,
+    prelude::*,
+};
+use vyuwer_rust::extract::OrbParams;
+use vyuwer_rust::model::get_camera_features;
+use vyuwer_rust::timestamp::format_unix_secs;
+use vyuwer_rust::video::{ingest_files, ingest_idempotent, ingest_stream, INGEST_CHUNK_SIZE};
+
+// Function to build a 128×128 noise frame; each seed gives a different texture
+fn synthetic_frame(seed: u8) -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(128, 128, CV_8UC1, Scalar::all(0.0)).unwrap();
+    for r in 0..128 {
+        for c in 0..128 {
+            *frame.at_2d_mut::<u8>(r, c).unwrap() = ((r * 7 + c * 13) as u8).wrapping_mul(seed | 1) ^ (c as u8);
+        }
+    }
+    frame
+}
+
+#[test]
+fn every_streamed_frame_is_persisted() {
+    let db = TempDb::new();
+    // More than one chunk, so both the in-loop and the final flush run
+    let total = INGEST_CHUNK_SIZE + 5;
+    let items = (0..total).map(|i| (synthetic_frame(i as u8), format_unix_secs(1_700_000_000 + i as i64)));
+
+    let report = ingest_stream(items, "cam", &OrbParams::default(), db.path()).unwrap();
+    assert_eq!(report.succeeded, total);
+    assert_eq!(report.failed, 0);
+    let features = get_camera_features("cam", db.path()).unwrap();
+    assert_eq!(features.len(), total);
+    assert!(features.iter().all(|f| f.frame_size.is_some()));
+}
+
+#[test]
+fn unreadable_file_is_reported_without_aborting() {
+    let db = TempDb::new();
+    let dir = tempfile::tempdir().unwrap();
+    let mut paths = Vec::new();
+    for i in 0..3u8 {
+        let path = dir.path().join(format!("frame{i}.png"));
+        imgcodecs::imwrite(path.to_str().unwrap(), &synthetic_frame(i), &Vector::new()).unwrap();
+        paths.push(path);
+    }
+    let broken = dir.path().join("broken.png");
+    std::fs::write(&broken, b"not a png").unwrap();
+    paths.insert(1, broken.clone());
+
+    let report = ingest_files(&paths, "cam", &OrbParams::default(), db.path()).unwrap();
+    assert_eq!(report.succeeded, 3);
+    assert_eq!(report.failed, 1);
+    assert_eq!(report.errors[0].0, broken.to_string_lossy());
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 3);
+}
+
+#[test]
+fn ingesting_the_same_image_twice_inserts_once() {
+    let db = TempDb::new();
+    assert!(ingest_idempotent(&synthetic_frame(3), "cam", db.path()).unwrap());
+    assert!(!ingest_idempotent(&synthetic_frame(3), "cam", db.path()).unwrap());
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 1);
+
+    // Other pixels, or the same pixels from another camera, are new content
+    assert!(ingest_idempotent(&synthetic_frame(4), "cam", db.path()).unwrap());
+    assert!(ingest_idempotent(&synthetic_frame(3), "other", db.path()).unwrap());
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 2);
+}
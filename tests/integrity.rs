@@ -0,0 +1,117 @@
+mod common;
+
+use common::{feature, TempDb};
+use rusqlite::{params, Connection};
+use vyuwer_rust::anomaly::set_baseline;
+use vyuwer_rust::codec;
+use vyuwer_rust::integrity::{check_referential_integrity, repair_inconsistent, scan_corrupt_features, RepairStrategy};
+use vyuwer_rust::model::{
+    delete_image_feature, get_feature_by_id, get_image_feature_checked, insert_image_description,
+    insert_image_feature, validate_feature, FeatureReadResult, ImageDescription, KeyPointData,
+};
+
+#[test]
+fn deleted_features_leave_dangling_references_in_the_report() {
+    let db = TempDb::new();
+    for camera_id in ["cam", "kept"] {
+        let mut stored = feature(&format!("{camera_id}-0"), camera_id, 1_700_000_000, 3);
+        let image_name = format!("{camera_id}-frame-0.png");
+        stored.img_filename = Some(image_name.clone());
+        insert_image_feature(&stored, db.path()).unwrap();
+        insert_image_description(
+            &ImageDescription {
+                image_name,
+                datetime: stored.created_at_utc.clone(),
+                camera_id: camera_id.to_string(),
+                anomaly: None,
+            },
+            db.path(),
+        )
+        .unwrap();
+    }
+    set_baseline("cam", "cam-0", db.path()).unwrap();
+    assert!(check_referential_integrity(db.path()).unwrap().is_clean());
+
+    delete_image_feature("cam", db.path()).unwrap();
+    // Written by some other tool, bypassing the cascade
+    Connection::open(db.path())
+        .unwrap()
+        .execute("INSERT INTO feature_tags (feature_id, key, value) VALUES ('ghost', 'weather', 'rain')", [])
+        .unwrap();
+
+    let report = check_referential_integrity(db.path()).unwrap();
+    assert!(!report.is_clean());
+    let orphaned: Vec<(&str, &str)> = report
+        .descriptions_without_feature
+        .iter()
+        .map(|d| (d.camera_id.as_str(), d.image_name.as_str()))
+        .collect();
+    assert_eq!(orphaned, [("cam", "cam-frame-0.png")]);
+    assert_eq!(report.tags_without_feature, [("ghost".to_string(), "weather".to_string())]);
+    assert_eq!(report.baselines_without_feature, [("cam".to_string(), "cam-0".to_string())]);
+}
+
+#[test]
+fn truncated_blob_is_reported_instead_of_aborting_the_scan() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam");
+    db.seed_features(1, "other");
+    assert!(scan_corrupt_features(db.path()).unwrap().is_empty());
+
+    let conn = Connection::open(db.path()).unwrap();
+    conn.execute("UPDATE image_features SET descriptors = substr(descriptors, 1, 10) WHERE id = 'cam-2'", [])
+        .unwrap();
+
+    let corrupt = scan_corrupt_features(db.path()).unwrap();
+    assert_eq!(corrupt.len(), 1);
+    assert_eq!((corrupt[0].id.as_str(), corrupt[0].camera_id.as_str()), ("cam-2", "cam"));
+    assert!(!corrupt[0].error.is_empty());
+
+    match get_image_feature_checked("cam", db.path()).unwrap() {
+        Some(FeatureReadResult::Corrupt(feature)) => assert_eq!(feature.id, "cam-2"),
+        other => panic!("expected a corrupt read, got {other:?}"),
+    }
+    assert!(matches!(get_image_feature_checked("other", db.path()).unwrap(), Some(FeatureReadResult::Ok(_))));
+    assert_eq!(get_image_feature_checked("missing", db.path()).unwrap(), None);
+}
+
+// Function to store a feature, then overwrite its keypoints with `keypoints` extra ones,
+// as a past bug might have
+fn insert_mismatched(db: &TempDb, id: &str, keypoints: usize) {
+    let stored = feature(id, "cam", 1_700_000_000, 3);
+    insert_image_feature(&stored, db.path()).unwrap();
+    let mut extra = stored.keypoints.clone();
+    extra.extend((0..keypoints).map(|i| KeyPointData { x: 100.0 + i as f32, y: 0.0, size: 31.0, angle: 0.0 }));
+    Connection::open(db.path())
+        .unwrap()
+        .execute(
+            "UPDATE image_features SET keypoints = ?1 WHERE id = ?2",
+            params![codec::encode(&extra).unwrap(), id],
+        )
+        .unwrap();
+}
+
+#[test]
+fn mismatched_features_are_truncated_to_equal_counts() {
+    let db = TempDb::new();
+    insert_image_feature(&feature("fine", "cam", 1_700_000_000, 1), db.path()).unwrap();
+    insert_mismatched(&db, "broken", 2);
+    assert!(validate_feature(&get_feature_by_id("broken", db.path()).unwrap().unwrap()).is_err());
+
+    assert_eq!(repair_inconsistent(db.path(), RepairStrategy::Truncate).unwrap(), 1);
+    let repaired = get_feature_by_id("broken", db.path()).unwrap().unwrap();
+    assert_eq!((repaired.keypoints.len(), repaired.descriptors.rows), (4, 4));
+    assert_eq!(repaired.descriptors, feature("broken", "cam", 1_700_000_000, 3).descriptors);
+    validate_feature(&repaired).unwrap();
+    assert_eq!(repair_inconsistent(db.path(), RepairStrategy::Truncate).unwrap(), 0);
+}
+
+#[test]
+fn mismatched_features_can_be_deleted_instead() {
+    let db = TempDb::new();
+    insert_image_feature(&feature("fine", "cam", 1_700_000_000, 1), db.path()).unwrap();
+    insert_mismatched(&db, "broken", 1);
+    assert_eq!(repair_inconsistent(db.path(), RepairStrategy::Delete).unwrap(), 1);
+    assert_eq!(get_feature_by_id("broken", db.path()).unwrap(), None);
+    assert!(get_feature_by_id("fine", db.path()).unwrap().is_some());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::TempDb;
+use vyuwer_rust::model::{
+    descriptor_entropy, get_image_feature, insert_image_feature, rescale_keypoints, DescriptorMatrix, FrameSize,
+    KeyPointData,
+};
+
+#[test]
+fn keypoints_scale_from_resized_to_original_frame() {
+    let mut keypoints = vec![KeyPointData {
+        x: 100.0,
+        y: 50.0,
+        size: 31.0,
+        angle: 90.0,
+    }];
+    rescale_keypoints(&mut keypoints, FrameSize::new(640, 360), FrameSize::new(1920, 1080)).unwrap();
+    assert_eq!(
+        keypoints[0],
+        KeyPointData {
+            x: 300.0,
+            y: 150.0,
+            size: 93.0,
+            angle: 90.0
+        }
+    );
+    assert!(rescale_keypoints(&mut keypoints, FrameSize::new(0, 360), FrameSize::new(1920, 1080)).is_err());
+}
+
+#[test]
+fn frame_size_is_stored() {
+    let db = TempDb::new();
+    let mut feature = common::feature("f", "cam", 1_700_000_000, 0);
+    feature.frame_size = Some(FrameSize::new(1920, 1080));
+    insert_image_feature(&feature, db.path()).unwrap();
+    assert_eq!(get_image_feature("cam", db.path()).unwrap().unwrap().frame_size, feature.frame_size);
+}
+
+#[test]
+fn entropy_separates_flat_and_random_descriptors() {
+    let flat = DescriptorMatrix::new(64, 32, vec![0; 64 * 32]).unwrap();
+    assert!(descriptor_entropy(&flat) < 1e-9);
+
+    // xorshift bytes stand in for a richly textured frame
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let random = (0..64 * 32)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let random = DescriptorMatrix::new(64, 32, random).unwrap();
+    assert!(descriptor_entropy(&random) > 7.5);
+
+    let mut feature = common::feature("f", "cam", 1_700_000_000, 0);
+    feature.descriptors = flat;
+    assert!(feature.quality_score() < 1e-9);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::{feature, TempDb};
+use rusqlite::Connection;
+use vyuwer_rust::layout::{descriptor_layout, migrate_to_normalized_descriptors, DescriptorLayout};
+use vyuwer_rust::model::{delete_image_feature, get_camera_features, get_feature_by_id, insert_image_feature};
+
+fn descriptor_rows(db: &TempDb) -> i64 {
+    let conn = Connection::open(db.path()).unwrap();
+    conn.query_row("SELECT COUNT(*) FROM descriptors", [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn normalized_reads_rebuild_features_and_deletes_cascade() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(2, "cam");
+    assert_eq!(migrate_to_normalized_descriptors(db.path()).unwrap(), 2);
+    assert_eq!(descriptor_layout(db.path()).unwrap(), DescriptorLayout::Normalized);
+
+    // Features migrated and features inserted afterwards both read back in full
+    let added = feature("late", "cam", 1_700_000_100, 9);
+    insert_image_feature(&added, db.path()).unwrap();
+    assert_eq!(get_feature_by_id("cam-0", db.path()).unwrap().as_ref(), Some(&seeded[0]));
+    assert_eq!(get_camera_features("cam", db.path()).unwrap(), [seeded[0].clone(), seeded[1].clone(), added]);
+    assert_eq!(descriptor_rows(&db), 3);
+
+    delete_image_feature("cam", db.path()).unwrap();
+    assert_eq!(descriptor_rows(&db), 0);
+}
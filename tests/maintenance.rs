@@ -0,0 +1,52 @@
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use common::TempDb;
+use vyuwer_rust::audit::audit_since;
+use vyuwer_rust::maintenance::{Maintenance, MaintenanceReport};
+use vyuwer_rust::model::get_camera_features;
+
+#[test]
+fn run_once_runs_every_configured_task() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam");
+    let day = Duration::from_secs(86_400);
+
+    let mut maintenance = Maintenance::new(db.path()).prune_every(day, day).checkpoint_every(day).vacuum_every(day);
+    let report = maintenance.run_once().unwrap();
+    assert_eq!(
+        report,
+        MaintenanceReport {
+            pruned: 3,
+            checkpointed: true,
+            vacuumed: true,
+        }
+    );
+    assert!(get_camera_features("cam", db.path()).unwrap().is_empty());
+
+    // Nothing configured, nothing done
+    assert_eq!(Maintenance::new(db.path()).run_once().unwrap(), MaintenanceReport::default());
+}
+
+#[test]
+fn zero_interval_does_not_spin() {
+    let db = TempDb::new();
+    let (shutdown, signal) = mpsc::channel();
+    let handle = Maintenance::new(db.path())
+        .prune_every(Duration::ZERO, Duration::from_secs(86_400))
+        .spawn(signal);
+    thread::sleep(Duration::from_millis(300));
+    shutdown.send(()).unwrap();
+    handle.join().unwrap().unwrap();
+
+    // The interval was raised to the minimum, so the first pass is the only one
+    let prunes = audit_since("1970-01-01T00:00:00Z", db.path())
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.operation == "prune")
+        .count();
+    assert_eq!(prunes, 1);
+}
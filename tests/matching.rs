@@ -0,0 +1,193 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::descriptor_sets::DescriptorKind;
+use vyuwer_rust::matching::{
+    distance_matrix, match_asymmetry, match_features, match_features_detailed, MatchParams, SimilarityMetric,
+    MAX_DISTANCE_MATRIX_CELLS,
+};
+use vyuwer_rust::model::{get_feature_by_id, insert_image_feature, DescriptorMatrix, ImageFeature};
+
+#[test]
+fn detailed_matches_point_at_real_keypoints() {
+    let query = feature("q", "cam", 1_700_000_000, 3);
+    let train = feature("t", "cam", 1_700_000_001, 3);
+    let params = MatchParams::default();
+
+    let detailed = match_features_detailed(&query, &train, &params);
+    assert_eq!(detailed.counts, match_features(&query, &train, &params));
+    assert!(!detailed.index_pairs.is_empty());
+    assert_eq!(detailed.index_pairs.len(), detailed.point_pairs.len());
+    for (&(qi, ti), (qp, tp)) in detailed.index_pairs.iter().zip(&detailed.point_pairs) {
+        assert!(qi < query.keypoints.len() && ti < train.keypoints.len());
+        assert_eq!((qp, tp), (&query.keypoints[qi], &train.keypoints[ti]));
+    }
+}
+
+#[test]
+fn looser_ratio_keeps_at_least_as_many_matches() {
+    let query = feature("q", "cam", 1_700_000_000, 3);
+    let strict = MatchParams::new(0.5).unwrap();
+    let loose = MatchParams::new(0.95).unwrap();
+    for seed in [3, 5, 40, 200] {
+        let train = feature("t", "cam", 1_700_000_001, seed);
+        let strict_matches = match_features(&query, &train, &strict).good_matches;
+        let loose_matches = match_features(&query, &train, &loose).good_matches;
+        assert!(loose_matches >= strict_matches, "seed {seed}: {loose_matches} < {strict_matches}");
+    }
+    // Ambiguous nearest neighbours only survive the looser test
+    let train = feature("t", "cam", 1_700_000_001, 5);
+    assert!(match_features(&query, &train, &loose).good_matches > match_features(&query, &train, &strict).good_matches);
+}
+
+#[test]
+fn ratio_outside_the_open_unit_interval_is_rejected() {
+    for ratio in [0.0, 1.0, -0.3, f64::NAN] {
+        assert!(MatchParams::new(ratio).is_err(), "{ratio} accepted");
+    }
+}
+
+#[test]
+fn zero_keypoint_frame_is_stored_and_matches_nothing() {
+    let db = TempDb::new();
+    let blank = ImageFeature {
+        keypoints: Vec::new(),
+        descriptors: DescriptorMatrix::default(),
+        ..feature("blank", "cam", 1_700_000_000, 0)
+    };
+    insert_image_feature(&blank, db.path()).unwrap();
+    let stored = get_feature_by_id("blank", db.path()).unwrap().unwrap();
+    assert_eq!(stored, blank);
+    assert!(stored.descriptors.is_empty());
+
+    let textured = feature("t", "cam", 1_700_000_001, 3);
+    for (query, train) in [(&stored, &textured), (&textured, &stored), (&stored, &stored)] {
+        let result = match_features(query, train, &MatchParams::default());
+        assert_eq!(result.good_matches, 0);
+        assert_eq!(result.match_ratio(), 0.0);
+    }
+}
+
+#[test]
+fn cross_check_keeps_no_more_matches_than_the_ratio_test() {
+    let ratio_test = MatchParams::default();
+    let cross_check = MatchParams::default().with_metric(SimilarityMetric::CrossCheck);
+    let query = feature("q", "cam", 1_700_000_000, 3);
+    for seed in [3, 4, 5, 40, 200] {
+        let train = feature("t", "cam", 1_700_000_001, seed);
+        let one_way = match_features(&query, &train, &ratio_test).good_matches;
+        let mutual = match_features(&query, &train, &cross_check).good_matches;
+        assert!(mutual <= one_way, "seed {seed}: {mutual} > {one_way}");
+    }
+
+    // Two query rows near the same train row: both pass the ratio test, but that train
+    // row's own nearest neighbour is only the first
+    let mut near_twins = feature("q", "cam", 1_700_000_000, 0);
+    let mut data = vec![0u8; 64];
+    data[32] = 0b11;
+    near_twins.descriptors = DescriptorMatrix::new(2, 32, data).unwrap();
+    near_twins.keypoints.truncate(2);
+    let mut train = feature("t", "cam", 1_700_000_001, 0);
+    let mut data = vec![0u8; 32];
+    data.extend([0xFF; 32]);
+    train.descriptors = DescriptorMatrix::new(2, 32, data).unwrap();
+    train.keypoints.truncate(2);
+    assert_eq!(match_features(&near_twins, &train, &ratio_test).good_matches, 2);
+    assert_eq!(match_features(&near_twins, &train, &cross_check).good_matches, 1);
+}
+
+// Function to build a feature with `rows` pseudo-random 32-byte descriptors
+fn random_feature(id: &str, rows: usize) -> ImageFeature {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let data = (0..rows * 32)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let base = feature(id, "cam", 1_700_000_000, 0);
+    ImageFeature {
+        keypoints: vec![base.keypoints[0].clone(); rows],
+        descriptors: DescriptorMatrix::new(rows, 32, data).unwrap(),
+        ..base
+    }
+}
+
+#[test]
+fn symmetric_pair_has_no_asymmetry() {
+    let a = random_feature("a", 16);
+    let b = random_feature("b", 16);
+    assert!(match_asymmetry(&a, &b).unwrap() < 1e-9);
+}
+
+#[test]
+fn occluded_view_is_asymmetric() {
+    let full = random_feature("full", 16);
+    // Half the scene hidden: only the first 8 descriptors survive
+    let occluded = ImageFeature {
+        keypoints: full.keypoints[..8].to_vec(),
+        descriptors: DescriptorMatrix::new(8, 32, full.descriptors.data[..8 * 32].to_vec()).unwrap(),
+        ..random_feature("occluded", 0)
+    };
+    let asymmetry = match_asymmetry(&full, &occluded).unwrap();
+    assert!(asymmetry > 0.3, "asymmetry {asymmetry}");
+    assert_eq!(match_asymmetry(&occluded, &full).unwrap(), asymmetry);
+
+    let wide = ImageFeature {
+        descriptors: DescriptorMatrix::new(1, 64, vec![0; 64]).unwrap(),
+        ..random_feature("wide", 1)
+    };
+    assert!(match_asymmetry(&full, &wide).is_err());
+}
+
+#[test]
+fn distance_matrix_matches_hand_computed_distances() {
+    // Rows differ only in their first byte, so distances are easy to work out by hand
+    let rows = |firsts: [u8; 3]| {
+        let data = firsts.iter().flat_map(|&first| std::iter::once(first).chain([0; 31])).collect();
+        DescriptorMatrix::new(3, 32, data).unwrap()
+    };
+    let a = rows([0b0000_0000, 0b0000_1111, 0b1111_1111]);
+    let b = rows([0b0000_0001, 0b1111_0000, 0b0000_0000]);
+    let hamming = distance_matrix(&a, &b, DescriptorKind::Orb).unwrap();
+    assert_eq!(hamming, vec![vec![1, 4, 0], vec![3, 8, 4], vec![7, 4, 8]]);
+
+    // As L2 on byte values: |0 - 240| = 240, |15 - 1| = 14, ...
+    let l2 = distance_matrix(&a, &b, DescriptorKind::Sift).unwrap();
+    assert_eq!(l2, vec![vec![1, 240, 0], vec![14, 225, 15], vec![254, 15, 255]]);
+
+    let narrow = DescriptorMatrix::new(1, 16, vec![0; 16]).unwrap();
+    assert!(distance_matrix(&a, &narrow, DescriptorKind::Orb).is_err());
+    let side = 1 + MAX_DISTANCE_MATRIX_CELLS.isqrt();
+    let huge = DescriptorMatrix::new(side, 1, vec![0; side]).unwrap();
+    assert!(distance_matrix(&huge, &huge, DescriptorKind::Orb).is_err());
+}
+
+#[test]
+fn sampling_compares_fewer_descriptors_and_full_sampling_changes_nothing() {
+    let query = random_feature("query", 200);
+    let train = random_feature("train", 200);
+    let full = match_features(&query, &train, &MatchParams::default());
+    let all = MatchParams::default().with_sampling(1.0, 7).unwrap();
+    assert_eq!(match_features(&query, &train, &all), full);
+    assert_eq!(full.good_matches, 200);
+
+    let quarter = MatchParams::default().with_sampling(0.25, 7).unwrap();
+    let sampled = match_features(&query, &train, &quarter);
+    assert_eq!(sampled.query_descriptors, 50);
+    assert_eq!(sampled.good_matches, 50);
+    assert_eq!(sampled.match_ratio(), full.match_ratio());
+    // Deterministic per seed, and every picked row points back at its own keypoint
+    assert_eq!(match_features_detailed(&query, &train, &quarter), match_features_detailed(&query, &train, &quarter));
+    let other_seed = MatchParams::default().with_sampling(0.25, 8).unwrap();
+    assert_ne!(
+        match_features_detailed(&query, &train, &quarter).index_pairs,
+        match_features_detailed(&query, &train, &other_seed).index_pairs
+    );
+
+    for fraction in [0.0, -0.5, 1.5, f64::NAN] {
+        assert!(MatchParams::default().with_sampling(fraction, 0).is_err(), "{fraction}");
+    }
+}
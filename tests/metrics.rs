@@ -0,0 +1,39 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use common::{feature, TempDb};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+use vyuwer_rust::anomaly::{classify_anomaly, DEFAULT_MIN_MATCH_RATIO};
+use vyuwer_rust::metrics::{record_db_size, ANOMALIES_DETECTED, DB_SIZE_BYTES, FEATURES_INSERTED, QUERIES_SERVED};
+use vyuwer_rust::model::{get_feature_by_id, insert_image_feature};
+
+fn value_of(snapshotter: &Snapshotter, name: &str) -> Option<DebugValue> {
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .find(|(key, ..)| key.key().name() == name)
+        .map(|(.., value)| value)
+}
+
+#[test]
+fn crud_and_anomaly_paths_update_their_metrics() {
+    let db = TempDb::new();
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        insert_image_feature(&feature("a", "cam", 1_700_000_000, 3), db.path()).unwrap();
+        get_feature_by_id("a", db.path()).unwrap();
+        let baseline = feature("base", "cam", 1_700_000_000, 3);
+        let changed = feature("b", "cam", 1_700_000_001, 200);
+        assert!(classify_anomaly(&baseline, &changed, DEFAULT_MIN_MATCH_RATIO).is_some());
+        let bytes = record_db_size(db.path()).unwrap();
+        assert!(bytes > 0);
+    });
+
+    assert_eq!(value_of(&snapshotter, FEATURES_INSERTED), Some(DebugValue::Counter(1)));
+    assert_eq!(value_of(&snapshotter, QUERIES_SERVED), Some(DebugValue::Counter(1)));
+    assert_eq!(value_of(&snapshotter, ANOMALIES_DETECTED), Some(DebugValue::Counter(1)));
+    assert!(matches!(value_of(&snapshotter, DB_SIZE_BYTES), Some(DebugValue::Gauge(size)) if size.into_inner() > 0.0));
+}
@@ -0,0 +1,42 @@
+use vyuwer_rust::extract::spatial_nms;
+use vyuwer_rust::model::{DescriptorMatrix, KeyPointData};
+
+// Function to build keypoints at `points`, with descriptor row i filled with byte i
+fn keypoints_at(points: &[(f32, f32)]) -> (Vec<KeyPointData>, DescriptorMatrix) {
+    let keypoints = points
+        .iter()
+        .map(|&(x, y)| KeyPointData {
+            x,
+            y,
+            size: 31.0,
+            angle: 0.0,
+        })
+        .collect();
+    let data = (0..points.len()).flat_map(|i| [i as u8; 32]).collect();
+    (keypoints, DescriptorMatrix::new(points.len(), 32, data).unwrap())
+}
+
+#[test]
+fn close_keypoints_collapse_to_the_stronger_one() {
+    let (keypoints, descriptors) = keypoints_at(&[(10.0, 10.0), (11.0, 10.5), (80.0, 80.0)]);
+    let (kept, descriptors) = spatial_nms(keypoints, descriptors, &[0.2, 0.9, 0.5], 5.0, 10).unwrap();
+    assert_eq!(kept.iter().map(|kp| (kp.x, kp.y)).collect::<Vec<_>>(), [(11.0, 10.5), (80.0, 80.0)]);
+    // Descriptor rows follow their keypoints
+    assert_eq!(descriptors.rows, 2);
+    assert_eq!(descriptors.row(0), [1u8; 32]);
+    assert_eq!(descriptors.row(1), [2u8; 32]);
+}
+
+#[test]
+fn max_kp_keeps_the_strongest_survivors() {
+    let (keypoints, descriptors) = keypoints_at(&[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0), (150.0, 0.0)]);
+    let (kept, descriptors) = spatial_nms(keypoints, descriptors, &[0.1, 0.4, 0.3, 0.2], 5.0, 2).unwrap();
+    assert_eq!(kept.iter().map(|kp| kp.x).collect::<Vec<_>>(), [50.0, 100.0]);
+    assert_eq!(descriptors.rows, 2);
+}
+
+#[test]
+fn mismatched_responses_are_rejected() {
+    let (keypoints, descriptors) = keypoints_at(&[(0.0, 0.0), (50.0, 0.0)]);
+    assert!(spatial_nms(keypoints, descriptors, &[0.1], 5.0, 10).is_err());
+}
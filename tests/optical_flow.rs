@@ -0,0 +1,27 @@
+#![cfg(feature = "opencv")]
+
+use opencv::{
+    core::{Mat, Rect, Scalar, CV_8UC1},
+    imgproc,
+    prelude::*,
+};
+use vyuwer_rust::flow::{flow_stats, optical_flow};
+
+// Function to draw a textured 40×40 block with its top-left corner at (`x`, 60)
+fn block_frame(x: i32) -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(160, 160, CV_8UC1, Scalar::all(30.0)).unwrap();
+    imgproc::rectangle(&mut frame, Rect::new(x, 60, 40, 40), Scalar::all(220.0), -1, imgproc::LINE_8, 0).unwrap();
+    imgproc::rectangle(&mut frame, Rect::new(x + 10, 70, 20, 20), Scalar::all(90.0), -1, imgproc::LINE_8, 0).unwrap();
+    frame
+}
+
+#[test]
+fn block_shifting_right_flows_rightwards() {
+    let flow = optical_flow(&block_frame(50), &block_frame(54)).unwrap();
+    let stats = flow_stats(&flow).unwrap();
+    assert_eq!(stats.dominant_direction, 0.0);
+    assert!(stats.mean_magnitude > 0.0);
+
+    let still = flow_stats(&optical_flow(&block_frame(50), &block_frame(50)).unwrap()).unwrap();
+    assert!(still.mean_magnitude < stats.mean_magnitude);
+}
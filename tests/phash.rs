@@ -0,0 +1,53 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::model::insert_image_feature;
+use vyuwer_rust::phash::{find_by_phash, phash_distance, phash_from_gray};
+
+// Function to render a 128×128 grayscale gradient overlaid with smooth blobs of the given spatial
+// frequencies, plus a small brightness offset and pixel noise
+fn scene(fx: f64, fy: f64, brightness: f64, noise_seed: u32) -> Vec<u8> {
+    let mut state = noise_seed | 1;
+    (0..128 * 128)
+        .map(|i| {
+            let (x, y) = ((i % 128) as f64, (i / 128) as f64);
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let noise = f64::from(state % 7) - 3.0;
+            let blobs = 60.0 * (x / fx).sin() * (y / fy).cos();
+            (40.0 + x * 0.8 + y * 0.4 + blobs + brightness + noise).clamp(0.0, 255.0) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn similar_images_hash_close_and_different_ones_far() {
+    let original = phash_from_gray(&scene(10.0, 13.0, 0.0, 1), 128, 128);
+    let brighter_and_noisy = phash_from_gray(&scene(10.0, 13.0, 12.0, 99), 128, 128);
+    let other_scene = phash_from_gray(&scene(4.0, 23.0, 0.0, 1), 128, 128);
+
+    let near = phash_distance(original, brighter_and_noisy);
+    let far = phash_distance(original, other_scene);
+    assert!(near <= 8, "similar images are {near} bits apart");
+    assert!(far >= 16, "different images are only {far} bits apart");
+}
+
+#[test]
+fn exact_phash_comes_first() {
+    let db = TempDb::new();
+    let target = 0xF0F0_0000_FFFF_1234u64;
+    // Stored oldest first: 3 bits off, exact, 1 bit off, far away, no hash
+    for (i, hash) in [Some(target ^ 0b111), Some(target), Some(target ^ (1 << 63)), Some(!target), None]
+        .into_iter()
+        .enumerate()
+    {
+        let mut f = feature(&format!("f{i}"), "cam", 1_700_000_000 + i as i64, 0);
+        f.phash = hash;
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+
+    let found = find_by_phash("cam", target, 4, db.path()).unwrap();
+    assert_eq!(found, [("f1".to_string(), 0), ("f2".to_string(), 1), ("f0".to_string(), 3)]);
+    assert!(find_by_phash("other", target, 64, db.path()).unwrap().is_empty());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::TempDb;
+use vyuwer_rust::model::{delete_features_in_range, get_camera_features, prune_older_than};
+use vyuwer_rust::timestamp::{now_utc_iso8601, parse_duration};
+use vyuwer_rust::Database;
+
+#[test]
+fn zero_age_prunes_everything_and_a_long_age_prunes_nothing() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam");
+
+    assert_eq!(prune_older_than(parse_duration("36500d").unwrap(), db.path()).unwrap(), 0);
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 3);
+    assert_eq!(prune_older_than(Duration::ZERO, db.path()).unwrap(), 3);
+    assert!(get_camera_features("cam", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn zero_age_prunes_features_stamped_this_second() {
+    let db = TempDb::new();
+    let mut handle = Database::open(db.path()).unwrap();
+    for i in 0..3 {
+        let mut feature = common::feature(&format!("now-{i}"), "cam", 0, i);
+        feature.created_at_utc = now_utc_iso8601();
+        handle.insert_image_feature(&feature).unwrap();
+    }
+    // Let the clock move past the last insert's millisecond
+    thread::sleep(Duration::from_millis(5));
+
+    assert_eq!(prune_older_than(Duration::ZERO, db.path()).unwrap(), 3);
+    assert!(get_camera_features("cam", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn durations_parse_from_human_units() {
+    assert_eq!(parse_duration("30d").unwrap(), Duration::from_secs(30 * 86_400));
+    assert_eq!(parse_duration("1d12h").unwrap(), Duration::from_secs(36 * 3600));
+    assert!(parse_duration("12").is_err());
+    assert!(parse_duration("d").is_err());
+}
+
+#[test]
+fn deleting_a_middle_window_keeps_both_ends() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(5, "cam");
+    db.seed_features(5, "other");
+
+    let deleted = delete_features_in_range("cam", &seeded[1].created_at_utc, &seeded[3].created_at_utc, db.path());
+    assert_eq!(deleted.unwrap(), 3);
+    let ids: Vec<String> = get_camera_features("cam", db.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(ids, vec!["cam-0", "cam-4"]);
+    assert_eq!(get_camera_features("other", db.path()).unwrap().len(), 5);
+
+    let reversed = delete_features_in_range("cam", &seeded[4].created_at_utc, &seeded[0].created_at_utc, db.path());
+    assert!(reversed.is_err());
+}
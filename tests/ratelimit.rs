@@ -0,0 +1,35 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{feature, TempDb};
+use vyuwer_rust::model::get_camera_features;
+use vyuwer_rust::ratelimit::{insert_rate_limited, RateLimiter};
+
+#[test]
+fn flood_is_capped_at_the_burst() {
+    let db = TempDb::new();
+    // At most 10 per hour, so no tokens are earned back while the loop runs
+    let mut limiter = RateLimiter::new(10.0 / 3600.0, 10).unwrap();
+    let mut accepted = 0;
+    for i in 0..100 {
+        let f = feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 0);
+        accepted += usize::from(insert_rate_limited(&mut limiter, &f, db.path()).unwrap());
+    }
+    assert_eq!(accepted, 10);
+    assert_eq!(limiter.dropped("cam"), 90);
+    assert_eq!(get_camera_features("cam", db.path()).unwrap().len(), 10);
+}
+
+#[test]
+fn tokens_refill_over_time_per_camera() {
+    let mut limiter = RateLimiter::new(10.0, 10).unwrap();
+    let start = Instant::now();
+    let accepted = (0..100).filter(|_| limiter.try_acquire_at("cam", start)).count();
+    assert_eq!(accepted, 10);
+    // One second later the bucket is full again, and other cameras were never limited
+    let later = start + Duration::from_secs(1);
+    assert_eq!((0..100).filter(|_| limiter.try_acquire_at("cam", later)).count(), 10);
+    assert!(limiter.try_acquire_at("other", later));
+    assert_eq!(limiter.total_dropped(), 180);
+}
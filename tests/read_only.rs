@@ -0,0 +1,31 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::{Database, VyuwerError};
+
+#[test]
+fn read_only_handle_queries_but_rejects_inserts() {
+    let db = TempDb::new();
+    db.seed_features(2, "cam");
+
+    let mut reader = Database::open_read_only(db.path()).unwrap();
+    assert!(reader.is_read_only());
+    assert!(reader.get_image_feature("cam").unwrap().is_some());
+
+    let err = reader.insert_image_feature(&feature("extra", "cam", 1_800_000_000, 9)).unwrap_err();
+    assert!(matches!(err, VyuwerError::ReadOnly(_)), "{err:?}");
+    assert!(matches!(reader.delete_image_feature("cam"), Err(VyuwerError::ReadOnly(_))));
+    // Raw SQL through the connection is refused by SQLite itself
+    assert!(reader.connection().execute("DELETE FROM image_features", []).is_err());
+
+    let count: i64 = reader.connection().query_row("SELECT COUNT(*) FROM image_features", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn read_only_open_does_not_create_a_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("missing.db");
+    assert!(Database::open_read_only(path.to_str().unwrap()).is_err());
+    assert!(!path.exists());
+}
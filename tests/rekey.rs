@@ -0,0 +1,48 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::anomaly::{load_baseline, set_baseline};
+use vyuwer_rust::frames::{load_frame_bytes, store_frame_bytes};
+use vyuwer_rust::model::{
+    features_without_description, get_feature_by_id, insert_image_description, insert_image_feature, rekey_feature,
+    ImageDescription,
+};
+use vyuwer_rust::tags::{add_tag, get_tags};
+use vyuwer_rust::{Database, VyuwerError};
+
+#[test]
+fn rekeyed_feature_keeps_its_description_and_references() {
+    let db = TempDb::new();
+    let mut stored = feature("old", "cam", 1_700_000_000, 3);
+    stored.img_filename = Some("frame-0.png".to_string());
+    insert_image_feature(&stored, db.path()).unwrap();
+    insert_image_feature(&feature("other", "cam", 1_700_000_001, 4), db.path()).unwrap();
+    insert_image_description(
+        &ImageDescription {
+            image_name: "frame-0.png".to_string(),
+            datetime: stored.created_at_utc.clone(),
+            camera_id: "cam".to_string(),
+            anomaly: None,
+        },
+        db.path(),
+    )
+    .unwrap();
+    add_tag("old", "weather", "rain", db.path()).unwrap();
+    store_frame_bytes("old", b"frame", db.path()).unwrap();
+    set_baseline("cam", "old", db.path()).unwrap();
+
+    rekey_feature("old", "4f1c9a2e", db.path()).unwrap();
+    assert_eq!(get_feature_by_id("old", db.path()).unwrap(), None);
+    let rekeyed = get_feature_by_id("4f1c9a2e", db.path()).unwrap().unwrap();
+    assert_eq!(rekeyed.descriptors, stored.descriptors);
+    // Still described, so not awaiting classification
+    let pending = features_without_description("cam", db.path()).unwrap();
+    assert_eq!(pending.iter().map(|f| f.id.as_str()).collect::<Vec<_>>(), ["other"]);
+    assert_eq!(get_tags("4f1c9a2e", db.path()).unwrap(), [("weather".to_string(), "rain".to_string())]);
+    assert_eq!(load_frame_bytes("4f1c9a2e", db.path()).unwrap(), Some(b"frame".to_vec()));
+    let handle = Database::open(db.path()).unwrap();
+    assert_eq!(load_baseline(handle.connection(), "cam").unwrap().unwrap().id, "4f1c9a2e");
+
+    assert!(matches!(rekey_feature("4f1c9a2e", "other", db.path()), Err(VyuwerError::FeatureExists(_))));
+    assert!(matches!(rekey_feature("old", "new", db.path()), Err(VyuwerError::FeatureNotFound(_))));
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::{feature, TempDb};
+use opencv::{
+    core::{Mat, Scalar, CV_8UC1},
+    prelude::*,
+};
+use vyuwer_rust::extract::OrbParams;
+use vyuwer_rust::frames::{reprocess_feature, store_frame};
+use vyuwer_rust::model::{get_feature_by_id, insert_image_feature};
+use vyuwer_rust::VyuwerError;
+
+// Function to build a 256×256 grayscale frame of noise, textured enough for any ORB budget
+fn noise_frame() -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(256, 256, CV_8UC1, Scalar::all(0.0)).unwrap();
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    for r in 0..256 {
+        for c in 0..256 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *frame.at_2d_mut::<u8>(r, c).unwrap() = state as u8;
+        }
+    }
+    frame
+}
+
+#[test]
+fn reprocessing_with_a_larger_budget_stores_more_keypoints_in_place() {
+    let db = TempDb::new();
+    let stored = feature("f", "cam", 1_700_000_000, 3);
+    insert_image_feature(&stored, db.path()).unwrap();
+    store_frame("f", &noise_frame(), db.path()).unwrap();
+
+    let small = OrbParams {
+        nfeatures: 50,
+        ..OrbParams::default()
+    };
+    reprocess_feature("f", &small, db.path()).unwrap();
+    let before = get_feature_by_id("f", db.path()).unwrap().unwrap();
+    let large = OrbParams {
+        nfeatures: 400,
+        ..OrbParams::default()
+    };
+    reprocess_feature("f", &large, db.path()).unwrap();
+    let after = get_feature_by_id("f", db.path()).unwrap().unwrap();
+
+    assert!(after.keypoints.len() > before.keypoints.len(), "{} <= {}", after.keypoints.len(), before.keypoints.len());
+    assert_eq!(after.descriptors.rows, after.keypoints.len());
+    assert_eq!((after.id.as_str(), after.created_at_utc.as_str()), ("f", stored.created_at_utc.as_str()));
+    assert_eq!(after.extraction_params, Some(large));
+}
+
+#[test]
+fn reprocessing_a_feature_without_a_frame_is_a_missing_frame() {
+    let db = TempDb::new();
+    insert_image_feature(&feature("f", "cam", 1_700_000_000, 3), db.path()).unwrap();
+    assert!(matches!(
+        reprocess_feature("f", &OrbParams::default(), db.path()),
+        Err(VyuwerError::MissingFrame(_))
+    ));
+}
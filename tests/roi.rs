@@ -0,0 +1,48 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::model::{features_in_roi, get_feature_by_id, insert_image_feature, ImageFeature, Roi};
+
+#[test]
+fn roi_round_trips_through_insert_and_get() {
+    let db = TempDb::new();
+    let tile = feature("tile", "cam", 1_700_000_000, 1);
+    let tile = ImageFeature {
+        roi: Some(Roi::new(640, 0, 640, 480)),
+        ..tile
+    };
+    insert_image_feature(&tile, db.path()).unwrap();
+    insert_image_feature(&feature("whole", "cam", 1_700_000_001, 2), db.path()).unwrap();
+    assert_eq!(get_feature_by_id("tile", db.path()).unwrap().unwrap(), tile);
+    assert_eq!(get_feature_by_id("whole", db.path()).unwrap().unwrap().roi, None);
+}
+
+#[test]
+fn only_overlapping_rectangles_find_the_tile() {
+    let db = TempDb::new();
+    let mut tile = feature("tile", "cam", 1_700_000_000, 1);
+    tile.roi = Some(Roi::new(640, 0, 640, 480));
+    insert_image_feature(&tile, db.path()).unwrap();
+    let mut elsewhere = feature("elsewhere", "other", 1_700_000_000, 1);
+    elsewhere.roi = tile.roi;
+    insert_image_feature(&elsewhere, db.path()).unwrap();
+    insert_image_feature(&feature("whole", "cam", 1_700_000_001, 2), db.path()).unwrap();
+
+    let ids = |roi: Roi| -> Vec<String> {
+        features_in_roi("cam", roi, db.path()).unwrap().into_iter().map(|f| f.id).collect()
+    };
+    assert_eq!(ids(Roi::new(600, 100, 100, 100)), ["tile"]);
+    assert_eq!(ids(Roi::new(700, 100, 10, 10)), ["tile"]);
+    assert_eq!(ids(Roi::new(0, 0, 1920, 1080)), ["tile"]);
+    // Touching the tile's left edge, or below it, is not an overlap
+    assert!(ids(Roi::new(0, 0, 640, 480)).is_empty());
+    assert!(ids(Roi::new(640, 480, 100, 100)).is_empty());
+}
+
+#[test]
+fn overlap_matches_the_query() {
+    let tile = Roi::new(640, 0, 640, 480);
+    assert!(tile.overlaps(&Roi::new(600, 100, 100, 100)));
+    assert!(!tile.overlaps(&Roi::new(0, 0, 640, 480)));
+    assert!(Roi::new(u32::MAX - 1, 0, u32::MAX, 1).overlaps(&Roi::new(u32::MAX - 1, 0, 1, 1)));
+}
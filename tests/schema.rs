@@ -0,0 +1,183 @@
+mod common;
+
+use common::TempDb;
+use rusqlite::{params, Connection};
+use vyuwer_rust::camera::set_camera_location;
+use vyuwer_rust::codec;
+use vyuwer_rust::frames::{load_frame_bytes, set_frame_directory, store_frame_bytes};
+use vyuwer_rust::model::{get_feature_by_id, insert_image_description, ImageDescription, KeyPointData};
+use vyuwer_rust::schema::{dump_schema, supports, table_columns, SCHEMA_VERSION};
+use vyuwer_rust::{Database, VyuwerError};
+
+#[test]
+fn opening_a_newer_database_is_refused() {
+    let db = TempDb::new();
+    Database::open_without_setup(db.path())
+        .unwrap()
+        .connection()
+        .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+
+    match Database::open(db.path()) {
+        Err(VyuwerError::IncompatibleSchema { found, supported }) => {
+            assert_eq!((found, supported), (SCHEMA_VERSION + 1, SCHEMA_VERSION));
+        }
+        other => panic!("expected an incompatibility error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn missing_parent_directories_are_created() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested/dir/test.db");
+    let mut db = Database::open(path.to_str().unwrap()).unwrap();
+    db.insert_image_feature(&common::feature("f", "cam", 1_700_000_000, 3)).unwrap();
+    assert!(path.is_file());
+
+    // A file where a directory should be cannot be worked around
+    std::fs::write(dir.path().join("blocker"), b"").unwrap();
+    let blocked = dir.path().join("blocker/sub/test.db");
+    match Database::open(blocked.to_str().unwrap()) {
+        Err(VyuwerError::Io(err)) => assert!(err.to_string().contains("blocker"), "{err}"),
+        other => panic!("expected an i/o error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn freshly_opened_file_accepts_inserts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("new.db").to_string_lossy().into_owned();
+    let mut db = Database::open(&path).unwrap();
+
+    let feature = common::feature("f", "cam", 1_700_000_000, 3);
+    db.insert_image_feature(&feature).unwrap();
+    assert_eq!(db.get_image_feature("cam").unwrap(), Some(feature));
+    // Descriptions and camera metadata are set up too
+    insert_image_description(
+        &ImageDescription {
+            image_name: "f.png".to_string(),
+            datetime: "2023-11-14T22:13:20Z".to_string(),
+            camera_id: "cam".to_string(),
+            anomaly: None,
+        },
+        &path,
+    )
+    .unwrap();
+    set_camera_location("cam", 12.97, 77.59, &path).unwrap();
+}
+
+#[test]
+fn opening_stamps_the_current_version() {
+    let db = TempDb::new();
+    let opened = Database::open(db.path()).unwrap();
+    let version: i64 = opened.connection().query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+}
+
+#[test]
+fn dump_lists_tables_and_indexes() {
+    let db = TempDb::new();
+    let dump = dump_schema(db.path()).unwrap();
+    assert!(dump.contains("CREATE TABLE image_features"));
+    assert!(dump.contains("ON image_features (camera_id, seq)"));
+    // Tables come before the indexes built on them
+    assert!(dump.find("CREATE TABLE").unwrap() < dump.find("CREATE INDEX").unwrap());
+
+    // The dump is a runnable script
+    rusqlite::Connection::open_in_memory().unwrap().execute_batch(&dump).unwrap();
+}
+
+#[test]
+fn version_one_file_is_upgraded_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("v1.db").to_string_lossy().into_owned();
+    let frames = tempfile::tempdir().unwrap();
+    {
+        // The layout as first stamped with user_version 1
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE image_features (
+                id TEXT PRIMARY KEY,
+                keypoints BLOB,
+                descriptors BLOB,
+                motion_mean REAL NOT NULL DEFAULT 0,
+                motion_std REAL NOT NULL DEFAULT 0,
+                created_at_utc TEXT NOT NULL,
+                img_filename TEXT,
+                camera_id TEXT NOT NULL,
+                feature_version INTEGER NOT NULL DEFAULT 1,
+                phash INTEGER
+            );
+            CREATE TABLE frames (
+                feature_id TEXT PRIMARY KEY,
+                image BLOB NOT NULL
+            );
+            CREATE TRIGGER frames_cascade_delete AFTER DELETE ON image_features
+            BEGIN
+                DELETE FROM frames WHERE feature_id = OLD.id;
+            END;
+            PRAGMA user_version = 1;",
+        )
+        .unwrap();
+        let flat: Vec<u8> = (0..64).collect();
+        conn.execute(
+            "INSERT INTO image_features (id, keypoints, descriptors, created_at_utc, camera_id, feature_version)
+            VALUES ('old', ?1, ?2, '2023-01-01T00:00:00Z', 'cam', 1)",
+            params![codec::encode(&Vec::<KeyPointData>::new()).unwrap(), codec::encode(&flat).unwrap()],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO frames (feature_id, image) VALUES ('old', x'89504e47')", []).unwrap();
+    }
+
+    let mut upgraded = Database::open(&path).unwrap();
+    assert_eq!(
+        upgraded.connection().query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0)).unwrap(),
+        SCHEMA_VERSION
+    );
+    let old = get_feature_by_id("old", &path).unwrap().unwrap();
+    assert_eq!(old.descriptors.rows, 2);
+    assert_eq!(load_frame_bytes("old", &path).unwrap(), Some(vec![0x89, 0x50, 0x4e, 0x47]));
+
+    // Columns and tables added since version 1 are usable
+    let feature = common::feature("new", "cam", 1_700_000_000, 3);
+    upgraded.insert_image_feature(&feature).unwrap();
+    assert_eq!(upgraded.get_image_feature("cam").unwrap(), Some(feature));
+    set_frame_directory(&frames.path().to_string_lossy(), &path).unwrap();
+    store_frame_bytes("new", b"frame", &path).unwrap();
+    assert_eq!(load_frame_bytes("new", &path).unwrap(), Some(b"frame".to_vec()));
+
+    // The rebuilt frames table still cascades
+    upgraded.connection().execute("DELETE FROM image_features WHERE id = 'old'", []).unwrap();
+    assert_eq!(load_frame_bytes("old", &path).unwrap(), None);
+}
+
+#[test]
+fn capabilities_follow_the_columns_present() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("old.db").to_string_lossy().into_owned();
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE image_features (
+                id TEXT PRIMARY KEY,
+                keypoints BLOB,
+                descriptors BLOB,
+                created_at_utc TEXT NOT NULL,
+                camera_id TEXT NOT NULL
+            )",
+        )
+        .unwrap();
+    assert_eq!(
+        table_columns("image_features", &path).unwrap(),
+        ["id", "keypoints", "descriptors", "created_at_utc", "camera_id"]
+    );
+    assert!(table_columns("frames", &path).unwrap().is_empty());
+    assert!(!supports("phash", &path).unwrap());
+    assert!(!supports("frames", &path).unwrap());
+    assert!(matches!(supports("teleport", &path), Err(VyuwerError::InvalidInput(_))));
+
+    let current = TempDb::new();
+    for capability in ["phash", "roi", "frames", "frame_files", "keypoint_gate", "description_repeats"] {
+        assert!(supports(capability, current.path()).unwrap(), "{capability}");
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::feature;
+use vyuwer_rust::Database;
+
+// Function to count the rows of `table` through `db`
+fn row_count(db: &Database, table: &str) -> i64 {
+    db.connection().query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn restored_snapshot_matches_the_original() {
+    let mut original = Database::open(":memory:").unwrap();
+    for i in 0..3 {
+        original.insert_image_feature(&feature(&format!("a-{i}"), "cam-a", 1_700_000_000 + i, i as u8)).unwrap();
+    }
+    original.insert_image_feature(&feature("b-0", "cam-b", 1_700_000_100, 9)).unwrap();
+    original
+        .connection()
+        .execute("INSERT INTO feature_tags (feature_id, key, value) VALUES ('a-1', 'weather', 'rain')", [])
+        .unwrap();
+
+    let bytes = original.serialize_to_bytes().unwrap();
+    let mut restored = Database::deserialize_from_bytes(&bytes).unwrap();
+
+    for table in ["image_features", "feature_tags"] {
+        assert_eq!(row_count(&restored, table), row_count(&original, table), "{table}");
+    }
+    assert_eq!(restored.get_image_feature("cam-a").unwrap(), original.get_image_feature("cam-a").unwrap());
+    assert_eq!(restored.get_image_feature("cam-b").unwrap(), original.get_image_feature("cam-b").unwrap());
+}
+
+#[test]
+fn restored_snapshot_is_independent_of_the_original() {
+    let mut original = Database::open(":memory:").unwrap();
+    original.insert_image_feature(&feature("a-0", "cam-a", 1_700_000_000, 1)).unwrap();
+    let mut restored = Database::deserialize_from_bytes(&original.serialize_to_bytes().unwrap()).unwrap();
+
+    restored.insert_image_feature(&feature("a-1", "cam-a", 1_700_000_001, 2)).unwrap();
+    assert_eq!(row_count(&restored, "image_features"), 2);
+    assert_eq!(row_count(&original, "image_features"), 1);
+}
+
+#[test]
+fn garbage_bytes_are_rejected() {
+    assert!(Database::deserialize_from_bytes(b"definitely not a sqlite file").is_err());
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::layout::migrate_to_normalized_descriptors;
+use vyuwer_rust::model::{insert_image_feature, DescriptorMatrix, KeyPointData};
+use vyuwer_rust::model::get_camera_features;
+use vyuwer_rust::storage::{compression_advice, enforce_max_size, storage_report};
+
+#[test]
+fn report_counts_features_and_descriptor_bytes() {
+    let db = TempDb::new();
+    let empty = storage_report(db.path()).unwrap();
+    assert_eq!(empty.table_rows["image_features"], 0);
+    assert_eq!(empty.descriptor_bytes, 0);
+
+    db.seed_features(5, "cam");
+    let report = storage_report(db.path()).unwrap();
+    assert_eq!(report.table_rows["image_features"], 5);
+    assert!(report.descriptor_bytes >= 5 * 4 * 32);
+    assert!(report.file_bytes > 0);
+    assert!(report.file_bytes >= report.descriptor_bytes);
+}
+
+#[test]
+fn normalized_descriptors_are_counted() {
+    let db = TempDb::new();
+    migrate_to_normalized_descriptors(db.path()).unwrap();
+    db.seed_features(3, "cam");
+    let report = storage_report(db.path()).unwrap();
+    assert_eq!(report.table_rows["descriptors"], 3);
+    assert_eq!(report.descriptor_bytes, 3 * 4 * 32);
+}
+
+// Function to store `n` features of 200 descriptors each, every row built by `row_byte`
+fn seed_descriptors(db: &TempDb, n: usize, row_byte: impl Fn(usize, usize) -> u8) {
+    seed_camera_descriptors(db, "cam", n, row_byte);
+}
+
+fn seed_camera_descriptors(db: &TempDb, camera_id: &str, n: usize, row_byte: impl Fn(usize, usize) -> u8) {
+    for i in 0..n {
+        let mut f = feature(&format!("{camera_id}-{i}"), camera_id, 1_700_000_000 + i as i64, 0);
+        f.keypoints = vec![KeyPointData { x: 1.0, y: 1.0, size: 31.0, angle: 0.0 }; 200];
+        let data = (0..200 * 32).map(|j| row_byte(i, j)).collect();
+        f.descriptors = DescriptorMatrix::new(200, 32, data).unwrap();
+        insert_image_feature(&f, db.path()).unwrap();
+    }
+}
+
+#[test]
+fn repetitive_descriptors_are_worth_compressing() {
+    let db = TempDb::new();
+    let empty = compression_advice(db.path()).unwrap();
+    assert_eq!((empty.sampled_blobs, empty.recommended), (0, false));
+
+    seed_descriptors(&db, 3, |_, j| (j % 32) as u8);
+    let advice = compression_advice(db.path()).unwrap();
+    assert_eq!(advice.sampled_blobs, 3);
+    assert!(advice.recommended, "{advice:?}");
+    assert!(advice.estimated_ratio < 0.2, "{advice:?}");
+    assert!(advice.projected_savings_bytes > advice.descriptor_bytes / 2);
+}
+
+#[test]
+fn random_descriptors_are_not_worth_compressing() {
+    let db = TempDb::new();
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let noise: Vec<u8> = (0..3 * 200 * 32)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    seed_descriptors(&db, 3, |i, j| noise[i * 200 * 32 + j]);
+    let advice = compression_advice(db.path()).unwrap();
+    assert!(!advice.recommended, "{advice:?}");
+    assert!(advice.estimated_ratio > 0.9, "{advice:?}");
+}
+
+#[test]
+fn exceeding_the_cap_evicts_oldest_features_below_it() {
+    let db = TempDb::new();
+    let file_len = || std::fs::metadata(db.path()).unwrap().len();
+    let byte = |i: usize, j: usize| (i * 7 + j * 13) as u8;
+    seed_camera_descriptors(&db, "vip", 20, byte);
+    seed_camera_descriptors(&db, "cam", 40, byte);
+    let full = file_len();
+    assert_eq!(enforce_max_size(full, &["vip"], db.path()).unwrap(), 0);
+
+    let cap = full / 2;
+    let evicted = enforce_max_size(cap, &["vip"], db.path()).unwrap();
+    assert!(evicted > 0);
+    assert!(file_len() <= cap, "{} > {cap}", file_len());
+
+    // Only the unlisted camera lost features, oldest first
+    assert_eq!(get_camera_features("vip", db.path()).unwrap().len(), 20);
+    let kept = get_camera_features("cam", db.path()).unwrap();
+    assert_eq!(kept.len(), 40 - evicted);
+    assert_eq!(kept.last().unwrap().id, "cam-39");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::store::{FeatureStore, MemoryStore};
+use vyuwer_rust::Database;
+
+// Function to insert three features sharing a timestamp, ids deliberately out of order
+fn insert_same_second(store: &mut impl FeatureStore) {
+    for (id, seed) in [("c", 1), ("a", 2), ("b", 3)] {
+        store.insert_image_feature(&feature(id, "cam", 1_700_000_000, seed)).unwrap();
+    }
+}
+
+#[test]
+fn same_timestamp_features_keep_insertion_order_in_both_stores() {
+    let db = TempDb::new();
+    let mut database = Database::open(db.path()).unwrap();
+    let mut memory = MemoryStore::new();
+    insert_same_second(&mut database);
+    insert_same_second(&mut memory);
+
+    for store in [&mut database as &mut dyn FeatureStore, &mut memory] {
+        let ids: Vec<String> = store.camera_features("cam").unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec!["c", "a", "b"]);
+        assert_eq!(store.get_image_feature("cam").unwrap().unwrap().id, "b");
+    }
+}
+
+#[test]
+fn both_stores_order_mixed_timestamp_forms_by_time() {
+    let db = TempDb::new();
+    let mut database = Database::open(db.path()).unwrap();
+    let mut memory = MemoryStore::new();
+    for store in [&mut database as &mut dyn FeatureStore, &mut memory] {
+        for (id, created_at_utc) in [("late", "2024-06-12T12:34:56.500Z"), ("early", "2024-06-12 12:34:56")] {
+            let mut stored = feature(id, "cam", 0, 3);
+            stored.created_at_utc = created_at_utc.to_string();
+            store.insert_image_feature(&stored).unwrap();
+        }
+        let ids: Vec<String> = store.camera_features("cam").unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, ["early", "late"]);
+        assert_eq!(store.get_image_feature("cam").unwrap().unwrap().id, "late");
+        assert_eq!(store.baseline("cam").unwrap().unwrap().id, "early");
+    }
+}
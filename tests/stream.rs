@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::TempDb;
+use vyuwer_rust::model::ImageFeature;
+use vyuwer_rust::stream::{replay, sample_features, stream_features};
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn consumer_thread_receives_every_feature() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(12, "cam");
+    db.seed_features(3, "other");
+
+    let (tx, rx) = mpsc::channel::<ImageFeature>();
+    let consumer = thread::spawn(move || rx.iter().map(|feature| feature.id).collect::<Vec<_>>());
+    stream_features("cam", db.path(), tx).unwrap();
+
+    let expected: Vec<String> = seeded.into_iter().map(|feature| feature.id).collect();
+    assert_eq!(consumer.join().unwrap(), expected);
+}
+
+#[test]
+fn disconnected_receiver_stops_the_stream() {
+    let db = TempDb::new();
+    db.seed_features(5, "cam");
+    let (tx, rx) = mpsc::channel::<ImageFeature>();
+    drop(rx);
+    stream_features("cam", db.path(), tx).unwrap();
+}
+
+#[test]
+fn seeded_samples_are_reproducible_and_bounded() {
+    let db = TempDb::new();
+    db.seed_features(20, "cam");
+
+    let first = sample_features("cam", 5, Some(42), db.path()).unwrap();
+    assert_eq!(first.len(), 5);
+    assert_eq!(first, sample_features("cam", 5, Some(42), db.path()).unwrap());
+    assert_eq!(sample_features("cam", 50, Some(42), db.path()).unwrap().len(), 20);
+    assert!(sample_features("cam", 0, None, db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn replay_at_speed_zero_delivers_in_order_without_sleeping() {
+    let db = TempDb::new();
+    // One second apart, so any sleeping would take the best part of ten seconds
+    let seeded = db.seed_features(10, "cam");
+    db.seed_features(2, "other");
+
+    let started = Instant::now();
+    let mut replayed = Vec::new();
+    replay("cam", 0.0, db.path(), |feature| replayed.push(feature.id)).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    let expected: Vec<String> = seeded.into_iter().map(|feature| feature.id).collect();
+    assert_eq!(replayed, expected);
+
+    assert!(matches!(replay("cam", -1.0, db.path(), |_| {}), Err(VyuwerError::InvalidInput(_))));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{feature, TempDb};
+use rusqlite::Connection;
+use vyuwer_rust::descriptor_sets::{set_descriptor_kind, DescriptorKind};
+use vyuwer_rust::layout::migrate_to_normalized_descriptors;
+use vyuwer_rust::model::{get_camera_features, insert_image_feature};
+use vyuwer_rust::sync::{diff_databases, push_missing};
+
+#[test]
+fn one_extra_feature_is_the_whole_diff() {
+    let (a, b) = (TempDb::new(), TempDb::new());
+    a.seed_features(3, "cam");
+    b.seed_features(3, "cam");
+    insert_image_feature(&feature("edge-only", "cam", 1_700_000_100, 7), b.path()).unwrap();
+
+    let diff = diff_databases(a.path(), b.path()).unwrap();
+    assert!(diff.only_in_a.is_empty());
+    assert_eq!(diff.only_in_b, ["edge-only"]);
+    assert!(diff.differing.is_empty());
+    assert!(diff_databases(a.path(), a.path()).unwrap().is_empty());
+}
+
+#[test]
+fn changed_content_and_layouts_are_told_apart() {
+    let (a, b) = (TempDb::new(), TempDb::new());
+    a.seed_features(2, "cam");
+    b.seed_features(2, "cam");
+    // The same features stored under the other layout are not a difference
+    migrate_to_normalized_descriptors(b.path()).unwrap();
+    assert!(diff_databases(a.path(), b.path()).unwrap().is_empty());
+
+    let conn = Connection::open(b.path()).unwrap();
+    conn.execute("UPDATE image_features SET motion_mean = 9.0 WHERE id = 'cam-1'", []).unwrap();
+    let diff = diff_databases(a.path(), b.path()).unwrap();
+    assert_eq!(diff.differing, ["cam-1"]);
+    assert!(diff.only_in_a.is_empty() && diff.only_in_b.is_empty());
+}
+
+#[test]
+fn pushing_leaves_the_destination_with_the_union() {
+    let (edge, central) = (TempDb::new(), TempDb::new());
+    edge.seed_features(4, "cam");
+    central.seed_features(2, "cam");
+    insert_image_feature(&feature("central-only", "cam", 1_700_000_050, 9), central.path()).unwrap();
+    set_descriptor_kind("cam-3", DescriptorKind::Orb, edge.path()).unwrap();
+    let only_in_edge = diff_databases(edge.path(), central.path()).unwrap().only_in_a;
+    assert_eq!(only_in_edge, ["cam-2", "cam-3"]);
+
+    assert_eq!(push_missing(edge.path(), central.path()).unwrap(), only_in_edge.len());
+    let ids: Vec<String> = get_camera_features("cam", central.path()).unwrap().into_iter().map(|f| f.id).collect();
+    assert_eq!(ids, ["cam-0", "cam-1", "cam-2", "cam-3", "central-only"]);
+    let diff = diff_databases(edge.path(), central.path()).unwrap();
+    assert!(diff.only_in_a.is_empty() && diff.differing.is_empty());
+    assert_eq!(diff.only_in_b, ["central-only"]);
+    // Nothing left to push
+    assert_eq!(push_missing(edge.path(), central.path()).unwrap(), 0);
+}
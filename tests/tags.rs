@@ -0,0 +1,54 @@
+mod common;
+
+use common::TempDb;
+use vyuwer_rust::model::delete_image_feature;
+use vyuwer_rust::tags::{add_tag, features_with_tag, get_tags, remove_tag, tag_features_in_range};
+use vyuwer_rust::timestamp::format_unix_secs;
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn features_are_found_by_tag() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam");
+    add_tag("cam-0", "weather", "rain", db.path()).unwrap();
+    add_tag("cam-0", "shift", "night", db.path()).unwrap();
+    add_tag("cam-1", "weather", "sun", db.path()).unwrap();
+
+    assert_eq!(features_with_tag("weather", "rain", db.path()).unwrap(), ["cam-0"]);
+    assert_eq!(
+        get_tags("cam-0", db.path()).unwrap(),
+        [("shift".to_string(), "night".to_string()), ("weather".to_string(), "rain".to_string())]
+    );
+
+    // One value per key: re-tagging replaces
+    add_tag("cam-1", "weather", "rain", db.path()).unwrap();
+    assert_eq!(features_with_tag("weather", "rain", db.path()).unwrap(), ["cam-0", "cam-1"]);
+    assert!(remove_tag("cam-1", "weather", db.path()).unwrap());
+    assert!(!remove_tag("cam-1", "weather", db.path()).unwrap());
+
+    assert!(matches!(
+        add_tag("missing", "weather", "rain", db.path()),
+        Err(VyuwerError::FeatureNotFound(_))
+    ));
+    delete_image_feature("cam", db.path()).unwrap();
+    assert!(get_tags("cam-0", db.path()).unwrap().is_empty());
+}
+
+#[test]
+fn range_tagging_only_touches_features_inside_the_window() {
+    let db = TempDb::new();
+    db.seed_features(5, "cam");
+    db.seed_features(2, "other");
+    add_tag("cam-1", "shift", "day", db.path()).unwrap();
+
+    let (start, end) = (format_unix_secs(1_700_000_001), format_unix_secs(1_700_000_003));
+    assert_eq!(tag_features_in_range("cam", &start, &end, "shift", "night", db.path()).unwrap(), 3);
+    assert_eq!(features_with_tag("shift", "night", db.path()).unwrap(), ["cam-1", "cam-2", "cam-3"]);
+    assert_eq!(get_tags("cam-0", db.path()).unwrap(), []);
+    assert_eq!(get_tags("other-1", db.path()).unwrap(), []);
+
+    assert!(matches!(
+        tag_features_in_range("cam", &end, &start, "shift", "night", db.path()),
+        Err(VyuwerError::InvalidInput(_))
+    ));
+}
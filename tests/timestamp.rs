@@ -0,0 +1,20 @@
+use vyuwer_rust::timestamp::to_local_display;
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn utc_instants_render_in_new_york_time() {
+    // Daylight saving time in June, standard time in January
+    assert_eq!(to_local_display("2024-06-12T12:34:56Z", "America/New_York").unwrap(), "2024-06-12T08:34:56-04:00");
+    assert_eq!(to_local_display("2024-01-15T03:00:00Z", "America/New_York").unwrap(), "2024-01-14T22:00:00-05:00");
+    assert_eq!(
+        to_local_display("2024-06-12T12:34:56.789Z", "America/New_York").unwrap(),
+        "2024-06-12T08:34:56.789-04:00"
+    );
+    assert_eq!(to_local_display("2024-06-12T12:34:56Z", "UTC").unwrap(), "2024-06-12T12:34:56+00:00");
+}
+
+#[test]
+fn unknown_zones_and_bad_timestamps_are_errors() {
+    assert!(matches!(to_local_display("2024-06-12T12:34:56Z", "Mars/Olympus_Mons"), Err(VyuwerError::InvalidInput(_))));
+    assert!(matches!(to_local_display("yesterday", "America/New_York"), Err(VyuwerError::InvalidTimestamp(_))));
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::TempDb;
+use opencv::{
+    core::{no_array, norm2, Mat, Scalar, CV_8UC1, NORM_INF},
+    prelude::*,
+};
+use vyuwer_rust::camera::{set_calibration, CameraCalibration};
+use vyuwer_rust::geometry::undistort;
+use vyuwer_rust::VyuwerError;
+
+#[test]
+fn identity_calibration_leaves_the_image_unchanged() {
+    let db = TempDb::new();
+    let mut image = Mat::new_rows_cols_with_default(48, 64, CV_8UC1, Scalar::all(0.0)).unwrap();
+    for y in 0..48 {
+        for x in 0..64 {
+            *image.at_2d_mut::<u8>(y, x).unwrap() = (x * 3 + y * 2) as u8;
+        }
+    }
+    assert!(matches!(undistort(&image, "cam", db.path()), Err(VyuwerError::MissingCalibration(_))));
+
+    let identity = CameraCalibration {
+        intrinsics: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        distortion: vec![0.0; 5],
+    };
+    set_calibration("cam", &identity, db.path()).unwrap();
+    let undistorted = undistort(&image, "cam", db.path()).unwrap();
+    assert_eq!(undistorted.size().unwrap(), image.size().unwrap());
+    assert_eq!(norm2(&image, &undistorted, NORM_INF, &no_array()).unwrap(), 0.0);
+}
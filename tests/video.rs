@@ -0,0 +1,113 @@
+#![cfg(feature = "opencv")]
+
+mod common;
+
+use common::TempDb;
+use opencv::{
+    core::{Mat, Rect, Scalar, Size, CV_8UC3},
+    imgproc,
+    prelude::*,
+    videoio,
+};
+use vyuwer_rust::frames::store_frame;
+use vyuwer_rust::model::get_camera_features;
+use vyuwer_rust::timestamp::parse_utc_millis;
+use vyuwer_rust::video::{export_timelapse, process_video_starting_at};
+use vyuwer_rust::VyuwerError;
+
+// Function to build a 160×120 frame whose bright square moves with `i`, so consecutive
+// frames pass the motion gate
+fn moving_square(i: i32) -> Mat {
+    let mut frame = Mat::new_rows_cols_with_default(120, 160, CV_8UC3, Scalar::all(30.0)).unwrap();
+    let square = Rect::new(10 + i * 10, 30, 40, 40);
+    imgproc::rectangle(&mut frame, square, Scalar::all(220.0), imgproc::FILLED, imgproc::LINE_8, 0).unwrap();
+    frame
+}
+
+#[test]
+fn frames_are_stamped_with_their_video_position() {
+    let db = TempDb::new();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clip.avi").to_string_lossy().into_owned();
+    let fourcc = videoio::VideoWriter::fourcc('M', 'J', 'P', 'G').unwrap();
+    let Ok(mut writer) = videoio::VideoWriter::new(&path, fourcc, 10.0, Size::new(160, 120), true) else {
+        eprintln!("skipping: no MJPG writer in this OpenCV build");
+        return;
+    };
+    if !writer.is_opened().unwrap() {
+        eprintln!("skipping: no MJPG writer in this OpenCV build");
+        return;
+    }
+    for i in 0..8 {
+        writer.write(&moving_square(i)).unwrap();
+    }
+    writer.release().unwrap();
+
+    let stored = match process_video_starting_at(&path, "cam", 2, "2024-01-01T00:00:00Z", db.path()) {
+        Err(VyuwerError::Video(reason)) => {
+            eprintln!("skipping: {reason}");
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert_eq!(stored, 4);
+
+    // Every second frame of a 10 fps clip: 200 ms apart, starting at the given time
+    let start = parse_utc_millis("2024-01-01T00:00:00Z").unwrap();
+    let offsets: Vec<i64> = get_camera_features("cam", db.path())
+        .unwrap()
+        .iter()
+        .map(|f| parse_utc_millis(&f.created_at_utc).unwrap() - start)
+        .collect();
+    assert_eq!(offsets.len(), 4);
+    for (i, offset) in offsets.iter().enumerate() {
+        assert!((offset - i as i64 * 200).abs() <= 20, "frame {i} at {offset} ms");
+    }
+}
+
+#[test]
+fn corrupt_video_is_a_clear_error() {
+    let db = TempDb::new();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.avi");
+    std::fs::write(&path, b"definitely not a video").unwrap();
+
+    let result = process_video_starting_at(path.to_str().unwrap(), "cam", 1, "2024-01-01T00:00:00Z", db.path());
+    assert!(matches!(result, Err(VyuwerError::Video(_))));
+}
+
+#[test]
+fn timelapse_holds_every_stored_frame() {
+    let db = TempDb::new();
+    let seeded = db.seed_features(5, "cam");
+    // The last feature has no frame and the third a smaller one
+    for (i, feature) in seeded.iter().take(4).enumerate() {
+        let mut frame = moving_square(i as i32);
+        if i == 2 {
+            let mut small = Mat::default();
+            imgproc::resize(&frame, &mut small, Size::new(80, 60), 0.0, 0.0, imgproc::INTER_AREA).unwrap();
+            frame = small;
+        }
+        store_frame(&feature.id, &frame, db.path()).unwrap();
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("timelapse.avi").to_string_lossy().into_owned();
+    let written = match export_timelapse("cam", 5.0, &path, db.path()) {
+        Err(VyuwerError::Video(_)) => {
+            eprintln!("skipping: no MJPG writer in this OpenCV build");
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert_eq!(written, 4);
+
+    let mut capture = videoio::VideoCapture::from_file(&path, videoio::CAP_ANY).unwrap();
+    let mut frame = Mat::default();
+    let mut read = 0;
+    while capture.read(&mut frame).unwrap() && !frame.empty() {
+        assert_eq!(frame.size().unwrap(), Size::new(160, 120));
+        read += 1;
+    }
+    assert_eq!(read, 4);
+    assert_eq!(export_timelapse("empty", 5.0, &path, db.path()).unwrap(), 0);
+}
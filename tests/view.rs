@@ -0,0 +1,15 @@
+mod common;
+
+use vyuwer_rust::view::FeatureView;
+
+#[test]
+fn view_omits_descriptors_unless_requested() {
+    let feature = common::feature("f", "cam", 1_700_000_000, 0);
+
+    let without: serde_json::Value = serde_json::to_value(FeatureView::new(&feature, false)).unwrap();
+    assert!(without.get("descriptors").is_none());
+    assert_eq!(without["id"], "f");
+
+    let with = serde_json::to_value(FeatureView::new(&feature, true)).unwrap();
+    assert_eq!(with, serde_json::to_value(&feature).unwrap());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::TempDb;
+use vyuwer_rust::model::get_camera_features;
+use vyuwer_rust::vocabulary::{
+    build_vocabulary, index_bow_histograms, load_vocabulary, quantize, save_vocabulary, search_by_bow,
+};
+
+#[test]
+fn quantized_histograms_sum_to_descriptor_count() {
+    let db = TempDb::new();
+    db.seed_features(3, "cam_a");
+    db.seed_features(2, "cam_b");
+
+    let vocabulary = build_vocabulary(&["cam_a", "cam_b"], 4, db.path()).unwrap();
+    assert_eq!(vocabulary.len(), 4);
+    for feature in get_camera_features("cam_a", db.path()).unwrap() {
+        let histogram = quantize(&feature.descriptors, &vocabulary);
+        assert_eq!(histogram.len(), 4);
+        assert_eq!(histogram.iter().sum::<u32>() as usize, feature.descriptors.rows);
+    }
+
+    save_vocabulary("default", &vocabulary, db.path()).unwrap();
+    assert_eq!(load_vocabulary("default", db.path()).unwrap(), Some(vocabulary));
+}
+
+#[test]
+fn own_histogram_ranks_its_feature_first() {
+    let db = TempDb::new();
+    db.seed_features(6, "cam");
+    db.seed_features(2, "other");
+    let vocabulary = build_vocabulary(&["cam"], 8, db.path()).unwrap();
+    assert!(search_by_bow(&[1; 8], "cam", 3, db.path()).unwrap().is_empty());
+    assert_eq!(index_bow_histograms("cam", &vocabulary, db.path()).unwrap(), 6);
+
+    for feature in get_camera_features("cam", db.path()).unwrap() {
+        let histogram = quantize(&feature.descriptors, &vocabulary);
+        let ranked = search_by_bow(&histogram, "cam", 3, db.path()).unwrap();
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0].0, feature.id);
+        assert!((ranked[0].1 - 1.0).abs() < 1e-9);
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+    // Unindexed cameras and histograms of another length find nothing
+    assert!(search_by_bow(&[1; 8], "other", 3, db.path()).unwrap().is_empty());
+    assert!(search_by_bow(&[1; 5], "cam", 3, db.path()).unwrap().is_empty());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{feature, TempDb};
+use vyuwer_rust::window::CameraWindow;
+
+#[test]
+fn push_beyond_capacity_evicts_the_oldest() {
+    let mut window = CameraWindow::new(3).unwrap();
+    for i in 0..5 {
+        window.push(feature(&format!("f{i}"), "cam", 1_700_000_000 + i, 0));
+    }
+    window.push(feature("other", "cam2", 1_700_000_000, 0));
+
+    assert_eq!(window.len("cam"), 3);
+    let ids: Vec<&str> = window.recent("cam", 10).iter().map(|f| f.id.as_str()).collect();
+    assert_eq!(ids, ["f4", "f3", "f2"]);
+    let newest: Vec<&str> = window.recent("cam", 1).iter().map(|f| f.id.as_str()).collect();
+    assert_eq!(newest, ["f4"]);
+    assert!(window.recent("missing", 3).is_empty());
+}
+
+#[test]
+fn hydrate_loads_the_latest_stored_features() {
+    let db = TempDb::new();
+    db.seed_features(5, "cam");
+    let mut window = CameraWindow::new(2).unwrap();
+    assert_eq!(window.hydrate("cam", db.path()).unwrap(), 2);
+    let ids: Vec<&str> = window.recent("cam", 2).iter().map(|f| f.id.as_str()).collect();
+    assert_eq!(ids, ["cam-4", "cam-3"]);
+}
+
+#[test]
+fn persisted_window_loads_back_in_the_same_order() {
+    let db = TempDb::new();
+    db.seed_features(1, "cam");
+    let mut window = CameraWindow::new(3).unwrap();
+    window.hydrate("cam", db.path()).unwrap();
+    for i in 1..5 {
+        window.push(feature(&format!("live-{i}"), "cam", 1_800_000_000 + i, 2));
+    }
+    window.push(feature("other", "cam2", 1_800_000_000, 2));
+
+    let report = window.persist(db.path()).unwrap();
+    assert_eq!((report.inserted, report.skipped), (4, 0));
+    assert_eq!(window.persist(db.path()).unwrap().skipped, 4);
+
+    let restored = CameraWindow::load("cam", 3, db.path()).unwrap();
+    let ids = |w: &CameraWindow| w.recent("cam", 10).iter().map(|f| f.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&restored), ids(&window));
+    assert_eq!(ids(&restored), ["live-4", "live-3", "live-2"]);
+    assert_eq!(restored.recent("cam", 1)[0], window.recent("cam", 1)[0]);
+    assert!(restored.is_empty("cam2"));
+}